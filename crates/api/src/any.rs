@@ -0,0 +1,161 @@
+use std::fmt;
+
+use prost::{DecodeError, Message, Name};
+use prost_wkt::{MessageSerde, MessageSerdeDecoderEntry};
+use prost_wkt_types::Any;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The `Any` holds another message than the one unpacked.
+    TypeUrlMismatch {
+        expected: String,
+        found: String,
+    },
+    /// No linked message has the type URL of the `Any`.
+    UnknownType(String),
+    Decode(DecodeError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TypeUrlMismatch { expected, found } => {
+                write!(f, "Expected type URL `{expected}`, found `{found}`")
+            }
+            Self::UnknownType(type_url) => write!(f, "No message linked for `{type_url}`"),
+            Self::Decode(error) => write!(f, "Failed to decode message: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decode(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+/// Packs `message` into an `Any` with its type URL.
+pub fn pack<T: Message + Name>(message: &T) -> Any {
+    Any {
+        type_url: T::type_url(),
+        value: message.encode_to_vec(),
+    }
+}
+
+/// Whether `any` holds a `T`.
+pub fn is<T: Name>(any: &Any) -> bool {
+    any.type_url == T::type_url()
+}
+
+/// Decodes the `T` packed in `any`.
+pub fn unpack<T: Message + Name + Default>(any: &Any) -> Result<T, Error> {
+    let expected = T::type_url();
+    if any.type_url != expected {
+        return Err(Error::TypeUrlMismatch {
+            expected,
+            found: any.type_url.to_owned(),
+        });
+    }
+    Ok(T::decode(any.value.as_slice())?)
+}
+
+/// Decodes the message packed in `any` as whichever generated message of the
+/// linked crates has its type URL, to serialize it or downcast it later.
+pub fn unpack_dyn(any: &Any) -> Result<Box<dyn MessageSerde>, Error> {
+    let entry = prost_wkt::inventory::iter::<MessageSerdeDecoderEntry>
+        .into_iter()
+        .find(|x| x.type_url == any.type_url)
+        .ok_or_else(|| Error::UnknownType(any.type_url.to_owned()))?;
+    Ok((entry.decoder)(&any.value)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Widget {
+        #[prost(string, tag = "1")]
+        name: String,
+    }
+
+    impl Name for Widget {
+        const NAME: &'static str = "Widget";
+        const PACKAGE: &'static str = "acme.widgets.v1";
+
+        fn type_url() -> String {
+            format!("{}/{}", crate::registry::TYPE_URL_DOMAIN, Self::full_name())
+        }
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Gadget {
+        #[prost(int64, tag = "1")]
+        id: i64,
+    }
+
+    impl Name for Gadget {
+        const NAME: &'static str = "Gadget";
+        const PACKAGE: &'static str = "acme.widgets.v1";
+
+        fn type_url() -> String {
+            format!("{}/{}", crate::registry::TYPE_URL_DOMAIN, Self::full_name())
+        }
+    }
+
+    #[test]
+    fn it_round_trips_messages() {
+        let widget = Widget {
+            name: "widgets/a".to_owned(),
+        };
+        let any = pack(&widget);
+        assert_eq!(any.type_url, "type.googleapis.com/acme.widgets.v1.Widget");
+        assert!(is::<Widget>(&any));
+        assert!(!is::<Gadget>(&any));
+        assert_eq!(unpack::<Widget>(&any).unwrap(), widget);
+    }
+
+    #[test]
+    fn it_rejects_mismatched_type_urls() {
+        let any = pack(&Widget::default());
+        match unpack::<Gadget>(&any) {
+            Err(Error::TypeUrlMismatch { expected, found }) => {
+                assert_eq!(expected, "type.googleapis.com/acme.widgets.v1.Gadget");
+                assert_eq!(found, "type.googleapis.com/acme.widgets.v1.Widget");
+            }
+            other => panic!("Expected a type URL mismatch, got {other:?}"),
+        }
+
+        let any = Any {
+            type_url: Widget::full_name(),
+            value: Vec::new(),
+        };
+        assert!(!is::<Widget>(&any));
+        assert!(matches!(
+            unpack::<Widget>(&any),
+            Err(Error::TypeUrlMismatch { found, .. }) if found == "acme.widgets.v1.Widget"
+        ));
+    }
+
+    #[test]
+    fn it_reports_undecodable_and_unknown_messages() {
+        let any = Any {
+            type_url: Widget::type_url(),
+            value: vec![0x0a, 0x05],
+        };
+        assert!(matches!(unpack::<Widget>(&any), Err(Error::Decode(_))));
+        assert!(matches!(
+            unpack_dyn(&any),
+            Err(Error::UnknownType(type_url)) if type_url == Widget::type_url()
+        ));
+    }
+}
@@ -0,0 +1,111 @@
+//! `proptest` strategies for the fields of generated messages deriving
+//! `proptest::arbitrary::Arbitrary`, keeping to values that protobuf and the
+//! `serde` representation accept: finite floats, known enum values, in range
+//! well-known types, and small collections so nested messages stay small.
+
+use std::{collections::HashMap, hash::Hash, ops::RangeInclusive};
+
+use proptest::{
+    arbitrary::{any, Arbitrary},
+    collection,
+    strategy::{Just, Strategy},
+};
+use prost_wkt_types::{Duration, FieldMask, ListValue, Struct, Timestamp, Value};
+
+/// Sizes of repeated and map fields.
+const SIZE: RangeInclusive<usize> = 0..=4;
+
+/// Seconds of `0001-01-01T00:00:00Z` and `9999-12-31T23:59:59Z`, the range of
+/// RFC 3339 timestamps.
+const TIMESTAMP_SECONDS: RangeInclusive<i64> = -62_135_596_800..=253_402_300_799;
+
+/// Seconds of about 10,000 years, the range of `google.protobuf.Duration`.
+const DURATION_SECONDS: i64 = 315_576_000_000;
+
+/// Seconds of durations an `f64` holds to the nanosecond.
+const PRECISE_DURATION_SECONDS: i64 = 4_000_000;
+
+pub fn repeated<T: Arbitrary>() -> impl Strategy<Value = Vec<T>> {
+    collection::vec(any::<T>(), SIZE)
+}
+
+pub fn repeated_of<S: Strategy>(element: S) -> impl Strategy<Value = Vec<S::Value>> {
+    collection::vec(element, SIZE)
+}
+
+pub fn map<K, V>() -> impl Strategy<Value = HashMap<K, V>>
+where
+    K: Arbitrary + Hash + Eq,
+    V: Arbitrary,
+{
+    collection::hash_map(any::<K>(), any::<V>(), SIZE)
+}
+
+pub fn map_of<K, S>(value: S) -> impl Strategy<Value = HashMap<K, S::Value>>
+where
+    K: Arbitrary + Hash + Eq,
+    S: Strategy,
+{
+    collection::hash_map(any::<K>(), value, SIZE)
+}
+
+/// One of the `values` of an enum, as the `i32` of enum fields.
+pub fn enum_value(values: &'static [i32]) -> impl Strategy<Value = i32> {
+    proptest::sample::select(values)
+}
+
+pub fn finite_f64() -> impl Strategy<Value = f64> {
+    use proptest::num::f64::{NEGATIVE, NORMAL, POSITIVE, SUBNORMAL, ZERO};
+    POSITIVE | NEGATIVE | NORMAL | SUBNORMAL | ZERO
+}
+
+pub fn finite_f32() -> impl Strategy<Value = f32> {
+    use proptest::num::f32::{NEGATIVE, NORMAL, POSITIVE, SUBNORMAL, ZERO};
+    POSITIVE | NEGATIVE | NORMAL | SUBNORMAL | ZERO
+}
+
+/// Timestamps between the years 1 and 9999.
+pub fn timestamp() -> impl Strategy<Value = Timestamp> {
+    (TIMESTAMP_SECONDS, 0..1_000_000_000).prop_map(|(seconds, nanos)| Timestamp { seconds, nanos })
+}
+
+/// Durations of whole seconds up to about 10,000 years either way, or of
+/// nanoseconds up to about 46 days. The `serde` impl formats durations
+/// through an `f64` and reads the fraction of negative ones as positive, so
+/// other durations do not survive JSON.
+pub fn duration() -> impl Strategy<Value = Duration> {
+    proptest::prop_oneof![
+        (-DURATION_SECONDS..=DURATION_SECONDS).prop_map(|seconds| Duration { seconds, nanos: 0 }),
+        (0..=PRECISE_DURATION_SECONDS, 0..1_000_000_000)
+            .prop_map(|(seconds, nanos)| Duration { seconds, nanos }),
+    ]
+}
+
+/// Masks of lower snake case paths, which survive the lower camel case of
+/// their JSON form.
+pub fn field_mask() -> impl Strategy<Value = FieldMask> {
+    repeated_of("[a-z]+(_[a-z]+)*(\\.[a-z]+(_[a-z]+)*)*").prop_map(|paths| FieldMask { paths })
+}
+
+pub fn value() -> impl Strategy<Value = Value> {
+    let leaf = proptest::prop_oneof![
+        Just(Value::null()),
+        finite_f64().prop_map(Value::number),
+        any::<String>().prop_map(Value::string),
+        any::<bool>().prop_map(Value::bool),
+    ];
+    leaf.prop_recursive(3, 16, *SIZE.end() as u32, |value| {
+        proptest::prop_oneof![
+            repeated_of(value.clone()).prop_map(Value::pb_list),
+            map_of(value).prop_map(Value::pb_struct),
+        ]
+    })
+}
+
+pub fn list_value() -> impl Strategy<Value = ListValue> {
+    repeated_of(value()).prop_map(|values| ListValue { values })
+}
+
+pub fn pb_struct() -> impl Strategy<Value = Struct> {
+    map_of(value()).prop_map(|fields| Struct { fields })
+}
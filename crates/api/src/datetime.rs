@@ -0,0 +1,415 @@
+//! Conversions between the `google.protobuf.Timestamp` and `Duration` of
+//! `prost-wkt-types` and the `std::time`, `chrono` and `time` types, failing
+//! outside of the range either side holds.
+//!
+//! They are traits rather than `From`/`TryFrom` impls because the orphan rule
+//! forbids implementing a foreign trait between two foreign types. The
+//! generated messages also get `<field>_as` and `set_<field>_from` methods
+//! for their `Timestamp` and `Duration` fields, built on these traits.
+
+use std::{
+    fmt,
+    time::{Duration as StdDuration, SystemTime, UNIX_EPOCH},
+};
+
+use prost_wkt_types::{Duration, Timestamp};
+
+/// Seconds of `0001-01-01T00:00:00Z`, the earliest valid `Timestamp`.
+const MIN_TIMESTAMP_SECONDS: i64 = -62_135_596_800;
+/// Seconds of `9999-12-31T23:59:59Z`, the latest valid `Timestamp`.
+const MAX_TIMESTAMP_SECONDS: i64 = 253_402_300_799;
+/// Seconds of 10000 years, the longest valid `Duration`.
+const MAX_DURATION_SECONDS: i64 = 315_576_000_000;
+const NANOS_PER_SECOND: i32 = 1_000_000_000;
+
+/// A time outside of the range of the type it is converted to, or a
+/// `Timestamp` or `Duration` outside of the range the protobuf spec allows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangeError {
+    pub target: &'static str,
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Time is out of the range of `{}`", self.target)
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+fn timestamp(seconds: i64, nanos: i32) -> Result<Timestamp, RangeError> {
+    let valid = (MIN_TIMESTAMP_SECONDS..=MAX_TIMESTAMP_SECONDS).contains(&seconds)
+        && (0..NANOS_PER_SECOND).contains(&nanos);
+    if !valid {
+        return Err(RangeError {
+            target: "google.protobuf.Timestamp",
+        });
+    }
+    Ok(Timestamp { seconds, nanos })
+}
+
+fn duration(seconds: i64, nanos: i32) -> Result<Duration, RangeError> {
+    let valid = (-MAX_DURATION_SECONDS..=MAX_DURATION_SECONDS).contains(&seconds)
+        && nanos.abs() < NANOS_PER_SECOND
+        && (seconds == 0 || nanos == 0 || (seconds < 0) == (nanos < 0));
+    if !valid {
+        return Err(RangeError {
+            target: "google.protobuf.Duration",
+        });
+    }
+    Ok(Duration { seconds, nanos })
+}
+
+/// Conversions of a `google.protobuf.Timestamp`, which fail when it is
+/// outside of the range of the protobuf spec or of the target type.
+pub trait TimestampExt {
+    fn to_system_time(&self) -> Result<SystemTime, RangeError>;
+    #[cfg(feature = "chrono")]
+    fn to_chrono(&self) -> Result<chrono::DateTime<chrono::Utc>, RangeError>;
+    #[cfg(feature = "time")]
+    fn to_offset_date_time(&self) -> Result<time::OffsetDateTime, RangeError>;
+}
+
+impl TimestampExt for Timestamp {
+    fn to_system_time(&self) -> Result<SystemTime, RangeError> {
+        let Timestamp { seconds, nanos } = timestamp(self.seconds, self.nanos)?;
+        let since_epoch = StdDuration::from_secs(seconds.unsigned_abs());
+        let time = if seconds >= 0 {
+            UNIX_EPOCH.checked_add(since_epoch)
+        } else {
+            UNIX_EPOCH.checked_sub(since_epoch)
+        };
+        time.and_then(|x| x.checked_add(StdDuration::from_nanos(nanos as u64)))
+            .ok_or(RangeError {
+                target: "SystemTime",
+            })
+    }
+
+    #[cfg(feature = "chrono")]
+    fn to_chrono(&self) -> Result<chrono::DateTime<chrono::Utc>, RangeError> {
+        let Timestamp { seconds, nanos } = timestamp(self.seconds, self.nanos)?;
+        chrono::DateTime::from_timestamp(seconds, nanos as u32).ok_or(RangeError {
+            target: "chrono::DateTime",
+        })
+    }
+
+    #[cfg(feature = "time")]
+    fn to_offset_date_time(&self) -> Result<time::OffsetDateTime, RangeError> {
+        let Timestamp { seconds, nanos } = timestamp(self.seconds, self.nanos)?;
+        let nanos = i128::from(seconds) * i128::from(NANOS_PER_SECOND) + i128::from(nanos);
+        time::OffsetDateTime::from_unix_timestamp_nanos(nanos).map_err(|_| RangeError {
+            target: "time::OffsetDateTime",
+        })
+    }
+}
+
+/// Conversion from a `google.protobuf.Timestamp`, see [`TimestampExt`].
+pub trait FromProtoTimestamp: Sized {
+    fn from_proto_timestamp(timestamp: &Timestamp) -> Result<Self, RangeError>;
+}
+
+impl FromProtoTimestamp for SystemTime {
+    fn from_proto_timestamp(timestamp: &Timestamp) -> Result<Self, RangeError> {
+        timestamp.to_system_time()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl FromProtoTimestamp for chrono::DateTime<chrono::Utc> {
+    fn from_proto_timestamp(timestamp: &Timestamp) -> Result<Self, RangeError> {
+        timestamp.to_chrono()
+    }
+}
+
+#[cfg(feature = "time")]
+impl FromProtoTimestamp for time::OffsetDateTime {
+    fn from_proto_timestamp(timestamp: &Timestamp) -> Result<Self, RangeError> {
+        timestamp.to_offset_date_time()
+    }
+}
+
+/// Conversion to a `google.protobuf.Timestamp`, which fails outside of the
+/// years 1 to 9999.
+pub trait ToTimestamp {
+    fn to_timestamp(&self) -> Result<Timestamp, RangeError>;
+}
+
+impl ToTimestamp for SystemTime {
+    fn to_timestamp(&self) -> Result<Timestamp, RangeError> {
+        let out_of_range = RangeError {
+            target: "google.protobuf.Timestamp",
+        };
+        match self.duration_since(UNIX_EPOCH) {
+            Ok(since_epoch) => timestamp(
+                i64::try_from(since_epoch.as_secs()).map_err(|_| out_of_range)?,
+                since_epoch.subsec_nanos() as i32,
+            ),
+            Err(error) => {
+                let before_epoch = error.duration();
+                let seconds = i64::try_from(before_epoch.as_secs()).map_err(|_| out_of_range)?;
+                match before_epoch.subsec_nanos() as i32 {
+                    0 => timestamp(-seconds, 0),
+                    nanos => timestamp(-seconds - 1, NANOS_PER_SECOND - nanos),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> ToTimestamp for chrono::DateTime<Tz> {
+    fn to_timestamp(&self) -> Result<Timestamp, RangeError> {
+        // Leap seconds are represented with nanoseconds past a second.
+        let nanos = self.timestamp_subsec_nanos() as i32;
+        if nanos >= NANOS_PER_SECOND {
+            timestamp(self.timestamp() + 1, nanos - NANOS_PER_SECOND)
+        } else {
+            timestamp(self.timestamp(), nanos)
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl ToTimestamp for time::OffsetDateTime {
+    fn to_timestamp(&self) -> Result<Timestamp, RangeError> {
+        timestamp(self.unix_timestamp(), self.nanosecond() as i32)
+    }
+}
+
+/// Conversions of a `google.protobuf.Duration`, which fail when it is
+/// outside of the range of the protobuf spec or of the target type.
+pub trait DurationExt {
+    /// Fails for negative durations, which `std::time::Duration` cannot hold.
+    fn to_std(&self) -> Result<StdDuration, RangeError>;
+    #[cfg(feature = "chrono")]
+    fn to_chrono(&self) -> Result<chrono::TimeDelta, RangeError>;
+    #[cfg(feature = "time")]
+    fn to_time(&self) -> Result<time::Duration, RangeError>;
+}
+
+impl DurationExt for Duration {
+    fn to_std(&self) -> Result<StdDuration, RangeError> {
+        let Duration { seconds, nanos } = duration(self.seconds, self.nanos)?;
+        if seconds < 0 || nanos < 0 {
+            return Err(RangeError {
+                target: "std::time::Duration",
+            });
+        }
+        Ok(StdDuration::new(seconds as u64, nanos as u32))
+    }
+
+    #[cfg(feature = "chrono")]
+    fn to_chrono(&self) -> Result<chrono::TimeDelta, RangeError> {
+        let Duration { seconds, nanos } = duration(self.seconds, self.nanos)?;
+        // `TimeDelta::new` takes nanoseconds to add to the seconds.
+        let (seconds, nanos) = if nanos < 0 {
+            (seconds - 1, nanos + NANOS_PER_SECOND)
+        } else {
+            (seconds, nanos)
+        };
+        chrono::TimeDelta::new(seconds, nanos as u32).ok_or(RangeError {
+            target: "chrono::TimeDelta",
+        })
+    }
+
+    #[cfg(feature = "time")]
+    fn to_time(&self) -> Result<time::Duration, RangeError> {
+        let Duration { seconds, nanos } = duration(self.seconds, self.nanos)?;
+        Ok(time::Duration::new(seconds, nanos))
+    }
+}
+
+/// Conversion from a `google.protobuf.Duration`, see [`DurationExt`].
+pub trait FromProtoDuration: Sized {
+    fn from_proto_duration(duration: &Duration) -> Result<Self, RangeError>;
+}
+
+impl FromProtoDuration for StdDuration {
+    fn from_proto_duration(duration: &Duration) -> Result<Self, RangeError> {
+        duration.to_std()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl FromProtoDuration for chrono::TimeDelta {
+    fn from_proto_duration(duration: &Duration) -> Result<Self, RangeError> {
+        duration.to_chrono()
+    }
+}
+
+#[cfg(feature = "time")]
+impl FromProtoDuration for time::Duration {
+    fn from_proto_duration(duration: &Duration) -> Result<Self, RangeError> {
+        duration.to_time()
+    }
+}
+
+/// Conversion to a `google.protobuf.Duration`, which fails beyond 10000
+/// years.
+pub trait ToProtoDuration {
+    fn to_proto_duration(&self) -> Result<Duration, RangeError>;
+}
+
+impl ToProtoDuration for StdDuration {
+    fn to_proto_duration(&self) -> Result<Duration, RangeError> {
+        let seconds = i64::try_from(self.as_secs()).map_err(|_| RangeError {
+            target: "google.protobuf.Duration",
+        })?;
+        duration(seconds, self.subsec_nanos() as i32)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ToProtoDuration for chrono::TimeDelta {
+    fn to_proto_duration(&self) -> Result<Duration, RangeError> {
+        duration(self.num_seconds(), self.subsec_nanos())
+    }
+}
+
+#[cfg(feature = "time")]
+impl ToProtoDuration for time::Duration {
+    fn to_proto_duration(&self) -> Result<Duration, RangeError> {
+        duration(self.whole_seconds(), self.subsec_nanoseconds())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_rejects_out_of_range_timestamps() {
+        for (seconds, nanos) in [
+            (MIN_TIMESTAMP_SECONDS - 1, 0),
+            (MAX_TIMESTAMP_SECONDS + 1, 0),
+            (0, -1),
+            (0, NANOS_PER_SECOND),
+        ] {
+            assert_eq!(
+                Timestamp { seconds, nanos }.to_system_time(),
+                Err(RangeError {
+                    target: "google.protobuf.Timestamp"
+                })
+            );
+        }
+        assert!(Timestamp {
+            seconds: MAX_TIMESTAMP_SECONDS,
+            nanos: NANOS_PER_SECOND - 1,
+        }
+        .to_system_time()
+        .is_ok());
+    }
+
+    #[test]
+    fn it_converts_pre_epoch_timestamps() {
+        let time = UNIX_EPOCH - StdDuration::new(1, 250_000_000);
+        let timestamp = time.to_timestamp().unwrap();
+        assert_eq!(
+            timestamp,
+            Timestamp {
+                seconds: -2,
+                nanos: 750_000_000,
+            }
+        );
+        assert_eq!(timestamp.to_system_time().unwrap(), time);
+        assert_eq!(
+            (UNIX_EPOCH - StdDuration::from_secs(3)).to_timestamp(),
+            Ok(Timestamp {
+                seconds: -3,
+                nanos: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn it_rejects_out_of_range_durations() {
+        for (seconds, nanos) in [
+            (MAX_DURATION_SECONDS + 1, 0),
+            (-MAX_DURATION_SECONDS - 1, 0),
+            (0, NANOS_PER_SECOND),
+            (1, -1),
+            (-1, 1),
+        ] {
+            assert_eq!(
+                Duration { seconds, nanos }.to_std(),
+                Err(RangeError {
+                    target: "google.protobuf.Duration"
+                })
+            );
+        }
+        assert_eq!(
+            Duration {
+                seconds: -1,
+                nanos: -500_000_000,
+            }
+            .to_std(),
+            Err(RangeError {
+                target: "std::time::Duration"
+            })
+        );
+        assert_eq!(
+            StdDuration::from_secs(u64::MAX).to_proto_duration(),
+            Err(RangeError {
+                target: "google.protobuf.Duration"
+            })
+        );
+    }
+
+    #[test]
+    fn it_round_trips_std_durations() {
+        let duration = StdDuration::new(5, 250_000_000);
+        let proto = duration.to_proto_duration().unwrap();
+        assert_eq!(
+            proto,
+            Duration {
+                seconds: 5,
+                nanos: 250_000_000,
+            }
+        );
+        assert_eq!(StdDuration::from_proto_duration(&proto), Ok(duration));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn it_normalizes_negative_chrono_durations() {
+        let proto = Duration {
+            seconds: -1,
+            nanos: -250_000_000,
+        };
+        let delta = proto.to_chrono().unwrap();
+        assert_eq!(delta, chrono::TimeDelta::milliseconds(-1250));
+        assert_eq!(delta.to_proto_duration(), Ok(proto));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn it_converts_pre_epoch_chrono_times() {
+        let timestamp = Timestamp {
+            seconds: -2,
+            nanos: 750_000_000,
+        };
+        let time = chrono::DateTime::<chrono::Utc>::from_proto_timestamp(&timestamp).unwrap();
+        assert_eq!(time.timestamp_millis(), -1250);
+        assert_eq!(time.to_timestamp(), Ok(timestamp));
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_converts_time_values() {
+        let timestamp = Timestamp {
+            seconds: -2,
+            nanos: 750_000_000,
+        };
+        let time = time::OffsetDateTime::from_proto_timestamp(&timestamp).unwrap();
+        assert_eq!(time.unix_timestamp_nanos(), -1_250_000_000);
+        assert_eq!(time.to_timestamp(), Ok(timestamp));
+
+        let proto = Duration {
+            seconds: -1,
+            nanos: -250_000_000,
+        };
+        let duration = time::Duration::from_proto_duration(&proto).unwrap();
+        assert_eq!(duration, time::Duration::milliseconds(-1250));
+        assert_eq!(duration.to_proto_duration(), Ok(proto));
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+
+use prost_wkt_types::FieldMask;
+
+/// A message whose fields can be selected with a `google.protobuf.FieldMask`.
+/// Implemented for the generated messages of packages with `field_masks`
+/// enabled, with paths of singular sub-messages of the same package reaching
+/// into their fields.
+pub trait FieldMasked: Default + Clone {
+    /// Fully qualified proto name of the message.
+    const FULL_NAME: &'static str;
+
+    /// Whether `path`, split on `.`, names a field of the message.
+    fn is_valid_path(path: &[&str]) -> bool;
+    /// Copies the field at the valid `path` from `source`.
+    fn copy_path(&mut self, source: &Self, path: &[&str]);
+    /// Clears the fields selected by none of the valid `paths`.
+    fn retain_paths(&mut self, paths: &[&[&str]]);
+}
+
+#[derive(Debug)]
+pub struct InvalidPathError {
+    pub message: String,
+    pub path: String,
+}
+
+impl fmt::Display for InvalidPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Field mask path `{}` does not name a field of `{}`",
+            self.path, self.message
+        )
+    }
+}
+
+impl std::error::Error for InvalidPathError {}
+
+/// Checks that every path of `mask` names a field of `T`.
+pub fn validate<T: FieldMasked>(mask: &FieldMask) -> Result<(), InvalidPathError> {
+    for path in &mask.paths {
+        if !T::is_valid_path(&split(path)) {
+            return Err(InvalidPathError {
+                message: T::FULL_NAME.to_owned(),
+                path: path.to_owned(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Copies the fields selected by `mask` from `source` into `target`, as an
+/// update RPC does, leaving the others unchanged.
+pub fn merge<T: FieldMasked>(
+    target: &mut T,
+    source: &T,
+    mask: &FieldMask,
+) -> Result<(), InvalidPathError> {
+    validate::<T>(mask)?;
+    for path in &mask.paths {
+        target.copy_path(source, &split(path));
+    }
+    Ok(())
+}
+
+/// Clears the fields of `message` not selected by `mask`, as a read RPC with
+/// a read mask does.
+pub fn retain<T: FieldMasked>(message: &mut T, mask: &FieldMask) -> Result<(), InvalidPathError> {
+    validate::<T>(mask)?;
+    let paths: Vec<Vec<&str>> = mask.paths.iter().map(|x| split(x)).collect();
+    let paths: Vec<&[&str]> = paths.iter().map(Vec::as_slice).collect();
+    message.retain_paths(&paths);
+    Ok(())
+}
+
+fn split(path: &str) -> Vec<&str> {
+    path.split('.').collect()
+}
+
+/// How the paths passed to [`FieldMasked::retain_paths`] select a field.
+#[doc(hidden)]
+pub enum Selection<'a, 'b> {
+    None,
+    Whole,
+    /// Paths of fields of the selected sub-message.
+    Fields(Vec<&'a [&'b str]>),
+}
+
+#[doc(hidden)]
+pub fn select<'a, 'b>(paths: &[&'a [&'b str]], field: &str) -> Selection<'a, 'b> {
+    let mut fields = Vec::new();
+    for path in paths {
+        match path.split_first() {
+            Some((name, [])) if *name == field => return Selection::Whole,
+            Some((name, rest)) if *name == field => fields.push(rest),
+            _ => {}
+        }
+    }
+    if fields.is_empty() {
+        Selection::None
+    } else {
+        Selection::Fields(fields)
+    }
+}
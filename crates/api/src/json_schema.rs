@@ -0,0 +1,88 @@
+//! JSON schemas of the well-known types of `prost-wkt-types`, matching their
+//! `serde` representation. Generated messages deriving `schemars::JsonSchema`
+//! refer to them with `#[schemars(with = "...")]` on fields of these types.
+
+use std::borrow::Cow;
+
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+
+macro_rules! well_known_schema {
+    ($(#[$meta:meta])* $name:ident, |$generator:ident| $schema:expr) => {
+        $(#[$meta])*
+        pub struct $name;
+
+        impl JsonSchema for $name {
+            fn schema_name() -> Cow<'static, str> {
+                Cow::Borrowed(stringify!($name))
+            }
+
+            fn schema_id() -> Cow<'static, str> {
+                Cow::Borrowed(concat!("google.protobuf.", stringify!($name)))
+            }
+
+            fn json_schema($generator: &mut SchemaGenerator) -> Schema {
+                $schema
+            }
+        }
+    };
+}
+
+well_known_schema!(
+    /// An object holding the `@type` URL of the packed message next to the
+    /// fields of the message.
+    Any,
+    |_generator| json_schema!({
+        "type": "object",
+        "properties": {
+            "@type": { "type": "string" },
+        },
+        "required": ["@type"],
+        "additionalProperties": true,
+    })
+);
+
+well_known_schema!(
+    /// Seconds with up to nine fractional digits and an `s` suffix, e.g.
+    /// `1.500000000s`.
+    Duration,
+    |_generator| json_schema!({
+        "type": "string",
+        "pattern": "^-?[0-9]+(\\.[0-9]{1,9})?s$",
+    })
+);
+
+well_known_schema!(FieldMask, |_generator| json_schema!({
+    "type": "object",
+    "properties": {
+        "paths": {
+            "type": "array",
+            "items": { "type": "string" },
+        },
+    },
+    "required": ["paths"],
+}));
+
+well_known_schema!(ListValue, |generator| json_schema!({
+    "type": "array",
+    "items": generator.subschema_for::<Value>(),
+}));
+
+well_known_schema!(Struct, |generator| json_schema!({
+    "type": "object",
+    "additionalProperties": generator.subschema_for::<Value>(),
+}));
+
+well_known_schema!(
+    /// An RFC 3339 date-time in UTC.
+    Timestamp,
+    |_generator| json_schema!({
+        "type": "string",
+        "format": "date-time",
+    })
+);
+
+well_known_schema!(
+    /// Any JSON value.
+    Value,
+    |_generator| Schema::from(true)
+);
@@ -1,12 +1,210 @@
+//! Macros including the code generated for API packages, the helpers that
+//! code uses, and the runtime dependencies the helpers are built with.
+//! Generated crates re-export their own runtime dependencies, which are the
+//! ones to use with their messages.
+
+#[cfg(feature = "prost-serde")]
+pub mod any;
+#[cfg(feature = "proptest")]
+pub mod arbitrary;
+#[cfg(feature = "prost-serde")]
+pub mod datetime;
+#[cfg(feature = "prost-serde")]
+pub mod field_mask;
+#[cfg(feature = "schemars")]
+pub mod json_schema;
+#[cfg(feature = "utoipa")]
+pub mod openapi;
+pub mod pagination;
+#[cfg(feature = "prost-serde")]
+pub mod registry;
+#[cfg(feature = "prost-serde")]
+pub mod sample;
+#[cfg(feature = "tonic")]
+pub mod status;
+#[cfg(feature = "text-format")]
+pub mod text_format;
+
+/// The `proptest` version of the `Arbitrary` helpers.
+#[cfg(feature = "proptest")]
+pub use proptest;
+/// The `prost` version the helpers are built with.
+#[cfg(feature = "prost-serde")]
+pub use prost;
+/// The `prost-reflect` version of the text format helpers, which generated
+/// messages are formatted as text with.
+#[cfg(feature = "text-format")]
+pub use prost_reflect;
+/// The well-known types the helpers use.
+#[cfg(feature = "prost-serde")]
+pub use prost_wkt_types;
+/// The `schemars` version of the JSON schema helpers.
+#[cfg(feature = "schemars")]
+pub use schemars;
+/// The `serde` version the helpers are built with.
+#[cfg(feature = "prost-serde")]
+pub use serde;
+/// The `tonic` version of the status and pagination helpers.
+#[cfg(feature = "tonic")]
+pub use tonic;
+/// The `utoipa` version of the OpenAPI helpers.
+#[cfg(feature = "utoipa")]
+pub use utoipa;
+
+/// Includes the generated bindings of the package from `OUT_DIR` as modules.
+///
+/// Without arguments, includes the `prost_serde` module when the
+/// `prost-serde` feature is enabled. Otherwise takes the backends to include,
+/// optionally followed by the subpath of `OUT_DIR` they were generated into,
+/// or by the directory relative to the crate root holding checked-in
+/// generated code:
+///
+/// ```ignore
+/// appbiotic_api::bindings!(prost_serde; out_dir = "protos");
+/// appbiotic_api::bindings!(prost_serde; dir = "src/gen");
+/// ```
+///
+/// The `prost_serde` backend holds the `prost` messages with their `serde`
+/// implementations and the `tonic` clients and servers of the package.
 #[macro_export]
 macro_rules! bindings {
     () => {
         #[cfg(feature = "prost-serde")]
+        $crate::bindings!(prost_serde);
+    };
+    ($($backend:ident),+ $(,)?) => {
+        $($crate::__bindings_backend!($backend, "OUT_DIR", "", "");)+
+    };
+    ($($backend:ident),+ ; out_dir = $out_dir:literal $(,)?) => {
+        $($crate::__bindings_backend!($backend, "OUT_DIR", "/", $out_dir);)+
+    };
+    ($($backend:ident),+ ; dir = $dir:literal $(,)?) => {
+        $($crate::__bindings_backend!($backend, "CARGO_MANIFEST_DIR", "/", $dir);)+
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bindings_backend {
+    (prost_serde, $root:literal, $separator:literal, $dir:literal) => {
         pub mod prost_serde {
             include!(concat!(
-                env!("OUT_DIR"),
+                env!($root),
+                $separator,
+                $dir,
                 "/appbiotic_api_prost_serde_build/_index.rs"
             ));
         }
     };
+    ($backend:ident, $root:literal, $separator:literal, $dir:literal) => {
+        compile_error!(concat!(
+            "Unsupported bindings backend `",
+            stringify!($backend),
+            "`, expected `prost_serde`, which also holds the `tonic` services"
+        ));
+    };
+}
+
+/// Includes the encoded `FileDescriptorSet` of the package generated into
+/// `OUT_DIR` as `pub const FILE_DESCRIPTOR_SET: &[u8]`, for registering with
+/// `tonic-reflection` or decoding with `prost-reflect`. Takes the same
+/// optional `out_dir` subpath or checked-in `dir` as [`bindings!`]:
+///
+/// ```ignore
+/// appbiotic_api::descriptor!(out_dir = "protos");
+/// ```
+#[macro_export]
+macro_rules! descriptor {
+    () => {
+        $crate::__descriptor!("OUT_DIR", "", "");
+    };
+    (out_dir = $out_dir:literal $(,)?) => {
+        $crate::__descriptor!("OUT_DIR", "/", $out_dir);
+    };
+    (dir = $dir:literal $(,)?) => {
+        $crate::__descriptor!("CARGO_MANIFEST_DIR", "/", $dir);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __descriptor {
+    ($root:literal, $separator:literal, $dir:literal) => {
+        pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!(concat!(
+            env!($root),
+            $separator,
+            $dir,
+            "/appbiotic_api_prost_serde_build/_descriptor.binpb"
+        ));
+    };
+}
+
+/// Includes the services of the package generated into `OUT_DIR` as the
+/// `services` module: their full names, the paths of their methods and
+/// `add_servers`, which adds servers for all of them to a `tonic` router. Must
+/// be invoked next to [`bindings!`], whose `prost_serde` module it refers to,
+/// and takes the same optional `out_dir` subpath or checked-in `dir`:
+///
+/// ```ignore
+/// appbiotic_api::bindings!(prost_serde);
+/// appbiotic_api::services!();
+///
+/// let router = Server::builder().add_service(health_service);
+/// services::add_servers(router, WidgetServiceImpl).serve(addr).await?;
+/// ```
+#[macro_export]
+macro_rules! services {
+    () => {
+        $crate::__services!("OUT_DIR", "", "");
+    };
+    (out_dir = $out_dir:literal $(,)?) => {
+        $crate::__services!("OUT_DIR", "/", $out_dir);
+    };
+    (dir = $dir:literal $(,)?) => {
+        $crate::__services!("CARGO_MANIFEST_DIR", "/", $dir);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __services {
+    ($root:literal, $separator:literal, $dir:literal) => {
+        pub mod services {
+            include!(concat!(
+                env!($root),
+                $separator,
+                $dir,
+                "/appbiotic_api_prost_serde_build/_services.rs"
+            ));
+        }
+    };
+}
+
+/// Builds a [`TypeRegistry`](crate::registry::TypeRegistry) of the messages
+/// of the listed generated crates, which must enable their `prost-serde`
+/// feature:
+///
+/// ```ignore
+/// let registry = appbiotic_api::type_registry!(acme_widgets, acme_gadgets);
+/// let message = registry.unpack(&any)?;
+/// ```
+#[macro_export]
+macro_rules! type_registry {
+    ($($crate_name:ident),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut registry = $crate::registry::TypeRegistry::default();
+        $(
+            registry
+                .register(
+                    stringify!($crate_name),
+                    $crate_name::prost_serde::FILE_DESCRIPTOR_SET,
+                )
+                .expect(concat!(
+                    "Failed to decode descriptor set of `",
+                    stringify!($crate_name),
+                    "`"
+                ));
+        )*
+        registry
+    }};
 }
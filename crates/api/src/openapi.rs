@@ -0,0 +1,224 @@
+//! OpenAPI schemas of the well-known types of `prost-wkt-types`, matching
+//! their `serde` representation. Generated messages deriving
+//! `utoipa::ToSchema` refer to them with `#[schema(value_type = ...)]` on
+//! fields of these types.
+//!
+//! Generated messages name their schemas with `#[schema(as = ...)]` paths,
+//! which escape package segments that are Rust keywords, such as the `type`
+//! of `google.r#type.LatLng`. [ProtoSchemaNames] renames those schemas to
+//! their full proto names.
+
+use std::borrow::Cow;
+
+use utoipa::{
+    openapi::{
+        schema::{AdditionalProperties, ArrayBuilder, ObjectBuilder, SchemaType, Type},
+        KnownFormat, OpenApi, RefOr, Schema, SchemaFormat,
+    },
+    Modify, PartialSchema, ToSchema,
+};
+
+const SCHEMA_REF_PREFIX: &str = "#/components/schemas/";
+
+macro_rules! well_known_schema {
+    (
+        $(#[$meta:meta])* $name:ident,
+        || $schema:expr
+        $(, refers_to = $referenced:ty)? $(,)?
+    ) => {
+        $(#[$meta])*
+        pub struct $name;
+
+        impl PartialSchema for $name {
+            fn schema() -> RefOr<Schema> {
+                $schema.into()
+            }
+        }
+
+        impl ToSchema for $name {
+            fn name() -> Cow<'static, str> {
+                Cow::Borrowed(concat!("google.protobuf.", stringify!($name)))
+            }
+
+            fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+                $(
+                    schemas.push((<$referenced>::name().into_owned(), <$referenced>::schema()));
+                    <$referenced as ToSchema>::schemas(schemas);
+                )?
+                let _ = schemas;
+            }
+        }
+    };
+}
+
+well_known_schema!(
+    /// An object holding the `@type` URL of the packed message next to the
+    /// fields of the message.
+    Any,
+    || ObjectBuilder::new()
+        .property("@type", ObjectBuilder::new().schema_type(Type::String))
+        .required("@type")
+        .additional_properties(Some(AdditionalProperties::FreeForm(true)))
+);
+
+well_known_schema!(
+    /// Seconds with up to nine fractional digits and an `s` suffix, e.g.
+    /// `1.500000000s`.
+    Duration,
+    || ObjectBuilder::new()
+        .schema_type(Type::String)
+        .pattern(Some("^-?[0-9]+(\\.[0-9]{1,9})?s$"))
+);
+
+well_known_schema!(FieldMask, || ObjectBuilder::new()
+    .property(
+        "paths",
+        ArrayBuilder::new().items(ObjectBuilder::new().schema_type(Type::String)),
+    )
+    .required("paths"));
+
+well_known_schema!(
+    ListValue,
+    || ArrayBuilder::new().items(Value::name_ref()),
+    refers_to = Value,
+);
+
+well_known_schema!(
+    Struct,
+    || ObjectBuilder::new().additional_properties(Some(Value::name_ref())),
+    refers_to = Value,
+);
+
+well_known_schema!(
+    /// An RFC 3339 date-time in UTC.
+    Timestamp,
+    || ObjectBuilder::new()
+        .schema_type(Type::String)
+        .format(Some(SchemaFormat::KnownFormat(KnownFormat::DateTime)))
+);
+
+well_known_schema!(
+    /// Any JSON value.
+    Value,
+    || ObjectBuilder::new().schema_type(SchemaType::AnyValue)
+);
+
+impl Value {
+    fn name_ref() -> RefOr<Schema> {
+        RefOr::Ref(utoipa::openapi::Ref::from_schema_name(Self::name()))
+    }
+}
+
+/// Renames the component schemas of `utoipa` documents named with escaped
+/// Rust keywords, e.g. `google.r#type.LatLng`, to their full proto names,
+/// e.g. `google.type.LatLng`, along with the references to them:
+///
+/// ```ignore
+/// #[derive(OpenApi)]
+/// #[openapi(components(schemas(LatLng)), modifiers(&ProtoSchemaNames))]
+/// struct ApiDoc;
+/// ```
+pub struct ProtoSchemaNames;
+
+impl Modify for ProtoSchemaNames {
+    fn modify(&self, openapi: &mut OpenApi) {
+        let Ok(mut document) = serde_json::to_value(&*openapi) else {
+            return;
+        };
+        if let Some(schemas) = document
+            .pointer_mut("/components/schemas")
+            .and_then(serde_json::Value::as_object_mut)
+        {
+            *schemas = std::mem::take(schemas)
+                .into_iter()
+                .map(|(name, schema)| (proto_schema_name(&name), schema))
+                .collect();
+        }
+        unescape_schema_refs(&mut document);
+        if let Ok(renamed) = serde_json::from_value(document) {
+            *openapi = renamed;
+        }
+    }
+}
+
+/// The full proto name of the schema named `name`.
+fn proto_schema_name(name: &str) -> String {
+    name.split('.')
+        .map(|x| x.strip_prefix("r#").unwrap_or(x))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Unescapes the schema names of the references below `value`.
+fn unescape_schema_refs(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (key, value) in object {
+                match value {
+                    serde_json::Value::String(reference) if key.eq("$ref") => {
+                        if let Some(name) = reference.strip_prefix(SCHEMA_REF_PREFIX) {
+                            *reference = format!("{SCHEMA_REF_PREFIX}{}", proto_schema_name(name));
+                        }
+                    }
+                    value => unescape_schema_refs(value),
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(unescape_schema_refs),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use utoipa::OpenApi as _;
+
+    use super::*;
+
+    #[allow(dead_code)]
+    #[derive(utoipa::ToSchema)]
+    #[schema(as = google::r#type::LatLng)]
+    struct LatLng {
+        latitude: f64,
+        longitude: f64,
+    }
+
+    #[allow(dead_code)]
+    #[derive(utoipa::ToSchema)]
+    #[schema(as = acme::places::v1::Place)]
+    struct Place {
+        name: String,
+        location: LatLng,
+        #[schema(value_type = Timestamp)]
+        visited: (),
+    }
+
+    #[derive(utoipa::OpenApi)]
+    #[openapi(components(schemas(Place)), modifiers(&ProtoSchemaNames))]
+    struct ApiDoc;
+
+    #[test]
+    fn it_names_schemas_of_keyword_packages_by_proto_name() {
+        let openapi = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let schemas = openapi["components"]["schemas"].as_object().unwrap();
+        let mut names: Vec<&str> = schemas.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "acme.places.v1.Place",
+                "google.protobuf.Timestamp",
+                "google.type.LatLng"
+            ]
+        );
+        let place = &schemas["acme.places.v1.Place"]["properties"];
+        assert_eq!(
+            place["location"]["$ref"],
+            "#/components/schemas/google.type.LatLng"
+        );
+        assert_eq!(
+            place["visited"]["$ref"],
+            "#/components/schemas/google.protobuf.Timestamp"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+/// A list request paginated with `page_size` and `page_token` fields, as in
+/// AIP-158. Implemented for the generated messages having both fields.
+pub trait Paginated {
+    fn page_size(&self) -> i32;
+    fn page_token(&self) -> &str;
+    fn set_page_token(&mut self, page_token: String);
+}
+
+/// A page of a list response, with a `next_page_token` field that is empty on
+/// the last page. Implemented for the generated messages having the field,
+/// with their first repeated field as the items.
+pub trait Page {
+    type Item;
+
+    fn next_page_token(&self) -> &str;
+    fn into_items(self) -> Vec<Self::Item>;
+}
+
+#[cfg(feature = "tonic")]
+mod stream {
+    use std::future::Future;
+
+    use futures_util::{stream, Stream, TryStreamExt};
+
+    use super::{Page, Paginated};
+
+    /// Pages of a list RPC, starting at `request` and calling `call` with the
+    /// request for each following page until the last one:
+    ///
+    /// ```ignore
+    /// let pages = appbiotic_api::pagination::pages(request, |request| {
+    ///     let mut client = client.clone();
+    ///     async move { client.list_widgets(request).await }
+    /// });
+    /// ```
+    pub fn pages<Req, Resp, F, Fut>(
+        request: Req,
+        call: F,
+    ) -> impl Stream<Item = Result<Resp, tonic::Status>>
+    where
+        Req: Paginated + Clone,
+        Resp: Page,
+        F: FnMut(Req) -> Fut,
+        Fut: Future<Output = Result<tonic::Response<Resp>, tonic::Status>>,
+    {
+        stream::try_unfold((Some(request), call), |(request, mut call)| async move {
+            let Some(mut request) = request else {
+                return Ok(None);
+            };
+            let page = call(request.clone()).await?.into_inner();
+            let next_request = (!page.next_page_token().is_empty()).then(|| {
+                request.set_page_token(page.next_page_token().to_owned());
+                request
+            });
+            Ok(Some((page, (next_request, call))))
+        })
+    }
+
+    /// Items of all pages of a list RPC, see [`pages`].
+    pub fn items<Req, Resp, F, Fut>(
+        request: Req,
+        call: F,
+    ) -> impl Stream<Item = Result<Resp::Item, tonic::Status>>
+    where
+        Req: Paginated + Clone,
+        Resp: Page,
+        F: FnMut(Req) -> Fut,
+        Fut: Future<Output = Result<tonic::Response<Resp>, tonic::Status>>,
+    {
+        pages(request, call)
+            .map_ok(|page| stream::iter(page.into_items().into_iter().map(Ok)))
+            .try_flatten()
+    }
+}
+
+#[cfg(feature = "tonic")]
+pub use stream::{items, pages};
@@ -0,0 +1,124 @@
+use std::{collections::BTreeMap, fmt};
+
+use prost::{DecodeError, Message};
+use prost_types::{DescriptorProto, FileDescriptorSet};
+use prost_wkt::{MessageSerde, MessageSerdeDecoderEntry};
+use prost_wkt_types::Any;
+
+type Decoder = fn(&[u8]) -> Result<Box<dyn MessageSerde>, DecodeError>;
+
+/// Domain of the type URLs the generated messages are packed with.
+pub const TYPE_URL_DOMAIN: &str = "type.googleapis.com";
+
+/// Messages of several generated crates by type URL, to unpack `Any` payloads
+/// of any of them. Built with [`type_registry!`](crate::type_registry).
+#[derive(Default)]
+pub struct TypeRegistry {
+    types: BTreeMap<String, RegisteredType>,
+}
+
+pub struct RegisteredType {
+    /// Name of the crate the message was generated into.
+    pub crate_name: &'static str,
+    /// Fully qualified proto name of the message, e.g. `acme.widgets.v1.Widget`.
+    pub full_name: String,
+    pub descriptor: DescriptorProto,
+    decoder: Option<Decoder>,
+}
+
+#[derive(Debug)]
+pub enum UnpackError {
+    /// No registered message has the type URL.
+    UnknownType(String),
+    /// The message is registered but its Rust type is not linked in.
+    MissingDecoder(String),
+    Decode(DecodeError),
+}
+
+impl fmt::Display for UnpackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownType(type_url) => write!(f, "No message registered for `{type_url}`"),
+            Self::MissingDecoder(type_url) => write!(f, "No decoder linked for `{type_url}`"),
+            Self::Decode(error) => write!(f, "Failed to decode message: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for UnpackError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decode(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl TypeRegistry {
+    /// Registers the messages of the encoded `file_descriptor_set` of the
+    /// crate named `crate_name`. Messages already registered by an earlier
+    /// crate keep their registration.
+    pub fn register(
+        &mut self,
+        crate_name: &'static str,
+        file_descriptor_set: &[u8],
+    ) -> Result<(), DecodeError> {
+        let decoders: BTreeMap<&str, _> = prost_wkt::inventory::iter::<MessageSerdeDecoderEntry>
+            .into_iter()
+            .map(|x| (x.type_url, x.decoder))
+            .collect();
+        let file_descriptor_set = FileDescriptorSet::decode(file_descriptor_set)?;
+        for file in file_descriptor_set.file {
+            let package = file.package().to_owned();
+            let mut messages: Vec<(String, DescriptorProto)> = file
+                .message_type
+                .into_iter()
+                .map(|x| (format!("{package}.{}", x.name()), x))
+                .collect();
+            while let Some((full_name, mut descriptor)) = messages.pop() {
+                for nested in std::mem::take(&mut descriptor.nested_type) {
+                    messages.push((format!("{full_name}.{}", nested.name()), nested));
+                }
+                let map_entry = descriptor
+                    .options
+                    .as_ref()
+                    .is_some_and(|x| x.map_entry.unwrap_or_default());
+                if map_entry {
+                    continue;
+                }
+                let type_url = format!("{TYPE_URL_DOMAIN}/{full_name}");
+                let decoder = decoders.get(type_url.as_str()).copied();
+                self.types.entry(type_url).or_insert(RegisteredType {
+                    crate_name,
+                    full_name,
+                    descriptor,
+                    decoder,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, type_url: &str) -> Option<&RegisteredType> {
+        self.types.get(type_url)
+    }
+
+    /// Registered messages by type URL.
+    pub fn types(&self) -> impl Iterator<Item = (&str, &RegisteredType)> {
+        self.types
+            .iter()
+            .map(|(type_url, x)| (type_url.as_str(), x))
+    }
+
+    /// Decodes the message packed in `any`, which can then be serialized or
+    /// downcast to its generated type.
+    pub fn unpack(&self, any: &Any) -> Result<Box<dyn MessageSerde>, UnpackError> {
+        let registered = self
+            .get(&any.type_url)
+            .ok_or_else(|| UnpackError::UnknownType(any.type_url.to_owned()))?;
+        let decoder = registered
+            .decoder
+            .ok_or_else(|| UnpackError::MissingDecoder(any.type_url.to_owned()))?;
+        decoder(&any.value).map_err(UnpackError::Decode)
+    }
+}
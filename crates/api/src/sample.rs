@@ -0,0 +1,607 @@
+//! Deterministic, realistic looking messages for demos, fixtures, and
+//! contract tests. The generated messages of packages with `samples` enabled
+//! implement [`Sample`], choosing the value of each field by its name and
+//! type, and by the `google.api.resource` patterns and `google.api.field_info`
+//! formats of the protos.
+
+use std::{collections::HashMap, hash::Hash};
+
+use prost_wkt_types::{Duration, ListValue, Struct, Timestamp, Value};
+
+/// A message with sample instances. A seed gives the same sample on every
+/// build and platform, and adding a field leaves the values of the others
+/// unchanged.
+pub trait Sample: Sized {
+    fn sample_from(sampler: &Sampler) -> Self;
+
+    /// The sample of seed `0`.
+    fn sample() -> Self {
+        Self::fake(0)
+    }
+
+    fn fake(seed: u64) -> Self {
+        Self::sample_from(&Sampler::new(seed))
+    }
+}
+
+/// The seed of a sample message, handing out the values of its fields.
+#[derive(Clone, Copy, Debug)]
+pub struct Sampler {
+    seed: u64,
+}
+
+impl Sampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// The values of the field `name`, independent of those of other fields.
+    pub fn field(&self, name: &'static str) -> Field {
+        Field {
+            name,
+            state: mix(self.seed ^ fnv1a(name)),
+        }
+    }
+}
+
+/// The values of a field, looking like what its name suggests.
+#[derive(Clone, Debug)]
+pub struct Field {
+    name: &'static str,
+    state: u64,
+}
+
+const FIRST_NAMES: &[&str] = &[
+    "Ada", "Alan", "Amara", "Bruno", "Chen", "Diego", "Elena", "Farah", "Grace", "Hiro", "Ines",
+    "Jonas", "Kofi", "Lena", "Mateo", "Nadia", "Omar", "Priya", "Sofia", "Tomas",
+];
+const LAST_NAMES: &[&str] = &[
+    "Andersen", "Baker", "Costa", "Dubois", "Evans", "Fischer", "Garcia", "Hughes", "Ito",
+    "Jensen", "Kim", "Lopez", "Moreau", "Novak", "Okafor", "Patel", "Rossi", "Schmidt", "Tanaka",
+    "Weber",
+];
+const ADJECTIVES: &[&str] = &[
+    "amber", "bright", "calm", "clever", "crimson", "eager", "gentle", "golden", "hidden", "lucky",
+    "misty", "quiet", "rapid", "silver", "steady", "sunny", "swift", "vivid", "wild", "young",
+];
+const NOUNS: &[&str] = &[
+    "anchor", "canyon", "comet", "falcon", "forest", "harbor", "island", "lantern", "meadow",
+    "nebula", "orchard", "pebble", "prairie", "river", "summit", "thunder", "valley", "willow",
+    "window", "zephyr",
+];
+const CITIES: &[&str] = &[
+    "Amsterdam",
+    "Berlin",
+    "Buenos Aires",
+    "Cape Town",
+    "Lisbon",
+    "Melbourne",
+    "Montreal",
+    "Nairobi",
+    "Osaka",
+    "Seattle",
+    "Seoul",
+    "Stockholm",
+];
+const COUNTRIES: &[(&str, &str)] = &[
+    ("AU", "Australia"),
+    ("BR", "Brazil"),
+    ("CA", "Canada"),
+    ("DE", "Germany"),
+    ("ES", "Spain"),
+    ("FR", "France"),
+    ("IN", "India"),
+    ("JP", "Japan"),
+    ("KE", "Kenya"),
+    ("US", "United States"),
+];
+const LANGUAGES: &[&str] = &["de", "en", "es", "fr", "hi", "ja", "ko", "pt", "sv", "sw"];
+const CURRENCIES: &[&str] = &[
+    "AUD", "BRL", "CAD", "EUR", "GBP", "INR", "JPY", "KES", "USD",
+];
+const TIME_ZONES: &[&str] = &[
+    "America/New_York",
+    "America/Sao_Paulo",
+    "Asia/Kolkata",
+    "Asia/Tokyo",
+    "Australia/Sydney",
+    "Europe/Berlin",
+    "Europe/Lisbon",
+    "UTC",
+];
+const STREETS: &[&str] = &[
+    "Birch", "Cedar", "Elm", "Harbor", "Hill", "Lake", "Maple", "Mill", "Oak", "Park", "River",
+];
+const COMPANIES: &[&str] = &[
+    "Acme Corp",
+    "Blue Harbor Labs",
+    "Globex",
+    "Initech",
+    "Northwind Traders",
+    "Stark Industries",
+    "Umbrella Systems",
+    "Wayne Enterprises",
+];
+const COLORS: &[&str] = &[
+    "amber", "azure", "coral", "crimson", "emerald", "indigo", "ivory", "olive", "teal", "violet",
+];
+const MIME_TYPES: &[&str] = &[
+    "application/json",
+    "application/pdf",
+    "image/jpeg",
+    "image/png",
+    "text/csv",
+    "text/plain",
+];
+const FILE_EXTENSIONS: &[&str] = &["csv", "json", "pdf", "png", "txt"];
+const KEYS: &[&str] = &["env", "owner", "region", "team", "tier", "zone"];
+
+impl Field {
+    /// One of `0..n`, as the index of the oneof variant to set.
+    pub fn choose(&mut self, n: usize) -> usize {
+        self.below(n as u64) as usize
+    }
+
+    pub fn string(&mut self) -> String {
+        if self.is(&["email", "email_address"]) {
+            let (first, last) = (self.pick(FIRST_NAMES), self.pick(LAST_NAMES));
+            format!("{first}.{last}@example.com").to_ascii_lowercase()
+        } else if self.is(&["url", "uri", "website", "homepage", "link", "href"]) {
+            let path = self.slug();
+            format!("https://example.com/{path}")
+        } else if self.is(&["phone", "phone_number", "mobile", "telephone"]) {
+            format!("+1-555-01{:02}", self.below(100))
+        } else if self.is(&["first_name", "given_name", "forename"]) {
+            self.pick(FIRST_NAMES).to_owned()
+        } else if self.is(&["last_name", "family_name", "surname"]) {
+            self.pick(LAST_NAMES).to_owned()
+        } else if self.is(&["username", "user_name", "login", "handle", "nickname"]) {
+            let (first, last) = (self.pick(FIRST_NAMES), self.pick(LAST_NAMES));
+            format!("{first}.{last}{}", self.below(100)).to_ascii_lowercase()
+        } else if self.is(&[
+            "full_name",
+            "display_name",
+            "author",
+            "owner",
+            "contact",
+            "person",
+        ]) {
+            let (first, last) = (self.pick(FIRST_NAMES), self.pick(LAST_NAMES));
+            format!("{first} {last}")
+        } else if self.is(&["title", "subject", "headline", "caption"]) {
+            let (adjective, noun) = (self.pick(ADJECTIVES), self.pick(NOUNS));
+            format!("The {} {}", capitalize(adjective), capitalize(noun))
+        } else if self.is(&[
+            "description",
+            "summary",
+            "comment",
+            "note",
+            "body",
+            "text",
+            "message",
+            "content",
+            "bio",
+            "detail",
+            "reason",
+        ]) {
+            self.sentence()
+        } else if self.is(&["city", "town"]) {
+            self.pick(CITIES).to_owned()
+        } else if self.is(&["country_code", "region_code"]) {
+            self.pick(COUNTRIES).0.to_owned()
+        } else if self.is(&["country"]) {
+            self.pick(COUNTRIES).1.to_owned()
+        } else if self.is(&["language", "language_code", "lang"]) {
+            self.pick(LANGUAGES).to_owned()
+        } else if self.is(&["locale"]) {
+            let (language, country) = (self.pick(LANGUAGES), self.pick(COUNTRIES).0);
+            format!("{language}-{country}")
+        } else if self.is(&["currency", "currency_code"]) {
+            self.pick(CURRENCIES).to_owned()
+        } else if self.is(&["time_zone", "timezone", "tz"]) {
+            self.pick(TIME_ZONES).to_owned()
+        } else if self.is(&["address", "street", "street_address", "address_line"]) {
+            let number = 1 + self.below(999);
+            format!("{number} {} Street", self.pick(STREETS))
+        } else if self.is(&["postal_code", "zip", "zip_code", "postcode"]) {
+            format!("{:05}", 10_000 + self.below(90_000))
+        } else if self.is(&[
+            "company",
+            "organization",
+            "employer",
+            "vendor",
+            "publisher",
+            "brand",
+        ]) {
+            self.pick(COMPANIES).to_owned()
+        } else if self.is(&["color", "colour"]) {
+            self.pick(COLORS).to_owned()
+        } else if self.is(&["id", "uuid", "guid"]) {
+            self.uuid()
+        } else if self.is(&[
+            "token",
+            "etag",
+            "secret",
+            "api_key",
+            "hash",
+            "checksum",
+            "digest",
+            "nonce",
+            "signature",
+        ]) {
+            self.hex(16)
+        } else if self.is(&["ip", "ip_address", "ipv4"]) {
+            self.ipv4()
+        } else if self.is(&["ipv6"]) {
+            self.ipv6()
+        } else if self.is(&["host", "hostname", "domain", "server"]) {
+            format!("{}.example.com", self.pick(NOUNS))
+        } else if self.is(&["version"]) {
+            format!(
+                "{}.{}.{}",
+                1 + self.below(3),
+                self.below(10),
+                self.below(20)
+            )
+        } else if self.is(&["file_name", "filename"]) {
+            let name = self.slug();
+            format!("{name}.{}", self.pick(FILE_EXTENSIONS))
+        } else if self.is(&["path", "file_path", "filepath"]) {
+            let (dir, name) = (self.pick(NOUNS), self.slug());
+            format!("/data/{dir}/{name}.{}", self.pick(FILE_EXTENSIONS))
+        } else if self.is(&["mime_type", "content_type", "media_type"]) {
+            self.pick(MIME_TYPES).to_owned()
+        } else if self.is(&["sku", "code", "serial", "serial_number"]) {
+            let letters = [self.letter(), self.letter()];
+            format!("{}{}-{:04}", letters[0], letters[1], self.below(10_000))
+        } else if self.is(&["password", "passphrase"]) {
+            let (adjective, noun) = (self.pick(ADJECTIVES), self.pick(NOUNS));
+            format!("{adjective}-{noun}-{}", self.below(100))
+        } else if self.is(&[
+            "tag", "label", "keyword", "category", "topic", "kind", "type", "genre",
+        ]) {
+            self.pick(NOUNS).to_owned()
+        } else if self.is(&["slug"]) {
+            self.slug()
+        } else if self.is(&["name"]) {
+            let (adjective, noun) = (self.pick(ADJECTIVES), self.pick(NOUNS));
+            format!("{} {}", capitalize(adjective), capitalize(noun))
+        } else {
+            let (adjective, noun) = (self.pick(ADJECTIVES), self.pick(NOUNS));
+            format!("{adjective} {noun}")
+        }
+    }
+
+    /// A string map key.
+    pub fn key(&mut self) -> String {
+        self.pick(KEYS).to_owned()
+    }
+
+    pub fn int64(&mut self) -> i64 {
+        let (low, high) = self.integer_range();
+        self.between(low, high)
+    }
+
+    pub fn int32(&mut self) -> i32 {
+        self.int64() as i32
+    }
+
+    pub fn uint64(&mut self) -> u64 {
+        self.int64() as u64
+    }
+
+    pub fn uint32(&mut self) -> u32 {
+        self.int64() as u32
+    }
+
+    pub fn double(&mut self) -> f64 {
+        let (low, high, decimals) = if self.is(&["latitude", "lat"]) {
+            (-90.0, 90.0, 5)
+        } else if self.is(&["longitude", "lng", "lon", "long"]) {
+            (-180.0, 180.0, 5)
+        } else if self.is(&["ratio", "fraction", "probability", "confidence", "share"]) {
+            (0.0, 1.0, 3)
+        } else if self.is(&["percent", "percentage"]) {
+            (0.0, 100.0, 1)
+        } else if self.is(&[
+            "price", "cost", "amount", "balance", "total", "fee", "salary",
+        ]) {
+            (1.0, 1000.0, 2)
+        } else if self.is(&["temperature", "temp"]) {
+            (-10.0, 40.0, 1)
+        } else if self.is(&["rating", "score"]) {
+            (0.0, 5.0, 1)
+        } else {
+            (0.0, 1000.0, 2)
+        };
+        let unit = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        let scale = 10f64.powi(decimals);
+        ((low + (high - low) * unit) * scale).round() / scale
+    }
+
+    pub fn float(&mut self) -> f32 {
+        self.double() as f32
+    }
+
+    pub fn bool(&mut self) -> bool {
+        self.next() & 1 == 1
+    }
+
+    pub fn bytes(&mut self) -> Vec<u8> {
+        let len = if self.is(&["hash", "checksum", "digest", "signature"]) {
+            32
+        } else {
+            16
+        };
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+
+    /// One of the `values` of an enum, other than the zero value when it has
+    /// others, as the `i32` of enum fields.
+    pub fn enum_value(&mut self, values: &[i32]) -> i32 {
+        match values
+            .iter()
+            .copied()
+            .filter(|x| *x != 0)
+            .collect::<Vec<_>>()
+        {
+            non_zero if !non_zero.is_empty() => self.pick(&non_zero),
+            _ => values.first().copied().unwrap_or_default(),
+        }
+    }
+
+    /// Whole seconds in the 2020s, or between 1950 and 2005 for birth dates.
+    pub fn timestamp(&mut self) -> Timestamp {
+        let seconds = if self.is(&["birth", "birthday", "birth_date", "date_of_birth", "dob"]) {
+            self.between(-631_152_000, 1_104_537_600)
+        } else {
+            self.between(1_577_836_800, 1_893_455_999)
+        };
+        Timestamp { seconds, nanos: 0 }
+    }
+
+    /// Whole seconds up to an hour, or up to a day for lifetimes.
+    pub fn duration(&mut self) -> Duration {
+        let seconds = if self.is(&["ttl", "retention", "expiration", "lifetime", "max_age"]) {
+            self.between(60, 86_400)
+        } else {
+            self.between(1, 3_600)
+        };
+        Duration { seconds, nanos: 0 }
+    }
+
+    pub fn value(&mut self) -> Value {
+        match self.below(3) {
+            0 => Value::string(self.pick(NOUNS).to_owned()),
+            1 => Value::number(self.below(1000) as f64),
+            _ => Value::bool(self.bool()),
+        }
+    }
+
+    pub fn list_value(&mut self) -> ListValue {
+        ListValue {
+            values: self.repeated(Field::value),
+        }
+    }
+
+    pub fn pb_struct(&mut self) -> Struct {
+        Struct {
+            fields: self.map(Field::key, Field::value),
+        }
+    }
+
+    /// A random (version 4) UUID.
+    pub fn uuid(&mut self) -> String {
+        let high = (self.next() & !0xf000) | 0x4000;
+        let low = (self.next() & !(0b11 << 62)) | (0b10 << 62);
+        format!(
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            high >> 32,
+            (high >> 16) & 0xffff,
+            high & 0xffff,
+            low >> 48,
+            low & 0xffff_ffff_ffff,
+        )
+    }
+
+    /// An address of the IPv4 documentation ranges.
+    pub fn ipv4(&mut self) -> String {
+        let network = self.pick(&["192.0.2", "198.51.100", "203.0.113"]);
+        format!("{network}.{}", 1 + self.below(254))
+    }
+
+    /// An address of the IPv6 documentation range.
+    pub fn ipv6(&mut self) -> String {
+        format!("2001:db8::{:x}", 1 + self.below(0xffff))
+    }
+
+    /// A resource name of the `pattern` of a `google.api.resource`, such as
+    /// `shelves/{shelf}/books/{book}`, with an id for each variable.
+    pub fn resource_name(&mut self, pattern: &str) -> String {
+        let mut name = String::with_capacity(pattern.len());
+        let mut rest = pattern;
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            let variable = &rest[start + 1..start + end];
+            let variable = variable.split('=').next().unwrap_or_default();
+            let variable = variable.strip_suffix("_id").unwrap_or(variable);
+            name.push_str(&rest[..start]);
+            name.push_str(&format!(
+                "{}-{:04x}",
+                variable.replace('_', "-"),
+                self.below(0x10000)
+            ));
+            rest = &rest[start + end + 1..];
+        }
+        name.push_str(rest);
+        name
+    }
+
+    pub fn message<T: Sample>(&mut self) -> T {
+        T::sample_from(&Sampler::new(self.next()))
+    }
+
+    /// One to three elements.
+    pub fn repeated<T>(&mut self, mut element: impl FnMut(&mut Field) -> T) -> Vec<T> {
+        let len = 1 + self.below(3);
+        (0..len).map(|_| element(&mut self.fork())).collect()
+    }
+
+    /// One to three entries, fewer when keys repeat.
+    pub fn map<K, V>(
+        &mut self,
+        mut key: impl FnMut(&mut Field) -> K,
+        mut value: impl FnMut(&mut Field) -> V,
+    ) -> HashMap<K, V>
+    where
+        K: Hash + Eq,
+    {
+        let len = 1 + self.below(3);
+        (0..len)
+            .map(|_| (key(&mut self.fork()), value(&mut self.fork())))
+            .collect()
+    }
+
+    /// Whether the field name, with its last word in the singular, is one of
+    /// `names` or ends with one of them after an underscore.
+    fn is(&self, names: &[&str]) -> bool {
+        let name = singular(self.name);
+        names.iter().any(|x| {
+            name == *x
+                || name
+                    .strip_suffix(x)
+                    .is_some_and(|prefix| prefix.ends_with('_'))
+        })
+    }
+
+    fn integer_range(&self) -> (i64, i64) {
+        if self.is(&["page_size", "limit", "max_result", "batch_size"]) {
+            (10, 100)
+        } else if self.is(&["age"]) {
+            (18, 90)
+        } else if self.is(&["year"]) {
+            (1990, 2030)
+        } else if self.is(&["month"]) {
+            (1, 12)
+        } else if self.is(&["day"]) {
+            (1, 28)
+        } else if self.is(&["hour"]) {
+            (0, 23)
+        } else if self.is(&["minute", "second"]) {
+            (0, 59)
+        } else if self.is(&["port"]) {
+            (1024, 65_535)
+        } else if self.is(&["priority", "rank", "rating", "star", "level"]) {
+            (1, 5)
+        } else if self.is(&["score", "percent", "percentage"]) {
+            (0, 100)
+        } else if self.is(&["ms", "milli", "millisecond", "timeout", "latency", "delay"]) {
+            (10, 5_000)
+        } else if self.is(&["byte"]) {
+            (1_024, 10_485_760)
+        } else if self.is(&["price", "cost", "amount", "balance", "total", "cent", "fee"]) {
+            (100, 100_000)
+        } else if self.is(&["version", "revision", "generation"]) {
+            (1, 20)
+        } else if self.is(&["offset", "index", "position"]) {
+            (0, 100)
+        } else if self.is(&["id"]) {
+            (1, 1_000_000)
+        } else {
+            (1, 100)
+        }
+    }
+
+    fn sentence(&mut self) -> String {
+        let len = 5 + self.below(6);
+        let words: Vec<&str> = (0..len)
+            .map(|i| match i % 2 {
+                0 => self.pick(ADJECTIVES),
+                _ => self.pick(NOUNS),
+            })
+            .collect();
+        format!("{}.", capitalize(&words.join(" ")))
+    }
+
+    fn slug(&mut self) -> String {
+        let (adjective, noun) = (self.pick(ADJECTIVES), self.pick(NOUNS));
+        format!("{adjective}-{noun}")
+    }
+
+    fn hex(&mut self, len: usize) -> String {
+        (0..len)
+            .map(|_| format!("{:02x}", self.next() as u8))
+            .collect()
+    }
+
+    fn letter(&mut self) -> char {
+        (b'A' + self.below(26) as u8) as char
+    }
+
+    fn pick<T: Copy>(&mut self, values: &[T]) -> T {
+        values[self.below(values.len() as u64) as usize]
+    }
+
+    fn fork(&mut self) -> Field {
+        Field {
+            name: self.name,
+            state: self.next(),
+        }
+    }
+
+    fn between(&mut self, low: i64, high: i64) -> i64 {
+        low + self.below(high.abs_diff(low) + 1) as i64
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n.max(1)
+    }
+
+    /// Steps `splitmix64`.
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.state)
+    }
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// The FNV-1a hash, to seed fields by name the same on every platform.
+fn fnv1a(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn singular(name: &str) -> String {
+    let name = name.to_ascii_lowercase();
+    let word = name.rsplit('_').next().unwrap_or_default();
+    if word.len() <= 2 {
+        name
+    } else if let Some(stem) = name.strip_suffix("ies") {
+        format!("{stem}y")
+    } else if ["sses", "xes", "ches", "shes"]
+        .iter()
+        .any(|x| word.ends_with(x))
+    {
+        name[..name.len() - 2].to_owned()
+    } else if word.ends_with('s') && !["ss", "us", "is"].iter().any(|x| word.ends_with(x)) {
+        name[..name.len() - 1].to_owned()
+    } else {
+        name
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,142 @@
+use prost::{bytes::Bytes, DecodeError, Message, Name};
+use prost_wkt_types::Any;
+
+use crate::any;
+
+/// The `google.rpc.Status` message, carrying rich error details packed as
+/// `Any` in the `grpc-status-details-bin` metadata of a `tonic::Status`.
+#[derive(Clone, PartialEq, Message, serde::Serialize, serde::Deserialize)]
+pub struct Status {
+    /// The `google.rpc.Code` of the error.
+    #[prost(int32, tag = "1")]
+    pub code: i32,
+    #[prost(string, tag = "2")]
+    pub message: String,
+    #[prost(message, repeated, tag = "3")]
+    pub details: Vec<Any>,
+}
+
+impl Name for Status {
+    const NAME: &'static str = "Status";
+    const PACKAGE: &'static str = "google.rpc";
+
+    fn type_url() -> String {
+        format!("{}/{}", crate::registry::TYPE_URL_DOMAIN, Self::full_name())
+    }
+}
+
+impl Status {
+    pub fn new(code: tonic::Code, message: impl Into<String>) -> Self {
+        Self {
+            code: code as i32,
+            message: message.into(),
+            details: Vec::new(),
+        }
+    }
+
+    /// Adds `detail`, e.g. a `google.rpc.BadRequest`, to the details.
+    pub fn with_detail<T: Message + Name>(mut self, detail: &T) -> Self {
+        self.details.push(any::pack(detail));
+        self
+    }
+
+    pub fn tonic_code(&self) -> tonic::Code {
+        tonic::Code::from(self.code)
+    }
+
+    /// The first detail holding a `T`, if any.
+    pub fn detail<T: Message + Name + Default>(&self) -> Option<Result<T, any::Error>> {
+        self.details
+            .iter()
+            .find(|x| any::is::<T>(x))
+            .map(any::unpack)
+    }
+}
+
+impl From<Status> for tonic::Status {
+    fn from(status: Status) -> Self {
+        let details = Bytes::from(status.encode_to_vec());
+        tonic::Status::with_details(status.tonic_code(), status.message, details)
+    }
+}
+
+impl TryFrom<&tonic::Status> for Status {
+    type Error = DecodeError;
+
+    /// Decodes the details of `status`, or takes only its code and message
+    /// when it has none.
+    fn try_from(status: &tonic::Status) -> Result<Self, Self::Error> {
+        if status.details().is_empty() {
+            return Ok(Self::new(status.code(), status.message()));
+        }
+        Self::decode(status.details())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clone, PartialEq, Message)]
+    struct ErrorInfo {
+        #[prost(string, tag = "1")]
+        reason: String,
+    }
+
+    impl Name for ErrorInfo {
+        const NAME: &'static str = "ErrorInfo";
+        const PACKAGE: &'static str = "google.rpc";
+
+        fn type_url() -> String {
+            format!("{}/{}", crate::registry::TYPE_URL_DOMAIN, Self::full_name())
+        }
+    }
+
+    #[test]
+    fn it_round_trips_tonic_statuses() {
+        let info = ErrorInfo {
+            reason: "QUOTA_EXCEEDED".to_owned(),
+        };
+        let status = Status::new(tonic::Code::ResourceExhausted, "Out of quota").with_detail(&info);
+        let tonic_status = tonic::Status::from(status.clone());
+        assert_eq!(tonic_status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(tonic_status.message(), "Out of quota");
+        assert_eq!(tonic_status.details(), status.encode_to_vec());
+
+        let decoded = Status::try_from(&tonic_status).unwrap();
+        assert_eq!(decoded, status);
+        assert_eq!(decoded.code, 8);
+        assert_eq!(decoded.detail::<ErrorInfo>().unwrap().unwrap(), info);
+        assert!(decoded.detail::<Status>().is_none());
+    }
+
+    #[test]
+    fn it_takes_the_code_and_message_of_statuses_without_details() {
+        let status = Status::try_from(&tonic::Status::not_found("No widget")).unwrap();
+        assert_eq!(status, Status::new(tonic::Code::NotFound, "No widget"));
+        assert!(status.details.is_empty());
+    }
+
+    #[test]
+    fn it_maps_unknown_codes() {
+        let status = Status {
+            code: 99,
+            message: "Unexpected".to_owned(),
+            details: Vec::new(),
+        };
+        assert_eq!(status.tonic_code(), tonic::Code::Unknown);
+        let tonic_status = tonic::Status::from(status.clone());
+        assert_eq!(tonic_status.code(), tonic::Code::Unknown);
+        assert_eq!(Status::try_from(&tonic_status).unwrap().code, 99);
+    }
+
+    #[test]
+    fn it_rejects_undecodable_details() {
+        let tonic_status = tonic::Status::with_details(
+            tonic::Code::Internal,
+            "Broken",
+            Bytes::from_static(&[0x1a, 0x05]),
+        );
+        assert!(Status::try_from(&tonic_status).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+//! The protobuf text format of the generated messages, as in `.textproto`
+//! files, read and written through the descriptors of their packages.
+
+use std::{fmt, sync::OnceLock};
+
+use prost::{DecodeError, Message};
+use prost_reflect::{
+    text_format::{FormatOptions, ParseError},
+    DescriptorPool, DynamicMessage, MessageDescriptor,
+};
+
+/// A message with a text format. Implemented for the generated messages of
+/// packages with `text_format` enabled, behind a `text-format` feature.
+pub trait TextFormat: Message + Default {
+    /// Fully qualified proto name of the message.
+    const FULL_NAME: &'static str;
+
+    /// The descriptors of the package of the message and of its imports.
+    fn descriptor_pool() -> &'static DescriptorPool;
+
+    fn from_text(text: &str) -> Result<Self, Error> {
+        Ok(DynamicMessage::parse_text_format(descriptor::<Self>(), text)?.transcode_to()?)
+    }
+
+    /// The message on a single line.
+    fn to_text(&self) -> String {
+        dynamic(self).to_text_format()
+    }
+
+    /// The message with a field per line, as in `.textproto` files.
+    fn to_text_pretty(&self) -> String {
+        dynamic(self).to_text_format_with_options(&FormatOptions::new().pretty(true))
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Parse(ParseError),
+    Decode(DecodeError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(error) => write!(f, "Failed to parse text format: {error}"),
+            Self::Decode(error) => write!(f, "Failed to decode message: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Parse(error) => Some(error),
+            Self::Decode(error) => Some(error),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Self::Parse(error)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+/// The descriptor pool of a generated package, decoded from its
+/// `FILE_DESCRIPTOR_SET` on first use.
+pub struct LazyDescriptorPool {
+    file_descriptor_set: &'static [u8],
+    pool: OnceLock<DescriptorPool>,
+}
+
+impl LazyDescriptorPool {
+    pub const fn new(file_descriptor_set: &'static [u8]) -> Self {
+        Self {
+            file_descriptor_set,
+            pool: OnceLock::new(),
+        }
+    }
+
+    pub fn get(&self) -> &DescriptorPool {
+        self.pool.get_or_init(|| {
+            DescriptorPool::decode(self.file_descriptor_set)
+                .expect("Expected valid descriptors of the generated package")
+        })
+    }
+}
+
+fn descriptor<T: TextFormat>() -> MessageDescriptor {
+    T::descriptor_pool()
+        .get_message_by_name(T::FULL_NAME)
+        .expect("Expected the message in the descriptors of its package")
+}
+
+fn dynamic<T: TextFormat>(message: &T) -> DynamicMessage {
+    let mut dynamic = DynamicMessage::new(descriptor::<T>());
+    dynamic
+        .transcode_from(message)
+        .expect("Expected the message to decode with its own descriptor");
+    dynamic
+}
@@ -0,0 +1,95 @@
+use std::{fs, path::Path, process::ExitCode};
+
+use anyhow::{anyhow, Context};
+use appbiotic_api_prost_serde_build::plugin::generate;
+use prost_types::{compiler::CodeGeneratorRequest, FileDescriptorSet};
+
+const PROTOS_DIR: &str = "protos";
+const CONFORMANCE_PROTO: &str = "conformance/conformance.proto";
+const TEST_MESSAGES_PROTOS: [&str; 2] = [
+    "google/protobuf/test_messages_proto2.proto",
+    "google/protobuf/test_messages_proto3.proto",
+];
+const WELL_KNOWN_TYPES_PROTOS: [&str; 6] = [
+    "google/protobuf/any.proto",
+    "google/protobuf/duration.proto",
+    "google/protobuf/field_mask.proto",
+    "google/protobuf/struct.proto",
+    "google/protobuf/timestamp.proto",
+    "google/protobuf/wrappers.proto",
+];
+
+fn main() -> ExitCode {
+    match build_all() {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn build_all() -> anyhow::Result<()> {
+    println!("cargo:rerun-if-changed={PROTOS_DIR}");
+    let out_dir = std::env::var("OUT_DIR").context("Failed to get OUT_DIR")?;
+    let out_dir = Path::new(&out_dir);
+
+    let fds = prost_build::Config::new()
+        .load_fds(
+            &[&[CONFORMANCE_PROTO][..], &TEST_MESSAGES_PROTOS].concat(),
+            &[PROTOS_DIR],
+        )
+        .context("Failed to compile conformance protos")?;
+
+    generate_mode(&fds, &[CONFORMANCE_PROTO], "", &out_dir.join("conformance"))?;
+    // One module per configuration mode, each compiled into its own testee so
+    // that the `prost-wkt` registries only see one type per type URL.
+    generate_mode(&fds, &TEST_MESSAGES_PROTOS, "", &out_dir.join("prost-wkt"))?;
+    generate_mode(
+        &fds,
+        &[&TEST_MESSAGES_PROTOS[..], &WELL_KNOWN_TYPES_PROTOS].concat(),
+        "compile_well_known_types",
+        &out_dir.join("compiled-wkt"),
+    )?;
+    Ok(())
+}
+
+fn generate_mode(
+    fds: &FileDescriptorSet,
+    files: &[&str],
+    options: &str,
+    out_dir: &Path,
+) -> anyhow::Result<()> {
+    let response = generate(CodeGeneratorRequest {
+        file_to_generate: files.iter().map(|x| x.to_string()).collect(),
+        parameter: Some(format!(
+            "include_file=mod.rs,client=false,server=false,{options}"
+        )),
+        proto_file: fds.file.clone(),
+        ..Default::default()
+    });
+    if let Some(error) = response.error {
+        return Err(anyhow!(error)).with_context(|| {
+            format!(
+                "Failed to generate code into `{}`",
+                out_dir.to_string_lossy()
+            )
+        });
+    }
+    fs::create_dir_all(out_dir).with_context(|| {
+        format!(
+            "Failed to create output directory `{}`",
+            out_dir.to_string_lossy()
+        )
+    })?;
+    for file in response.file {
+        let path = out_dir.join(file.name());
+        fs::write(&path, file.content()).with_context(|| {
+            format!(
+                "Failed to write generated file `{}`",
+                path.to_string_lossy()
+            )
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,29 @@
+//! Conformance testee of the messages generated with the well-known types
+//! mapped to \`prost-wkt-types\`.
+
+use std::io;
+
+use appbiotic_api_conformance::{
+    respond, serve, skipped, PROTO2_MESSAGE_TYPE, PROTO3_MESSAGE_TYPE,
+};
+
+#[allow(dead_code, clippy::all)]
+mod protos {
+    include!(concat!(env!("OUT_DIR"), "/prost-wkt/mod.rs"));
+}
+
+use crate::protos::protobuf_test_messages::{
+    proto2::TestAllTypesProto2, proto3::TestAllTypesProto3,
+};
+
+fn main() -> anyhow::Result<()> {
+    serve(
+        io::stdin().lock(),
+        io::stdout().lock(),
+        |request| match request.message_type.as_str() {
+            PROTO3_MESSAGE_TYPE => respond::<TestAllTypesProto3>(&request),
+            PROTO2_MESSAGE_TYPE => respond::<TestAllTypesProto2>(&request),
+            message_type => skipped(format!("Message type `{message_type}` is not supported")),
+        },
+    )
+}
@@ -0,0 +1,305 @@
+//! Testees of the protobuf conformance suite, which exercise the `serde` and
+//! `prost` impls generated by `appbiotic-api-prost-serde-build` for the
+//! suite's test messages, one testee per configuration mode:
+//!
+//! - `conformance-testee-prost-wkt` maps the well-known types to
+//!   `prost-wkt-types`, as packages do by default.
+//! - `conformance-testee-compiled-wkt` generates the well-known types as
+//!   regular messages, as with `compile_well_known_types`.
+//!
+//! The `appbiotic-api-conformance` binary runs `conformance_test_runner` over
+//! each testee and checks the results against the failure lists.
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    str::FromStr,
+};
+
+use anyhow::Context;
+use prost::Message;
+use serde::{de::DeserializeOwned, Serialize};
+
+include!(concat!(env!("OUT_DIR"), "/conformance/mod.rs"));
+
+use crate::conformance::{
+    conformance_request::Payload, conformance_response::Result as ResponseResult,
+    ConformanceRequest, ConformanceResponse, WireFormat,
+};
+
+pub const PROTO3_MESSAGE_TYPE: &str = "protobuf_test_messages.proto3.TestAllTypesProto3";
+pub const PROTO2_MESSAGE_TYPE: &str = "protobuf_test_messages.proto2.TestAllTypesProto2";
+
+/// Answers the requests of the runner, each framed by its little-endian
+/// length, until `reader` is closed.
+pub fn serve(
+    mut reader: impl Read,
+    mut writer: impl Write,
+    mut handle: impl FnMut(ConformanceRequest) -> ConformanceResponse,
+) -> anyhow::Result<()> {
+    loop {
+        let mut len = [0; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(error) => return Err(error).context("Failed to read request length"),
+        }
+        let mut request = vec![0; u32::from_le_bytes(len) as usize];
+        reader
+            .read_exact(&mut request)
+            .context("Failed to read request")?;
+        let request = ConformanceRequest::decode(request.as_slice())
+            .context("Failed to decode conformance request")?;
+
+        let response = handle(request).encode_to_vec();
+        writer
+            .write_all(&(response.len() as u32).to_le_bytes())
+            .and_then(|_| writer.write_all(&response))
+            .and_then(|_| writer.flush())
+            .context("Failed to write conformance response")?;
+    }
+}
+
+/// Parses the payload of `request` as a `T` and serializes it in the
+/// requested output format, with `prost` for the binary format and
+/// `serde_json` for JSON.
+pub fn respond<T>(request: &ConformanceRequest) -> ConformanceResponse
+where
+    T: Message + Default + Serialize + DeserializeOwned,
+{
+    let message = match &request.payload {
+        Some(Payload::ProtobufPayload(payload)) => {
+            T::decode(payload.as_slice()).map_err(|x| x.to_string())
+        }
+        Some(Payload::JsonPayload(payload)) => {
+            serde_json::from_str::<T>(payload).map_err(|x| x.to_string())
+        }
+        Some(Payload::JspbPayload(_)) => return skipped("JSPB input is not supported"),
+        Some(Payload::TextPayload(_)) => return skipped("Text format input is not supported"),
+        None => return runtime_error("Request has no payload"),
+    };
+    let message = match message {
+        Ok(message) => message,
+        Err(error) => return response(ResponseResult::ParseError(error)),
+    };
+    match request.requested_output_format() {
+        WireFormat::Protobuf => response(ResponseResult::ProtobufPayload(message.encode_to_vec())),
+        WireFormat::Json => match serde_json::to_string(&message) {
+            Ok(json) => response(ResponseResult::JsonPayload(json)),
+            Err(error) => response(ResponseResult::SerializeError(error.to_string())),
+        },
+        WireFormat::Jspb => skipped("JSPB output is not supported"),
+        WireFormat::TextFormat => skipped("Text format output is not supported"),
+        WireFormat::Unspecified => runtime_error("Request has no output format"),
+    }
+}
+
+pub fn skipped(reason: impl Into<String>) -> ConformanceResponse {
+    response(ResponseResult::Skipped(reason.into()))
+}
+
+fn runtime_error(error: impl Into<String>) -> ConformanceResponse {
+    response(ResponseResult::RuntimeError(error.into()))
+}
+
+fn response(result: ResponseResult) -> ConformanceResponse {
+    ConformanceResponse {
+        result: Some(result),
+    }
+}
+
+/// Counts of a suite run, as summarized by `conformance_test_runner` in
+/// lines like `CONFORMANCE SUITE PASSED: 10 successes, 2 skipped, 1 expected
+/// failures, 0 unexpected failures.`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SuiteSummary {
+    pub successes: u32,
+    pub skipped: u32,
+    pub expected_failures: u32,
+    pub unexpected_failures: u32,
+}
+
+impl SuiteSummary {
+    pub const PREFIX: &str = "CONFORMANCE SUITE ";
+
+    /// Summaries of the suites in the output of a runner, in order.
+    pub fn parse_output(output: &str) -> Vec<Self> {
+        output
+            .lines()
+            .filter(|x| x.starts_with(Self::PREFIX))
+            .filter_map(|x| x.parse().ok())
+            .collect()
+    }
+
+    pub fn total(&self) -> u32 {
+        self.successes + self.skipped + self.expected_failures + self.unexpected_failures
+    }
+}
+
+impl FromStr for SuiteSummary {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (_, counts) = s
+            .strip_prefix(Self::PREFIX)
+            .and_then(|x| x.split_once(": "))
+            .with_context(|| format!("Expected suite summary, found `{s}`"))?;
+        let mut summary = Self::default();
+        for count in counts.trim_end_matches('.').split(", ") {
+            let (value, name) = count
+                .split_once(' ')
+                .with_context(|| format!("Expected count, found `{count}`"))?;
+            let value = value
+                .parse()
+                .with_context(|| format!("Failed to parse count `{count}`"))?;
+            match name {
+                "successes" => summary.successes = value,
+                "skipped" => summary.skipped = value,
+                "expected failures" => summary.expected_failures = value,
+                "unexpected failures" => summary.unexpected_failures = value,
+                _ => anyhow::bail!("Unknown count `{count}`"),
+            }
+        }
+        Ok(summary)
+    }
+}
+
+impl fmt::Display for SuiteSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} successes, {} skipped, {} expected failures, {} unexpected failures",
+            self.successes, self.skipped, self.expected_failures, self.unexpected_failures
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::conformance::FailureSet;
+
+    fn request(payload: Payload, output: WireFormat) -> ConformanceRequest {
+        ConformanceRequest {
+            payload: Some(payload),
+            requested_output_format: output as i32,
+            message_type: "conformance.FailureSet".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn failure_set() -> FailureSet {
+        FailureSet {
+            failure: vec!["Required.Proto3.JsonInput.Foo".to_owned()],
+        }
+    }
+
+    #[test]
+    fn it_converts_json_to_protobuf() {
+        let response = respond::<FailureSet>(&request(
+            Payload::JsonPayload(r#"{"failure": ["Required.Proto3.JsonInput.Foo"]}"#.to_owned()),
+            WireFormat::Protobuf,
+        ));
+        assert_eq!(
+            response.result,
+            Some(ResponseResult::ProtobufPayload(
+                failure_set().encode_to_vec()
+            ))
+        );
+    }
+
+    #[test]
+    fn it_converts_protobuf_to_json() {
+        let response = respond::<FailureSet>(&request(
+            Payload::ProtobufPayload(failure_set().encode_to_vec()),
+            WireFormat::Json,
+        ));
+        assert_eq!(
+            response.result,
+            Some(ResponseResult::JsonPayload(
+                r#"{"failure":["Required.Proto3.JsonInput.Foo"]}"#.to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn it_reports_parse_errors() {
+        let response = respond::<FailureSet>(&request(
+            Payload::JsonPayload(r#"{"failure": 1}"#.to_owned()),
+            WireFormat::Json,
+        ));
+        assert!(matches!(
+            response.result,
+            Some(ResponseResult::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn it_skips_text_format() {
+        let response = respond::<FailureSet>(&request(
+            Payload::ProtobufPayload(Vec::new()),
+            WireFormat::TextFormat,
+        ));
+        assert!(matches!(response.result, Some(ResponseResult::Skipped(_))));
+    }
+
+    #[test]
+    fn it_serves_length_prefixed_messages() {
+        let mut input = Vec::new();
+        for _ in 0..2 {
+            let request = request(
+                Payload::ProtobufPayload(failure_set().encode_to_vec()),
+                WireFormat::Protobuf,
+            )
+            .encode_to_vec();
+            input.extend((request.len() as u32).to_le_bytes());
+            input.extend(request);
+        }
+        let mut output = Vec::new();
+        serve(Cursor::new(input), &mut output, |x| {
+            respond::<FailureSet>(&x)
+        })
+        .unwrap();
+
+        let mut output = output.as_slice();
+        for _ in 0..2 {
+            let (len, rest) = output.split_at(4);
+            let (response, rest) =
+                rest.split_at(u32::from_le_bytes(len.try_into().unwrap()) as usize);
+            assert_eq!(
+                ConformanceResponse::decode(response).unwrap().result,
+                Some(ResponseResult::ProtobufPayload(
+                    failure_set().encode_to_vec()
+                ))
+            );
+            output = rest;
+        }
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn it_parses_suite_summaries() {
+        let output = "\nCONFORMANCE TEST BEGIN ====\n\n\
+            CONFORMANCE SUITE FAILED: 1203 successes, 110 skipped, 0 expected failures, 1291 unexpected failures.\n\n\
+            CONFORMANCE SUITE PASSED: 0 successes, 883 skipped, 0 expected failures, 0 unexpected failures.\n";
+        let summaries = SuiteSummary::parse_output(output);
+        assert_eq!(
+            summaries,
+            vec![
+                SuiteSummary {
+                    successes: 1203,
+                    skipped: 110,
+                    expected_failures: 0,
+                    unexpected_failures: 1291,
+                },
+                SuiteSummary {
+                    skipped: 883,
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(summaries[0].total(), 2604);
+    }
+}
@@ -0,0 +1,219 @@
+//! Runs the protobuf conformance suite over the testee of each configuration
+//! mode and prints a summary table:
+//!
+//! ```sh
+//! cargo build -p appbiotic-api-conformance
+//! target/debug/appbiotic-api-conformance --runner path/to/conformance_test_runner
+//! ```
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, ExitCode},
+};
+
+use anyhow::{ensure, Context};
+use appbiotic_api_conformance::SuiteSummary;
+use clap::{Parser, ValueEnum};
+
+/// Suites of `conformance_test_runner`, in the order it runs them.
+const SUITES: [&str; 2] = ["Binary and JSON", "Text format"];
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Path of `conformance_test_runner`, built from the protobuf repository.
+    #[arg(long, env = "CONFORMANCE_TEST_RUNNER")]
+    runner: PathBuf,
+    /// Configuration modes to run, all by default.
+    #[arg(long = "mode", value_enum)]
+    modes: Vec<Mode>,
+    /// Directory of the failure list of each mode, `<mode>.txt`. A mode
+    /// without a failure list expects no failures.
+    #[arg(long, default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/failure_lists"))]
+    failure_lists: PathBuf,
+    /// Rewrites the failure lists with the tests failing now instead of
+    /// checking them.
+    #[arg(long)]
+    update_failure_lists: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Mode {
+    /// Well-known types mapped to `prost-wkt-types`, the default.
+    ProstWkt,
+    /// Well-known types generated with `compile_well_known_types`.
+    CompiledWkt,
+}
+
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Self::ProstWkt => "prost-wkt",
+            Self::CompiledWkt => "compiled-wkt",
+        }
+    }
+}
+
+struct ModeRun {
+    mode: Mode,
+    passed: bool,
+    summaries: Vec<SuiteSummary>,
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("{error:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> anyhow::Result<bool> {
+    let modes = if args.modes.is_empty() {
+        Mode::value_variants().to_vec()
+    } else {
+        args.modes.clone()
+    };
+    let mut runs = Vec::new();
+    for mode in modes {
+        runs.push(run_mode(&args, mode)?);
+    }
+    print!("{}", render_table(&runs));
+    Ok(args.update_failure_lists || runs.iter().all(|x| x.passed))
+}
+
+fn run_mode(args: &Args, mode: Mode) -> anyhow::Result<ModeRun> {
+    let testee = testee_path(mode)?;
+    let failure_list = args.failure_lists.join(format!("{}.txt", mode.name()));
+    let output_dir = tempfile::tempdir().context("Failed to create output directory")?;
+
+    let mut command = Command::new(&args.runner);
+    command.arg("--output_dir").arg(output_dir.path());
+    if !args.update_failure_lists {
+        if failure_list.exists() {
+            command.arg("--failure_list").arg(&failure_list);
+        } else {
+            eprintln!(
+                "No failure list `{}` for mode `{}`, expecting no failures",
+                failure_list.to_string_lossy(),
+                mode.name()
+            );
+        }
+    }
+    eprintln!("Running conformance suite in mode `{}`", mode.name());
+    let output = command.arg(&testee).output().with_context(|| {
+        format!(
+            "Failed to run conformance test runner `{}`",
+            args.runner.to_string_lossy()
+        )
+    })?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let summaries = SuiteSummary::parse_output(&stderr);
+    ensure!(
+        !summaries.is_empty(),
+        "Conformance test runner printed no suite summary:\n{stderr}"
+    );
+    if !output.status.success() && !args.update_failure_lists {
+        eprint!("{stderr}");
+    }
+
+    if args.update_failure_lists {
+        write_failure_list(
+            mode,
+            &output_dir.path().join("failing_tests.txt"),
+            &failure_list,
+        )?;
+    }
+    Ok(ModeRun {
+        mode,
+        passed: output.status.success(),
+        summaries,
+    })
+}
+
+/// The testee binaries are built next to this one.
+fn testee_path(mode: Mode) -> anyhow::Result<PathBuf> {
+    let exe = env::current_exe().context("Failed to get path of current executable")?;
+    let testee = exe.with_file_name(format!(
+        "conformance-testee-{}{}",
+        mode.name(),
+        env::consts::EXE_SUFFIX
+    ));
+    ensure!(
+        testee.exists(),
+        "Testee `{}` not found, build it with `cargo build -p appbiotic-api-conformance`",
+        testee.to_string_lossy()
+    );
+    Ok(testee)
+}
+
+fn write_failure_list(mode: Mode, failing_tests: &Path, failure_list: &Path) -> anyhow::Result<()> {
+    let mut failures: Vec<String> = match fs::read_to_string(failing_tests) {
+        Ok(content) => content.lines().map(str::to_owned).collect(),
+        // Not written when no test fails.
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(error) => {
+            return Err(error).with_context(|| {
+                format!(
+                    "Failed to read failing tests `{}`",
+                    failing_tests.to_string_lossy()
+                )
+            })
+        }
+    };
+    failures.sort();
+    let mut content = format!(
+        "# Conformance tests failing in mode `{}`, updated by\n\
+         # `appbiotic-api-conformance --update-failure-lists`.\n",
+        mode.name()
+    );
+    for failure in failures {
+        content.push_str(&failure);
+        content.push('\n');
+    }
+    if let Some(parent) = failure_list.parent() {
+        fs::create_dir_all(parent).with_context(|| {
+            format!(
+                "Failed to create failure list directory `{}`",
+                parent.to_string_lossy()
+            )
+        })?;
+    }
+    fs::write(failure_list, content).with_context(|| {
+        format!(
+            "Failed to write failure list `{}`",
+            failure_list.to_string_lossy()
+        )
+    })
+}
+
+fn render_table(runs: &[ModeRun]) -> String {
+    let mut table = String::from(
+        "| Mode | Suite | Passing | Successes | Skipped | Expected failures | Unexpected failures |\n\
+         | --- | --- | --- | --- | --- | --- | --- |\n",
+    );
+    for run in runs {
+        for (index, summary) in run.summaries.iter().enumerate() {
+            let tested = summary.total() - summary.skipped;
+            let passing = if tested == 0 {
+                "-".to_owned()
+            } else {
+                format!("{:.1}%", 100.0 * summary.successes as f64 / tested as f64)
+            };
+            table.push_str(&format!(
+                "| {} | {} | {passing} | {} | {} | {} | {} |\n",
+                run.mode.name(),
+                SUITES.get(index).unwrap_or(&"Unknown"),
+                summary.successes,
+                summary.skipped,
+                summary.expected_failures,
+                summary.unexpected_failures,
+            ));
+        }
+    }
+    table
+}
@@ -0,0 +1,491 @@
+//! Diffs of the messages, enums, and services of a proto package between two
+//! `FileDescriptorSet`s, classifying each change by what it breaks.
+
+use std::{collections::BTreeMap, fmt};
+
+use prost_types::{
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorSet,
+    ServiceDescriptorProto,
+};
+
+/// What a change breaks, ordered from the most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Existing clients or stored data may no longer decode the binary
+    /// encoding, or RPCs may no longer resolve.
+    WireBreaking,
+    /// The binary encoding is unchanged, but the JSON encoding changes.
+    JsonBreaking,
+    /// Both encodings are unchanged, but code generated from the protos may no
+    /// longer compile.
+    SourceBreaking,
+    /// Adds API surface without affecting existing clients.
+    Safe,
+}
+
+impl ChangeKind {
+    pub fn is_breaking(self) -> bool {
+        self != ChangeKind::Safe
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChangeKind::WireBreaking => "wire-breaking",
+            ChangeKind::JsonBreaking => "json-breaking",
+            ChangeKind::SourceBreaking => "source-breaking",
+            ChangeKind::Safe => "safe",
+        })
+    }
+}
+
+/// A single schema change between two descriptor sets.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// Fully qualified name of the changed element.
+    pub path: String,
+    pub description: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.kind, self.path, self.description)
+    }
+}
+
+/// The changes between two descriptor sets.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct DiffReport {
+    pub changes: Vec<Change>,
+}
+
+impl DiffReport {
+    /// The most severe kind among the changes, if any.
+    pub fn max_kind(&self) -> Option<ChangeKind> {
+        max_change_kind(&self.changes)
+    }
+
+    pub fn is_breaking(&self) -> bool {
+        self.max_kind().is_some_and(ChangeKind::is_breaking)
+    }
+
+    pub fn breaking_changes(&self) -> impl Iterator<Item = &Change> {
+        self.changes.iter().filter(|x| x.kind.is_breaking())
+    }
+
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|x| x.kind == kind).count()
+    }
+}
+
+impl IntoIterator for DiffReport {
+    type Item = Change;
+    type IntoIter = std::vec::IntoIter<Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+/// Lists the changes from `old` to `new` to the messages, enums, and services
+/// of proto package `proto_package`, ignoring the imported files of either
+/// set.
+pub fn diff_descriptor_sets(
+    old: &FileDescriptorSet,
+    new: &FileDescriptorSet,
+    proto_package: &str,
+) -> DiffReport {
+    let old = Index::new(old, proto_package);
+    let new = Index::new(new, proto_package);
+    let mut changes = Vec::new();
+
+    for (name, old_message) in &old.messages {
+        match new.messages.get(name) {
+            Some(new_message) => diff_message(name, old_message, new_message, &mut changes),
+            None => changes.push(change(ChangeKind::SourceBreaking, name, "message removed")),
+        }
+    }
+    for name in new
+        .messages
+        .keys()
+        .filter(|x| !old.messages.contains_key(*x))
+    {
+        changes.push(change(ChangeKind::Safe, name, "message added"));
+    }
+
+    for (name, old_enum) in &old.enums {
+        match new.enums.get(name) {
+            Some(new_enum) => diff_enum(name, old_enum, new_enum, &mut changes),
+            None => changes.push(change(ChangeKind::SourceBreaking, name, "enum removed")),
+        }
+    }
+    for name in new.enums.keys().filter(|x| !old.enums.contains_key(*x)) {
+        changes.push(change(ChangeKind::Safe, name, "enum added"));
+    }
+
+    for (name, old_service) in &old.services {
+        match new.services.get(name) {
+            Some(new_service) => diff_service(name, old_service, new_service, &mut changes),
+            None => changes.push(change(ChangeKind::WireBreaking, name, "service removed")),
+        }
+    }
+    for name in new
+        .services
+        .keys()
+        .filter(|x| !old.services.contains_key(*x))
+    {
+        changes.push(change(ChangeKind::Safe, name, "service added"));
+    }
+
+    DiffReport { changes }
+}
+
+/// The most severe kind among `changes`, if any.
+pub fn max_change_kind(changes: &[Change]) -> Option<ChangeKind> {
+    changes.iter().map(|x| x.kind).min()
+}
+
+fn diff_message(
+    name: &str,
+    old: &DescriptorProto,
+    new: &DescriptorProto,
+    changes: &mut Vec<Change>,
+) {
+    let new_fields: BTreeMap<i32, &FieldDescriptorProto> =
+        new.field.iter().map(|x| (x.number(), x)).collect();
+    for old_field in &old.field {
+        let path = format!("{name}.{}", old_field.name());
+        let Some(new_field) = new_fields.get(&old_field.number()) else {
+            // Reusing the number of a removed field is what breaks the wire.
+            let kind = if is_reserved_field(new, old_field.number()) {
+                ChangeKind::SourceBreaking
+            } else {
+                ChangeKind::WireBreaking
+            };
+            changes.push(change(
+                kind,
+                &path,
+                &format!("field {} removed", old_field.number()),
+            ));
+            continue;
+        };
+        if old_field.name() != new_field.name() || old_field.json_name() != new_field.json_name() {
+            changes.push(change(
+                ChangeKind::JsonBreaking,
+                &path,
+                &format!(
+                    "field {} renamed to `{}`, changing its JSON name",
+                    old_field.number(),
+                    new_field.name()
+                ),
+            ));
+        }
+        if old_field.r#type() != new_field.r#type()
+            || old_field.type_name() != new_field.type_name()
+        {
+            changes.push(change(
+                ChangeKind::WireBreaking,
+                &path,
+                &format!(
+                    "field {} type changed from {} to {}",
+                    old_field.number(),
+                    field_type_name(old_field),
+                    field_type_name(new_field)
+                ),
+            ));
+        }
+        if old_field.label() != new_field.label() {
+            changes.push(change(
+                ChangeKind::WireBreaking,
+                &path,
+                &format!(
+                    "field {} label changed from {} to {}",
+                    old_field.number(),
+                    old_field.label().as_str_name(),
+                    new_field.label().as_str_name()
+                ),
+            ));
+        }
+        if old_field.oneof_index.is_some() != new_field.oneof_index.is_some() {
+            changes.push(change(
+                ChangeKind::WireBreaking,
+                &path,
+                &format!("field {} moved into or out of a oneof", old_field.number()),
+            ));
+        }
+    }
+    let old_numbers: Vec<i32> = old.field.iter().map(|x| x.number()).collect();
+    for new_field in new
+        .field
+        .iter()
+        .filter(|x| !old_numbers.contains(&x.number()))
+    {
+        changes.push(change(
+            ChangeKind::Safe,
+            &format!("{name}.{}", new_field.name()),
+            &format!("field {} added", new_field.number()),
+        ));
+    }
+}
+
+fn diff_enum(
+    name: &str,
+    old: &EnumDescriptorProto,
+    new: &EnumDescriptorProto,
+    changes: &mut Vec<Change>,
+) {
+    for old_value in &old.value {
+        let path = format!("{name}.{}", old_value.name());
+        match new.value.iter().find(|x| x.number() == old_value.number()) {
+            None => {
+                let kind = if is_reserved_value(new, old_value.number()) {
+                    ChangeKind::SourceBreaking
+                } else {
+                    ChangeKind::WireBreaking
+                };
+                changes.push(change(
+                    kind,
+                    &path,
+                    &format!("value {} removed", old_value.number()),
+                ))
+            }
+            Some(new_value) if new_value.name() != old_value.name() => changes.push(change(
+                ChangeKind::JsonBreaking,
+                &path,
+                &format!(
+                    "value {} renamed to `{}`, changing its JSON name",
+                    old_value.number(),
+                    new_value.name()
+                ),
+            )),
+            Some(_) => {}
+        }
+    }
+    for new_value in new
+        .value
+        .iter()
+        .filter(|x| !old.value.iter().any(|y| y.number() == x.number()))
+    {
+        changes.push(change(
+            ChangeKind::Safe,
+            &format!("{name}.{}", new_value.name()),
+            &format!("value {} added", new_value.number()),
+        ));
+    }
+}
+
+fn diff_service(
+    name: &str,
+    old: &ServiceDescriptorProto,
+    new: &ServiceDescriptorProto,
+    changes: &mut Vec<Change>,
+) {
+    for old_method in &old.method {
+        let path = format!("{name}.{}", old_method.name());
+        let Some(new_method) = new.method.iter().find(|x| x.name() == old_method.name()) else {
+            changes.push(change(ChangeKind::WireBreaking, &path, "method removed"));
+            continue;
+        };
+        if old_method.input_type() != new_method.input_type() {
+            changes.push(change(
+                ChangeKind::WireBreaking,
+                &path,
+                &format!(
+                    "input type changed from `{}` to `{}`",
+                    old_method.input_type(),
+                    new_method.input_type()
+                ),
+            ));
+        }
+        if old_method.output_type() != new_method.output_type() {
+            changes.push(change(
+                ChangeKind::WireBreaking,
+                &path,
+                &format!(
+                    "output type changed from `{}` to `{}`",
+                    old_method.output_type(),
+                    new_method.output_type()
+                ),
+            ));
+        }
+        if old_method.client_streaming() != new_method.client_streaming()
+            || old_method.server_streaming() != new_method.server_streaming()
+        {
+            changes.push(change(ChangeKind::WireBreaking, &path, "streaming changed"));
+        }
+    }
+    for new_method in new
+        .method
+        .iter()
+        .filter(|x| !old.method.iter().any(|y| y.name() == x.name()))
+    {
+        changes.push(change(
+            ChangeKind::Safe,
+            &format!("{name}.{}", new_method.name()),
+            "method added",
+        ));
+    }
+}
+
+fn field_type_name(field: &FieldDescriptorProto) -> String {
+    match field.type_name.as_deref() {
+        Some(type_name) => format!("`{type_name}`"),
+        None => field.r#type().as_str_name().to_owned(),
+    }
+}
+
+fn is_reserved_field(message: &DescriptorProto, number: i32) -> bool {
+    // Message reserved ranges exclude their end.
+    message
+        .reserved_range
+        .iter()
+        .any(|x| (x.start()..x.end()).contains(&number))
+}
+
+fn is_reserved_value(enum_: &EnumDescriptorProto, number: i32) -> bool {
+    // Enum reserved ranges include their end.
+    enum_
+        .reserved_range
+        .iter()
+        .any(|x| (x.start()..=x.end()).contains(&number))
+}
+
+fn change(kind: ChangeKind, path: &str, description: &str) -> Change {
+    Change {
+        kind,
+        path: path.to_owned(),
+        description: description.to_owned(),
+    }
+}
+
+/// Messages, enums, and services of a proto package keyed by fully qualified
+/// name, including nested types.
+struct Index<'a> {
+    messages: BTreeMap<String, &'a DescriptorProto>,
+    enums: BTreeMap<String, &'a EnumDescriptorProto>,
+    services: BTreeMap<String, &'a ServiceDescriptorProto>,
+}
+
+impl<'a> Index<'a> {
+    fn new(fds: &'a FileDescriptorSet, proto_package: &str) -> Self {
+        let mut index = Self {
+            messages: BTreeMap::new(),
+            enums: BTreeMap::new(),
+            services: BTreeMap::new(),
+        };
+        for file in fds.file.iter().filter(|x| x.package() == proto_package) {
+            let prefix = format!(".{}", file.package());
+            for message in &file.message_type {
+                index.add_message(&prefix, message);
+            }
+            for enum_ in &file.enum_type {
+                index
+                    .enums
+                    .insert(format!("{prefix}.{}", enum_.name()), enum_);
+            }
+            for service in &file.service {
+                index
+                    .services
+                    .insert(format!("{prefix}.{}", service.name()), service);
+            }
+        }
+        index
+    }
+
+    fn add_message(&mut self, prefix: &str, message: &'a DescriptorProto) {
+        let name = format!("{prefix}.{}", message.name());
+        for nested in &message.nested_type {
+            self.add_message(&name, nested);
+        }
+        for enum_ in &message.enum_type {
+            self.enums.insert(format!("{name}.{}", enum_.name()), enum_);
+        }
+        self.messages.insert(name, message);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prost_types::{
+        descriptor_proto::ReservedRange, field_descriptor_proto::Type, FileDescriptorProto,
+    };
+
+    use super::*;
+
+    fn fds(message: DescriptorProto) -> FileDescriptorSet {
+        FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("foo/v1/foo.proto".to_owned()),
+                package: Some("foo.v1".to_owned()),
+                message_type: vec![message],
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn message(fields: &[(&str, i32)]) -> DescriptorProto {
+        DescriptorProto {
+            name: Some("Foo".to_owned()),
+            field: fields
+                .iter()
+                .map(|(name, number)| FieldDescriptorProto {
+                    name: Some(name.to_string()),
+                    number: Some(*number),
+                    r#type: Some(Type::String as i32),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn kinds(old: DescriptorProto, new: DescriptorProto) -> Vec<ChangeKind> {
+        diff_descriptor_sets(&fds(old), &fds(new), "foo.v1")
+            .into_iter()
+            .map(|x| x.kind)
+            .collect()
+    }
+
+    #[test]
+    fn it_classifies_field_changes() {
+        let old = message(&[("name", 1), ("title", 2)]);
+        assert_eq!(
+            kinds(
+                old.clone(),
+                message(&[("name", 1), ("title", 2), ("size", 3)])
+            ),
+            vec![ChangeKind::Safe]
+        );
+        assert_eq!(
+            kinds(old.clone(), message(&[("name", 1), ("label", 2)])),
+            vec![ChangeKind::JsonBreaking]
+        );
+        assert_eq!(
+            kinds(old.clone(), message(&[("name", 1)])),
+            vec![ChangeKind::WireBreaking]
+        );
+
+        let mut reserved = message(&[("name", 1)]);
+        reserved.reserved_range.push(ReservedRange {
+            start: Some(2),
+            end: Some(3),
+        });
+        assert_eq!(kinds(old, reserved), vec![ChangeKind::SourceBreaking]);
+    }
+
+    #[test]
+    fn it_reports_the_most_severe_kind() {
+        let report = diff_descriptor_sets(
+            &fds(message(&[("name", 1), ("title", 2)])),
+            &fds(message(&[("label", 1), ("size", 3)])),
+            "foo.v1",
+        );
+        assert_eq!(report.max_kind(), Some(ChangeKind::WireBreaking));
+        assert!(report.is_breaking());
+        assert_eq!(report.breaking_changes().count(), 2);
+        assert_eq!(report.count(ChangeKind::Safe), 1);
+    }
+}
@@ -5,10 +5,22 @@ include!(concat!(
 
 #[cfg(test)]
 mod test {
-    use prost_wkt_types::{Any, MessageSerde};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use appbiotic_api::{
+        datetime::RangeError,
+        field_mask, pagination,
+        sample::Sample,
+        text_format::{self, TextFormat},
+    };
+    use futures_util::{StreamExt, TryStreamExt};
+    use prost_wkt_types::{Any, FieldMask, MessageSerde};
     use serde_json::json;
 
-    use crate::prost_serde::{Container, ContainerConfig};
+    use crate::prost_serde::{
+        Container, ContainerConfig, Inventory, ListContainersRequest, ListContainersResponse,
+        Schedule, Shelf, inventory::Location,
+    };
 
     #[test]
     fn it_compiles() {
@@ -28,4 +40,299 @@ mod test {
 
         assert_eq!(config_ref.name(), "abc-123");
     }
+
+    #[test]
+    fn it_samples_deterministically() {
+        assert_eq!(Container::fake(7), Container::fake(7));
+        assert_eq!(Container::sample(), Container::fake(0));
+        assert_ne!(Container::fake(7), Container::fake(8));
+    }
+
+    #[test]
+    fn it_round_trips_text_format() {
+        let container = Container {
+            id: Some(123),
+            ints: vec![123, 456, 789],
+            config: Some(
+                Any::try_pack(ContainerConfig {
+                    name: Some("abc-123".to_owned()),
+                })
+                .unwrap(),
+            ),
+        };
+        let text = container.to_text();
+        assert_eq!(Container::from_text(&text).unwrap(), container);
+        assert_eq!(
+            Container::from_text(&container.to_text_pretty()).unwrap(),
+            container
+        );
+        assert_eq!(
+            Container::from_text("id: 123 ints: [1, 2]").unwrap(),
+            Container {
+                id: Some(123),
+                ints: vec![1, 2],
+                config: None,
+            }
+        );
+    }
+
+    #[test]
+    fn it_rejects_malformed_text_format() {
+        assert!(matches!(
+            Container::from_text("id: \"abc\""),
+            Err(text_format::Error::Parse(_))
+        ));
+        assert!(matches!(
+            Container::from_text("unknown: 1"),
+            Err(text_format::Error::Parse(_))
+        ));
+        assert!(matches!(
+            Container::from_text("ints: [1,"),
+            Err(text_format::Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn it_converts_datetime_fields() {
+        let mut schedule = Schedule::default();
+        assert_eq!(schedule.start_time_as::<SystemTime>(), Ok(None));
+
+        let start = UNIX_EPOCH + Duration::from_millis(1_500);
+        schedule.set_start_time_from(&start).unwrap();
+        schedule
+            .set_interval_from(&Duration::from_secs(90))
+            .unwrap();
+        assert_eq!(schedule.start_time_as::<SystemTime>(), Ok(Some(start)));
+        assert_eq!(
+            schedule.interval_as::<Duration>(),
+            Ok(Some(Duration::from_secs(90)))
+        );
+
+        schedule.interval = Some(prost_wkt_types::Duration {
+            seconds: -90,
+            nanos: 0,
+        });
+        assert_eq!(
+            schedule.interval_as::<Duration>(),
+            Err(RangeError {
+                target: "std::time::Duration"
+            })
+        );
+    }
+
+    fn mask(paths: &[&str]) -> FieldMask {
+        FieldMask {
+            paths: paths.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    fn inventory(name: &str, label: &str, capacity: i32, count: i64) -> Inventory {
+        Inventory {
+            name: name.to_owned(),
+            shelf: Some(Shelf {
+                label: label.to_owned(),
+                capacity,
+            }),
+            shelves: vec![Shelf {
+                label: label.to_owned(),
+                capacity,
+            }],
+            counts: [(name.to_owned(), count)].into(),
+            location: Some(Location::Aisle(label.to_owned())),
+        }
+    }
+
+    #[test]
+    fn it_validates_field_mask_paths() {
+        field_mask::validate::<Inventory>(&mask(&[
+            "name",
+            "shelf",
+            "shelf.label",
+            "shelves",
+            "counts",
+            "aisle",
+            "bin",
+        ]))
+        .unwrap();
+        for path in [
+            "unknown",
+            "shelf.unknown",
+            "name.label",
+            "shelves.label",
+            "counts.a",
+            "location",
+            "",
+        ] {
+            let error = field_mask::validate::<Inventory>(&mask(&["name", path])).unwrap_err();
+            assert_eq!(error.path, path);
+            assert_eq!(
+                error.message,
+                "appbiotic.prost_serde_build.examples.Inventory"
+            );
+        }
+    }
+
+    #[test]
+    fn it_merges_field_masks() {
+        let mut target = inventory("a", "a", 1, 1);
+        let source = inventory("b", "b", 2, 2);
+        field_mask::merge(
+            &mut target,
+            &source,
+            &mask(&["name", "shelf.capacity", "shelves", "counts", "bin"]),
+        )
+        .unwrap();
+        assert_eq!(
+            target,
+            Inventory {
+                name: "b".to_owned(),
+                shelf: Some(Shelf {
+                    label: "a".to_owned(),
+                    capacity: 2,
+                }),
+                // Neither message sets `bin`, so the oneof is left as is.
+                location: Some(Location::Aisle("a".to_owned())),
+                ..source.clone()
+            }
+        );
+
+        field_mask::merge(&mut target, &source, &mask(&["shelf", "aisle"])).unwrap();
+        assert_eq!(target, source);
+
+        let mut unchanged = inventory("a", "a", 1, 1);
+        assert!(field_mask::merge(&mut unchanged, &source, &mask(&["name", "unknown"])).is_err());
+        assert_eq!(unchanged, inventory("a", "a", 1, 1));
+    }
+
+    #[test]
+    fn it_retains_field_masks() {
+        let mut message = inventory("a", "a", 1, 1);
+        field_mask::retain(&mut message, &mask(&["shelf.label", "shelves", "bin"])).unwrap();
+        assert_eq!(
+            message,
+            Inventory {
+                name: String::new(),
+                shelf: Some(Shelf {
+                    label: "a".to_owned(),
+                    capacity: 0,
+                }),
+                shelves: inventory("a", "a", 1, 1).shelves,
+                counts: Default::default(),
+                location: None,
+            }
+        );
+
+        let mut message = inventory("a", "a", 1, 1);
+        field_mask::retain(&mut message, &mask(&["counts", "aisle"])).unwrap();
+        assert_eq!(
+            message,
+            Inventory {
+                counts: inventory("a", "a", 1, 1).counts,
+                location: Some(Location::Aisle("a".to_owned())),
+                ..Default::default()
+            }
+        );
+
+        assert!(field_mask::retain(&mut message, &mask(&["shelves.label"])).is_err());
+    }
+
+    /// Lists `count` containers, failing for the page at offset `fail_at`.
+    async fn list_containers(
+        request: ListContainersRequest,
+        count: i64,
+        fail_at: Option<i64>,
+    ) -> Result<tonic::Response<ListContainersResponse>, tonic::Status> {
+        let offset: i64 = match request.page_token.as_str() {
+            "" => 0,
+            token => token
+                .parse()
+                .map_err(|_| tonic::Status::invalid_argument("Invalid page token"))?,
+        };
+        if fail_at == Some(offset) {
+            return Err(tonic::Status::unavailable("Unavailable"));
+        }
+        let end = (offset + i64::from(request.page_size)).min(count);
+        Ok(tonic::Response::new(ListContainersResponse {
+            containers: (offset..end)
+                .map(|id| Container {
+                    id: Some(id),
+                    ..Default::default()
+                })
+                .collect(),
+            next_page_token: if end < count {
+                end.to_string()
+            } else {
+                String::new()
+            },
+        }))
+    }
+
+    fn list_request() -> ListContainersRequest {
+        ListContainersRequest {
+            page_size: 2,
+            page_token: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_streams_pages_until_the_last_one() {
+        let mut tokens = Vec::new();
+        let pages: Vec<ListContainersResponse> = pagination::pages(list_request(), |request| {
+            tokens.push(request.page_token.to_owned());
+            list_containers(request, 5, None)
+        })
+        .try_collect()
+        .await
+        .unwrap();
+        assert_eq!(tokens, ["", "2", "4"]);
+        assert_eq!(
+            pages
+                .iter()
+                .map(|x| x.next_page_token.as_str())
+                .collect::<Vec<_>>(),
+            ["2", "4", ""]
+        );
+
+        let pages: Vec<ListContainersResponse> =
+            pagination::pages(list_request(), |request| list_containers(request, 0, None))
+                .try_collect()
+                .await
+                .unwrap();
+        assert_eq!(pages, [ListContainersResponse::default()]);
+    }
+
+    #[tokio::test]
+    async fn it_streams_items_of_all_pages() {
+        let ids: Vec<Option<i64>> =
+            pagination::items(list_request(), |request| list_containers(request, 5, None))
+                .map_ok(|x| x.id)
+                .try_collect()
+                .await
+                .unwrap();
+        assert_eq!(ids, [Some(0), Some(1), Some(2), Some(3), Some(4)]);
+    }
+
+    #[tokio::test]
+    async fn it_ends_streams_at_errors() {
+        let results: Vec<Result<ListContainersResponse, tonic::Status>> =
+            pagination::pages(list_request(), |request| {
+                list_containers(request, 5, Some(2))
+            })
+            .collect()
+            .await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().next_page_token, "2");
+        assert_eq!(
+            results[1].as_ref().unwrap_err().code(),
+            tonic::Code::Unavailable
+        );
+
+        let error = pagination::items(list_request(), |request| {
+            list_containers(request, 5, Some(4))
+        })
+        .try_collect::<Vec<Container>>()
+        .await
+        .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::Unavailable);
+    }
 }
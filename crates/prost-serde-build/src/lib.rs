@@ -1,17 +1,32 @@
 use std::{
-    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     fs::{create_dir_all, File},
-    io::BufWriter,
-    path::PathBuf,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
     sync::OnceLock,
 };
 
-use anyhow::{anyhow, Context};
-use appbiotic_api_protogen_spec::{ExternPath, ProtoPackageSpec, ProtogenSpec};
+use anyhow::{anyhow, ensure, Context};
+use appbiotic_api_protogen_fetch::Fetcher;
+use appbiotic_api_protogen_spec::{
+    DependencyLock, ExternPath, ProtoPackageSpec, ProtoSrc as SpecProtoSrc, ProtogenSpec,
+    RustPackage,
+};
 use handlebars::Handlebars;
-use heck::ToLowerCamelCase;
-use prost_types::{DescriptorProto, EnumDescriptorProto};
+use heck::{ToShoutySnakeCase, ToSnakeCase, ToUpperCamelCase};
+use prost_reflect::{
+    Cardinality, DescriptorPool, ExtensionDescriptor, FieldDescriptor, Kind, MessageDescriptor,
+    Value,
+};
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+    FileDescriptorSet,
+};
 use serde_json::json;
+use sha2::{Digest, Sha256};
+
+pub mod plugin;
 
 #[derive(serde::Deserialize)]
 pub struct Config {
@@ -48,6 +63,16 @@ fn prost_wkt_extern_paths() -> &'static BTreeSet<ExternPath> {
     SET.get_or_init(|| serde_json::from_str(include_str!("prost-wkt-extern-paths.json")).unwrap())
 }
 
+/// Configuration of `prost-build` shared by the build script and the protoc
+/// plugin, deriving `serde` for every type.
+fn prost_config() -> prost_build::Config {
+    let mut config = prost_build::Config::new();
+    config
+        .type_attribute(".", "#[derive(serde::Serialize,serde::Deserialize)]")
+        .type_name_domain(["."], "type.googleapis.com");
+    config
+}
+
 pub fn build(
     protogen_spec: ProtogenSpec,
     package_name: &str,
@@ -71,35 +96,156 @@ pub fn build(
     let proto_package_spec_file = prost_serde_out_path.join("_proto_package_spec.json");
     let metadata_rs_file = prost_serde_out_path.join("_metadata.rs");
     let index_rs_file = prost_serde_out_path.join("_index.rs");
+    let services_rs_file = prost_serde_out_path.join("_services.rs");
+    let field_mask_rs_file = prost_serde_out_path.join("_field_mask.rs");
+    let sample_rs_file = prost_serde_out_path.join("_sample.rs");
+    let text_format_rs_file = prost_serde_out_path.join("_text_format.rs");
 
-    let rust_package = protogen_spec
-        .rust
-        .iter()
-        .find(|x| x.name.eq(package_name))
-        .ok_or_else(|| {
-            anyhow!("Failed to find rust package named `{package_name}` in protogen_spec")
-        })?;
+    let rust_package = protogen_spec.package(package_name)?;
 
     let dependencies: HashMap<String, ProtoPackageSpec> =
         HashMap::from_iter(dependencies.into_iter().map(|x| (x.name.to_owned(), x)));
+    let lock = dependency_lock()?;
+    for dependency in dependencies.values() {
+        verify_descriptor(&lock, dependency)?;
+        verify_compatibility(rust_package, dependency)?;
+    }
+    for dependency_name in rust_package
+        .protogen_dependencies
+        .iter()
+        .chain(rust_package.api_dependencies.iter().map(|x| &x.name))
+    {
+        ensure!(
+            dependencies.contains_key(dependency_name),
+            "Missing proto package spec of dependency `{dependency_name}` for package `{package_name}`"
+        );
+    }
+
+    // Local proto directories are relative to the package, which cargo uses as
+    // the working directory of build scripts.
+    let package_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let fetcher = Fetcher::shared(prost_serde_out_path.join("_remote"));
+    let ProtocInputs {
+        files: tonic_protos,
+        include_dirs,
+        protoc_args: import_path_args,
+    } = protoc_inputs(rust_package, &package_dir, &fetcher)?;
+    for dir in rust_package.import_paths.values() {
+        println!(
+            "cargo:rerun-if-changed={}",
+            package_dir.join(dir).to_string_lossy()
+        );
+    }
+
+    // Sources compiling well-known types may redefine them, in which case the
+    // prost-wkt mapping is dropped so references resolve to the local types.
+    let compile_wkt_srcs: Vec<(&SpecProtoSrc, &PathBuf)> = rust_package
+        .protos
+        .iter()
+        .zip(&include_dirs)
+        .filter(|(x, _)| {
+            x.compile_well_known_types
+                .unwrap_or(rust_package.compile_well_known_protos)
+        })
+        .collect();
+    let locally_defined_wkts: HashSet<String> = if compile_wkt_srcs.is_empty() {
+        HashSet::new()
+    } else {
+        let files: Vec<PathBuf> = compile_wkt_srcs
+            .iter()
+            .flat_map(|(x, dir)| x.files.iter().map(|f| dir.join(f)))
+            .collect();
+        let names: HashSet<String> = compile_wkt_srcs
+            .iter()
+            .flat_map(|(x, _)| x.files.iter().map(|f| f.to_string_lossy().to_string()))
+            .collect();
+        let mut fds_config = prost_build::Config::new();
+        for arg in &import_path_args {
+            fds_config.protoc_arg(arg);
+        }
+        let fds = fds_config
+            .load_fds(&files, &include_dirs)
+            .context("Failed to load descriptors of sources compiling well-known types")?;
+        fds.file
+            .iter()
+            .filter(|f| names.contains(f.name()))
+            .flat_map(|f| {
+                f.message_type
+                    .iter()
+                    .map(|x| x.name())
+                    .chain(f.enum_type.iter().map(|x| x.name()))
+                    .map(|x| format!(".{}.{x}", f.package()))
+            })
+            .collect()
+    };
 
     let extern_paths: HashSet<&ExternPath> = HashSet::from_iter(
         dependencies
             .iter()
             .flat_map(|x| &x.1.extern_paths)
-            .chain(prost_wkt_extern_paths().iter()),
+            .chain(&rust_package.extern_paths)
+            .chain(rust_package.includes.iter().flat_map(|x| &x.extern_paths))
+            .chain(
+                prost_wkt_extern_paths()
+                    .iter()
+                    .filter(|x| !locally_defined_wkts.contains(&x.proto_path)),
+            ),
     );
-    let include_dirs: Vec<&PathBuf> = Vec::from_iter(rust_package.protos.iter().map(|x| &x.dir));
 
-    let mut prost_config = prost_build::Config::new();
+    let mut prost_config = prost_config();
 
-    prost_config
-        .type_attribute(".", "#[derive(serde::Serialize,serde::Deserialize)]")
-        .type_name_domain(["."], "type.googleapis.com");
+    for rule in &rust_package.type_attributes {
+        for attribute in &rule.attributes {
+            ensure!(
+                attribute.starts_with("#[") && attribute.ends_with(']'),
+                "Type attribute `{attribute}` for path `{}` must look like `#[...]`",
+                rule.path
+            );
+            prost_config.type_attribute(&rule.path, attribute);
+        }
+    }
+
+    let wkt_paths: HashSet<&str> = prost_wkt_extern_paths()
+        .iter()
+        .map(|x| x.proto_path.as_str())
+        .filter(|x| !locally_defined_wkts.contains(*x))
+        .collect();
 
-    if rust_package.compile_well_known_protos {
+    if rust_package.json_schemas || rust_package.openapi_schemas || rust_package.arbitrary {
+        let mut fds_config = prost_build::Config::new();
+        for arg in &import_path_args {
+            fds_config.protoc_arg(arg);
+        }
+        let fds = fds_config
+            .load_fds(&tonic_protos, &include_dirs)
+            .context("Failed to load descriptors of types with derives")?;
+        schema_attributes(
+            &mut prost_config,
+            &fds.file,
+            &wkt_paths,
+            rust_package.json_schemas,
+            rust_package.openapi_schemas,
+            true,
+        );
+        if rust_package.arbitrary {
+            arbitrary_attributes(
+                &mut prost_config,
+                &fds.file,
+                &wkt_paths,
+                !compile_wkt_srcs.is_empty(),
+                true,
+            );
+        }
+    }
+
+    if !compile_wkt_srcs.is_empty() {
         prost_config.compile_well_known_types();
     }
+    for arg in &import_path_args {
+        prost_config.protoc_arg(arg);
+    }
     for ExternPath {
         proto_path,
         rust_path,
@@ -108,31 +254,33 @@ pub fn build(
         prost_config.extern_path(proto_path.to_owned(), rust_path.to_owned());
     }
 
+    // Clients and servers are gated on the features of the generated crate.
+    // The transport dependent client constructors cannot be gated that way, so
+    // they are only generated when the build script sees `transport` enabled.
     let config = tonic_build::configure()
         .include_file(&include_file)
         .file_descriptor_set_path(&descriptor_file)
         .build_client(true)
+        .client_mod_attribute(".", r#"#[cfg(feature = "tonic-client")]"#)
         .build_server(true)
-        .build_transport(true)
+        .server_mod_attribute(".", r#"#[cfg(feature = "tonic-server")]"#)
+        .build_transport(std::env::var_os("CARGO_FEATURE_TRANSPORT").is_some())
+        .generate_default_stubs(!rust_package.bins.is_empty())
         .out_dir(&prost_serde_out_path);
 
-    let tonic_protos: Vec<PathBuf> = rust_package
-        .protos
-        .iter()
-        .flat_map(|x| x.files.iter().map(|f| x.dir.join(f)))
-        .collect::<Vec<PathBuf>>();
-
     config
         .compile_protos_with_config(prost_config, &tonic_protos, include_dirs.as_slice())
         .unwrap();
 
-    let descriptor_bytes = std::fs::read(descriptor_file).unwrap();
+    let descriptor_bytes = std::fs::read(&descriptor_file).unwrap();
 
     let mut descriptor = <prost_wkt_build::FileDescriptorSet as prost_wkt_build::Message>::decode(
         &descriptor_bytes[..],
     )
     .unwrap();
 
+    let recursive: HashSet<String> = recursive_fields(&descriptor.file).into_iter().collect();
+
     // NOTE(https://github.com/tokio-rs/prost/issues/880)
     let retain_files: HashSet<String> = HashSet::from_iter(
         rust_package
@@ -144,7 +292,31 @@ pub fn build(
         retain_files.contains(f.name()) && rust_package.proto_package_name.eq(f.package())
     });
 
-    let root_rust_path = format!("{}::prost_serde", rust_package.name.to_lower_camel_case());
+    let samples = if rust_package.samples {
+        let pool = DescriptorPool::decode(&descriptor_bytes[..])
+            .context("Failed to decode descriptors of samples")?;
+        // Protogen dependencies set `samples` too, so their messages
+        // implement `Sample`.
+        let dependency_names: HashSet<&str> = rust_package
+            .protogen_dependencies
+            .iter()
+            .filter_map(|x| dependencies.get(x))
+            .flat_map(|x| &x.extern_paths)
+            .map(|x| x.proto_path.trim_start_matches('.'))
+            .collect();
+        sample_data(
+            &pool,
+            &descriptor,
+            &dependency_names,
+            &recursive,
+            &wkt_paths,
+            !compile_wkt_srcs.is_empty(),
+        )
+    } else {
+        Vec::new()
+    };
+
+    let root_rust_path = format!("::{}::prost_serde", rust_package.name.to_snake_case());
 
     let mut types: VecDeque<ProtoType> = VecDeque::new();
     for file in &descriptor.file {
@@ -175,7 +347,7 @@ pub fn build(
                         rust_path: format!(
                             "{}::{}",
                             type_.rust_path,
-                            descriptor_proto.name().to_lower_camel_case()
+                            descriptor_proto.name().to_snake_case()
                         ),
                         def: ProtoDef::Message(embedded_msg),
                     })
@@ -186,29 +358,41 @@ pub fn build(
                         rust_path: format!(
                             "{}::{}",
                             type_.rust_path,
-                            descriptor_proto.name().to_lower_camel_case()
+                            descriptor_proto.name().to_snake_case()
                         ),
                         def: ProtoDef::Enum(embedded_enum),
                     })
                 }
 
-                extern_paths.push(ExternPath {
-                    proto_path: format!("{}.{}", type_.proto_path, descriptor_proto.name()),
-                    rust_path: format!("{}::{}", type_.rust_path, descriptor_proto.name()),
-                });
+                extern_paths.push(ExternPath::new(
+                    format!("{}.{}", type_.proto_path, descriptor_proto.name()),
+                    format!(
+                        "{}::{}",
+                        type_.rust_path,
+                        descriptor_proto.name().to_upper_camel_case()
+                    ),
+                )?);
             }
             ProtoDef::Enum(enum_descriptor_proto) => {
-                extern_paths.push(ExternPath {
-                    proto_path: format!("{}.{}", type_.proto_path, enum_descriptor_proto.name()),
-                    rust_path: format!("{}::{}", type_.rust_path, enum_descriptor_proto.name()),
-                });
+                extern_paths.push(ExternPath::new(
+                    format!("{}.{}", type_.proto_path, enum_descriptor_proto.name()),
+                    format!(
+                        "{}::{}",
+                        type_.rust_path,
+                        enum_descriptor_proto.name().to_upper_camel_case()
+                    ),
+                )?);
             }
         }
     }
 
     let proto_package_spec = ProtoPackageSpec {
         name: package_name.to_owned(),
+        version: Some(rust_package.version.to_owned()),
+        compatibility: rust_package.compatibility,
         extern_paths,
+        descriptor_sha256: Some(sha256_hex(&descriptor_bytes)),
+        descriptor_path: Some(descriptor_file),
     };
 
     serde_json::to_writer_pretty(
@@ -227,12 +411,28 @@ pub fn build(
         )
     })?;
 
+    // Packages declaring `links` pass the spec on to the build scripts of
+    // their dependents, see `dependency_specs`.
+    if std::env::var_os("CARGO_MANIFEST_LINKS").is_some() {
+        println!(
+            "cargo:proto_package_spec={}",
+            proto_package_spec_file.to_string_lossy()
+        );
+    }
+
     {
         let mut handlebars = Handlebars::new();
 
         let templates = [
             ("index.rs", include_str!("templates/index.rs.hbs")),
             ("metadata.rs", include_str!("templates/metadata.rs.hbs")),
+            ("services.rs", include_str!("templates/services.rs.hbs")),
+            ("field_mask.rs", include_str!("templates/field_mask.rs.hbs")),
+            ("sample.rs", include_str!("templates/sample.rs.hbs")),
+            (
+                "text_format.rs",
+                include_str!("templates/text_format.rs.hbs"),
+            ),
         ];
         for (name, tpl_str) in templates {
             handlebars
@@ -244,11 +444,35 @@ pub fn build(
             (
                 "index.rs",
                 json!({
-                    "rust_package_rel_path": rust_package.proto_package_name.replace('.', "::")
+                    "rust_package_rel_path": rust_package.proto_package_name.replace('.', "::"),
+                    "types": type_names(&descriptor),
+                    "services": services_data(&descriptor),
+                    "paginated": paginated_names(&descriptor),
+                    "pages": pages_data(&descriptor),
+                    "datetimes": datetime_data(&descriptor, &wkt_paths),
+                    "field_masks": rust_package.field_masks,
+                    "samples": rust_package.samples,
+                    "text_format": rust_package.text_format,
                 }),
                 index_rs_file,
             ),
             ("metadata.rs", json!({}), metadata_rs_file),
+            (
+                "services.rs",
+                json!({ "services": services_data(&descriptor) }),
+                services_rs_file,
+            ),
+            (
+                "field_mask.rs",
+                json!({ "messages": field_mask_data(&descriptor) }),
+                field_mask_rs_file,
+            ),
+            ("sample.rs", json!({ "messages": samples }), sample_rs_file),
+            (
+                "text_format.rs",
+                json!({ "messages": message_names(&descriptor) }),
+                text_format_rs_file,
+            ),
         ];
 
         for (name, data, path) in outputs {
@@ -276,3 +500,1331 @@ pub fn build(
 
     Ok(())
 }
+
+/// Rust names of the top-level messages and enums of the package's
+/// `descriptor`, for its prelude.
+fn type_names(descriptor: &FileDescriptorSet) -> BTreeSet<String> {
+    descriptor
+        .file
+        .iter()
+        .flat_map(|file| {
+            file.message_type
+                .iter()
+                .map(|x| x.name())
+                .chain(file.enum_type.iter().map(|x| x.name()))
+        })
+        .map(|x| x.to_upper_camel_case())
+        .collect()
+}
+
+/// Names of the top-level messages of the package's `descriptor` with
+/// AIP-158 `page_size` and `page_token` fields.
+fn paginated_names(descriptor: &FileDescriptorSet) -> Vec<String> {
+    descriptor
+        .file
+        .iter()
+        .flat_map(|x| &x.message_type)
+        .filter(|x| {
+            has_field(x, "page_size", Type::Int32) && has_field(x, "page_token", Type::String)
+        })
+        .map(|x| x.name().to_upper_camel_case())
+        .collect()
+}
+
+/// Top-level messages of the package's `descriptor` with an AIP-158
+/// `next_page_token` field, with their first repeated field as the items of
+/// the page. Pages whose item type is outside the package are skipped.
+fn pages_data(descriptor: &FileDescriptorSet) -> Vec<serde_json::Value> {
+    descriptor
+        .file
+        .iter()
+        .flat_map(|file| file.message_type.iter().map(move |x| (file.package(), x)))
+        .filter(|(_, message)| has_field(message, "next_page_token", Type::String))
+        .filter_map(|(package, message)| {
+            let items = message
+                .field
+                .iter()
+                .find(|x| x.label() == Label::Repeated)?;
+            let map = message.nested_type.iter().any(|x| {
+                x.options.as_ref().is_some_and(|x| x.map_entry())
+                    && items.type_name().ends_with(&format!(".{}", x.name()))
+            });
+            if map {
+                return None;
+            }
+            Some(json!({
+                "name": message.name().to_upper_camel_case(),
+                "items_field": items.name().to_snake_case(),
+                "item_type": field_rust_type(package, items)?,
+            }))
+        })
+        .collect()
+}
+
+/// Rust paths and full names of every message of the package's
+/// `descriptor`, for the `_text_format.rs` template.
+fn message_names(descriptor: &FileDescriptorSet) -> Vec<serde_json::Value> {
+    let mut data = Vec::new();
+    for file in &descriptor.file {
+        let mut messages: VecDeque<(String, String, &DescriptorProto)> = file
+            .message_type
+            .iter()
+            .map(|x| (file.package().to_owned(), String::new(), x))
+            .collect();
+        while let Some((scope, module, message)) = messages.pop_front() {
+            if message.options.as_ref().is_some_and(|x| x.map_entry()) {
+                continue;
+            }
+            let full_name = format!("{scope}.{}", message.name());
+            let message_module =
+                format!("{module}{}::", rust_ident(message.name().to_snake_case()));
+            for nested in &message.nested_type {
+                messages.push_back((full_name.to_owned(), message_module.to_owned(), nested));
+            }
+            data.push(json!({
+                "rust_path": format!("{module}{}", rust_ident(message.name().to_upper_camel_case())),
+                "full_name": full_name,
+            }));
+        }
+    }
+    data
+}
+
+/// Messages of the package's `descriptor` with `prost-wkt-types` `Timestamp`
+/// or `Duration` fields outside of oneofs, with those fields and the
+/// `appbiotic_api::datetime` traits converting them, for the helpers of the
+/// index.
+fn datetime_data(
+    descriptor: &FileDescriptorSet,
+    wkt_paths: &HashSet<&str>,
+) -> Vec<serde_json::Value> {
+    let mut data = Vec::new();
+    for file in &descriptor.file {
+        let mut messages: VecDeque<(String, &DescriptorProto)> = file
+            .message_type
+            .iter()
+            .map(|x| (String::new(), x))
+            .collect();
+        while let Some((module, message)) = messages.pop_front() {
+            if message.options.as_ref().is_some_and(|x| x.map_entry()) {
+                continue;
+            }
+            let message_module =
+                format!("{module}{}::", rust_ident(message.name().to_snake_case()));
+            for nested in &message.nested_type {
+                messages.push_back((message_module.to_owned(), nested));
+            }
+            let fields: Vec<_> = message
+                .field
+                .iter()
+                .filter(|x| {
+                    wkt_paths.contains(x.type_name())
+                        && x.label() == Label::Optional
+                        && (x.oneof_index.is_none() || x.proto3_optional())
+                })
+                .filter_map(|x| {
+                    let (std_type, from, to) = match x.type_name() {
+                        ".google.protobuf.Timestamp" => {
+                            ("SystemTime", "FromProtoTimestamp", "ToTimestamp")
+                        }
+                        ".google.protobuf.Duration" => (
+                            "std::time::Duration",
+                            "FromProtoDuration",
+                            "ToProtoDuration",
+                        ),
+                        _ => return None,
+                    };
+                    Some(json!({
+                        "name": x.name().to_snake_case(),
+                        "field": rust_ident(x.name().to_snake_case()),
+                        "std_type": std_type,
+                        "from_trait": from,
+                        "from_fn": from.to_snake_case(),
+                        "to_trait": to,
+                        "to_fn": to.to_snake_case(),
+                    }))
+                })
+                .collect();
+            if fields.is_empty() {
+                continue;
+            }
+            data.push(json!({
+                "rust_path": format!("{module}{}", rust_ident(message.name().to_upper_camel_case())),
+                "fields": fields,
+            }));
+        }
+    }
+    data
+}
+
+/// Fields and oneofs of every message of the package's `descriptor`, for the
+/// `_field_mask.rs` template.
+fn field_mask_data(descriptor: &FileDescriptorSet) -> Vec<serde_json::Value> {
+    let mut data = Vec::new();
+    for file in &descriptor.file {
+        let mut messages: VecDeque<(String, String, &DescriptorProto)> = file
+            .message_type
+            .iter()
+            .map(|x| (file.package().to_owned(), String::new(), x))
+            .collect();
+        while let Some((scope, module, message)) = messages.pop_front() {
+            if message.options.as_ref().is_some_and(|x| x.map_entry()) {
+                continue;
+            }
+            let message_module =
+                format!("{module}{}::", rust_ident(message.name().to_snake_case()));
+            let full_name = format!("{scope}.{}", message.name());
+            for nested in &message.nested_type {
+                messages.push_back((full_name.to_owned(), message_module.to_owned(), nested));
+            }
+            let fields: Vec<_> = message
+                .field
+                .iter()
+                .filter(|x| x.oneof_index.is_none() || x.proto3_optional())
+                .map(|x| {
+                    let sub_message = (x.label() == Label::Optional && x.r#type() == Type::Message)
+                        .then(|| field_rust_type(file.package(), x))
+                        .flatten();
+                    json!({
+                        "name": x.name(),
+                        "field": rust_ident(x.name().to_snake_case()),
+                        "message": sub_message,
+                    })
+                })
+                .collect();
+            let oneofs: Vec<_> = message
+                .oneof_decl
+                .iter()
+                .enumerate()
+                .filter_map(|(index, oneof)| {
+                    let members: Vec<_> = message
+                        .field
+                        .iter()
+                        .filter(|x| x.oneof_index == Some(index as i32) && !x.proto3_optional())
+                        .map(|x| {
+                            json!({
+                                "name": x.name(),
+                                "variant": rust_ident(x.name().to_upper_camel_case()),
+                            })
+                        })
+                        .collect();
+                    (!members.is_empty()).then(|| {
+                        json!({
+                            "field": rust_ident(oneof.name().to_snake_case()),
+                            "rust_type": format!(
+                                "{message_module}{}",
+                                rust_ident(oneof.name().to_upper_camel_case())
+                            ),
+                            "members": members,
+                        })
+                    })
+                })
+                .collect();
+            data.push(json!({
+                "rust_path": format!("{module}{}", rust_ident(message.name().to_upper_camel_case())),
+                "full_name": full_name,
+                "fields": fields,
+                "oneofs": oneofs,
+            }));
+        }
+    }
+    data
+}
+
+/// Fields and oneofs of every message of the package's `descriptor` with the
+/// `appbiotic_api::sample` calls sampling them, for the `_sample.rs`
+/// template. Message fields are sampled through the `Sample` impls of the
+/// package and of `dependency_names`. Fields that would recurse, `Any`s,
+/// `Empty`s, `FieldMask`s, and messages of other packages are left unset.
+fn sample_data(
+    pool: &DescriptorPool,
+    descriptor: &FileDescriptorSet,
+    dependency_names: &HashSet<&str>,
+    recursive: &HashSet<String>,
+    wkt_paths: &HashSet<&str>,
+    compile_well_known_types: bool,
+) -> Vec<serde_json::Value> {
+    let mut queue: VecDeque<MessageDescriptor> = descriptor
+        .file
+        .iter()
+        .filter_map(|x| pool.get_file_by_name(x.name()))
+        .flat_map(|x| x.messages().collect::<Vec<_>>())
+        .collect();
+    let mut messages = Vec::new();
+    while let Some(message) = queue.pop_front() {
+        queue.extend(message.child_messages());
+        if !message.is_map_entry() {
+            messages.push(message);
+        }
+    }
+    let sampled: HashSet<&str> = messages
+        .iter()
+        .map(MessageDescriptor::full_name)
+        .chain(dependency_names.iter().copied())
+        .collect();
+    let annotations = Annotations::new(pool);
+
+    let element = |field: &FieldDescriptor| -> Option<String> {
+        let method = match field.kind() {
+            Kind::Double => "double()",
+            Kind::Float => "float()",
+            Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => "int32()",
+            Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => "int64()",
+            Kind::Uint32 | Kind::Fixed32 => "uint32()",
+            Kind::Uint64 | Kind::Fixed64 => "uint64()",
+            Kind::Bool => "bool()",
+            Kind::String => return Some(annotations.string_element(field)),
+            Kind::Bytes => "bytes()",
+            Kind::Enum(enum_) => {
+                let mut values: Vec<i32> = enum_.values().map(|x| x.number()).collect();
+                values.sort_unstable();
+                values.dedup();
+                return Some(format!(
+                    "enum_value(&[{}])",
+                    values
+                        .iter()
+                        .map(i32::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+            Kind::Message(message) => {
+                let full_name = message.full_name();
+                if wkt_paths.contains(format!(".{full_name}").as_str()) {
+                    match message.name() {
+                        "Duration" => "duration()",
+                        "ListValue" => "list_value()",
+                        "Struct" => "pb_struct()",
+                        "Timestamp" => "timestamp()",
+                        "Value" => "value()",
+                        _ => return None,
+                    }
+                } else if let Some(name) = full_name
+                    .strip_prefix("google.protobuf.")
+                    .filter(|_| !compile_well_known_types)
+                {
+                    // `prost-build` maps the wrappers to the primitives they
+                    // wrap, and the other well-known types to `prost-types`.
+                    match name {
+                        "BoolValue" => "bool()",
+                        "BytesValue" => "bytes()",
+                        "DoubleValue" => "double()",
+                        "FloatValue" => "float()",
+                        "Int32Value" => "int32()",
+                        "Int64Value" => "int64()",
+                        "StringValue" => return Some(annotations.string_element(field)),
+                        "UInt32Value" => "uint32()",
+                        "UInt64Value" => "uint64()",
+                        _ => return None,
+                    }
+                } else if full_name != "google.protobuf.Any" && sampled.contains(full_name) {
+                    "message()"
+                } else {
+                    return None;
+                }
+            }
+        };
+        Some(method.to_owned())
+    };
+
+    let mut data = Vec::new();
+    for message in &messages {
+        let full_name = message.full_name();
+        let mut segments: Vec<&str> = full_name
+            .strip_prefix(message.package_name())
+            .unwrap_or(full_name)
+            .trim_start_matches('.')
+            .split('.')
+            .collect();
+        let name = segments.pop().unwrap_or_default();
+        let module: String = segments
+            .iter()
+            .map(|x| format!("{}::", rust_ident(x.to_snake_case())))
+            .collect();
+        let message_module = format!("{module}{}::", rust_ident(name.to_snake_case()));
+
+        let mut fields = Vec::new();
+        for field in message.fields() {
+            let name = field.name();
+            let proto3_optional = field.field_descriptor_proto().proto3_optional();
+            if (field.containing_oneof().is_some() && !proto3_optional)
+                || recursive.contains(&format!(".{full_name}.{name}"))
+            {
+                continue;
+            }
+            let value = match field.kind() {
+                Kind::Message(entry) if field.is_map() => {
+                    let key = entry.map_entry_key_field();
+                    let key = match key.kind() {
+                        Kind::String => Some("key()".to_owned()),
+                        _ => element(&key),
+                    };
+                    let (Some(key), Some(value)) = (key, element(&entry.map_entry_value_field()))
+                    else {
+                        continue;
+                    };
+                    format!(r#"sampler.field("{name}").map(|x| x.{key}, |x| x.{value})"#)
+                }
+                _ => {
+                    let Some(element) = element(&field) else {
+                        continue;
+                    };
+                    if field.is_list() {
+                        format!(r#"sampler.field("{name}").repeated(|x| x.{element})"#)
+                    } else if field.supports_presence()
+                        && field.cardinality() != Cardinality::Required
+                    {
+                        format!(r#"Some(sampler.field("{name}").{element})"#)
+                    } else {
+                        format!(r#"sampler.field("{name}").{element}"#)
+                    }
+                }
+            };
+            fields.push(json!({
+                "field": rust_ident(name.to_snake_case()),
+                "value": value,
+            }));
+        }
+
+        let mut oneofs = Vec::new();
+        for oneof in message.oneofs() {
+            let variants: Vec<_> = oneof
+                .fields()
+                .filter(|x| {
+                    !x.field_descriptor_proto().proto3_optional()
+                        && !recursive.contains(&format!(
+                            ".{full_name}.{}.{}",
+                            oneof.name(),
+                            x.name()
+                        ))
+                })
+                .filter_map(|x| {
+                    let element = element(&x)?;
+                    Some(json!({
+                        "variant": rust_ident(x.name().to_upper_camel_case()),
+                        "value": format!(r#"sampler.field("{}").{element}"#, x.name()),
+                    }))
+                })
+                .collect();
+            if variants.is_empty() {
+                continue;
+            }
+            oneofs.push(json!({
+                "name": oneof.name(),
+                "field": rust_ident(oneof.name().to_snake_case()),
+                "rust_type": format!(
+                    "{message_module}{}",
+                    rust_ident(oneof.name().to_upper_camel_case())
+                ),
+                "variant_count": variants.len(),
+                "variants": variants,
+            }));
+        }
+
+        data.push(json!({
+            "rust_path": format!("{module}{}", rust_ident(name.to_upper_camel_case())),
+            "fields": fields,
+            "oneofs": oneofs,
+        }));
+    }
+    data
+}
+
+/// The `google.api` annotations of a descriptor pool choosing the values of
+/// string fields, when the pool has their extensions.
+struct Annotations {
+    /// The first pattern of each resource type.
+    patterns: HashMap<String, String>,
+    /// The name field and pattern of each resource message.
+    name_fields: HashMap<String, (String, String)>,
+    resource_reference: Option<ExtensionDescriptor>,
+    field_info: Option<ExtensionDescriptor>,
+}
+
+impl Annotations {
+    fn new(pool: &DescriptorPool) -> Self {
+        let mut patterns = HashMap::new();
+        let mut name_fields = HashMap::new();
+        let resource = |descriptor: &Value| -> Option<(String, String, String)> {
+            let descriptor = descriptor.as_message()?;
+            let string = |name: &str| {
+                descriptor
+                    .get_field_by_name(name)
+                    .and_then(|x| x.as_str().map(str::to_owned))
+                    .unwrap_or_default()
+            };
+            let pattern = descriptor
+                .get_field_by_name("pattern")?
+                .as_list()?
+                .first()?
+                .as_str()?
+                .to_owned();
+            let name_field = Some(string("name_field"))
+                .filter(|x| !x.is_empty())
+                .unwrap_or_else(|| "name".to_owned());
+            Some((string("type"), pattern, name_field))
+        };
+        if let Some(extension) = pool.get_extension_by_name("google.api.resource_definition") {
+            for file in pool.files() {
+                let options = file.options();
+                if !options.has_extension(&extension) {
+                    continue;
+                }
+                if let Some(definitions) = options.get_extension(&extension).as_list() {
+                    for (type_, pattern, _) in definitions.iter().filter_map(resource) {
+                        patterns.entry(type_).or_insert(pattern);
+                    }
+                }
+            }
+        }
+        if let Some(extension) = pool.get_extension_by_name("google.api.resource") {
+            for message in pool.all_messages() {
+                let options = message.options();
+                if !options.has_extension(&extension) {
+                    continue;
+                }
+                if let Some((type_, pattern, name_field)) =
+                    resource(&options.get_extension(&extension))
+                {
+                    patterns.insert(type_, pattern.to_owned());
+                    name_fields.insert(message.full_name().to_owned(), (name_field, pattern));
+                }
+            }
+        }
+        Self {
+            patterns,
+            name_fields,
+            resource_reference: pool.get_extension_by_name("google.api.resource_reference"),
+            field_info: pool.get_extension_by_name("google.api.field_info"),
+        }
+    }
+
+    /// The `Field` method sampling the string `field`: the resource names of
+    /// name fields and resource references, the formats of `field_info`, or
+    /// strings looking like what the field name suggests.
+    fn string_element(&self, field: &FieldDescriptor) -> String {
+        let resource_name = |pattern: &String| format!("resource_name({pattern:?})");
+        if let Some((name_field, pattern)) =
+            self.name_fields.get(field.parent_message().full_name())
+        {
+            if name_field == field.name() {
+                return resource_name(pattern);
+            }
+        }
+        let options = field.options();
+        if let Some(extension) = &self.resource_reference {
+            if options.has_extension(extension) {
+                let reference = options.get_extension(extension);
+                let pattern = reference
+                    .as_message()
+                    .and_then(|x| x.get_field_by_name("type"))
+                    .and_then(|x| self.patterns.get(x.as_str()?).cloned());
+                if let Some(pattern) = pattern {
+                    return resource_name(&pattern);
+                }
+            }
+        }
+        if let Some(extension) = &self.field_info {
+            if options.has_extension(extension) {
+                let format = options
+                    .get_extension(extension)
+                    .as_message()
+                    .and_then(|x| x.get_field_by_name("format"))
+                    .and_then(|x| x.as_enum_number());
+                // `UUID4`, `IPV4`, `IPV6`, and `IPV4_OR_IPV6`.
+                match format {
+                    Some(1) => return "uuid()".to_owned(),
+                    Some(2 | 4) => return "ipv4()".to_owned(),
+                    Some(3) => return "ipv6()".to_owned(),
+                    _ => {}
+                }
+            }
+        }
+        "string()".to_owned()
+    }
+}
+
+/// Adds the attributes deriving `schemars::JsonSchema` and
+/// `utoipa::ToSchema` for the types generated from `files` to `config`, gated
+/// on the `schemars` and `utoipa` features of generated crates when
+/// `feature_gated`. Fields typed by the `prost-wkt-types` well-known types in
+/// `wkt_paths`, which implement neither, refer to the schemas of
+/// `appbiotic_api` instead.
+fn schema_attributes(
+    config: &mut prost_build::Config,
+    files: &[FileDescriptorProto],
+    wkt_paths: &HashSet<&str>,
+    json_schemas: bool,
+    openapi_schemas: bool,
+    feature_gated: bool,
+) {
+    let gated = |feature: &str, attribute: &str| gated_attribute(feature_gated, feature, attribute);
+    if json_schemas {
+        config.type_attribute(".", gated("schemars", "derive(::schemars::JsonSchema)"));
+        for (path, schema) in
+            well_known_field_schemas(files, wkt_paths, "::appbiotic_api::json_schema")
+        {
+            config.field_attribute(
+                path,
+                gated("schemars", &format!(r#"schemars(with = "{schema}")"#)),
+            );
+        }
+    }
+    if openapi_schemas {
+        // The derive comes before the `schema` helper attributes, which
+        // name every type by its full proto name so that types of different
+        // packages or parents do not collide.
+        config.type_attribute(".", gated("utoipa", "derive(::utoipa::ToSchema)"));
+        for (path, name) in openapi_schema_names(files) {
+            config.type_attribute(path, gated("utoipa", &format!("schema(as = {name})")));
+        }
+        for (path, schema) in well_known_field_schemas(files, wkt_paths, "::appbiotic_api::openapi")
+        {
+            config.field_attribute(
+                path,
+                gated("utoipa", &format!("schema(value_type = {schema})")),
+            );
+        }
+        // `utoipa` collects the schemas a type refers to recursively.
+        for path in recursive_fields(files) {
+            config.field_attribute(path, gated("utoipa", "schema(no_recursion)"));
+        }
+    }
+}
+
+/// `#[attribute]`, or `#[cfg_attr(feature = "feature", attribute)]` when
+/// `feature_gated`.
+fn gated_attribute(feature_gated: bool, feature: &str, attribute: &str) -> String {
+    if feature_gated {
+        format!(r#"#[cfg_attr(feature = "{feature}", {attribute})]"#)
+    } else {
+        format!("#[{attribute}]")
+    }
+}
+
+/// Adds the attributes deriving `proptest::arbitrary::Arbitrary` for the
+/// types generated from `files` to `config`, gated on the `proptest` feature
+/// of generated crates when `feature_gated`. Fields get the strategies of
+/// `appbiotic_api::arbitrary` where `any` would produce values protobuf or
+/// JSON reject, such as unknown enum values and non-finite floats, or large
+/// collections. Fields that would recurse, `Any`s, which pack messages
+/// `Arbitrary` cannot know, and `Empty`s, which JSON cannot tell from unset,
+/// are left empty. With `compile_well_known_types`, the wrapper types are
+/// messages rather than the primitives they wrap.
+fn arbitrary_attributes(
+    config: &mut prost_build::Config,
+    files: &[FileDescriptorProto],
+    wkt_paths: &HashSet<&str>,
+    compile_well_known_types: bool,
+    feature_gated: bool,
+) {
+    let gated = |attribute: &str| gated_attribute(feature_gated, "proptest", attribute);
+    config.type_attribute(".", gated("derive(::proptest_derive::Arbitrary)"));
+
+    let mut enum_values: HashMap<String, Vec<i32>> = HashMap::new();
+    let mut messages: VecDeque<(String, &DescriptorProto, bool)> = VecDeque::new();
+    for file in files {
+        let proto3 = file.syntax() == "proto3";
+        for enum_ in &file.enum_type {
+            enum_values.insert(
+                format!(".{}.{}", file.package(), enum_.name()),
+                values(enum_),
+            );
+        }
+        messages.extend(
+            file.message_type
+                .iter()
+                .map(|x| (format!(".{}.{}", file.package(), x.name()), x, proto3)),
+        );
+    }
+    let mut fields = Vec::new();
+    while let Some((full_name, message, proto3)) = messages.pop_front() {
+        for enum_ in &message.enum_type {
+            enum_values.insert(format!("{full_name}.{}", enum_.name()), values(enum_));
+        }
+        for nested in &message.nested_type {
+            messages.push_back((format!("{full_name}.{}", nested.name()), nested, proto3));
+        }
+        if !message.options.as_ref().is_some_and(|x| x.map_entry()) {
+            fields.push((full_name, message, proto3));
+        }
+    }
+    let recursive: HashSet<String> = recursive_fields(files).into_iter().collect();
+
+    let element = |field: &FieldDescriptorProto| -> Element {
+        let module = "::appbiotic_api::arbitrary";
+        let strategy = |name: &str| Element::Strategy(format!("{module}::{name}()"));
+        match field.r#type() {
+            Type::Double => strategy("finite_f64"),
+            Type::Float => strategy("finite_f32"),
+            Type::Enum => match enum_values.get(field.type_name()) {
+                Some(values) => Element::Strategy(format!(
+                    "{module}::enum_value(&[{}])",
+                    values
+                        .iter()
+                        .map(i32::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+                None => Element::Any,
+            },
+            Type::Message | Type::Group => {
+                let type_name = field.type_name();
+                if wkt_paths.contains(type_name) {
+                    match type_name.rsplit_once('.').map_or("", |x| x.1) {
+                        "Duration" => strategy("duration"),
+                        "FieldMask" => strategy("field_mask"),
+                        "ListValue" => strategy("list_value"),
+                        "Struct" => strategy("pb_struct"),
+                        "Timestamp" => strategy("timestamp"),
+                        "Value" => strategy("value"),
+                        _ => Element::Empty,
+                    }
+                } else if compile_well_known_types {
+                    Element::Any
+                } else {
+                    // `prost-build` maps the wrappers to the primitives they
+                    // wrap, `Empty` to `()`, which `serde` writes as the
+                    // `null` of unset fields, and the other well-known types
+                    // to `prost-types`.
+                    match type_name.strip_prefix(".google.protobuf.") {
+                        Some("DoubleValue") => strategy("finite_f64"),
+                        Some("FloatValue") => strategy("finite_f32"),
+                        Some(
+                            "BoolValue" | "BytesValue" | "Int32Value" | "Int64Value"
+                            | "StringValue" | "UInt32Value" | "UInt64Value",
+                        )
+                        | None => Element::Any,
+                        Some(_) => Element::Empty,
+                    }
+                }
+            }
+            _ => Element::Any,
+        }
+    };
+
+    let empty = r#"proptest(value = "::core::default::Default::default()")"#;
+    for (full_name, message, proto3) in fields {
+        let oneofs: BTreeMap<i32, Vec<(&FieldDescriptorProto, Element)>> = message
+            .field
+            .iter()
+            .filter(|x| x.oneof_index.is_some() && !x.proto3_optional())
+            .fold(BTreeMap::new(), |mut oneofs, field| {
+                let path = format!(
+                    "{full_name}.{}.{}",
+                    message.oneof_decl[field.oneof_index() as usize].name(),
+                    field.name()
+                );
+                let element = if recursive.contains(&path) {
+                    Element::Empty
+                } else {
+                    element(field)
+                };
+                oneofs
+                    .entry(field.oneof_index())
+                    .or_default()
+                    .push((field, element));
+                oneofs
+            });
+        for (index, variants) in &oneofs {
+            let oneof = message.oneof_decl[*index as usize].name();
+            let any_supported = variants.iter().any(|x| !matches!(x.1, Element::Empty));
+            for (field, element) in variants {
+                let variant = rust_ident(field.name().to_upper_camel_case());
+                let attribute = match element {
+                    Element::Any => continue,
+                    Element::Strategy(strategy) => format!(
+                        r#"proptest(strategy = "::proptest::strategy::Strategy::prop_map({strategy}, Self::{variant})")"#
+                    ),
+                    Element::Empty if any_supported => "proptest(skip)".to_owned(),
+                    Element::Empty => format!(
+                        r#"proptest(value = "Self::{variant}(::core::default::Default::default())")"#
+                    ),
+                };
+                config.field_attribute(
+                    format!("{full_name}.{oneof}.{}", field.name()),
+                    gated(&attribute),
+                );
+            }
+            // The enum of such a oneof still needs a variant, but the message
+            // leaves it unset. Without a leading `.`, the path does not match
+            // the variants too.
+            if !any_supported {
+                config.field_attribute(format!("{}.{oneof}", &full_name[1..]), gated(empty));
+            }
+        }
+
+        for field in &message.field {
+            if field.oneof_index.is_some() && !field.proto3_optional() {
+                continue;
+            }
+            let path = format!("{full_name}.{}", field.name());
+            let map_entry = message.nested_type.iter().find(|x| {
+                x.options.as_ref().is_some_and(|x| x.map_entry())
+                    && field.type_name() == format!("{full_name}.{}", x.name())
+            });
+            let element = if recursive.contains(&path) {
+                Element::Empty
+            } else {
+                match map_entry {
+                    Some(entry) if entry.field.len() == 2 => element(&entry.field[1]),
+                    Some(_) => continue,
+                    None => element(field),
+                }
+            };
+            let optional = field.proto3_optional()
+                || (field.label() == Label::Optional
+                    && (!proto3 || matches!(field.r#type(), Type::Message | Type::Group)));
+            let strategy = match (element, map_entry.is_some(), field.label()) {
+                (Element::Empty, _, _) => {
+                    config.field_attribute(path, gated(empty));
+                    continue;
+                }
+                (Element::Any, true, _) => "::appbiotic_api::arbitrary::map()".to_owned(),
+                (Element::Strategy(value), true, _) => {
+                    format!("::appbiotic_api::arbitrary::map_of({value})")
+                }
+                (Element::Any, false, Label::Repeated) => {
+                    "::appbiotic_api::arbitrary::repeated()".to_owned()
+                }
+                (Element::Strategy(element), false, Label::Repeated) => {
+                    format!("::appbiotic_api::arbitrary::repeated_of({element})")
+                }
+                (Element::Any, false, _) => continue,
+                (Element::Strategy(element), false, _) if optional => {
+                    format!("::proptest::option::of({element})")
+                }
+                (Element::Strategy(element), false, _) => element,
+            };
+            config.field_attribute(
+                path,
+                gated(&format!(r#"proptest(strategy = "{strategy}")"#)),
+            );
+        }
+    }
+}
+
+/// The strategy of the values of a field, or of the elements of repeated
+/// and map fields.
+enum Element {
+    /// `proptest::arbitrary::any`.
+    Any,
+    Strategy(String),
+    /// No values, leaving the field at its default.
+    Empty,
+}
+
+/// Values of `enum_`, without aliases.
+fn values(enum_: &EnumDescriptorProto) -> Vec<i32> {
+    let mut values: Vec<i32> = enum_.value.iter().map(|x| x.number()).collect();
+    values.sort_unstable();
+    values.dedup();
+    values
+}
+
+/// `(path, type)` pairs of the fields of `files` typed by the
+/// `prost-wkt-types` well-known types in `wkt_paths`, with the type of their
+/// schema in `module`, such as `::std::option::Option<{module}::Timestamp>`.
+/// The paths of oneof fields are those of their variants.
+fn well_known_field_schemas(
+    files: &[FileDescriptorProto],
+    wkt_paths: &HashSet<&str>,
+    module: &str,
+) -> Vec<(String, String)> {
+    let mut schemas = Vec::new();
+    for file in files {
+        let mut messages: VecDeque<(String, &DescriptorProto)> = file
+            .message_type
+            .iter()
+            .map(|x| (format!(".{}.{}", file.package(), x.name()), x))
+            .collect();
+        while let Some((full_name, message)) = messages.pop_front() {
+            for nested in &message.nested_type {
+                messages.push_back((format!("{full_name}.{}", nested.name()), nested));
+            }
+            for field in &message.field {
+                let map_entry = message.nested_type.iter().find(|x| {
+                    x.options.as_ref().is_some_and(|x| x.map_entry())
+                        && field.type_name() == format!("{full_name}.{}", x.name())
+                });
+                let (key, value) = match map_entry {
+                    Some(entry) if entry.field.len() == 2 => {
+                        (Some(&entry.field[0]), &entry.field[1])
+                    }
+                    Some(_) => continue,
+                    None => (None, field),
+                };
+                if !wkt_paths.contains(value.type_name()) {
+                    continue;
+                }
+                let Some((_, name)) = value.type_name().rsplit_once('.') else {
+                    continue;
+                };
+                let schema = format!("{module}::{name}");
+                let oneof = field
+                    .oneof_index
+                    .filter(|_| !field.proto3_optional())
+                    .and_then(|x| message.oneof_decl.get(x as usize));
+                let (path, schema) = match (oneof, key) {
+                    (Some(oneof), _) => (format!("{full_name}.{}", oneof.name()), schema),
+                    (None, Some(key)) => (
+                        full_name.to_owned(),
+                        format!(
+                            "::std::collections::HashMap<{}, {schema}>",
+                            field_rust_type(file.package(), key).unwrap_or_default()
+                        ),
+                    ),
+                    (None, None) if field.label() == Label::Repeated => {
+                        (full_name.to_owned(), format!("::std::vec::Vec<{schema}>"))
+                    }
+                    (None, None) => (
+                        full_name.to_owned(),
+                        format!("::std::option::Option<{schema}>"),
+                    ),
+                };
+                schemas.push((format!("{path}.{}", field.name()), schema));
+            }
+        }
+    }
+    schemas
+}
+
+/// `(path, name)` pairs naming the OpenAPI schema of each message, enum and
+/// oneof of `files` by its full proto name, e.g. `acme::widgets::v1::Widget`
+/// for `acme.widgets.v1.Widget`. The paths have no leading `.`, which
+/// `prost-build` matches as suffixes, so that the names of parents do not
+/// apply to their nested types. Package segments that are Rust keywords are
+/// escaped, which `appbiotic_api::openapi::ProtoSchemaNames` undoes.
+fn openapi_schema_names(files: &[FileDescriptorProto]) -> Vec<(String, String)> {
+    let schema_name = |full_name: &str| {
+        full_name
+            .split('.')
+            .map(|x| rust_ident(x.to_owned()))
+            .collect::<Vec<_>>()
+            .join("::")
+    };
+    let mut names = Vec::new();
+    for file in files {
+        for enum_ in &file.enum_type {
+            let full_name = format!("{}.{}", file.package(), enum_.name());
+            names.push((full_name.clone(), schema_name(&full_name)));
+        }
+        let mut messages: VecDeque<(String, &DescriptorProto)> = file
+            .message_type
+            .iter()
+            .map(|x| (format!("{}.{}", file.package(), x.name()), x))
+            .collect();
+        while let Some((full_name, message)) = messages.pop_front() {
+            if message.options.as_ref().is_some_and(|x| x.map_entry()) {
+                continue;
+            }
+            names.push((full_name.clone(), schema_name(&full_name)));
+            for enum_ in &message.enum_type {
+                let enum_name = format!("{full_name}.{}", enum_.name());
+                names.push((enum_name.clone(), schema_name(&enum_name)));
+            }
+            for (index, oneof) in message.oneof_decl.iter().enumerate() {
+                let synthetic = message
+                    .field
+                    .iter()
+                    .any(|x| x.oneof_index == Some(index as i32) && x.proto3_optional());
+                if !synthetic {
+                    names.push((
+                        format!("{full_name}.{}", oneof.name()),
+                        schema_name(&format!(
+                            "{full_name}.{}",
+                            oneof.name().to_upper_camel_case()
+                        )),
+                    ));
+                }
+            }
+            for nested in &message.nested_type {
+                messages.push_back((format!("{full_name}.{}", nested.name()), nested));
+            }
+        }
+    }
+    names
+}
+
+/// Paths of the fields of `files` whose messages refer back to the message
+/// of the field, directly or through other messages. The paths of oneof
+/// fields are those of their variants.
+fn recursive_fields(files: &[FileDescriptorProto]) -> Vec<String> {
+    let mut messages: Vec<(String, &DescriptorProto)> = Vec::new();
+    for file in files {
+        let mut queue: VecDeque<(String, &DescriptorProto)> = file
+            .message_type
+            .iter()
+            .map(|x| (format!(".{}.{}", file.package(), x.name()), x))
+            .collect();
+        while let Some((full_name, message)) = queue.pop_front() {
+            for nested in &message.nested_type {
+                queue.push_back((format!("{full_name}.{}", nested.name()), nested));
+            }
+            messages.push((full_name, message));
+        }
+    }
+    let references: HashMap<&str, Vec<&str>> = messages
+        .iter()
+        .map(|(full_name, message)| {
+            let types = message
+                .field
+                .iter()
+                .filter(|x| x.r#type() == Type::Message)
+                .map(|x| x.type_name())
+                .collect();
+            (full_name.as_str(), types)
+        })
+        .collect();
+    let reaches = |from: &str, to: &str| {
+        let mut visited = HashSet::new();
+        let mut stack = vec![from];
+        while let Some(name) = stack.pop() {
+            if name == to {
+                return true;
+            }
+            if visited.insert(name) {
+                stack.extend(references.get(name).into_iter().flatten());
+            }
+        }
+        false
+    };
+
+    let mut paths = Vec::new();
+    for (full_name, message) in &messages {
+        if message.options.as_ref().is_some_and(|x| x.map_entry()) {
+            continue;
+        }
+        for field in &message.field {
+            if field.r#type() != Type::Message || !reaches(field.type_name(), full_name) {
+                continue;
+            }
+            let oneof = field
+                .oneof_index
+                .filter(|_| !field.proto3_optional())
+                .and_then(|x| message.oneof_decl.get(x as usize));
+            paths.push(match oneof {
+                Some(oneof) => format!("{full_name}.{}.{}", oneof.name(), field.name()),
+                None => format!("{full_name}.{}", field.name()),
+            });
+        }
+    }
+    paths
+}
+
+/// Escapes `name` the way `prost-build` does when it is a Rust keyword.
+fn rust_ident(name: String) -> String {
+    match name.as_str() {
+        "as" | "break" | "const" | "continue" | "else" | "enum" | "false" | "fn" | "for" | "if"
+        | "impl" | "in" | "let" | "loop" | "match" | "mod" | "move" | "mut" | "pub" | "ref"
+        | "return" | "static" | "struct" | "trait" | "true" | "type" | "unsafe" | "use"
+        | "where" | "while" | "dyn" | "abstract" | "become" | "box" | "do" | "final" | "macro"
+        | "override" | "priv" | "typeof" | "unsized" | "virtual" | "yield" | "async" | "await"
+        | "try" => format!("r#{name}"),
+        "_" | "super" | "self" | "Self" | "extern" | "crate" => format!("{name}_"),
+        _ => name,
+    }
+}
+
+fn has_field(message: &DescriptorProto, name: &str, type_: Type) -> bool {
+    message.field.iter().any(|x| {
+        x.name() == name
+            && x.r#type() == type_
+            && x.label() == Label::Optional
+            && !x.proto3_optional()
+    })
+}
+
+/// Rust type of the elements of `field` relative to the module of `package`,
+/// or `None` for messages of other packages.
+fn field_rust_type(package: &str, field: &FieldDescriptorProto) -> Option<String> {
+    let scalar = match field.r#type() {
+        Type::Double => "f64",
+        Type::Float => "f32",
+        Type::Int64 | Type::Sint64 | Type::Sfixed64 => "i64",
+        Type::Uint64 | Type::Fixed64 => "u64",
+        Type::Int32 | Type::Sint32 | Type::Sfixed32 | Type::Enum => "i32",
+        Type::Uint32 | Type::Fixed32 => "u32",
+        Type::Bool => "bool",
+        Type::String => "String",
+        Type::Bytes => "Vec<u8>",
+        Type::Message | Type::Group => {
+            let name = field.type_name().strip_prefix(&format!(".{package}."))?;
+            let mut segments: Vec<&str> = name.split('.').collect();
+            let type_name = segments.pop()?.to_upper_camel_case();
+            return Some(
+                segments
+                    .into_iter()
+                    .map(|x| x.to_snake_case())
+                    .chain([type_name])
+                    .collect::<Vec<_>>()
+                    .join("::"),
+            );
+        }
+    };
+    Some(scalar.to_owned())
+}
+
+/// Names of the services of the package's `descriptor` and the paths of
+/// their methods, for the `_services.rs` template.
+fn services_data(descriptor: &FileDescriptorSet) -> Vec<serde_json::Value> {
+    descriptor
+        .file
+        .iter()
+        .flat_map(|file| file.service.iter().map(move |x| (file.package(), x)))
+        .map(|(package, service)| {
+            let full_name = format!("{package}.{}", service.name());
+            let methods: Vec<_> = service
+                .method
+                .iter()
+                .map(|x| {
+                    json!({
+                        "const_name": x.name().to_shouty_snake_case(),
+                        "path": format!("/{full_name}/{}", x.name()),
+                    })
+                })
+                .collect();
+            json!({
+                "name": service.name().to_upper_camel_case(),
+                "module": service.name().to_snake_case(),
+                "client_module": format!("{}_client", service.name().to_snake_case()),
+                "server_module": format!("{}_server", service.name().to_snake_case()),
+                "full_name": full_name,
+                "methods": methods,
+            })
+        })
+        .collect()
+}
+
+/// Everything protoc needs to compile the protos of a package.
+pub struct ProtocInputs {
+    /// Proto files to compile.
+    pub files: Vec<PathBuf>,
+    /// Include directories, starting with one per `protos` source in order,
+    /// followed by the `includes`.
+    pub include_dirs: Vec<PathBuf>,
+    /// Additional protoc arguments such as remapped import paths.
+    pub protoc_args: Vec<String>,
+}
+
+/// Resolves the protoc inputs of `rust_package`, whose relative local paths
+/// are resolved against `package_dir`, fetching remote sources with
+/// `fetcher`.
+pub fn protoc_inputs(
+    rust_package: &RustPackage,
+    package_dir: &Path,
+    fetcher: &Fetcher,
+) -> anyhow::Result<ProtocInputs> {
+    fetcher.ensure_cached(rust_package)?;
+    let mut include_dirs: Vec<PathBuf> = rust_package
+        .protos
+        .iter()
+        .map(|x| fetcher.resolve_dir(x, package_dir))
+        .collect::<anyhow::Result<_>>()?;
+    for include in &rust_package.includes {
+        include_dirs.push(fetcher.resolve_include_dir(include, package_dir)?);
+    }
+
+    let files = rust_package
+        .protos
+        .iter()
+        .zip(&include_dirs)
+        .flat_map(|(x, dir)| x.files.iter().map(|f| dir.join(f)))
+        .collect();
+
+    let protoc_args = rust_package
+        .import_paths
+        .iter()
+        .map(|(prefix, dir)| {
+            format!(
+                "--proto_path={}={}",
+                prefix.trim_end_matches('/'),
+                package_dir.join(dir).to_string_lossy()
+            )
+        })
+        .collect();
+
+    Ok(ProtocInputs {
+        files,
+        include_dirs,
+        protoc_args,
+    })
+}
+
+/// Compiles the protos of `rust_package` into a descriptor set including
+/// their imports, without generating any code.
+pub fn compile_descriptor_set(
+    rust_package: &RustPackage,
+    package_dir: &Path,
+    fetcher: &Fetcher,
+) -> anyhow::Result<FileDescriptorSet> {
+    let bytes = encoded_descriptor_set(rust_package, package_dir, fetcher)?;
+    <FileDescriptorSet as prost_wkt_build::Message>::decode(bytes.as_slice()).with_context(|| {
+        format!(
+            "Failed to decode descriptors of package `{}`",
+            rust_package.name
+        )
+    })
+}
+
+/// Hex encoded SHA-256 of the descriptor set [`build`] writes for
+/// `rust_package`, as recorded in its [`ProtoPackageSpec`].
+pub fn descriptor_set_sha256(
+    rust_package: &RustPackage,
+    package_dir: &Path,
+    fetcher: &Fetcher,
+) -> anyhow::Result<String> {
+    Ok(sha256_hex(&encoded_descriptor_set(
+        rust_package,
+        package_dir,
+        fetcher,
+    )?))
+}
+
+/// The descriptor set of `rust_package` as protoc encodes it, compiled the
+/// way [`build`] compiles it.
+fn encoded_descriptor_set(
+    rust_package: &RustPackage,
+    package_dir: &Path,
+    fetcher: &Fetcher,
+) -> anyhow::Result<Vec<u8>> {
+    let inputs = protoc_inputs(rust_package, package_dir, fetcher)?;
+    let path = std::env::temp_dir().join(format!(
+        "{}-{}-descriptor.binpb",
+        rust_package.name,
+        std::process::id()
+    ));
+    let mut config = prost_build::Config::new();
+    config.file_descriptor_set_path(&path);
+    for arg in &inputs.protoc_args {
+        config.protoc_arg(arg);
+    }
+    config
+        .load_fds(&inputs.files, &inputs.include_dirs)
+        .with_context(|| {
+            format!(
+                "Failed to compile descriptors of package `{}`",
+                rust_package.name
+            )
+        })?;
+    let bytes = std::fs::read(&path).with_context(|| {
+        format!(
+            "Failed to read descriptors of package `{}` at path `{}`",
+            rust_package.name,
+            path.to_string_lossy()
+        )
+    });
+    let _ = std::fs::remove_file(&path);
+    bytes
+}
+
+/// Loads the proto package specs of the generated packages named
+/// `dependency_names`, which must be direct dependencies of the package being
+/// built and declare their own name as `links`.
+pub fn dependency_specs(dependency_names: &[&str]) -> anyhow::Result<Vec<ProtoPackageSpec>> {
+    dependency_names
+        .iter()
+        .map(|name| {
+            let var = format!(
+                "DEP_{}_PROTO_PACKAGE_SPEC",
+                name.to_uppercase().replace('-', "_")
+            );
+            let path = PathBuf::from(std::env::var(&var).with_context(|| {
+                format!("Failed to get `{var}` for the proto package spec of dependency `{name}`")
+            })?);
+            println!("cargo:rerun-if-changed={}", path.to_string_lossy());
+            let file = File::open(&path).with_context(|| {
+                format!(
+                    "Failed to open proto package spec at path `{}`",
+                    path.to_string_lossy()
+                )
+            })?;
+            serde_json::from_reader(BufReader::new(file)).with_context(|| {
+                format!(
+                    "Failed to parse proto package spec at path `{}`",
+                    path.to_string_lossy()
+                )
+            })
+        })
+        .collect()
+}
+
+/// Reads the [`DependencyLock`] next to the manifest of the package being
+/// built, which packages not generated by `rust-build` may not have.
+fn dependency_lock() -> anyhow::Result<DependencyLock> {
+    let Some(manifest_dir) = std::env::var_os("CARGO_MANIFEST_DIR") else {
+        return Ok(DependencyLock::default());
+    };
+    let path = PathBuf::from(manifest_dir).join(DependencyLock::FILE_NAME);
+    println!("cargo:rerun-if-changed={}", path.to_string_lossy());
+    if !path.exists() {
+        return Ok(DependencyLock::default());
+    }
+    let file = File::open(&path).with_context(|| {
+        format!(
+            "Failed to open dependency lock at path `{}`",
+            path.to_string_lossy()
+        )
+    })?;
+    serde_json::from_reader(BufReader::new(file)).with_context(|| {
+        format!(
+            "Failed to parse dependency lock at path `{}`",
+            path.to_string_lossy()
+        )
+    })
+}
+
+/// Fails when the descriptor a dependency was built from differs from the
+/// one recorded in `lock` when the package was generated, meaning the
+/// dependency changed without the package being regenerated against it.
+fn verify_descriptor(lock: &DependencyLock, dependency: &ProtoPackageSpec) -> anyhow::Result<()> {
+    let (Some(expected), Some(actual)) = (
+        lock.descriptor_sha256.get(&dependency.name),
+        &dependency.descriptor_sha256,
+    ) else {
+        return Ok(());
+    };
+    ensure!(
+        actual == expected,
+        "Descriptor of dependency `{}` has hash `{actual}` but the package was generated against `{expected}`, regenerate the package",
+        dependency.name
+    );
+    Ok(())
+}
+
+/// Checks the dependency's declared compatibility against the level the
+/// consuming package requires, warning unless the package is strict.
+fn verify_compatibility(
+    rust_package: &RustPackage,
+    dependency: &ProtoPackageSpec,
+) -> anyhow::Result<()> {
+    let Some(required) = rust_package.required_dependency_compatibility else {
+        return Ok(());
+    };
+    if dependency.compatibility >= required {
+        return Ok(());
+    }
+    let message = format!(
+        "Dependency `{}`{} declares `{}` compatibility but `{}` requires `{required}`",
+        dependency.name,
+        dependency
+            .version
+            .as_ref()
+            .map(|x| format!(" version `{x}`"))
+            .unwrap_or_default(),
+        dependency.compatibility,
+        rust_package.name,
+    );
+    if rust_package.strict_compatibility {
+        return Err(anyhow!(message));
+    }
+    println!("cargo:warning={message}");
+    Ok(())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect()
+}
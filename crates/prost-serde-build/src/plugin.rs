@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{anyhow, bail, ensure, Context};
+use appbiotic_api_protogen_spec::ExternPath;
+use heck::ToSnakeCase;
+use prost_build::Module;
+use prost_types::{
+    compiler::{
+        code_generator_response::{Feature, File},
+        CodeGeneratorRequest, CodeGeneratorResponse,
+    },
+    FileDescriptorSet,
+};
+
+use crate::{
+    arbitrary_attributes, prost_config, prost_wkt_extern_paths, rust_ident, schema_attributes,
+};
+
+/// Options of the protoc plugin, passed as comma separated `key=value`
+/// parameters, e.g. `--appbiotic-serde_opt=extern_path=.acme.common=::acme_common`.
+/// Commas within a value are escaped as `\,`.
+pub struct PluginOptions {
+    pub extern_paths: Vec<ExternPath>,
+    /// `(path, attribute)` pairs, as `type_attribute=PATH=ATTRIBUTE`.
+    pub type_attributes: Vec<(String, String)>,
+    pub compile_well_known_types: bool,
+    /// Derive `schemars::JsonSchema` for every type.
+    pub json_schemas: bool,
+    /// Derive `utoipa::ToSchema` for every type, named by its full proto
+    /// name.
+    pub openapi_schemas: bool,
+    /// Derive `proptest::arbitrary::Arbitrary` for every type.
+    pub arbitrary: bool,
+    /// File nesting the generated files in modules named after the packages.
+    pub include_file: Option<String>,
+    pub client: bool,
+    pub server: bool,
+    pub transport: bool,
+}
+
+impl Default for PluginOptions {
+    fn default() -> Self {
+        Self {
+            extern_paths: Vec::new(),
+            type_attributes: Vec::new(),
+            compile_well_known_types: false,
+            json_schemas: false,
+            openapi_schemas: false,
+            arbitrary: false,
+            include_file: None,
+            client: true,
+            server: true,
+            transport: true,
+        }
+    }
+}
+
+impl PluginOptions {
+    pub fn parse(parameter: &str) -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        for param in split_parameter(parameter) {
+            let (key, value) = match param.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (param.as_str(), None),
+            };
+            let required = || value.ok_or_else(|| anyhow!("Plugin option `{key}` needs a value"));
+            match key {
+                "extern_path" => {
+                    let (proto_path, rust_path) = required()?.split_once('=').ok_or_else(|| {
+                        anyhow!("Plugin option `extern_path` must look like `PROTO_PATH=RUST_PATH`")
+                    })?;
+                    options
+                        .extern_paths
+                        .push(ExternPath::new(proto_path, rust_path)?);
+                }
+                "type_attribute" => {
+                    let (path, attribute) = required()?.split_once('=').ok_or_else(|| {
+                        anyhow!("Plugin option `type_attribute` must look like `PATH=ATTRIBUTE`")
+                    })?;
+                    ensure!(
+                        attribute.starts_with("#[") && attribute.ends_with(']'),
+                        "Type attribute `{attribute}` for path `{path}` must look like `#[...]`"
+                    );
+                    options
+                        .type_attributes
+                        .push((path.to_owned(), attribute.to_owned()));
+                }
+                "include_file" => options.include_file = Some(required()?.to_owned()),
+                "compile_well_known_types" => {
+                    options.compile_well_known_types = parse_bool(key, value)?
+                }
+                "json_schemas" => options.json_schemas = parse_bool(key, value)?,
+                "openapi_schemas" => options.openapi_schemas = parse_bool(key, value)?,
+                "arbitrary" => options.arbitrary = parse_bool(key, value)?,
+                "client" => options.client = parse_bool(key, value)?,
+                "server" => options.server = parse_bool(key, value)?,
+                "transport" => options.transport = parse_bool(key, value)?,
+                _ => bail!("Unknown plugin option `{key}`"),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn split_parameter(parameter: &str) -> Vec<String> {
+    let mut params = Vec::new();
+    let mut param = String::new();
+    let mut chars = parameter.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.clone().next() == Some(',') => {
+                param.push(',');
+                chars.next();
+            }
+            ',' => params.push(std::mem::take(&mut param)),
+            c => param.push(c),
+        }
+    }
+    params.push(param);
+    params.retain(|x| !x.trim().is_empty());
+    params
+}
+
+fn parse_bool(key: &str, value: Option<&str>) -> anyhow::Result<bool> {
+    match value {
+        None | Some("true") => Ok(true),
+        Some("false") => Ok(false),
+        Some(value) => bail!("Plugin option `{key}` must be `true` or `false`, found `{value}`"),
+    }
+}
+
+/// Generates the same `prost` messages with `serde` derives, `tonic` services
+/// and `prost-wkt` `MessageSerde` impls as the build script does, one file
+/// per proto package of the files to generate. Errors are reported in the
+/// response, as protoc expects.
+pub fn generate(request: CodeGeneratorRequest) -> CodeGeneratorResponse {
+    let mut response = match generate_files(request) {
+        Ok(file) => CodeGeneratorResponse {
+            file,
+            ..Default::default()
+        },
+        Err(error) => CodeGeneratorResponse {
+            error: Some(format!("{error:#}")),
+            ..Default::default()
+        },
+    };
+    response.supported_features = Some(Feature::Proto3Optional as u64);
+    response
+}
+
+fn generate_files(request: CodeGeneratorRequest) -> anyhow::Result<Vec<File>> {
+    let options = PluginOptions::parse(request.parameter())?;
+
+    let file_to_generate: HashSet<&str> = request
+        .file_to_generate
+        .iter()
+        .map(String::as_str)
+        .collect();
+    let files: Vec<_> = request
+        .proto_file
+        .iter()
+        .filter(|x| file_to_generate.contains(x.name()))
+        .cloned()
+        .collect();
+
+    let mut config = prost_config();
+    for (path, attribute) in &options.type_attributes {
+        config.type_attribute(path, attribute);
+    }
+    if options.compile_well_known_types {
+        config.compile_well_known_types();
+    } else {
+        for ExternPath {
+            proto_path,
+            rust_path,
+        } in prost_wkt_extern_paths()
+        {
+            config.extern_path(proto_path, rust_path);
+        }
+    }
+    for ExternPath {
+        proto_path,
+        rust_path,
+    } in &options.extern_paths
+    {
+        config.extern_path(proto_path, rust_path);
+    }
+    if options.json_schemas || options.openapi_schemas || options.arbitrary {
+        let wkt_paths: HashSet<&str> = if options.compile_well_known_types {
+            HashSet::new()
+        } else {
+            prost_wkt_extern_paths()
+                .iter()
+                .map(|x| x.proto_path.as_str())
+                .collect()
+        };
+        schema_attributes(
+            &mut config,
+            &files,
+            &wkt_paths,
+            options.json_schemas,
+            options.openapi_schemas,
+            false,
+        );
+        if options.arbitrary {
+            // The imported files hold the values of the enums fields refer to.
+            arbitrary_attributes(
+                &mut config,
+                &request.proto_file,
+                &wkt_paths,
+                options.compile_well_known_types,
+                false,
+            );
+        }
+    }
+    config.service_generator(
+        tonic_build::configure()
+            .build_client(options.client)
+            .build_server(options.server)
+            .build_transport(options.transport)
+            .compile_well_known_types(options.compile_well_known_types)
+            .service_generator(),
+    );
+
+    let modules = config
+        .generate(
+            files
+                .iter()
+                .map(|x| (Module::from_protobuf_package_name(x.package()), x.clone()))
+                .collect(),
+        )
+        .context("Failed to generate prost code")?;
+
+    // prost-wkt appends its impls to files named like prost-build names them.
+    let out_dir = tempfile::tempdir().context("Failed to create temporary output directory")?;
+    let mut file_names: BTreeMap<Module, String> = BTreeMap::new();
+    for (module, content) in modules {
+        let file_name = module.to_file_name_or("_");
+        let path = out_dir.path().join(&file_name);
+        std::fs::write(&path, content).with_context(|| {
+            format!(
+                "Failed to write generated file `{}`",
+                path.to_string_lossy()
+            )
+        })?;
+        file_names.insert(module, file_name);
+    }
+    prost_wkt_build::add_serde(out_dir.path().to_owned(), FileDescriptorSet { file: files });
+
+    let mut generated = Vec::new();
+    for file_name in file_names.values() {
+        let path = out_dir.path().join(file_name);
+        let content = std::fs::read_to_string(&path).with_context(|| {
+            format!("Failed to read generated file `{}`", path.to_string_lossy())
+        })?;
+        generated.push(File {
+            name: Some(file_name.to_owned()),
+            content: Some(content),
+            ..Default::default()
+        });
+    }
+    if let Some(include_file) = options.include_file {
+        generated.push(File {
+            name: Some(include_file),
+            content: Some(include_file_content(&file_names)),
+            ..Default::default()
+        });
+    }
+    Ok(generated)
+}
+
+fn include_file_content(file_names: &BTreeMap<Module, String>) -> String {
+    let mut content = String::new();
+    let mut stack: Vec<String> = Vec::new();
+    for (module, file_name) in file_names {
+        let parts: Vec<String> = module
+            .parts()
+            .map(|x| rust_ident(x.to_snake_case()))
+            .collect();
+        while !parts.starts_with(&stack) {
+            stack.pop();
+            content.push_str(&format!("{}}}\n", "    ".repeat(stack.len())));
+        }
+        while stack.len() < parts.len() {
+            let part = parts[stack.len()].to_owned();
+            content.push_str(&format!(
+                "{}pub mod {part} {{\n",
+                "    ".repeat(stack.len())
+            ));
+            stack.push(part);
+        }
+        content.push_str(&format!(
+            "{}include!(\"{file_name}\");\n",
+            "    ".repeat(stack.len())
+        ));
+    }
+    for depth in (0..stack.len()).rev() {
+        content.push_str(&format!("{}}}\n", "    ".repeat(depth)));
+    }
+    content
+}
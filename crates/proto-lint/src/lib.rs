@@ -0,0 +1,315 @@
+//! Style rules over proto descriptors, shared by `rust-build lint` and other
+//! tools linting the protos of protogen specs.
+//!
+//! Each rule has a severity in a [`RuleSet`], and is suppressed for an
+//! element and the elements it encloses by a leading comment such as
+//! `// protogen:lint:ignore field_name`. Suppressions on the `package`
+//! statement apply to the whole file.
+
+mod rules;
+pub mod source_path;
+
+use std::{collections::BTreeMap, fmt};
+
+pub use appbiotic_api_protogen_spec::lint::Severity;
+use prost_types::{
+    DescriptorProto, EnumDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+    ServiceDescriptorProto,
+};
+
+pub use crate::rules::builtin_rules;
+use crate::source_path::{
+    FILE_ENUM_TYPE, FILE_MESSAGE_TYPE, FILE_PACKAGE, FILE_SERVICE, MESSAGE_ENUM_TYPE,
+    MESSAGE_NESTED_TYPE,
+};
+
+/// Prefix of suppression comments, followed by the names of the rules.
+pub const IGNORE_DIRECTIVE: &str = "protogen:lint:ignore";
+
+/// A lint rule, visiting each element of the linted files. `path` is the
+/// source code location path of the element.
+pub trait Rule {
+    /// Name of the rule in severity configs and suppression comments, e.g.
+    /// `field_name`.
+    fn name(&self) -> &str;
+
+    fn check_file(&self, _file: &FileDescriptorProto, _cx: &mut Context<'_>) {}
+    fn check_message(&self, _message: &DescriptorProto, _path: &[i32], _cx: &mut Context<'_>) {}
+    fn check_enum(&self, _enum_: &EnumDescriptorProto, _path: &[i32], _cx: &mut Context<'_>) {}
+    fn check_service(
+        &self,
+        _service: &ServiceDescriptorProto,
+        _path: &[i32],
+        _cx: &mut Context<'_>,
+    ) {
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Finding {
+    pub rule: String,
+    pub severity: Severity,
+    /// Proto file as imported, e.g. `acme/widgets/v1/widgets.proto`.
+    pub file: String,
+    /// One-based line of the offending element, zero when unknown.
+    pub line: i32,
+    /// One-based column of the offending element, zero when unknown.
+    pub column: i32,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}[{}] {}",
+            self.file, self.line, self.column, self.severity, self.rule, self.message
+        )
+    }
+}
+
+/// Where a rule reports its findings on the file being linted.
+pub struct Context<'a> {
+    rule: &'a str,
+    severity: Severity,
+    file: &'a FileDescriptorProto,
+    findings: &'a mut Vec<Finding>,
+}
+
+impl Context<'_> {
+    pub fn file(&self) -> &FileDescriptorProto {
+        self.file
+    }
+
+    /// Reports the element at `path`, unless a comment suppresses the rule
+    /// for it.
+    pub fn report(&mut self, path: &[i32], message: impl Into<String>) {
+        if self.is_suppressed(path) {
+            return;
+        }
+        let (line, column) = self
+            .file
+            .source_code_info
+            .as_ref()
+            .and_then(|info| info.location.iter().find(|x| x.path == path))
+            .and_then(|location| Some((location.span.first()? + 1, location.span.get(1)? + 1)))
+            .unwrap_or_default();
+        self.findings.push(Finding {
+            rule: self.rule.to_owned(),
+            severity: self.severity,
+            file: self.file.name().to_owned(),
+            line,
+            column,
+            message: message.into(),
+        });
+    }
+
+    fn is_suppressed(&self, path: &[i32]) -> bool {
+        let Some(info) = &self.file.source_code_info else {
+            return false;
+        };
+        info.location
+            .iter()
+            .filter(|x| x.path == [FILE_PACKAGE] || path.starts_with(&x.path))
+            .filter_map(|x| x.leading_comments.as_deref())
+            .flat_map(str::lines)
+            .filter_map(|x| x.trim().strip_prefix(IGNORE_DIRECTIVE))
+            .flat_map(|x| x.split(|c: char| c == ',' || c.is_whitespace()))
+            .any(|x| x == self.rule)
+    }
+}
+
+/// Rules with their severities, which default to `warn`.
+pub struct RuleSet {
+    rules: Vec<Box<dyn Rule>>,
+    severities: BTreeMap<String, Severity>,
+}
+
+impl Default for RuleSet {
+    /// The built-in rules.
+    fn default() -> Self {
+        Self {
+            rules: builtin_rules(),
+            severities: BTreeMap::new(),
+        }
+    }
+}
+
+impl RuleSet {
+    pub fn empty() -> Self {
+        Self {
+            rules: Vec::new(),
+            severities: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_rule(&mut self, rule: impl Rule + 'static) -> &mut Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    pub fn set_severity(&mut self, rule: impl Into<String>, severity: Severity) -> &mut Self {
+        self.severities.insert(rule.into(), severity);
+        self
+    }
+
+    pub fn severity(&self, rule: &str) -> Severity {
+        self.severities.get(rule).copied().unwrap_or_default()
+    }
+
+    pub fn rule_names(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|x| x.name())
+    }
+
+    /// Runs every rule not set to `allow` over the files of `fds` declaring
+    /// `proto_package`. `fds` must include source code info for findings to
+    /// have locations and for comments to suppress them.
+    pub fn lint(&self, fds: &FileDescriptorSet, proto_package: &str) -> Vec<Finding> {
+        fds.file
+            .iter()
+            .filter(|x| x.package() == proto_package)
+            .flat_map(|x| self.lint_file(x))
+            .collect()
+    }
+
+    pub fn lint_file(&self, file: &FileDescriptorProto) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for rule in &self.rules {
+            let severity = self.severity(rule.name());
+            if severity == Severity::Allow {
+                continue;
+            }
+            let mut cx = Context {
+                rule: rule.name(),
+                severity,
+                file,
+                findings: &mut findings,
+            };
+            visit_file(rule.as_ref(), file, &mut cx);
+        }
+        findings
+    }
+}
+
+fn visit_file(rule: &dyn Rule, file: &FileDescriptorProto, cx: &mut Context<'_>) {
+    rule.check_file(file, cx);
+    for (index, message) in file.message_type.iter().enumerate() {
+        visit_message(rule, message, &[FILE_MESSAGE_TYPE, index as i32], cx);
+    }
+    for (index, enum_) in file.enum_type.iter().enumerate() {
+        rule.check_enum(enum_, &[FILE_ENUM_TYPE, index as i32], cx);
+    }
+    for (index, service) in file.service.iter().enumerate() {
+        rule.check_service(service, &[FILE_SERVICE, index as i32], cx);
+    }
+}
+
+fn visit_message(rule: &dyn Rule, message: &DescriptorProto, path: &[i32], cx: &mut Context<'_>) {
+    rule.check_message(message, path, cx);
+    for (index, nested) in message.nested_type.iter().enumerate() {
+        visit_message(
+            rule,
+            nested,
+            &[path, &[MESSAGE_NESTED_TYPE, index as i32]].concat(),
+            cx,
+        );
+    }
+    for (index, enum_) in message.enum_type.iter().enumerate() {
+        rule.check_enum(
+            enum_,
+            &[path, &[MESSAGE_ENUM_TYPE, index as i32]].concat(),
+            cx,
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use appbiotic_api_protogen_spec::lint::ProtoLintRule;
+    use prost_types::{source_code_info::Location, FieldDescriptorProto, SourceCodeInfo};
+
+    use super::*;
+    use crate::source_path::MESSAGE_FIELD;
+
+    fn location(path: &[i32], comments: &str) -> Location {
+        Location {
+            path: path.to_vec(),
+            span: vec![path.len() as i32, 2, 10],
+            leading_comments: Some(comments.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn file(locations: Vec<Location>) -> FileDescriptorProto {
+        FileDescriptorProto {
+            name: Some("foo/v1/foo.proto".to_owned()),
+            package: Some("foo.v1".to_owned()),
+            message_type: vec![DescriptorProto {
+                name: Some("foo_bar".to_owned()),
+                field: vec![FieldDescriptorProto {
+                    name: Some("fooBar".to_owned()),
+                    number: Some(1),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            source_code_info: Some(SourceCodeInfo {
+                location: locations,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn rules(findings: Vec<Finding>) -> Vec<String> {
+        findings.into_iter().map(|x| x.rule).collect()
+    }
+
+    #[test]
+    fn it_names_builtin_rules_like_the_spec() {
+        let names: Vec<String> = ProtoLintRule::ALL.iter().map(|x| x.to_string()).collect();
+        assert_eq!(RuleSet::default().rule_names().collect::<Vec<_>>(), names);
+    }
+
+    #[test]
+    fn it_locates_findings() {
+        let findings = RuleSet::default().lint_file(&file(vec![location(
+            &[FILE_MESSAGE_TYPE, 0, MESSAGE_FIELD, 0],
+            "",
+        )]));
+        let field = findings.iter().find(|x| x.rule == "field_name").unwrap();
+        assert_eq!((field.line, field.column), (5, 3));
+        assert_eq!(rules(findings), vec!["type_name", "field_name"]);
+    }
+
+    #[test]
+    fn it_suppresses_rules_of_enclosed_elements() {
+        let file = file(vec![location(
+            &[FILE_MESSAGE_TYPE, 0],
+            " Foo.\n protogen:lint:ignore field_name, type_name\n",
+        )]);
+        assert!(RuleSet::default().lint_file(&file).is_empty());
+    }
+
+    #[test]
+    fn it_suppresses_rules_of_the_file_on_the_package() {
+        let file = file(vec![location(
+            &[FILE_PACKAGE],
+            " protogen:lint:ignore type_name\n",
+        )]);
+        assert_eq!(
+            rules(RuleSet::default().lint_file(&file)),
+            vec!["field_name"]
+        );
+    }
+
+    #[test]
+    fn it_skips_allowed_rules() {
+        let mut rule_set = RuleSet::default();
+        rule_set
+            .set_severity("field_name", Severity::Allow)
+            .set_severity("type_name", Severity::Deny);
+        let findings = rule_set.lint_file(&file(Vec::new()));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Deny);
+    }
+}
@@ -0,0 +1,160 @@
+use std::path::Path;
+
+use appbiotic_api_protogen_spec::lint::is_version_segment;
+use heck::{ToShoutySnakeCase, ToSnakeCase, ToUpperCamelCase};
+use prost_types::{
+    DescriptorProto, EnumDescriptorProto, FileDescriptorProto, ServiceDescriptorProto,
+};
+
+use crate::{
+    source_path::{ENUM_VALUE, FILE_PACKAGE, MESSAGE_FIELD},
+    Context, Rule,
+};
+
+/// The rules of `ProtoLintRule`, named like it serializes them.
+pub fn builtin_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(PackageVersionSuffix),
+        Box::new(PackageDirectory),
+        Box::new(TypeName),
+        Box::new(FieldName),
+        Box::new(EnumValueName),
+        Box::new(EnumZeroValue),
+    ]
+}
+
+struct PackageVersionSuffix;
+
+impl Rule for PackageVersionSuffix {
+    fn name(&self) -> &str {
+        "package_version_suffix"
+    }
+
+    fn check_file(&self, file: &FileDescriptorProto, cx: &mut Context<'_>) {
+        let proto_package = file.package();
+        if !proto_package
+            .rsplit('.')
+            .next()
+            .is_some_and(is_version_segment)
+        {
+            cx.report(
+                &[FILE_PACKAGE],
+                format!("package `{proto_package}` does not end in a version such as `v1`"),
+            );
+        }
+    }
+}
+
+struct PackageDirectory;
+
+impl Rule for PackageDirectory {
+    fn name(&self) -> &str {
+        "package_directory"
+    }
+
+    fn check_file(&self, file: &FileDescriptorProto, cx: &mut Context<'_>) {
+        let proto_package = file.package();
+        let expected_dir = proto_package.replace('.', "/");
+        if Path::new(file.name()).parent() != Some(Path::new(&expected_dir)) {
+            cx.report(
+                &[FILE_PACKAGE],
+                format!("file of package `{proto_package}` is not in directory `{expected_dir}`"),
+            );
+        }
+    }
+}
+
+struct TypeName;
+
+impl TypeName {
+    fn check(&self, kind: &str, name: &str, path: &[i32], cx: &mut Context<'_>) {
+        let expected = name.to_upper_camel_case();
+        if name != expected {
+            cx.report(
+                path,
+                format!("{kind} `{name}` is not UpperCamelCase, e.g. `{expected}`"),
+            );
+        }
+    }
+}
+
+impl Rule for TypeName {
+    fn name(&self) -> &str {
+        "type_name"
+    }
+
+    fn check_message(&self, message: &DescriptorProto, path: &[i32], cx: &mut Context<'_>) {
+        self.check("message", message.name(), path, cx);
+    }
+
+    fn check_enum(&self, enum_: &EnumDescriptorProto, path: &[i32], cx: &mut Context<'_>) {
+        self.check("enum", enum_.name(), path, cx);
+    }
+
+    fn check_service(&self, service: &ServiceDescriptorProto, path: &[i32], cx: &mut Context<'_>) {
+        self.check("service", service.name(), path, cx);
+    }
+}
+
+struct FieldName;
+
+impl Rule for FieldName {
+    fn name(&self) -> &str {
+        "field_name"
+    }
+
+    fn check_message(&self, message: &DescriptorProto, path: &[i32], cx: &mut Context<'_>) {
+        for (index, field) in message.field.iter().enumerate() {
+            let name = field.name();
+            let expected = name.to_snake_case();
+            if name != expected {
+                cx.report(
+                    &[path, &[MESSAGE_FIELD, index as i32]].concat(),
+                    format!("field `{name}` is not lower_snake_case, e.g. `{expected}`"),
+                );
+            }
+        }
+    }
+}
+
+struct EnumValueName;
+
+impl Rule for EnumValueName {
+    fn name(&self) -> &str {
+        "enum_value_name"
+    }
+
+    fn check_enum(&self, enum_: &EnumDescriptorProto, path: &[i32], cx: &mut Context<'_>) {
+        let prefix = format!("{}_", enum_.name().to_shouty_snake_case());
+        for (index, value) in enum_.value.iter().enumerate() {
+            let name = value.name();
+            if name != name.to_shouty_snake_case() || !name.starts_with(&prefix) {
+                cx.report(
+                    &[path, &[ENUM_VALUE, index as i32]].concat(),
+                    format!("value `{name}` is not UPPER_SNAKE_CASE prefixed with `{prefix}`"),
+                );
+            }
+        }
+    }
+}
+
+struct EnumZeroValue;
+
+impl Rule for EnumZeroValue {
+    fn name(&self) -> &str {
+        "enum_zero_value"
+    }
+
+    fn check_enum(&self, enum_: &EnumDescriptorProto, path: &[i32], cx: &mut Context<'_>) {
+        let expected = format!("{}_UNSPECIFIED", enum_.name().to_shouty_snake_case());
+        for (index, value) in enum_.value.iter().enumerate() {
+            let name = value.name();
+            if value.number() == 0 && name != expected {
+                cx.report(
+                    &[path, &[ENUM_VALUE, index as i32]].concat(),
+                    format!("zero value `{name}` is not named `{expected}`"),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,12 @@
+//! Field numbers of the descriptor messages, which make up the paths of
+//! source code locations.
+
+pub const FILE_PACKAGE: i32 = 2;
+pub const FILE_MESSAGE_TYPE: i32 = 4;
+pub const FILE_ENUM_TYPE: i32 = 5;
+pub const FILE_SERVICE: i32 = 6;
+pub const MESSAGE_FIELD: i32 = 2;
+pub const MESSAGE_NESTED_TYPE: i32 = 3;
+pub const MESSAGE_ENUM_TYPE: i32 = 4;
+pub const ENUM_VALUE: i32 = 2;
+pub const SERVICE_METHOD: i32 = 2;
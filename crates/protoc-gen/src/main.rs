@@ -0,0 +1,31 @@
+//! `protoc-gen-appbiotic-serde`, a protoc and buf plugin generating the same
+//! code as the `appbiotic-api-prost-serde-build` build script, for pipelines
+//! generating code without cargo:
+//!
+//! ```sh
+//! protoc --plugin=protoc-gen-appbiotic-serde --appbiotic-serde_out=src/gen \
+//!     --appbiotic-serde_opt=include_file=mod.rs acme/widgets/v1/widgets.proto
+//! ```
+
+use std::io::{self, Read, Write};
+
+use anyhow::Context;
+use appbiotic_api_prost_serde_build::plugin::generate;
+use prost::Message;
+use prost_types::compiler::CodeGeneratorRequest;
+
+fn main() -> anyhow::Result<()> {
+    let mut request = Vec::new();
+    io::stdin()
+        .read_to_end(&mut request)
+        .context("Failed to read code generator request from stdin")?;
+    let request = CodeGeneratorRequest::decode(request.as_slice())
+        .context("Failed to decode code generator request")?;
+
+    let response = generate(request);
+
+    io::stdout()
+        .write_all(&response.encode_to_vec())
+        .context("Failed to write code generator response to stdout")?;
+    Ok(())
+}
@@ -0,0 +1,105 @@
+//! Publishing to and pinning modules of a Buf Schema Registry with the `buf`
+//! CLI, which handles BSR authentication.
+
+use std::{
+    fs::{copy, create_dir_all, write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use appbiotic_api_protogen_spec::{BsrModule, BsrModuleRef};
+
+use crate::{run_tool, tool_output};
+
+/// A proto file of a module, copied from `src` to `path` within the module.
+pub struct ModuleFile {
+    pub src: PathBuf,
+    pub path: PathBuf,
+}
+
+/// Writes a `buf.yaml` module named `module` with `files` and `deps` into
+/// `dir`, locking the deps with `buf dep update`.
+pub fn write_module(
+    dir: &Path,
+    module: &BsrModule,
+    files: &[ModuleFile],
+    deps: &[BsrModuleRef],
+) -> anyhow::Result<()> {
+    for file in files {
+        let path = dir.join(&file.path);
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).with_context(|| {
+                format!("Failed to create directory `{}`", parent.to_string_lossy())
+            })?;
+        }
+        copy(&file.src, &path).with_context(|| {
+            format!(
+                "Failed to copy `{}` to `{}`",
+                file.src.to_string_lossy(),
+                path.to_string_lossy()
+            )
+        })?;
+    }
+
+    let mut buf_yaml = format!("version: v2\nmodules:\n  - path: .\n    name: {module}\n");
+    if !deps.is_empty() {
+        buf_yaml.push_str("deps:\n");
+        for dep in deps {
+            buf_yaml.push_str(&format!("  - {dep}\n"));
+        }
+    }
+    let buf_yaml_path = dir.join("buf.yaml");
+    write(&buf_yaml_path, buf_yaml)
+        .with_context(|| format!("Failed to write `{}`", buf_yaml_path.to_string_lossy()))?;
+
+    if !deps.is_empty() {
+        run_tool("buf", dir, &["dep", "update"])?;
+    }
+    Ok(())
+}
+
+/// Pushes the module written to `dir` by [`write_module`] with `labels`,
+/// creating it on the registry when `create` is set. Returns the commit
+/// `buf push` reports.
+pub fn push_module(dir: &Path, labels: &[String], create: bool) -> anyhow::Result<String> {
+    let mut args = vec!["push".to_owned()];
+    for label in labels {
+        args.extend(["--label".to_owned(), label.to_owned()]);
+    }
+    if create {
+        args.push("--create".to_owned());
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    Ok(tool_output("buf", dir, &args)?.trim().to_owned())
+}
+
+#[derive(serde::Deserialize)]
+struct Commit {
+    id: String,
+}
+
+/// Resolves the reference of `bsr`, e.g. a label, to the ID of the commit it
+/// points at, which unlike labels never moves.
+pub fn resolve_commit(bsr: &BsrModuleRef) -> anyhow::Result<String> {
+    let output = tool_output(
+        "buf",
+        Path::new("."),
+        &[
+            "registry",
+            "commit",
+            "resolve",
+            &bsr.to_string(),
+            "--format",
+            "json",
+        ],
+    )?;
+    let commit: Commit = output
+        .lines()
+        .find(|x| !x.trim().is_empty())
+        .ok_or_else(|| anyhow!("`buf registry commit resolve {bsr}` printed no commit"))
+        .and_then(|x| {
+            serde_json::from_str(x)
+                .with_context(|| format!("Failed to parse commit of BSR module `{bsr}`"))
+        })?;
+    Ok(commit.id)
+}
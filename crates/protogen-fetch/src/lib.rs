@@ -0,0 +1,423 @@
+pub mod bsr;
+pub mod protofetch;
+
+use std::{
+    env,
+    fs::{create_dir_all, read_dir, remove_dir_all, rename, write, File},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{ensure, Context};
+use appbiotic_api_protogen_spec::{BsrModuleRef, GitSrc, ProtoInclude, ProtoSrc, RustPackage};
+use sha2::{Digest, Sha256};
+
+/// Environment variable that, when set to anything but empty or `0`, forbids
+/// fetching remote proto sources, e.g. in build scripts of hermetic builds.
+pub const OFFLINE_ENV: &str = "PROTOGEN_OFFLINE";
+
+/// Whether [`OFFLINE_ENV`] forbids network access.
+pub fn offline_from_env() -> bool {
+    env::var_os(OFFLINE_ENV).is_some_and(|x| !x.is_empty() && x != "0")
+}
+
+/// Environment variable overriding the [shared cache
+/// directory](shared_cache_dir).
+pub const CACHE_DIR_ENV: &str = "PROTOGEN_CACHE_DIR";
+
+/// Longest readable prefix of a [`cache_key`], keeping cache paths short.
+const CACHE_KEY_PREFIX_LEN: usize = 48;
+
+/// Directory remote proto sources are cached in across specs and builds:
+/// [`CACHE_DIR_ENV`] when set, else `appbiotic-protogen` in the XDG cache
+/// directory. `None` when neither that nor the home directory is known.
+pub fn shared_cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os(CACHE_DIR_ENV).filter(|x| !x.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|x| x.is_absolute())
+        .or_else(|| {
+            env::var_os("HOME")
+                .filter(|x| !x.is_empty())
+                .map(|x| PathBuf::from(x).join(".cache"))
+        })?;
+    Some(cache_home.join("appbiotic-protogen"))
+}
+
+/// Resolves proto sources to local include directories, fetching remote
+/// sources into `cache_dir` on first use.
+pub struct Fetcher {
+    cache_dir: PathBuf,
+    offline: bool,
+}
+
+impl Fetcher {
+    /// Creates a fetcher caching into `cache_dir`, offline when
+    /// [`OFFLINE_ENV`] is set.
+    pub fn new(cache_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            cache_dir: cache_dir.into(),
+            offline: offline_from_env(),
+        }
+    }
+
+    /// Fails when offline and remote sources of `package` are not cached,
+    /// listing all of them.
+    pub fn ensure_cached(&self, package: &RustPackage) -> anyhow::Result<()> {
+        if !self.offline {
+            return Ok(());
+        }
+        let missing: Vec<String> = package
+            .protos
+            .iter()
+            .map(|x| (&x.git, &x.bsr))
+            .chain(package.includes.iter().map(|x| (&x.git, &x.bsr)))
+            .filter_map(|sources| match sources {
+                (Some(git), _) if !self.git_checkout_dir(git).exists() => {
+                    Some(format!("git `{}` at `{}`", git.url, git.rev))
+                }
+                (_, Some(bsr)) if !self.bsr_export_dir(bsr).exists() => {
+                    Some(format!("BSR module `{bsr}`"))
+                }
+                _ => None,
+            })
+            .collect();
+        ensure!(
+            missing.is_empty(),
+            "Offline mode forbids fetching remote proto sources of package `{}` missing from `{}`: {}",
+            package.name,
+            self.cache_dir.to_string_lossy(),
+            missing.join(", ")
+        );
+        Ok(())
+    }
+
+    /// Creates a fetcher caching below the [shared cache
+    /// directory](shared_cache_dir), or into `fallback_dir` when there is
+    /// none.
+    pub fn shared(fallback_dir: impl Into<PathBuf>) -> Self {
+        match shared_cache_dir() {
+            Some(cache_dir) => Self::new(cache_dir.join("remote")),
+            None => Self::new(fallback_dir),
+        }
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Returns the include directory for `src`, with local directories
+    /// resolved against `base_dir`.
+    pub fn resolve_dir(&self, src: &ProtoSrc, base_dir: &Path) -> anyhow::Result<PathBuf> {
+        self.resolve(&src.dir, &src.git, &src.bsr, base_dir)
+    }
+
+    /// Returns the directory for the include-only `include`, with local
+    /// directories resolved against `base_dir`.
+    pub fn resolve_include_dir(
+        &self,
+        include: &ProtoInclude,
+        base_dir: &Path,
+    ) -> anyhow::Result<PathBuf> {
+        self.resolve(&include.dir, &include.git, &include.bsr, base_dir)
+    }
+
+    fn resolve(
+        &self,
+        dir: &Path,
+        git: &Option<GitSrc>,
+        bsr: &Option<BsrModuleRef>,
+        base_dir: &Path,
+    ) -> anyhow::Result<PathBuf> {
+        ensure!(
+            (git.is_none() && bsr.is_none()) || dir.as_os_str().is_empty(),
+            "Remote proto sources must not also set `dir` (found `{}`)",
+            dir.to_string_lossy()
+        );
+        match (git, bsr) {
+            (Some(git), None) => self.fetch_git(git),
+            (None, Some(bsr)) => self.fetch_bsr(bsr),
+            (None, None) => Ok(base_dir.join(dir)),
+            (Some(git), Some(bsr)) => Err(anyhow::anyhow!(
+                "Proto source sets both git repository `{}` and BSR module `{bsr}`",
+                git.url
+            )),
+        }
+    }
+
+    fn git_checkout_dir(&self, git: &GitSrc) -> PathBuf {
+        self.cache_dir
+            .join("git")
+            .join(cache_key(&[&git.url, &git.rev]))
+    }
+
+    fn bsr_export_dir(&self, bsr: &BsrModuleRef) -> PathBuf {
+        self.cache_dir.join("bsr").join(cache_key(&[
+            &bsr.remote,
+            &bsr.owner,
+            &bsr.module,
+            &bsr.reference,
+        ]))
+    }
+
+    /// Checks out `git` into the cache once. Branches are resolved when
+    /// first fetched and the checkout is kept afterwards, so later commits
+    /// are only picked up once the cache entry is removed; pin commits or
+    /// tags to keep builds reproducible.
+    pub fn fetch_git(&self, git: &GitSrc) -> anyhow::Result<PathBuf> {
+        let checkout_dir = self.git_checkout_dir(git);
+        let source = format!("git repository `{}` at `{}`", git.url, git.rev);
+        self.cache_entry(&checkout_dir, &source, |staging_dir| {
+            run_git(staging_dir, &["init", "--quiet"])?;
+            run_git(
+                staging_dir,
+                &["fetch", "--quiet", "--depth", "1", &git.url, &git.rev],
+            )?;
+            run_git(staging_dir, &["checkout", "--quiet", "FETCH_HEAD"])
+        })?;
+
+        let include_dir = match &git.subdir {
+            Some(subdir) => checkout_dir.join(subdir),
+            None => checkout_dir,
+        };
+        ensure!(
+            include_dir.is_dir(),
+            "Include path `{}` does not exist in git repository `{}` at `{}`",
+            include_dir.to_string_lossy(),
+            git.url,
+            git.rev
+        );
+        Ok(include_dir)
+    }
+
+    /// Exports the module sources with `buf export`, which handles BSR
+    /// authentication and dependency resolution.
+    pub fn fetch_bsr(&self, bsr: &BsrModuleRef) -> anyhow::Result<PathBuf> {
+        let export_dir = self.bsr_export_dir(bsr);
+        let source = format!("BSR module `{bsr}`");
+        self.cache_entry(&export_dir, &source, |staging_dir| {
+            run_tool(
+                "buf",
+                Path::new("."),
+                &[
+                    "export",
+                    &bsr.to_string(),
+                    "--output",
+                    &staging_dir.to_string_lossy(),
+                ],
+            )
+        })?;
+        Ok(export_dir)
+    }
+
+    /// Ensures the cache entry at `entry_dir` holds `source`, fetching it
+    /// into a staging directory with `fetch` when missing. Entries are locked
+    /// while in use, so concurrent generators fetch each source once, and
+    /// entries whose protos no longer match the digest recorded when they
+    /// were fetched are fetched again.
+    fn cache_entry(
+        &self,
+        entry_dir: &Path,
+        source: &str,
+        fetch: impl FnOnce(&Path) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let _lock = lock_entry(entry_dir)?;
+        let digest_path = sibling(entry_dir, "sha256");
+        if entry_dir.exists() {
+            let recorded = std::fs::read_to_string(&digest_path).unwrap_or_default();
+            if recorded.trim() == proto_digest(entry_dir)? {
+                return Ok(());
+            }
+            ensure!(
+                !self.offline,
+                "Cached {source} at `{}` failed its integrity check and offline mode forbids \
+                 fetching it again",
+                entry_dir.to_string_lossy()
+            );
+            remove_dir_all(entry_dir).with_context(|| {
+                format!(
+                    "Failed to remove corrupted cache path `{}`",
+                    entry_dir.to_string_lossy()
+                )
+            })?;
+        }
+
+        ensure!(!self.offline, "Offline mode forbids fetching {source}");
+        let staging_dir = sibling(entry_dir, "partial");
+        if staging_dir.exists() {
+            remove_dir_all(&staging_dir).with_context(|| {
+                format!(
+                    "Failed to remove stale staging path `{}`",
+                    staging_dir.to_string_lossy()
+                )
+            })?;
+        }
+        create_dir_all(&staging_dir).with_context(|| {
+            format!(
+                "Failed to create staging path `{}`",
+                staging_dir.to_string_lossy()
+            )
+        })?;
+
+        fetch(&staging_dir)?;
+
+        // Recorded before the entry appears, so an entry without a digest
+        // was never completed.
+        write(&digest_path, proto_digest(&staging_dir)?).with_context(|| {
+            format!(
+                "Failed to write cache digest to path `{}`",
+                digest_path.to_string_lossy()
+            )
+        })?;
+        rename(&staging_dir, entry_dir).with_context(|| {
+            format!(
+                "Failed to move {source} into cache path `{}`",
+                entry_dir.to_string_lossy()
+            )
+        })
+    }
+}
+
+/// Takes an exclusive lock on the cache entry at `entry_dir`, released when
+/// the returned file is dropped.
+fn lock_entry(entry_dir: &Path) -> anyhow::Result<File> {
+    if let Some(parent) = entry_dir.parent() {
+        create_dir_all(parent).with_context(|| {
+            format!("Failed to create cache path `{}`", parent.to_string_lossy())
+        })?;
+    }
+    let lock_path = sibling(entry_dir, "lock");
+    let lock = File::create(&lock_path).with_context(|| {
+        format!(
+            "Failed to create cache lock at path `{}`",
+            lock_path.to_string_lossy()
+        )
+    })?;
+    lock.lock().with_context(|| {
+        format!(
+            "Failed to lock cache path `{}`",
+            entry_dir.to_string_lossy()
+        )
+    })?;
+    Ok(lock)
+}
+
+/// The path next to `path` with `extension` appended to its file name.
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".");
+    file_name.push(extension);
+    path.with_file_name(file_name)
+}
+
+/// Hex encoded SHA-256 over the relative paths and contents of the proto
+/// files below `dir`, in sorted order.
+fn proto_digest(dir: &Path) -> anyhow::Result<String> {
+    fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+        let entries = read_dir(dir)
+            .with_context(|| format!("Failed to read directory `{}`", dir.to_string_lossy()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                if path.file_name().is_some_and(|x| x != ".git") {
+                    collect(&path, files)?;
+                }
+            } else if path.extension().is_some_and(|x| x == "proto") {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+    let mut files = Vec::new();
+    collect(dir, &mut files)?;
+    files.sort();
+    let mut hasher = Sha256::new();
+    for file in files {
+        let contents = std::fs::read(&file)
+            .with_context(|| format!("Failed to read file at path `{}`", file.to_string_lossy()))?;
+        let relative = file.strip_prefix(dir)?.to_string_lossy().into_owned();
+        hasher.update((relative.len() as u64).to_le_bytes());
+        hasher.update(relative.as_bytes());
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect())
+}
+
+fn run_git(dir: &Path, args: &[&str]) -> anyhow::Result<()> {
+    run_tool("git", dir, args)
+}
+
+pub(crate) fn run_tool(program: &str, dir: &Path, args: &[&str]) -> anyhow::Result<()> {
+    tool_output(program, dir, args).map(|_| ())
+}
+
+/// Runs `program` to completion, returning its standard output.
+pub(crate) fn tool_output(program: &str, dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .with_context(|| format!("Failed to run `{program} {}`", args.join(" ")))?;
+    ensure!(
+        output.status.success(),
+        "`{program} {}` failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Name of the cache entry of the source identified by `parts`, such as a URL
+/// and a revision: their SHA-256, prefixed with a sanitized and shortened form
+/// of them for readability only, as different sources may sanitize alike.
+fn cache_key(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    let prefix: String = parts
+        .join("-")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(CACHE_KEY_PREFIX_LEN)
+        .collect();
+    let digest: String = hasher
+        .finalize()
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect();
+    format!("{prefix}-{digest}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_keys_sources_sanitizing_alike_apart() {
+        let a = cache_key(&["https://example.com/a/b", "main"]);
+        let b = cache_key(&["https://example.com/a_b", "main"]);
+        assert!(a.starts_with("https___example.com_a_b-main-"));
+        assert!(b.starts_with("https___example.com_a_b-main-"));
+        assert_ne!(a, b);
+        assert_ne!(cache_key(&["a-b", "c"]), cache_key(&["a", "b-c"]));
+        assert_eq!(
+            cache_key(&["https://example.com/a/b", "main"]),
+            cache_key(&["https://example.com/a/b", "main"])
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    collections::BTreeMap,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, ensure, Context};
+use tracing::warn;
+
+use crate::{offline_from_env, run_tool, shared_cache_dir};
+
+const DEFAULT_PROTO_OUT_DIR: &str = "proto_src";
+const LOCK_FILE_NAME: &str = "protofetch.lock";
+/// Top-level keys of a protofetch manifest besides `name` and
+/// `proto_out_dir` that are not dependency tables.
+const SETTINGS_KEYS: [&str; 2] = ["description", "edition"];
+
+/// The parts of a `protofetch.toml` manifest needed to locate fetched protos.
+pub struct ProtofetchManifest {
+    pub path: PathBuf,
+    pub name: String,
+    pub proto_out_dir: PathBuf,
+    pub dependencies: BTreeMap<String, ProtofetchDependency>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ProtofetchDependency {
+    pub url: String,
+    #[serde(default)]
+    pub revision: Option<String>,
+    #[serde(default)]
+    pub branch: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ProtofetchLock {
+    #[serde(default)]
+    pub dependencies: Vec<ProtofetchLockedDependency>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ProtofetchLockedDependency {
+    pub name: String,
+    pub commit_hash: String,
+}
+
+impl ProtofetchManifest {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = read_to_string(path).with_context(|| {
+            format!(
+                "Failed to read protofetch manifest at path `{}`",
+                path.to_string_lossy()
+            )
+        })?;
+        Self::parse(path, &contents)
+    }
+
+    fn parse(path: &Path, contents: &str) -> anyhow::Result<Self> {
+        let mut table: toml::Table = toml::from_str(contents).with_context(|| {
+            format!(
+                "Failed to parse protofetch manifest at path `{}`",
+                path.to_string_lossy()
+            )
+        })?;
+
+        let name = match table.remove("name") {
+            Some(toml::Value::String(name)) => name,
+            _ => {
+                return Err(anyhow!(
+                    "Expected string `name` in protofetch manifest at path `{}`",
+                    path.to_string_lossy()
+                ))
+            }
+        };
+        let proto_out_dir = match table.remove("proto_out_dir") {
+            Some(toml::Value::String(dir)) => PathBuf::from(dir),
+            _ => PathBuf::from(DEFAULT_PROTO_OUT_DIR),
+        };
+        for key in SETTINGS_KEYS {
+            table.remove(key);
+        }
+
+        let mut dependencies = BTreeMap::new();
+        for (dep_name, value) in table {
+            if !value.is_table() {
+                warn!(
+                    key = %dep_name,
+                    path = %path.to_string_lossy(),
+                    "Ignoring unknown key of protofetch manifest"
+                );
+                continue;
+            }
+            let dependency: ProtofetchDependency = value
+                .try_into()
+                .with_context(|| format!("Failed to parse protofetch dependency `{dep_name}`"))?;
+            dependencies.insert(dep_name, dependency);
+        }
+
+        Ok(Self {
+            path: path.to_owned(),
+            name,
+            proto_out_dir,
+            dependencies,
+        })
+    }
+
+    /// Directory containing the manifest, which protofetch resolves its
+    /// output and lock paths against.
+    pub fn root_dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new(""))
+    }
+
+    /// The include directory protofetch copies all dependency protos into.
+    pub fn include_dir(&self) -> PathBuf {
+        self.root_dir().join(&self.proto_out_dir)
+    }
+
+    pub fn lock_path(&self) -> PathBuf {
+        self.root_dir().join(LOCK_FILE_NAME)
+    }
+
+    pub fn load_lock(&self) -> anyhow::Result<ProtofetchLock> {
+        let lock_path = self.lock_path();
+        let contents = read_to_string(&lock_path).with_context(|| {
+            format!(
+                "Failed to read protofetch lock at path `{}`, run `protofetch lock` first",
+                lock_path.to_string_lossy()
+            )
+        })?;
+        toml::from_str(&contents).with_context(|| {
+            format!(
+                "Failed to parse protofetch lock at path `{}`",
+                lock_path.to_string_lossy()
+            )
+        })
+    }
+
+    /// Runs `protofetch fetch` for the manifest, which locks dependencies that
+    /// are not locked yet and copies their protos into `proto_out_dir`,
+    /// caching downloads below the [shared cache directory](shared_cache_dir)
+    /// if any. With `locked`, protofetch fails instead of updating the lock.
+    /// Offline, the fetched protos are only [verified](Self::verify).
+    pub fn fetch(&self, locked: bool) -> anyhow::Result<()> {
+        if offline_from_env() {
+            return self.verify();
+        }
+        let file_name = self
+            .path
+            .file_name()
+            .context("Expected file_name from protofetch manifest path")?
+            .to_string_lossy();
+        let cache_dir = shared_cache_dir().map(|x| x.join("protofetch"));
+        let cache_dir = cache_dir.as_ref().map(|x| x.to_string_lossy());
+        let mut args = vec!["--module-location", &file_name];
+        if let Some(cache_dir) = &cache_dir {
+            args.extend(["--cache-directory", cache_dir]);
+        }
+        args.push("fetch");
+        if locked {
+            args.push("--locked");
+        }
+        let root_dir = match self.root_dir() {
+            dir if dir.as_os_str().is_empty() => Path::new("."),
+            dir => dir,
+        };
+        run_tool("protofetch", root_dir, &args)
+    }
+
+    /// Checks that every dependency is locked and that the fetched protos are
+    /// present on disk.
+    pub fn verify(&self) -> anyhow::Result<()> {
+        let lock = self.load_lock()?;
+        let missing: Vec<&str> = self
+            .dependencies
+            .keys()
+            .filter(|name| !lock.dependencies.iter().any(|x| x.name.eq(*name)))
+            .map(String::as_str)
+            .collect();
+        ensure!(
+            missing.is_empty(),
+            "Protofetch dependencies [{}] are missing from `{}`, run `protofetch lock`",
+            missing.join(", "),
+            self.lock_path().to_string_lossy()
+        );
+
+        let include_dir = self.include_dir();
+        ensure!(
+            self.dependencies.is_empty() || include_dir.is_dir(),
+            "Protofetch output path `{}` does not exist, run `protofetch fetch`",
+            include_dir.to_string_lossy()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_parses_dependency_tables() {
+        let manifest = ProtofetchManifest::parse(
+            Path::new("protofetch.toml"),
+            r#"
+name = "acme"
+description = "Acme protos"
+proto_out_dir = "protos"
+edition = "2023"
+
+[googleapis]
+url = "github.com/googleapis/googleapis"
+revision = "1.0.0"
+"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.name, "acme");
+        assert_eq!(manifest.proto_out_dir, PathBuf::from("protos"));
+        assert_eq!(
+            manifest.dependencies.keys().collect::<Vec<_>>(),
+            vec!["googleapis"]
+        );
+        assert_eq!(
+            manifest.dependencies["googleapis"].revision.as_deref(),
+            Some("1.0.0")
+        );
+    }
+
+    #[test]
+    fn it_rejects_malformed_dependency_tables() {
+        let result = ProtofetchManifest::parse(
+            Path::new("protofetch.toml"),
+            r#"
+name = "acme"
+
+[googleapis]
+revision = "1.0.0"
+"#,
+        );
+        assert!(result.is_err());
+    }
+}
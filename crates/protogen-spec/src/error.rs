@@ -0,0 +1,111 @@
+use std::{io, path::PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpecError {
+    #[error("Failed to read protogen spec at path `{}`", path.to_string_lossy())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error(
+        "Failed to find protogen.json in `{}` or any parent directory",
+        dir.to_string_lossy()
+    )]
+    NotFound { dir: PathBuf },
+    #[error("Failed to parse protogen spec at path `{}`", path.to_string_lossy())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error(
+        "Protogen spec at path `{}` uses schema {schema}, newer than the supported {}",
+        path.to_string_lossy(),
+        crate::migrate::SCHEMA_VERSION
+    )]
+    UnsupportedSchema { path: PathBuf, schema: u32 },
+    #[error("Failed to write protogen spec to path `{}`", path.to_string_lossy())]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to find package `{name}` in protogen spec")]
+    UnknownPackage {
+        name: String,
+        available: Vec<String>,
+    },
+    #[error("Package `{package}` depends on unknown package `{dependency}`")]
+    UnknownDependency { package: String, dependency: String },
+    #[error("Packages form a dependency cycle: {}", cycle.join(" -> "))]
+    DependencyCycle { cycle: Vec<String> },
+    #[error("Invalid path `{}`: {reason}", path.to_string_lossy())]
+    InvalidPath { path: PathBuf, reason: String },
+    #[error("Invalid extern path `{proto_path}` => `{rust_path}`: {reason}")]
+    InvalidExternPath {
+        proto_path: String,
+        rust_path: String,
+        reason: String,
+    },
+    #[error("Invalid BSR module reference `{reference}`: {reason}")]
+    InvalidBsrModule { reference: String, reason: String },
+    #[error("Invalid BSR module name `{name}`")]
+    InvalidBsrModuleName { name: String },
+    #[error("Spec lint failed with {count} denied finding(s)")]
+    LintDenied { count: usize },
+}
+
+impl SpecError {
+    /// A hint on how to resolve the error, suitable for showing to users.
+    pub fn guidance(&self) -> Option<String> {
+        match self {
+            SpecError::Read { .. } => {
+                Some("Check that `--protogen-path` points to an existing protogen.json".to_owned())
+            }
+            SpecError::NotFound { .. } => Some(
+                "Run from a directory below the spec or pass `--protogen-path`".to_owned(),
+            ),
+            SpecError::Parse { source, .. } => Some(format!(
+                "Fix the JSON syntax or schema error at line {}, column {}",
+                source.line(),
+                source.column()
+            )),
+            SpecError::UnsupportedSchema { .. } => {
+                Some("Upgrade rust-build to a version supporting this spec".to_owned())
+            }
+            SpecError::Write { .. } => None,
+            SpecError::UnknownPackage { available, .. } if !available.is_empty() => Some(format!(
+                "Available packages are: {}",
+                available.join(", ")
+            )),
+            SpecError::UnknownPackage { .. } => {
+                Some("The spec does not declare any `rust` packages".to_owned())
+            }
+            SpecError::UnknownDependency { .. } => Some(
+                "Add the dependency to the spec or remove it from `protogen_dependencies`"
+                    .to_owned(),
+            ),
+            SpecError::DependencyCycle { .. } => Some(
+                "Remove one of the `protogen_dependencies` entries forming the cycle".to_owned(),
+            ),
+            SpecError::InvalidPath { .. } => None,
+            SpecError::InvalidExternPath { .. } => Some(
+                "Extern paths look like `.my.pkg.Message` => `::my_crate::prost_serde::Message`"
+                    .to_owned(),
+            ),
+            SpecError::InvalidBsrModule { .. } => Some(
+                "Use the form `<remote>/<owner>/<module>:<reference>`, e.g. `buf.build/googleapis/googleapis:<commit>`"
+                    .to_owned(),
+            ),
+            SpecError::InvalidBsrModuleName { .. } => Some(
+                "Use the form `<remote>/<owner>/<module>` without a reference, e.g. `buf.build/acme/widgets`"
+                    .to_owned(),
+            ),
+            SpecError::LintDenied { .. } => Some(
+                "Fix the denied findings or lower the severity of their rules under `lint.rules`"
+                    .to_owned(),
+            ),
+        }
+    }
+}
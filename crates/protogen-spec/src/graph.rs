@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use crate::{ProtogenSpec, RustPackage, SpecError};
+
+impl ProtogenSpec {
+    /// Orders packages so every package comes after its
+    /// `protogen_dependencies`, keeping spec order otherwise.
+    pub fn dependency_order(&self) -> Result<Vec<&RustPackage>, SpecError> {
+        Ok(self.dependency_levels()?.into_iter().flatten().collect())
+    }
+
+    /// Groups packages into levels where each package only depends on
+    /// packages in earlier levels, so packages within a level are
+    /// independent of each other.
+    pub fn dependency_levels(&self) -> Result<Vec<Vec<&RustPackage>>, SpecError> {
+        let mut levels: BTreeMap<&str, usize> = BTreeMap::new();
+        for package in &self.rust {
+            self.assign_level(package, &mut levels, &mut Vec::new())?;
+        }
+        let mut grouped: Vec<Vec<&RustPackage>> = Vec::new();
+        for package in &self.rust {
+            let level = levels[package.name.as_str()];
+            if grouped.len() <= level {
+                grouped.resize_with(level + 1, Vec::new);
+            }
+            grouped[level].push(package);
+        }
+        Ok(grouped)
+    }
+
+    /// `(dependency, option)` pairs of the
+    /// [`inherited_options`](RustPackage::inherited_options) `package` sets
+    /// that one of its protogen dependencies does not.
+    pub fn missing_inherited_options<'a>(
+        &'a self,
+        package: &RustPackage,
+    ) -> Vec<(&'a str, &'static str)> {
+        let mut missing = Vec::new();
+        for dependency in package
+            .protogen_dependencies
+            .iter()
+            .filter_map(|x| self.rust_package(x))
+        {
+            for ((option, set), (_, dependency_set)) in package
+                .inherited_options()
+                .into_iter()
+                .zip(dependency.inherited_options())
+            {
+                if set && !dependency_set {
+                    missing.push((dependency.name.as_str(), option));
+                }
+            }
+        }
+        missing
+    }
+
+    fn assign_level<'a>(
+        &'a self,
+        package: &'a RustPackage,
+        levels: &mut BTreeMap<&'a str, usize>,
+        visiting: &mut Vec<&'a str>,
+    ) -> Result<usize, SpecError> {
+        if let Some(level) = levels.get(package.name.as_str()) {
+            return Ok(*level);
+        }
+        if let Some(position) = visiting.iter().position(|x| x.eq(&package.name)) {
+            let mut cycle: Vec<String> =
+                visiting[position..].iter().map(|x| x.to_string()).collect();
+            cycle.push(package.name.to_owned());
+            return Err(SpecError::DependencyCycle { cycle });
+        }
+        visiting.push(&package.name);
+        let mut level = 0;
+        for dependency_name in &package.protogen_dependencies {
+            let dependency =
+                self.rust_package(dependency_name)
+                    .ok_or_else(|| SpecError::UnknownDependency {
+                        package: package.name.to_owned(),
+                        dependency: dependency_name.to_owned(),
+                    })?;
+            level = level.max(self.assign_level(dependency, levels, visiting)? + 1);
+        }
+        visiting.pop();
+        levels.insert(&package.name, level);
+        Ok(level)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_finds_inherited_options_missing_from_dependencies() {
+        let spec: ProtogenSpec = serde_json::from_value(serde_json::json!({ "rust": [
+            { "name": "a", "version": "0.1.0", "path": "a", "proto_package_name": "a.v1",
+              "json_schemas": true },
+            { "name": "b", "version": "0.1.0", "path": "b", "proto_package_name": "b.v1",
+              "json_schemas": true, "arbitrary": true, "protogen_dependencies": ["a"] },
+        ]}))
+        .unwrap();
+        assert_eq!(
+            spec.missing_inherited_options(spec.rust_package("b").unwrap()),
+            vec![("a", "arbitrary")]
+        );
+        assert!(spec
+            .missing_inherited_options(spec.rust_package("a").unwrap())
+            .is_empty());
+    }
+}
@@ -1,41 +1,731 @@
-use std::path::PathBuf;
+mod error;
+mod graph;
+pub mod lint;
+pub mod migrate;
+pub mod paths;
 
-#[derive(serde::Deserialize, serde::Serialize)]
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    fs::{write, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub use error::SpecError;
+use lint::LintConfig;
+
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
 pub struct ProtogenSpec {
+    /// Version of the spec format, see [`migrate`]. Zero for specs written
+    /// before the format was versioned.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub schema: u32,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub rust: Vec<RustPackage>,
+    #[serde(default, skip_serializing_if = "LintConfig::is_empty")]
+    pub lint: LintConfig,
+    /// Dependency versions keyed by crate name, e.g. `prost` or `tonic`,
+    /// that override the defaults in generated package manifests.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub versions: BTreeMap<String, String>,
+    /// SPDX license expression of every generated crate, e.g.
+    /// `MIT OR Apache-2.0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// License files, relative to the spec directory, copied into every
+    /// generated crate. The first is set as `license-file` when no `license`
+    /// is set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub license_files: Vec<PathBuf>,
+    /// Format generated Rust sources with `rustfmt`, so they pass format
+    /// checks of the repository they are committed to.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fmt: bool,
+    /// Generators for other languages, run over the protos of the packages
+    /// after generating the Rust packages.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<GenerationTarget>,
+    /// Commands run around generating each package.
+    #[serde(default, skip_serializing_if = "GenerationHooks::is_empty")]
+    pub hooks: GenerationHooks,
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+impl ProtogenSpec {
+    pub fn load(path: &Path) -> Result<Self, SpecError> {
+        let file = File::open(path).map_err(|source| SpecError::Read {
+            path: path.to_owned(),
+            source,
+        })?;
+        Self::from_reader(BufReader::new(file), path)
+    }
+
+    /// Reads a spec from `reader`, e.g. standard input, naming it `path` in
+    /// errors.
+    pub fn from_reader(reader: impl io::Read, path: &Path) -> Result<Self, SpecError> {
+        let spec: Self = serde_json::from_reader(reader).map_err(|source| SpecError::Parse {
+            path: path.to_owned(),
+            source,
+        })?;
+        if spec.schema > migrate::SCHEMA_VERSION {
+            return Err(SpecError::UnsupportedSchema {
+                path: path.to_owned(),
+                schema: spec.schema,
+            });
+        }
+        Ok(spec)
+    }
+
+    /// Writes the spec to `path` in its canonical form: pretty printed with a
+    /// four space indent, omitting defaulted fields.
+    pub fn save(&self, path: &Path) -> Result<(), SpecError> {
+        let contents = self.to_json().map_err(|source| SpecError::Write {
+            path: path.to_owned(),
+            source: source.into(),
+        })?;
+        write(path, contents).map_err(|source| SpecError::Write {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    /// Serializes the spec in the canonical form written by [`Self::save`].
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        to_canonical_json(self)
+    }
+
+    /// Looks up a package by name, failing with the list of known packages.
+    pub fn package(&self, name: &str) -> Result<&RustPackage, SpecError> {
+        self.rust_package(name)
+            .ok_or_else(|| SpecError::UnknownPackage {
+                name: name.to_owned(),
+                available: self.rust.iter().map(|x| x.name.to_owned()).collect(),
+            })
+    }
+
+    pub fn rust_package(&self, name: &str) -> Option<&RustPackage> {
+        self.rust.iter().find(|x| x.name.eq(name))
+    }
+
+    pub fn rust_package_mut(&mut self, name: &str) -> Option<&mut RustPackage> {
+        self.rust.iter_mut().find(|x| x.name.eq(name))
+    }
+
+    /// Packages included in `profile`, or every package when no profile is
+    /// selected.
+    pub fn rust_packages_in_profile(&self, profile: Option<&str>) -> Vec<&RustPackage> {
+        self.rust
+            .iter()
+            .filter(|x| profile.is_none_or(|profile| x.in_profile(profile)))
+            .collect()
+    }
+}
+
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
 pub struct RustPackage {
     pub name: String,
     pub version: String,
+    /// Package directory, relative to the directory containing the spec file.
     pub path: PathBuf,
     pub proto_package_name: String,
+    /// What the package is for, used in its manifest and README.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     #[serde(default)]
     pub compile_well_known_protos: bool,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub protos: Vec<ProtoSrc>,
+    /// Proto directories available for imports whose types are never
+    /// generated by this package.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<ProtoInclude>,
+    /// Packages of the spec this package depends on. Generating the package
+    /// records the hashes of their descriptors in its
+    /// [`DependencyLock`], and requires `protoc`.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub protogen_dependencies: Vec<String>,
+    /// Generated API crates defined outside of this spec that this package
+    /// depends on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_dependencies: Vec<ApiDependency>,
+    /// Compatibility contract this package promises to its consumers.
+    #[serde(default, skip_serializing_if = "Compatibility::is_none")]
+    pub compatibility: Compatibility,
+    /// Minimum compatibility contract expected from every dependency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub required_dependency_compatibility: Option<Compatibility>,
+    /// Fail the build instead of warning when a dependency does not meet
+    /// `required_dependency_compatibility`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict_compatibility: bool,
+    /// Profiles such as `public` or `internal` that include this package. A
+    /// package without profiles is included in every profile.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub profiles: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bins: Vec<RustBin>,
+    /// Generate `tests/smoke.rs`, which round-trips every top-level message
+    /// through JSON and creates every service client. Requires `protoc` when
+    /// generating.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub smoke_tests: bool,
+    /// Generate runnable `examples/`: a client per service and a JSON
+    /// example of the package messages. Requires `protoc` when generating.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub examples: bool,
+    /// Generate criterion benchmarks of JSON and protobuf encoding and
+    /// decoding of the package messages. Requires `protoc` when generating.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub benches: bool,
+    /// Implement `appbiotic_api::field_mask::FieldMasked` for the package
+    /// messages, to apply `google.protobuf.FieldMask`s to them.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub field_masks: bool,
+    /// Derive `schemars::JsonSchema` for the package messages and enums behind
+    /// a `schemars` feature, with schemas matching their `serde`
+    /// representation.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub json_schemas: bool,
+    /// Derive `utoipa::ToSchema` for the package messages and enums behind a
+    /// `utoipa` feature, named by their full proto names, such as
+    /// `acme.widgets.v1.Widget`, as the OpenAPI path items of
+    /// `appbiotic-api-transcoding` refer to them.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub openapi_schemas: bool,
+    /// Derive `proptest::arbitrary::Arbitrary` for the package messages and
+    /// enums behind a `proptest` feature, generating values protobuf and JSON
+    /// accept, such as known enum values and finite floats.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub arbitrary: bool,
+    /// Implement `appbiotic_api::sample::Sample` for the package messages,
+    /// building realistic, seeded instances from the names and types of their
+    /// fields and the `google.api.resource` and `google.api.field_info`
+    /// annotations.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub samples: bool,
+    /// Implement `appbiotic_api::text_format::TextFormat` for the package
+    /// messages behind a `text-format` feature, reading and writing the
+    /// protobuf text format through the package descriptors.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub text_format: bool,
+    /// Generate an additional `cdylib` crate next to the package exposing
+    /// some of its messages through `extern "C"` functions. Requires `protoc`
+    /// when generating.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ffi: Option<RustFfi>,
+    /// Remaps logical import prefixes, e.g. `common/v1`, to directories
+    /// relative to the package path, so imports resolve without matching the
+    /// on-disk layout.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub import_paths: BTreeMap<String, PathBuf>,
+    /// Whether and where the generated crate may be published. Defaults to
+    /// `[registry]` when only `registry` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publish: Option<Publish>,
+    /// Cargo registry the generated crate is published to and that other
+    /// generated crates depend on it from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    /// Attributes added to generated types matching a proto path.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub type_attributes: Vec<TypeAttributeRule>,
+    /// Additional extern paths for types generated outside of this package.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extern_paths: Vec<ExternPath>,
+    /// BSR module the local protos of the package are pushed to by
+    /// `rust-build bsr-push`, labeled with the package version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bsr_module: Option<BsrModule>,
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+impl RustPackage {
+    pub fn in_profile(&self, profile: &str) -> bool {
+        self.profiles.is_empty() || self.profiles.contains(profile)
+    }
+
+    /// Options whose generated code refers to the code generated for the same
+    /// option in protogen dependencies, which must set them too, with whether
+    /// the package sets them.
+    pub fn inherited_options(&self) -> [(&'static str, bool); 4] {
+        [
+            ("json_schemas", self.json_schemas),
+            ("openapi_schemas", self.openapi_schemas),
+            ("arbitrary", self.arbitrary),
+            ("samples", self.samples),
+        ]
+    }
+
+    /// Name of the FFI crate generated for this package.
+    pub fn ffi_name(&self) -> String {
+        format!("{}-ffi", self.name)
+    }
+
+    /// Directory of the FFI crate, next to the package directory.
+    pub fn ffi_path(&self) -> PathBuf {
+        let mut dir_name = self.path.file_name().unwrap_or_default().to_owned();
+        dir_name.push("-ffi");
+        self.path.with_file_name(dir_name)
+    }
+
+    /// The effective `publish` setting for the generated manifest.
+    pub fn effective_publish(&self) -> Option<Publish> {
+        match (&self.publish, &self.registry) {
+            (Some(publish), _) => Some(publish.to_owned()),
+            (None, Some(registry)) => Some(Publish::Registries(vec![registry.to_owned()])),
+            (None, None) => None,
+        }
+    }
+}
+
+/// A generated API crate from another spec, e.g. published from another
+/// repository, whose build exposes its proto package spec.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct ApiDependency {
+    pub name: String,
+    pub version: String,
+    /// Crate directory, relative to the package path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+}
+
+/// Mirrors the Cargo manifest `publish` field.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(untagged)]
+pub enum Publish {
+    Enabled(bool),
+    Registries(Vec<String>),
+}
+
+/// Schema evolution guarantee of a proto package, ordered from weakest to
+/// strongest.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Compatibility {
+    /// No guarantees, any change may break consumers.
+    #[default]
+    None,
+    /// New versions can read data written by older versions.
+    Backward,
+    /// Old and new versions can read each other's data.
+    Full,
+}
+
+impl Compatibility {
+    pub fn is_none(&self) -> bool {
+        matches!(self, Compatibility::None)
+    }
+}
+
+impl fmt::Display for Compatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Compatibility::None => "none",
+            Compatibility::Backward => "backward",
+            Compatibility::Full => "full",
+        })
+    }
+}
+
+/// An external code generator, e.g. `protoc` with a TypeScript plugin, run
+/// once per package from the spec directory.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct GenerationTarget {
+    pub name: String,
+    /// Program and arguments, e.g. `["protoc", "--ts_out={out_dir}"]`.
+    /// `{package}` and `{proto_package}` are replaced with the names of the
+    /// package and `{out_dir}` with the absolute `out_dir`. The import path
+    /// arguments of the package, a `--proto_path` per include directory, and
+    /// its proto files are appended.
+    pub command: Vec<String>,
+    /// Output directory relative to the spec directory, created before the
+    /// generator runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub out_dir: Option<PathBuf>,
+    /// Names of the packages to run the generator for, every package when
+    /// empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<String>,
+}
+
+impl GenerationTarget {
+    pub fn includes_package(&self, name: &str) -> bool {
+        self.packages.is_empty() || self.packages.iter().any(|x| x == name)
+    }
+}
+
+/// Commands, each a program and its arguments, run from the spec directory
+/// before rendering or after writing a package, e.g. `["buf", "lint"]`. The
+/// package is passed in the `PROTOGEN_PACKAGE`, `PROTOGEN_PACKAGE_PATH`, and
+/// `PROTOGEN_PACKAGE_SPEC` environment variables.
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct GenerationHooks {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_generate: Vec<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_generate: Vec<Vec<String>>,
+}
+
+impl GenerationHooks {
+    pub fn is_empty(&self) -> bool {
+        self.pre_generate.is_empty() && self.post_generate.is_empty()
+    }
+}
+
+/// A server binary serving some of the package's services.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct RustBin {
+    pub name: String,
+    /// Service names, either unqualified or qualified with the package's
+    /// proto package name.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<String>,
+    #[serde(default = "RustBin::default_port")]
+    pub port: u16,
+}
+
+/// A C ABI over some of the package messages, for embedding in non-Rust
+/// hosts.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct RustFfi {
+    /// Top-level message names, either unqualified or qualified with the
+    /// package's proto package name. Each gets functions converting between
+    /// JSON and protobuf bytes.
+    pub messages: Vec<String>,
+}
+
+impl RustBin {
+    fn default_port() -> u16 {
+        50051
+    }
+}
+
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
 pub struct ProtoSrc {
+    /// Local include directory, relative to the package path. Left empty when
+    /// the protos come from a remote source such as `git`.
+    #[serde(default, skip_serializing_if = "is_empty_path")]
     pub dir: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitSrc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bsr: Option<BsrModuleRef>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub files: Vec<PathBuf>,
+    /// Overrides the package's `compile_well_known_protos` for this source.
+    /// Well-known types defined by a compiling source are generated locally
+    /// instead of mapping to `prost-wkt-types`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compile_well_known_types: Option<bool>,
+}
+
+impl ProtoSrc {
+    pub fn is_remote(&self) -> bool {
+        self.git.is_some() || self.bsr.is_some()
+    }
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+/// Attaches Rust attributes to generated types whose proto path matches
+/// `path`, using prost's matching rules: `.` matches every type, `.pkg`
+/// every type in a package, and `.pkg.Message` a single type.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct TypeAttributeRule {
+    pub path: String,
+    /// Attributes such as `#[serde(deny_unknown_fields)]`.
+    pub attributes: Vec<String>,
+}
+
+/// An include-only proto directory. Imported types resolve through
+/// `extern_paths` rather than being generated.
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct ProtoInclude {
+    /// Local include directory, relative to the package path. Left empty when
+    /// the protos come from a remote source such as `git`.
+    #[serde(default, skip_serializing_if = "is_empty_path")]
+    pub dir: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitSrc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bsr: Option<BsrModuleRef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extern_paths: Vec<ExternPath>,
+}
+
+impl ProtoInclude {
+    pub fn is_remote(&self) -> bool {
+        self.git.is_some() || self.bsr.is_some()
+    }
+}
+
+/// A git repository fetched into a cache and used as an include directory.
+#[derive(Clone, Hash, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct GitSrc {
+    pub url: String,
+    /// Commit, tag, or branch to check out. Branches are cached at the commit
+    /// they pointed to when first fetched.
+    pub rev: String,
+    /// Include directory within the repository, defaults to the repository
+    /// root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subdir: Option<PathBuf>,
+}
+
+/// A Buf Schema Registry module reference such as
+/// `buf.build/googleapis/googleapis:<commit>`.
+#[derive(Clone, Hash, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BsrModuleRef {
+    pub remote: String,
+    pub owner: String,
+    pub module: String,
+    /// Commit, label, or tag of the module. Required so fetched sources can
+    /// be cached.
+    pub reference: String,
+}
+
+impl FromStr for BsrModuleRef {
+    type Err = SpecError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, reference) = s
+            .split_once(':')
+            .ok_or_else(|| SpecError::InvalidBsrModule {
+                reference: s.to_owned(),
+                reason: "missing a `:<reference>` suffix".to_owned(),
+            })?;
+        let parts: Vec<&str> = name.split('/').collect();
+        match (parts.as_slice(), reference.is_empty()) {
+            ([remote, owner, module], false)
+                if !remote.is_empty() && !owner.is_empty() && !module.is_empty() =>
+            {
+                Ok(Self {
+                    remote: remote.to_string(),
+                    owner: owner.to_string(),
+                    module: module.to_string(),
+                    reference: reference.to_string(),
+                })
+            }
+            _ => Err(SpecError::InvalidBsrModule {
+                reference: s.to_owned(),
+                reason: "expected the form `<remote>/<owner>/<module>:<reference>`".to_owned(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<String> for BsrModuleRef {
+    type Error = SpecError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<BsrModuleRef> for String {
+    fn from(value: BsrModuleRef) -> Self {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for BsrModuleRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}/{}:{}",
+            self.remote, self.owner, self.module, self.reference
+        )
+    }
+}
+
+/// A Buf Schema Registry module without a reference, such as
+/// `buf.build/acme/widgets`.
+#[derive(Clone, Hash, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BsrModule {
+    pub remote: String,
+    pub owner: String,
+    pub module: String,
+}
+
+impl BsrModule {
+    pub fn at(&self, reference: impl Into<String>) -> BsrModuleRef {
+        BsrModuleRef {
+            remote: self.remote.to_owned(),
+            owner: self.owner.to_owned(),
+            module: self.module.to_owned(),
+            reference: reference.into(),
+        }
+    }
+}
+
+impl FromStr for BsrModule {
+    type Err = SpecError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('/').collect();
+        match parts.as_slice() {
+            [remote, owner, module]
+                if !remote.is_empty() && !owner.is_empty() && !module.is_empty() =>
+            {
+                Ok(Self {
+                    remote: remote.to_string(),
+                    owner: owner.to_string(),
+                    module: module.to_string(),
+                })
+            }
+            _ => Err(SpecError::InvalidBsrModuleName { name: s.to_owned() }),
+        }
+    }
+}
+
+impl TryFrom<String> for BsrModule {
+    type Error = SpecError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<BsrModule> for String {
+    fn from(value: BsrModule) -> Self {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for BsrModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.remote, self.owner, self.module)
+    }
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub struct ProtoPackageSpec {
     pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Compatibility::is_none")]
+    pub compatibility: Compatibility,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub extern_paths: Vec<ExternPath>,
+    /// Location of the package's encoded `FileDescriptorSet`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub descriptor_path: Option<PathBuf>,
+    /// Hex encoded SHA-256 of the file at `descriptor_path` when this spec was
+    /// emitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub descriptor_sha256: Option<String>,
 }
 
-#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize)]
+/// The hashes of the descriptors of the protogen dependencies a package was
+/// generated against, written next to its manifest by `rust-build generate`,
+/// so its build fails when a dependency changes without it being regenerated.
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct DependencyLock {
+    /// Hex encoded SHA-256 of the `descriptor_path` of each dependency, by
+    /// name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub descriptor_sha256: BTreeMap<String, String>,
+}
+
+impl DependencyLock {
+    pub const FILE_NAME: &'static str = "protogen-dependencies.lock";
+}
+
+/// Maps a fully qualified proto type or package to an existing Rust path.
+/// Proto paths are normalized to have a leading `.`.
+#[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "RawExternPath")]
 pub struct ExternPath {
     pub proto_path: String,
     pub rust_path: String,
 }
+
+impl ExternPath {
+    pub fn new(
+        proto_path: impl Into<String>,
+        rust_path: impl Into<String>,
+    ) -> Result<Self, SpecError> {
+        let proto_path: String = proto_path.into();
+        let rust_path: String = rust_path.into();
+        let proto_path = if proto_path.starts_with('.') {
+            proto_path
+        } else {
+            format!(".{proto_path}")
+        };
+
+        if !proto_path[1..].split('.').all(is_identifier) {
+            return Err(SpecError::InvalidExternPath {
+                proto_path,
+                rust_path,
+                reason: "proto path must be a dot separated list of identifiers".to_owned(),
+            });
+        }
+        let rust_segments = rust_path.strip_prefix("::").unwrap_or(&rust_path);
+        if !rust_segments.split("::").all(is_identifier) {
+            return Err(SpecError::InvalidExternPath {
+                proto_path,
+                rust_path,
+                reason: "rust path must be a `::` separated list of identifiers".to_owned(),
+            });
+        }
+
+        Ok(Self {
+            proto_path,
+            rust_path,
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct RawExternPath {
+    proto_path: String,
+    rust_path: String,
+}
+
+impl TryFrom<RawExternPath> for ExternPath {
+    type Error = SpecError;
+
+    fn try_from(value: RawExternPath) -> Result<Self, Self::Error> {
+        ExternPath::new(value.proto_path, value.rust_path)
+    }
+}
+
+fn is_identifier(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && value != "_"
+}
+
+fn is_empty_path(path: &Path) -> bool {
+    path.as_os_str().is_empty()
+}
+
+/// Pretty prints `value` with a four space indent and a trailing newline.
+fn to_canonical_json(value: &impl serde::Serialize) -> Result<String, serde_json::Error> {
+    let mut contents = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut contents, formatter);
+    value.serialize(&mut serializer)?;
+    contents.push(b'\n');
+    String::from_utf8(contents).map_err(|error| serde_json::Error::io(io::Error::other(error)))
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
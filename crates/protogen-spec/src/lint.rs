@@ -0,0 +1,344 @@
+use std::{collections::BTreeMap, fmt, path::Component};
+
+use crate::{ProtogenSpec, RustPackage};
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum LintRule {
+    /// Package names are lowercase kebab-case.
+    PackageName,
+    /// Versions are `MAJOR.MINOR.PATCH` with optional pre-release and build
+    /// metadata.
+    VersionFormat,
+    /// Package paths are relative, free of `.`/`..` components, and unique.
+    PathHygiene,
+    /// Crate names start with the kebab-cased proto package, ignoring a
+    /// trailing version segment such as `v1`.
+    ProtoPackageCrateName,
+    /// Dependencies are included in every profile of the packages depending
+    /// on them, so selecting a profile never drops a required package.
+    ProfileDependency,
+}
+
+impl LintRule {
+    pub const ALL: [LintRule; 5] = [
+        LintRule::PackageName,
+        LintRule::VersionFormat,
+        LintRule::PathHygiene,
+        LintRule::ProtoPackageCrateName,
+        LintRule::ProfileDependency,
+    ];
+}
+
+impl fmt::Display for LintRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LintRule::PackageName => "package_name",
+            LintRule::VersionFormat => "version_format",
+            LintRule::PathHygiene => "path_hygiene",
+            LintRule::ProtoPackageCrateName => "proto_package_crate_name",
+            LintRule::ProfileDependency => "profile_dependency",
+        })
+    }
+}
+
+/// Style rules applied to the protos of a package by `rust-build lint`.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtoLintRule {
+    /// Proto packages end in a version segment such as `v1` or `v1beta1`.
+    PackageVersionSuffix,
+    /// Files are in a directory matching their proto package, e.g.
+    /// `acme/widgets/v1` for `acme.widgets.v1`.
+    PackageDirectory,
+    /// Messages, enums, and services are UpperCamelCase.
+    TypeName,
+    /// Fields are lower_snake_case.
+    FieldName,
+    /// Enum values are UPPER_SNAKE_CASE prefixed with the name of their enum.
+    EnumValueName,
+    /// The zero value of an enum is `<ENUM>_UNSPECIFIED`.
+    EnumZeroValue,
+}
+
+impl ProtoLintRule {
+    pub const ALL: [ProtoLintRule; 6] = [
+        ProtoLintRule::PackageVersionSuffix,
+        ProtoLintRule::PackageDirectory,
+        ProtoLintRule::TypeName,
+        ProtoLintRule::FieldName,
+        ProtoLintRule::EnumValueName,
+        ProtoLintRule::EnumZeroValue,
+    ];
+}
+
+impl fmt::Display for ProtoLintRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProtoLintRule::PackageVersionSuffix => "package_version_suffix",
+            ProtoLintRule::PackageDirectory => "package_directory",
+            ProtoLintRule::TypeName => "type_name",
+            ProtoLintRule::FieldName => "field_name",
+            ProtoLintRule::EnumValueName => "enum_value_name",
+            ProtoLintRule::EnumZeroValue => "enum_zero_value",
+        })
+    }
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Allow,
+    #[default]
+    Warn,
+    Deny,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Allow => "allow",
+            Severity::Warn => "warn",
+            Severity::Deny => "deny",
+        })
+    }
+}
+
+/// Per-rule severity overrides. Rules not listed default to `warn`.
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct LintConfig {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rules: BTreeMap<LintRule, Severity>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub proto_rules: BTreeMap<ProtoLintRule, Severity>,
+}
+
+impl LintConfig {
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.proto_rules.is_empty()
+    }
+
+    pub fn severity(&self, rule: LintRule) -> Severity {
+        self.rules.get(&rule).copied().unwrap_or_default()
+    }
+
+    pub fn proto_severity(&self, rule: ProtoLintRule) -> Severity {
+        self.proto_rules.get(&rule).copied().unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct LintFinding {
+    pub rule: LintRule,
+    pub severity: Severity,
+    pub package: String,
+    pub message: String,
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}] package `{}`: {}",
+            self.severity, self.rule, self.package, self.message
+        )
+    }
+}
+
+impl ProtogenSpec {
+    /// Runs every lint rule not set to `allow` over the spec, using the
+    /// severities configured in the spec's `lint` section.
+    pub fn lint(&self) -> Vec<LintFinding> {
+        let mut findings = Vec::new();
+        for package in &self.rust {
+            for rule in LintRule::ALL {
+                let severity = self.lint.severity(rule);
+                if severity == Severity::Allow {
+                    continue;
+                }
+                for message in check(rule, package, self) {
+                    findings.push(LintFinding {
+                        rule,
+                        severity,
+                        package: package.name.to_owned(),
+                        message,
+                    });
+                }
+            }
+        }
+        findings
+    }
+}
+
+fn check(rule: LintRule, package: &RustPackage, spec: &ProtogenSpec) -> Vec<String> {
+    let mut messages = Vec::new();
+    match rule {
+        LintRule::PackageName => {
+            if !is_kebab_case(&package.name) {
+                messages.push(format!(
+                    "name `{}` is not lowercase kebab-case",
+                    package.name
+                ));
+            }
+        }
+        LintRule::VersionFormat => {
+            if !is_semver(&package.version) {
+                messages.push(format!(
+                    "version `{}` is not a `MAJOR.MINOR.PATCH` semantic version",
+                    package.version
+                ));
+            }
+        }
+        LintRule::PathHygiene => {
+            let path = package.path.to_string_lossy();
+            if !package.path.is_relative() {
+                messages.push(format!("path `{path}` is not relative"));
+            }
+            if package
+                .path
+                .components()
+                .any(|x| matches!(x, Component::CurDir | Component::ParentDir))
+            {
+                messages.push(format!("path `{path}` contains `.` or `..` components"));
+            }
+            if spec
+                .rust
+                .iter()
+                .filter(|x| x.path.eq(&package.path))
+                .count()
+                > 1
+            {
+                messages.push(format!("path `{path}` is shared with another package"));
+            }
+        }
+        LintRule::ProtoPackageCrateName => {
+            let mut segments: Vec<&str> = package.proto_package_name.split('.').collect();
+            if segments.len() > 1 && segments.last().is_some_and(|x| is_version_segment(x)) {
+                segments.pop();
+            }
+            let expected = segments.join("-").replace('_', "-");
+            if !package.name.starts_with(&expected) {
+                messages.push(format!(
+                    "name `{}` does not start with `{expected}` derived from proto package `{}`",
+                    package.name, package.proto_package_name
+                ));
+            }
+        }
+        LintRule::ProfileDependency => {
+            for dependency in package
+                .protogen_dependencies
+                .iter()
+                .filter_map(|x| spec.rust_package(x))
+            {
+                if package.profiles.is_empty() {
+                    if !dependency.profiles.is_empty() {
+                        messages.push(format!(
+                            "dependency `{}` is limited to profiles [{}] but this package is in every profile",
+                            dependency.name,
+                            Vec::from_iter(dependency.profiles.iter().map(String::as_str)).join(", ")
+                        ));
+                    }
+                    continue;
+                }
+                let missing: Vec<&str> = package
+                    .profiles
+                    .iter()
+                    .filter(|x| !dependency.in_profile(x))
+                    .map(String::as_str)
+                    .collect();
+                if !missing.is_empty() {
+                    messages.push(format!(
+                        "dependency `{}` is excluded from profiles [{}]",
+                        dependency.name,
+                        missing.join(", ")
+                    ));
+                }
+            }
+        }
+    }
+    messages
+}
+
+fn is_kebab_case(value: &str) -> bool {
+    !value.is_empty()
+        && value.split('-').all(|x| {
+            !x.is_empty()
+                && x.chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        })
+}
+
+fn is_semver(value: &str) -> bool {
+    let value = value.split_once('+').map_or(value, |x| x.0);
+    let core = value.split_once('-').map_or(value, |x| x.0);
+    let parts: Vec<&str> = core.split('.').collect();
+    parts.len() == 3
+        && parts.iter().all(|x| {
+            !x.is_empty()
+                && x.chars().all(|c| c.is_ascii_digit())
+                && (x.len() == 1 || !x.starts_with('0'))
+        })
+}
+
+/// Whether a proto package segment is a version such as `v1` or `v1beta1`.
+pub fn is_version_segment(value: &str) -> bool {
+    value
+        .strip_prefix('v')
+        .and_then(|x| x.chars().next())
+        .is_some_and(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spec(packages: serde_json::Value) -> ProtogenSpec {
+        serde_json::from_value(serde_json::json!({ "rust": packages })).unwrap()
+    }
+
+    #[test]
+    fn it_reports_each_rule() {
+        let spec = spec(serde_json::json!([{
+            "name": "Foo_Api",
+            "version": "1.0",
+            "path": "../foo",
+            "proto_package_name": "bar.v1",
+        }]));
+        let rules: Vec<LintRule> = spec.lint().into_iter().map(|x| x.rule).collect();
+        assert_eq!(
+            rules,
+            vec![
+                LintRule::PackageName,
+                LintRule::VersionFormat,
+                LintRule::PathHygiene,
+                LintRule::ProtoPackageCrateName,
+            ]
+        );
+    }
+
+    #[test]
+    fn it_accepts_conventional_packages() {
+        let spec = spec(serde_json::json!([{
+            "name": "foo-api",
+            "version": "1.2.3-rc.1",
+            "path": "crates/foo-api",
+            "proto_package_name": "foo.v1",
+        }]));
+        assert!(spec.lint().is_empty());
+    }
+}
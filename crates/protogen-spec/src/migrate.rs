@@ -0,0 +1,105 @@
+//! Upgrades spec documents written for older versions of the spec format.
+
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+use crate::SpecError;
+
+/// Version of the spec format read and written by this crate.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A step upgrading a spec document to schema version `to` from the one
+/// before it.
+struct Migration {
+    to: u32,
+    description: &'static str,
+    apply: fn(&mut Map<String, Value>),
+}
+
+/// Every migration in order. Additive changes to the format need no step of
+/// their own, as older specs still parse.
+const MIGRATIONS: &[Migration] = &[Migration {
+    to: 1,
+    description: "Record the spec format version in `schema`",
+    apply: |_| {},
+}];
+
+/// Rewrites the spec `document` read from `path` to the latest schema
+/// version, keeping the order of its entries, and returns descriptions of the
+/// applied steps. Specs without a `schema` predate versioning.
+pub fn migrate(document: &mut Value, path: &Path) -> Result<Vec<&'static str>, SpecError> {
+    let object = document.as_object_mut().ok_or_else(|| SpecError::Parse {
+        path: path.to_owned(),
+        source: serde::de::Error::custom("expected the spec to be a JSON object"),
+    })?;
+    let schema = match object.get("schema") {
+        None => 0,
+        Some(value) => value
+            .as_u64()
+            .and_then(|x| u32::try_from(x).ok())
+            .ok_or_else(|| SpecError::Parse {
+                path: path.to_owned(),
+                source: serde::de::Error::custom(format!(
+                    "expected `schema` to be a version number, found `{value}`"
+                )),
+            })?,
+    };
+    if schema > SCHEMA_VERSION {
+        return Err(SpecError::UnsupportedSchema {
+            path: path.to_owned(),
+            schema,
+        });
+    }
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|x| x.to > schema) {
+        (migration.apply)(object);
+        set_schema(object, migration.to);
+        applied.push(migration.description);
+    }
+    Ok(applied)
+}
+
+/// Serializes a migrated `document` in the canonical form of
+/// [`crate::ProtogenSpec::save`], keeping the order of its entries.
+pub fn to_json(document: &Value) -> Result<String, serde_json::Error> {
+    crate::to_canonical_json(document)
+}
+
+/// Sets `schema`, adding it as the first entry when missing.
+fn set_schema(object: &mut Map<String, Value>, schema: u32) {
+    if let Some(value) = object.get_mut("schema") {
+        *value = schema.into();
+        return;
+    }
+    let mut migrated = Map::new();
+    migrated.insert("schema".to_owned(), schema.into());
+    migrated.append(object);
+    *object = migrated;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_stamps_unversioned_specs() {
+        let mut document = serde_json::json!({ "rust": [] });
+        let applied = migrate(&mut document, Path::new("protogen.json")).unwrap();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(document["schema"], SCHEMA_VERSION);
+        assert!(migrate(&mut document, Path::new("protogen.json"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn it_rejects_newer_specs() {
+        let mut document = serde_json::json!({ "schema": SCHEMA_VERSION + 1 });
+        assert!(matches!(
+            migrate(&mut document, Path::new("protogen.json")),
+            Err(SpecError::UnsupportedSchema { .. })
+        ));
+    }
+}
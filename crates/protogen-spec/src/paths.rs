@@ -0,0 +1,85 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::{ProtogenSpec, SpecError};
+
+impl ProtogenSpec {
+    /// Rewrites every relative path in the spec to an absolute one. Package
+    /// paths are resolved against `spec_dir`, the directory containing the
+    /// spec file, and local proto, include, and import directories as well as
+    /// API dependency paths against their package path.
+    /// Paths that exist are canonicalized.
+    pub fn normalize_paths(&mut self, spec_dir: &Path) -> Result<(), SpecError> {
+        let spec_dir = spec_dir
+            .canonicalize()
+            .map_err(|error| SpecError::InvalidPath {
+                path: spec_dir.to_owned(),
+                reason: error.to_string(),
+            })?;
+        for package in &mut self.rust {
+            package.path = resolve_path(&spec_dir, &package.path)?;
+            for src in package.protos.iter_mut().filter(|x| !x.is_remote()) {
+                src.dir = resolve_path(&package.path, &src.dir)?;
+            }
+            for include in package.includes.iter_mut().filter(|x| !x.is_remote()) {
+                include.dir = resolve_path(&package.path, &include.dir)?;
+            }
+            for dir in package.import_paths.values_mut() {
+                *dir = resolve_path(&package.path, dir)?;
+            }
+            for dependency in &mut package.api_dependencies {
+                if let Some(path) = &mut dependency.path {
+                    *path = resolve_path(&package.path, path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Joins `path` onto `base` unless it is already absolute, then normalizes
+/// the result, canonicalizing it when it exists.
+pub fn resolve_path(base: &Path, path: &Path) -> Result<PathBuf, SpecError> {
+    let path = normalize_path(&base.join(path));
+    if path.exists() {
+        path.canonicalize().map_err(|error| SpecError::InvalidPath {
+            reason: error.to_string(),
+            path,
+        })
+    } else {
+        Ok(path)
+    }
+}
+
+/// Lexically removes `.` and `..` components without touching the
+/// filesystem.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Returns the relative path leading from directory `base` to `path`, both
+/// of which should be absolute and normalized.
+pub fn relative_path(base: &Path, path: &Path) -> PathBuf {
+    let base: Vec<Component> = base.components().collect();
+    let path: Vec<Component> = path.components().collect();
+    let common = base.iter().zip(&path).take_while(|(a, b)| a.eq(b)).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &path[common..] {
+        relative.push(component);
+    }
+    relative
+}
@@ -0,0 +1,263 @@
+//! gRPC server reflection (`grpc.reflection.v1`) over the descriptors of any
+//! number of generated packages, merged into one pool:
+//!
+//! ```ignore
+//! let reflection = appbiotic_api_reflection::reflection!(acme_widgets, acme_gadgets);
+//! Server::builder()
+//!     .add_service(reflection.into_server())
+//!     .add_service(WidgetServiceServer::new(widgets))
+//! ```
+
+use std::{collections::HashSet, fmt};
+
+use futures_util::{stream::BoxStream, StreamExt, TryStreamExt};
+use prost::{DecodeError, Message};
+use prost_reflect::{DescriptorError, DescriptorPool, FileDescriptor};
+use prost_types::FileDescriptorSet;
+use tonic::{Code, Request, Response, Status, Streaming};
+pub use tonic_reflection::pb::v1 as pb;
+
+use crate::pb::{
+    server_reflection_request::MessageRequest,
+    server_reflection_response::MessageResponse,
+    server_reflection_server::{ServerReflection, ServerReflectionServer},
+    ErrorResponse, ExtensionNumberResponse, ExtensionRequest, FileDescriptorResponse,
+    ListServiceResponse, ServerReflectionRequest, ServerReflectionResponse, ServiceResponse,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Decode(DecodeError),
+    /// Two descriptor sets hold different versions of the same file.
+    ConflictingFile(String),
+    Descriptor(DescriptorError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(error) => write!(f, "Failed to decode descriptor set: {error}"),
+            Self::ConflictingFile(name) => {
+                write!(f, "Descriptor sets hold different versions of `{name}`")
+            }
+            Self::Descriptor(error) => write!(f, "Invalid descriptor set: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decode(error) => Some(error),
+            Self::ConflictingFile(_) => None,
+            Self::Descriptor(error) => Some(error),
+        }
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+impl From<DescriptorError> for Error {
+    fn from(error: DescriptorError) -> Self {
+        Self::Descriptor(error)
+    }
+}
+
+/// Descriptors served by reflection, which include the reflection service
+/// itself.
+#[derive(Clone, Debug)]
+pub struct Reflection {
+    pool: DescriptorPool,
+}
+
+impl Default for Reflection {
+    fn default() -> Self {
+        let mut reflection = Self {
+            pool: DescriptorPool::new(),
+        };
+        reflection
+            .register(pb::FILE_DESCRIPTOR_SET)
+            .expect("Expected valid descriptors of the reflection service");
+        reflection
+    }
+}
+
+impl Reflection {
+    /// Adds the files of an encoded `FileDescriptorSet`, such as the
+    /// `FILE_DESCRIPTOR_SET` of a generated package. Files already registered,
+    /// e.g. imports shared by several packages, must be identical and are
+    /// skipped.
+    pub fn register(&mut self, file_descriptor_set: &[u8]) -> Result<(), Error> {
+        for file in FileDescriptorSet::decode(file_descriptor_set)?.file {
+            if self
+                .pool
+                .get_file_by_name(file.name())
+                .is_some_and(|x| x.file_descriptor_proto() != &file)
+            {
+                return Err(Error::ConflictingFile(file.name().to_owned()));
+            }
+        }
+        // Decoded by the pool to keep the custom options of the files.
+        self.pool.decode_file_descriptor_set(file_descriptor_set)?;
+        Ok(())
+    }
+
+    pub fn pool(&self) -> &DescriptorPool {
+        &self.pool
+    }
+
+    pub fn into_server(self) -> ServerReflectionServer<ReflectionService> {
+        ServerReflectionServer::new(ReflectionService { pool: self.pool })
+    }
+}
+
+pub struct ReflectionService {
+    pool: DescriptorPool,
+}
+
+#[tonic::async_trait]
+impl ServerReflection for ReflectionService {
+    type ServerReflectionInfoStream = BoxStream<'static, Result<ServerReflectionResponse, Status>>;
+
+    async fn server_reflection_info(
+        &self,
+        request: Request<Streaming<ServerReflectionRequest>>,
+    ) -> Result<Response<Self::ServerReflectionInfoStream>, Status> {
+        let pool = self.pool.clone();
+        let responses = request
+            .into_inner()
+            .map_ok(move |request| respond(&pool, request));
+        Ok(Response::new(responses.boxed()))
+    }
+}
+
+/// Answers `request`, with an error response for what is not found rather
+/// than failing the stream.
+fn respond(pool: &DescriptorPool, request: ServerReflectionRequest) -> ServerReflectionResponse {
+    let message_response = match &request.message_request {
+        Some(MessageRequest::FileByFilename(name)) => pool
+            .get_file_by_name(name)
+            .map(file_response)
+            .unwrap_or_else(|| not_found(format!("File `{name}` not found"))),
+        Some(MessageRequest::FileContainingSymbol(symbol)) => file_containing_symbol(pool, symbol)
+            .map(file_response)
+            .unwrap_or_else(|| not_found(format!("Symbol `{symbol}` not found"))),
+        Some(MessageRequest::FileContainingExtension(ExtensionRequest {
+            containing_type,
+            extension_number,
+        })) => pool
+            .get_message_by_name(containing_type)
+            .and_then(|x| {
+                x.extensions()
+                    .find(|x| x.number() as i32 == *extension_number)
+            })
+            .map(|x| file_response(x.parent_file()))
+            .unwrap_or_else(|| {
+                not_found(format!(
+                    "Extension {extension_number} of `{containing_type}` not found"
+                ))
+            }),
+        Some(MessageRequest::AllExtensionNumbersOfType(type_name)) => pool
+            .get_message_by_name(type_name)
+            .map(|x| {
+                MessageResponse::AllExtensionNumbersResponse(ExtensionNumberResponse {
+                    base_type_name: type_name.to_owned(),
+                    extension_number: x.extensions().map(|x| x.number() as i32).collect(),
+                })
+            })
+            .unwrap_or_else(|| not_found(format!("Message `{type_name}` not found"))),
+        Some(MessageRequest::ListServices(_)) => {
+            MessageResponse::ListServicesResponse(ListServiceResponse {
+                service: pool
+                    .services()
+                    .map(|x| ServiceResponse {
+                        name: x.full_name().to_owned(),
+                    })
+                    .collect(),
+            })
+        }
+        None => error_response(Code::InvalidArgument, "Missing message request".to_owned()),
+    };
+    ServerReflectionResponse {
+        valid_host: request.host.to_owned(),
+        original_request: Some(request),
+        message_response: Some(message_response),
+    }
+}
+
+fn file_containing_symbol(pool: &DescriptorPool, symbol: &str) -> Option<FileDescriptor> {
+    let symbol = symbol.strip_prefix('.').unwrap_or(symbol);
+    let member = || {
+        let (parent, name) = symbol.rsplit_once('.')?;
+        let method = pool
+            .get_service_by_name(parent)
+            .and_then(|x| x.methods().find(|x| x.name() == name))
+            .map(|x| x.parent_file());
+        method.or_else(|| {
+            pool.get_message_by_name(parent)?
+                .get_field_by_name(name)
+                .map(|x| x.parent_file())
+        })
+    };
+    pool.get_message_by_name(symbol)
+        .map(|x| x.parent_file())
+        .or_else(|| pool.get_enum_by_name(symbol).map(|x| x.parent_file()))
+        .or_else(|| pool.get_service_by_name(symbol).map(|x| x.parent_file()))
+        .or_else(|| pool.get_extension_by_name(symbol).map(|x| x.parent_file()))
+        .or_else(member)
+}
+
+/// The file with its transitive dependencies, which clients need to resolve
+/// it.
+fn file_response(file: FileDescriptor) -> MessageResponse {
+    let mut names = HashSet::from([file.name().to_owned()]);
+    let mut files = vec![file];
+    let mut index = 0;
+    while index < files.len() {
+        let file = files[index].clone();
+        for dependency in file.dependencies() {
+            if names.insert(dependency.name().to_owned()) {
+                files.push(dependency);
+            }
+        }
+        index += 1;
+    }
+    MessageResponse::FileDescriptorResponse(FileDescriptorResponse {
+        file_descriptor_proto: files.iter().map(FileDescriptor::encode_to_vec).collect(),
+    })
+}
+
+fn not_found(message: String) -> MessageResponse {
+    error_response(Code::NotFound, message)
+}
+
+fn error_response(code: Code, error_message: String) -> MessageResponse {
+    MessageResponse::ErrorResponse(ErrorResponse {
+        error_code: code as i32,
+        error_message,
+    })
+}
+
+/// Builds a [`Reflection`] of the descriptors of the listed generated crates,
+/// which must enable their `prost-serde` feature.
+#[macro_export]
+macro_rules! reflection {
+    ($($crate_name:ident),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut reflection = $crate::Reflection::default();
+        $(
+            reflection
+                .register($crate_name::prost_serde::FILE_DESCRIPTOR_SET)
+                .expect(concat!(
+                    "Failed to register descriptor set of `",
+                    stringify!($crate_name),
+                    "`"
+                ));
+        )*
+        reflection
+    }};
+}
@@ -0,0 +1,133 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{ensure, Context};
+use appbiotic_api_descriptor_diff::{diff_descriptor_sets, Change};
+use appbiotic_api_protogen_spec::{ProtogenSpec, RustPackage};
+use tempfile::TempDir;
+use tracing::{debug, warn};
+
+use crate::descriptor::compile_package_descriptors;
+
+/// A schema change to the protos of a generated package.
+#[derive(serde::Serialize)]
+pub struct PackageChange {
+    pub package: String,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+impl fmt::Display for PackageChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.package, self.change)
+    }
+}
+
+/// Lists the schema changes of `packages` since git revision `against`,
+/// comparing descriptors compiled from a checkout of that revision with the
+/// current ones. `protogen_path` must be absolute. Packages missing from the
+/// spec at `against` are new and have no changes.
+pub fn package_changes(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    packages: &[&RustPackage],
+    against: &str,
+) -> anyhow::Result<Vec<PackageChange>> {
+    let spec_dir = protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")?
+        .canonicalize()?;
+    let repo_dir =
+        PathBuf::from(git(&spec_dir, &["rev-parse", "--show-toplevel"])?.trim()).canonicalize()?;
+    let worktree = Worktree::add(&repo_dir, against)?;
+    let old_path = worktree
+        .dir
+        .path()
+        .join(spec_dir.strip_prefix(&repo_dir)?)
+        .join(
+            protogen_path
+                .file_name()
+                .context("Expected file_name from protogen_path")?,
+        );
+    let old_protogen = ProtogenSpec::load(&old_path)
+        .with_context(|| format!("Failed to load the spec at revision `{against}`"))?;
+
+    let mut changes = Vec::new();
+    for package in packages {
+        if old_protogen.rust_package(&package.name).is_none() {
+            debug!(package = %package.name, "Package is new since `{against}`");
+            continue;
+        }
+        let old = compile_package_descriptors(&old_protogen, &old_path, &package.name)
+            .with_context(|| format!("Failed to compile protos at revision `{against}`"))?;
+        let new = compile_package_descriptors(protogen, protogen_path, &package.name)?;
+        changes.extend(
+            diff_descriptor_sets(&old, &new, &package.proto_package_name)
+                .into_iter()
+                .map(|change| PackageChange {
+                    package: package.name.to_owned(),
+                    change,
+                }),
+        );
+    }
+    Ok(changes)
+}
+
+/// A detached git worktree in a temporary directory, removed on drop.
+struct Worktree {
+    repo_dir: PathBuf,
+    dir: TempDir,
+}
+
+impl Worktree {
+    fn add(repo_dir: &Path, rev: &str) -> anyhow::Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix("protogen-breaking-")
+            .tempdir()
+            .context("Failed to create temporary directory")?;
+        git(
+            repo_dir,
+            &[
+                "worktree",
+                "add",
+                "--detach",
+                "--quiet",
+                &dir.path().to_string_lossy(),
+                rev,
+            ],
+        )
+        .with_context(|| format!("Failed to check out revision `{rev}`"))?;
+        Ok(Self {
+            repo_dir: repo_dir.to_owned(),
+            dir,
+        })
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let path = self.dir.path().to_string_lossy();
+        if let Err(error) = git(&self.repo_dir, &["worktree", "remove", "--force", &path]) {
+            warn!("Failed to remove git worktree `{path}`: {error:?}");
+        }
+    }
+}
+
+/// Runs git in `dir`, returning its standard output.
+pub fn git(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .with_context(|| format!("Failed to run `git {}`", args.join(" ")))?;
+    ensure!(
+        output.status.success(),
+        "`git {}` failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    String::from_utf8(output.stdout).context("Expected UTF-8 output from git")
+}
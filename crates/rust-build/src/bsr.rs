@@ -0,0 +1,146 @@
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context};
+use appbiotic_api_protogen_fetch::bsr::{push_module, resolve_commit, write_module, ModuleFile};
+use appbiotic_api_protogen_spec::{paths::normalize_path, BsrModuleRef, ProtogenSpec, RustPackage};
+use tracing::info;
+
+use crate::descriptor::remote_fetcher;
+
+pub struct BsrPushOptions {
+    /// Labels besides the package version to push with.
+    pub labels: Vec<String>,
+    /// Create modules missing from the registry.
+    pub create: bool,
+    pub dry_run: bool,
+}
+
+/// Pushes the local protos of each of `packages` with a `bsr_module` as that
+/// module, labeled with the package version. The module depends on the BSR
+/// sources and includes of the package and on the modules of its protogen
+/// dependencies at their versions, so `packages` must be in dependency
+/// order. With `dry_run` the modules are only printed.
+pub fn push_packages(
+    protogen: &ProtogenSpec,
+    spec_dir: &Path,
+    packages: &[&RustPackage],
+    options: &BsrPushOptions,
+) -> anyhow::Result<()> {
+    for package in packages {
+        let Some(module) = &package.bsr_module else {
+            continue;
+        };
+        let files = module_files(spec_dir, package)?;
+        let deps = module_deps(protogen, package)?;
+        let mut labels = vec![package.version.to_owned()];
+        labels.extend(options.labels.iter().cloned());
+
+        if options.dry_run {
+            println!("{} -> {module} ({})", package.name, labels.join(", "));
+            for file in &files {
+                println!("  {}", file.path.to_string_lossy());
+            }
+            for dep in &deps {
+                println!("  dep {dep}");
+            }
+            continue;
+        }
+
+        let module_dir = tempfile::tempdir().context("Failed to create module directory")?;
+        write_module(module_dir.path(), module, &files, &deps)?;
+        let commit = push_module(module_dir.path(), &labels, options.create)
+            .with_context(|| format!("Failed to push package `{}` to `{module}`", package.name))?;
+        info!(package = %package.name, %module, %commit, "Pushed BSR module");
+    }
+    Ok(())
+}
+
+fn module_files(spec_dir: &Path, package: &RustPackage) -> anyhow::Result<Vec<ModuleFile>> {
+    ensure!(
+        !package.protos.iter().any(|x| x.git.is_some())
+            && !package.includes.iter().any(|x| x.git.is_some()),
+        "Package `{}` has git proto sources, which BSR modules cannot depend on; vendor them with `rust-build vendor` first",
+        package.name
+    );
+    let package_path = normalize_path(&spec_dir.join(&package.path));
+    let files: Vec<ModuleFile> = package
+        .protos
+        .iter()
+        .filter(|x| !x.is_remote())
+        .flat_map(|src| {
+            let dir = package_path.join(&src.dir);
+            src.files.iter().map(move |file| ModuleFile {
+                src: dir.join(file),
+                path: file.to_owned(),
+            })
+        })
+        .collect();
+    ensure!(
+        !files.is_empty(),
+        "Package `{}` has no local protos to push",
+        package.name
+    );
+    Ok(files)
+}
+
+fn module_deps(
+    protogen: &ProtogenSpec,
+    package: &RustPackage,
+) -> anyhow::Result<Vec<BsrModuleRef>> {
+    let mut deps: Vec<BsrModuleRef> = package
+        .protos
+        .iter()
+        .filter_map(|x| x.bsr.clone())
+        .chain(package.includes.iter().filter_map(|x| x.bsr.clone()))
+        .collect();
+    for name in &package.protogen_dependencies {
+        let dependency = protogen.package(name)?;
+        let Some(module) = &dependency.bsr_module else {
+            bail!(
+                "Dependency `{name}` of package `{}` has no `bsr_module` for its protos",
+                package.name
+            );
+        };
+        deps.push(module.at(&dependency.version));
+    }
+    deps.sort_by_key(|x| x.to_string());
+    deps.dedup_by_key(|x| x.to_string());
+    Ok(deps)
+}
+
+/// Fetches the BSR modules of the proto sources and includes of the packages
+/// named `package_names` into the fetch cache. With `pin` their references,
+/// such as labels, are first resolved to the commits they point at, so later
+/// generation is reproducible. Returns the references that were pinned.
+pub fn pull_packages(
+    protogen: &mut ProtogenSpec,
+    spec_dir: &Path,
+    package_names: &[String],
+    pin: bool,
+) -> anyhow::Result<Vec<BsrModuleRef>> {
+    let fetcher = remote_fetcher(spec_dir);
+    let mut pinned = Vec::new();
+    for package in protogen
+        .rust
+        .iter_mut()
+        .filter(|x| package_names.contains(&x.name))
+    {
+        let refs = package
+            .protos
+            .iter_mut()
+            .filter_map(|x| x.bsr.as_mut())
+            .chain(package.includes.iter_mut().filter_map(|x| x.bsr.as_mut()));
+        for bsr in refs {
+            if pin {
+                let commit = resolve_commit(bsr)?;
+                if bsr.reference != commit {
+                    bsr.reference = commit;
+                    pinned.push(bsr.clone());
+                }
+            }
+            let dir = fetcher.fetch_bsr(bsr)?;
+            info!(package = %package.name, module = %bsr, dir = %dir.to_string_lossy(), "Pulled BSR module");
+        }
+    }
+    Ok(pinned)
+}
@@ -0,0 +1,116 @@
+use std::path::Path;
+
+use anyhow::Context;
+use appbiotic_api_prost_serde_build::{compile_descriptor_set, descriptor_set_sha256};
+use appbiotic_api_protogen_fetch::Fetcher;
+use appbiotic_api_protogen_spec::{ProtogenSpec, RustPackage};
+use prost::Message;
+use prost_reflect::ReflectMessage;
+use prost_types::FileDescriptorSet;
+use tracing::debug_span;
+
+pub use appbiotic_api_proto_lint::source_path::*;
+
+/// Compiles the descriptor set of the package named `package_name`, including
+/// imports. `protogen_path` must be absolute. Remote proto sources are
+/// fetched by the [`remote_fetcher`] of the spec.
+pub fn compile_package_descriptors(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    package_name: &str,
+) -> anyhow::Result<FileDescriptorSet> {
+    let _span = debug_span!("protoc", package = package_name).entered();
+    let spec_dir = protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")?;
+    let mut normalized = protogen.clone();
+    normalized.normalize_paths(spec_dir)?;
+    let package = normalized.package(package_name)?;
+    compile_descriptor_set(package, &package.path, &remote_fetcher(spec_dir))
+}
+
+/// Hex encoded SHA-256 of the descriptor set the build script of the package
+/// named `package_name` records in its proto package spec. `protogen_path`
+/// must be absolute.
+pub fn package_descriptor_sha256(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    package_name: &str,
+) -> anyhow::Result<String> {
+    let _span = debug_span!("protoc", package = package_name).entered();
+    let spec_dir = protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")?;
+    let mut normalized = protogen.clone();
+    normalized.normalize_paths(spec_dir)?;
+    let package = normalized.package(package_name)?;
+    descriptor_set_sha256(package, &package.path, &remote_fetcher(spec_dir))
+}
+
+/// Fetches remote proto sources of the spec in `spec_dir` into the shared
+/// cache, or into `target/protogen/remote` next to it when there is none.
+pub fn remote_fetcher(spec_dir: &Path) -> Fetcher {
+    Fetcher::shared(spec_dir.join("target").join("protogen").join("remote"))
+}
+
+/// Reads a binary encoded `FileDescriptorSet`, e.g. one written by
+/// `protoc --descriptor_set_out`.
+pub fn load_descriptor_set(path: &Path) -> anyhow::Result<FileDescriptorSet> {
+    let bytes = std::fs::read(path).with_context(|| {
+        format!(
+            "Failed to read descriptor set at path `{}`",
+            path.to_string_lossy()
+        )
+    })?;
+    FileDescriptorSet::decode(bytes.as_slice()).with_context(|| {
+        format!(
+            "Failed to decode descriptor set at path `{}`",
+            path.to_string_lossy()
+        )
+    })
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum DescriptorFormat {
+    /// Binary encoded, as written by `protoc --descriptor_set_out`.
+    Binpb,
+    /// The protobuf JSON mapping of the descriptor set.
+    Json,
+}
+
+/// Narrows the compiled `fds` of `package` to the files of its proto package
+/// unless `include_imports` is set, and drops source code info unless
+/// `include_source_info` is set, matching the `protoc` flags of those names.
+pub fn export_descriptor_set(
+    mut fds: FileDescriptorSet,
+    package: &RustPackage,
+    include_imports: bool,
+    include_source_info: bool,
+) -> FileDescriptorSet {
+    if !include_imports {
+        fds.file
+            .retain(|x| x.package() == package.proto_package_name);
+    }
+    if !include_source_info {
+        for file in &mut fds.file {
+            file.source_code_info = None;
+        }
+    }
+    fds
+}
+
+/// Encodes `fds` in `format`.
+pub fn encode_descriptor_set(
+    fds: &FileDescriptorSet,
+    format: DescriptorFormat,
+) -> anyhow::Result<Vec<u8>> {
+    match format {
+        DescriptorFormat::Binpb => Ok(fds.encode_to_vec()),
+        DescriptorFormat::Json => {
+            let mut json = serde_json::to_vec_pretty(&fds.transcode_to_dynamic())
+                .context("Failed to serialize descriptor set to JSON")?;
+            json.push(b'\n');
+            Ok(json)
+        }
+    }
+}
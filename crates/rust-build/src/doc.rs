@@ -0,0 +1,329 @@
+use std::{collections::BTreeSet, fmt::Write};
+
+use appbiotic_api_protogen_spec::RustPackage;
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+    FileDescriptorSet, ServiceDescriptorProto,
+};
+
+use crate::descriptor::{
+    ENUM_VALUE, FILE_ENUM_TYPE, FILE_MESSAGE_TYPE, FILE_SERVICE, MESSAGE_ENUM_TYPE, MESSAGE_FIELD,
+    MESSAGE_NESTED_TYPE, SERVICE_METHOD,
+};
+
+/// Renders a Markdown API reference of the services, messages, and enums of
+/// the proto package of `package`, with their comments. `fds` must include
+/// source code info for comments to be rendered.
+pub fn render_markdown(package: &RustPackage, fds: &FileDescriptorSet) -> String {
+    let proto_package = &package.proto_package_name;
+    let files: Vec<&FileDescriptorProto> = fds
+        .file
+        .iter()
+        .filter(|x| x.package() == proto_package)
+        .collect();
+    let mut doc = Doc {
+        proto_package,
+        local_types: BTreeSet::new(),
+        out: String::new(),
+    };
+    for file in &files {
+        for message in &file.message_type {
+            doc.collect_message_types(message, &format!(".{proto_package}"));
+        }
+        for enum_ in &file.enum_type {
+            doc.local_types
+                .insert(format!(".{proto_package}.{}", enum_.name()));
+        }
+    }
+
+    let _ = writeln!(doc.out, "# {proto_package}\n");
+    let _ = writeln!(
+        doc.out,
+        "Rust package `{}` version {}.",
+        package.name, package.version
+    );
+    if files.iter().any(|x| !x.service.is_empty()) {
+        doc.out.push_str("\n## Services\n");
+        for file in &files {
+            for (index, service) in file.service.iter().enumerate() {
+                doc.render_service(file, service, &[FILE_SERVICE, index as i32]);
+            }
+        }
+    }
+    if files.iter().any(|x| !x.message_type.is_empty()) {
+        doc.out.push_str("\n## Messages\n");
+        for file in &files {
+            for (index, message) in file.message_type.iter().enumerate() {
+                doc.render_message(file, message, "", &[FILE_MESSAGE_TYPE, index as i32]);
+            }
+        }
+    }
+    if files.iter().any(|x| has_enums(x)) {
+        doc.out.push_str("\n## Enums\n");
+        for file in &files {
+            for (index, enum_) in file.enum_type.iter().enumerate() {
+                doc.render_enum(file, enum_, "", &[FILE_ENUM_TYPE, index as i32]);
+            }
+            for (index, message) in file.message_type.iter().enumerate() {
+                doc.render_nested_enums(file, message, "", &[FILE_MESSAGE_TYPE, index as i32]);
+            }
+        }
+    }
+    doc.out
+}
+
+struct Doc<'a> {
+    proto_package: &'a str,
+    /// Fully qualified names of the types documented on the page, which
+    /// references link to.
+    local_types: BTreeSet<String>,
+    out: String,
+}
+
+impl Doc<'_> {
+    fn collect_message_types(&mut self, message: &DescriptorProto, scope: &str) {
+        let name = format!("{scope}.{}", message.name());
+        for nested in &message.nested_type {
+            self.collect_message_types(nested, &name);
+        }
+        for enum_ in &message.enum_type {
+            self.local_types.insert(format!("{name}.{}", enum_.name()));
+        }
+        if !is_map_entry(message) {
+            self.local_types.insert(name);
+        }
+    }
+
+    fn render_service(
+        &mut self,
+        file: &FileDescriptorProto,
+        service: &ServiceDescriptorProto,
+        path: &[i32],
+    ) {
+        let _ = writeln!(self.out, "\n### {}\n", service.name());
+        self.render_comment(file, path);
+        self.out
+            .push_str("| Method | Request | Response | Description |\n");
+        self.out.push_str("| --- | --- | --- | --- |\n");
+        for (index, method) in service.method.iter().enumerate() {
+            let stream = |streaming: bool| if streaming { "stream " } else { "" };
+            let _ = writeln!(
+                self.out,
+                "| `{}` | {}{} | {}{} | {} |",
+                method.name(),
+                stream(method.client_streaming()),
+                self.type_reference(method.input_type()),
+                stream(method.server_streaming()),
+                self.type_reference(method.output_type()),
+                table_cell(&comment(
+                    file,
+                    &[path, &[SERVICE_METHOD, index as i32]].concat()
+                )),
+            );
+        }
+    }
+
+    fn render_message(
+        &mut self,
+        file: &FileDescriptorProto,
+        message: &DescriptorProto,
+        scope: &str,
+        path: &[i32],
+    ) {
+        if is_map_entry(message) {
+            return;
+        }
+        let name = format!("{scope}{}", message.name());
+        let _ = writeln!(self.out, "\n### {name}\n");
+        self.render_comment(file, path);
+        if message.field.is_empty() {
+            self.out.push_str("This message has no fields.\n");
+        } else {
+            self.out
+                .push_str("| Field | Number | Type | Description |\n");
+            self.out.push_str("| --- | --- | --- | --- |\n");
+            for (index, field) in message.field.iter().enumerate() {
+                let _ = writeln!(
+                    self.out,
+                    "| `{}` | {} | {} | {} |",
+                    field.name(),
+                    field.number(),
+                    self.field_type(message, field),
+                    table_cell(&comment(
+                        file,
+                        &[path, &[MESSAGE_FIELD, index as i32]].concat()
+                    )),
+                );
+            }
+        }
+        for (index, nested) in message.nested_type.iter().enumerate() {
+            self.render_message(
+                file,
+                nested,
+                &format!("{name}."),
+                &[path, &[MESSAGE_NESTED_TYPE, index as i32]].concat(),
+            );
+        }
+    }
+
+    fn render_nested_enums(
+        &mut self,
+        file: &FileDescriptorProto,
+        message: &DescriptorProto,
+        scope: &str,
+        path: &[i32],
+    ) {
+        let name = format!("{scope}{}", message.name());
+        for (index, enum_) in message.enum_type.iter().enumerate() {
+            self.render_enum(
+                file,
+                enum_,
+                &format!("{name}."),
+                &[path, &[MESSAGE_ENUM_TYPE, index as i32]].concat(),
+            );
+        }
+        for (index, nested) in message.nested_type.iter().enumerate() {
+            self.render_nested_enums(
+                file,
+                nested,
+                &format!("{name}."),
+                &[path, &[MESSAGE_NESTED_TYPE, index as i32]].concat(),
+            );
+        }
+    }
+
+    fn render_enum(
+        &mut self,
+        file: &FileDescriptorProto,
+        enum_: &EnumDescriptorProto,
+        scope: &str,
+        path: &[i32],
+    ) {
+        let _ = writeln!(self.out, "\n### {scope}{}\n", enum_.name());
+        self.render_comment(file, path);
+        self.out.push_str("| Value | Number | Description |\n");
+        self.out.push_str("| --- | --- | --- |\n");
+        for (index, value) in enum_.value.iter().enumerate() {
+            let _ = writeln!(
+                self.out,
+                "| `{}` | {} | {} |",
+                value.name(),
+                value.number(),
+                table_cell(&comment(
+                    file,
+                    &[path, &[ENUM_VALUE, index as i32]].concat()
+                )),
+            );
+        }
+    }
+
+    fn render_comment(&mut self, file: &FileDescriptorProto, path: &[i32]) {
+        let comment = comment(file, path);
+        if !comment.is_empty() {
+            let _ = writeln!(self.out, "{comment}\n");
+        }
+    }
+
+    fn field_type(&self, message: &DescriptorProto, field: &FieldDescriptorProto) -> String {
+        let map_entry = message
+            .nested_type
+            .iter()
+            .filter(|x| is_map_entry(x))
+            .find(|x| {
+                field
+                    .type_name()
+                    .ends_with(&format!(".{}.{}", message.name(), x.name()))
+            });
+        if let (Label::Repeated, Some(entry)) = (field.label(), map_entry) {
+            if let [key, value] = entry.field.as_slice() {
+                return format!(
+                    "map<{}, {}>",
+                    self.scalar_or_reference(key),
+                    self.scalar_or_reference(value)
+                );
+            }
+        }
+        let prefix = match field.label() {
+            Label::Repeated => "repeated ",
+            _ if field.proto3_optional() => "optional ",
+            _ => "",
+        };
+        format!("{prefix}{}", self.scalar_or_reference(field))
+    }
+
+    fn scalar_or_reference(&self, field: &FieldDescriptorProto) -> String {
+        match field.r#type() {
+            Type::Message | Type::Enum | Type::Group => self.type_reference(field.type_name()),
+            scalar => format!(
+                "`{}`",
+                scalar
+                    .as_str_name()
+                    .trim_start_matches("TYPE_")
+                    .to_lowercase()
+            ),
+        }
+    }
+
+    /// Links fully qualified `type_name` to its section when documented on
+    /// the page.
+    fn type_reference(&self, type_name: &str) -> String {
+        if self.local_types.contains(type_name) {
+            let name = type_name
+                .trim_start_matches('.')
+                .trim_start_matches(self.proto_package)
+                .trim_start_matches('.');
+            format!("[`{name}`](#{})", name.to_lowercase().replace('.', ""))
+        } else {
+            format!("`{}`", type_name.trim_start_matches('.'))
+        }
+    }
+}
+
+fn is_map_entry(message: &DescriptorProto) -> bool {
+    message
+        .options
+        .as_ref()
+        .is_some_and(|x| x.map_entry.unwrap_or_default())
+}
+
+fn has_enums(file: &FileDescriptorProto) -> bool {
+    fn message_has_enums(message: &DescriptorProto) -> bool {
+        !message.enum_type.is_empty() || message.nested_type.iter().any(message_has_enums)
+    }
+    !file.enum_type.is_empty() || file.message_type.iter().any(message_has_enums)
+}
+
+/// The leading comment of the element at `path`, or else its trailing
+/// comment, with the comment markers' indentation removed.
+fn comment(file: &FileDescriptorProto, path: &[i32]) -> String {
+    let Some(location) = file
+        .source_code_info
+        .as_ref()
+        .and_then(|info| info.location.iter().find(|x| x.path == path))
+    else {
+        return String::new();
+    };
+    let comment = location
+        .leading_comments
+        .as_deref()
+        .or(location.trailing_comments.as_deref())
+        .unwrap_or_default();
+    comment
+        .lines()
+        .map(|x| x.strip_prefix(' ').unwrap_or(x).trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+/// Fits a comment into a table cell, which cannot span lines.
+fn table_cell(comment: &str) -> String {
+    comment
+        .split("\n\n")
+        .map(|x| x.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("<br><br>")
+        .replace('|', "\\|")
+}
@@ -0,0 +1,204 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+use appbiotic_api_protogen_fetch::offline_from_env;
+use appbiotic_api_protogen_spec::ProtogenSpec;
+
+use crate::{
+    descriptor::remote_fetcher,
+    generate::{dependency_versions, GenerateOptions},
+    output_manifest::LOCK_FILE_NAME,
+    verify::verify_target_dir,
+};
+
+/// External tools the generator may run, with the environment variable
+/// overriding where each is found, if any.
+const TOOLS: [(&str, Option<&str>); 6] = [
+    ("protoc", Some("PROTOC")),
+    ("rustfmt", Some("RUSTFMT")),
+    ("cargo", Some("CARGO")),
+    ("git", None),
+    ("buf", None),
+    ("protofetch", None),
+];
+
+/// The configuration the generator resolves for a spec, e.g. to compare
+/// runs on CI with local ones.
+#[derive(serde::Serialize)]
+pub struct Environment {
+    pub generator_version: &'static str,
+    pub spec_path: PathBuf,
+    pub spec_dir: PathBuf,
+    pub schema: u32,
+    pub output_dir: PathBuf,
+    pub lock_path: PathBuf,
+    pub templates_dir: Option<PathBuf>,
+    pub fmt: bool,
+    /// Whether fetching remote proto sources is forbidden.
+    pub offline: bool,
+    pub remote_cache_dir: PathBuf,
+    pub verify_target_dir: PathBuf,
+    pub tools: Vec<Tool>,
+    pub versions: BTreeMap<String, String>,
+    pub packages: Vec<PackageEnvironment>,
+}
+
+#[derive(serde::Serialize)]
+pub struct Tool {
+    pub name: &'static str,
+    /// Where the tool was found, missing when it is not installed.
+    pub path: Option<PathBuf>,
+    /// First line of `<tool> --version`.
+    pub version: Option<String>,
+    /// Environment variable the path was taken from, if any.
+    pub source: Option<&'static str>,
+}
+
+#[derive(serde::Serialize)]
+pub struct PackageEnvironment {
+    pub name: String,
+    pub version: String,
+    /// Absolute directory the package is generated into.
+    pub path: PathBuf,
+    pub proto_package_name: String,
+}
+
+/// Resolves the environment of the spec at the absolute `protogen_path`
+/// when generating with `options`.
+pub fn resolve_environment(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    options: &GenerateOptions,
+) -> anyhow::Result<Environment> {
+    let spec_dir = protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")?;
+    let output_dir = options.output_dir(spec_dir)?;
+    let canonical_dir = spec_dir.canonicalize()?;
+    let mut normalized = protogen.clone();
+    normalized.normalize_paths(&canonical_dir)?;
+
+    Ok(Environment {
+        generator_version: env!("CARGO_PKG_VERSION"),
+        spec_path: protogen_path.to_owned(),
+        spec_dir: spec_dir.to_owned(),
+        schema: protogen.schema,
+        lock_path: output_dir.join(LOCK_FILE_NAME),
+        templates_dir: options.templates_dir.to_owned(),
+        fmt: options.fmt || protogen.fmt,
+        offline: offline_from_env(),
+        remote_cache_dir: remote_fetcher(spec_dir).cache_dir().to_owned(),
+        verify_target_dir: env::var_os("CARGO_TARGET_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| verify_target_dir(spec_dir)),
+        tools: TOOLS
+            .iter()
+            .map(|(name, var)| find_tool(name, *var))
+            .collect(),
+        versions: dependency_versions(protogen)?,
+        packages: normalized
+            .rust
+            .iter()
+            .map(|package| PackageEnvironment {
+                name: package.name.to_owned(),
+                version: package.version.to_owned(),
+                path: match package.path.strip_prefix(&canonical_dir) {
+                    Ok(path) if options.output_root.is_some() => output_dir.join(path),
+                    _ => package.path.to_owned(),
+                },
+                proto_package_name: package.proto_package_name.to_owned(),
+            })
+            .collect(),
+        output_dir,
+    })
+}
+
+/// Finds `name` the way it is run: from the environment variable `var` when
+/// set, else on `PATH`.
+fn find_tool(name: &'static str, var: Option<&'static str>) -> Tool {
+    let (path, source) = match var.and_then(|x| env::var_os(x).map(|path| (x, path))) {
+        Some((var, path)) => (Some(PathBuf::from(path)), Some(var)),
+        None => (find_on_path(name), None),
+    };
+    let version = path.as_ref().and_then(|path| {
+        let output = Command::new(path).arg("--version").output().ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Some(stdout.lines().next()?.trim().to_owned())
+    });
+    Tool {
+        name,
+        path,
+        version,
+        source,
+    }
+}
+
+fn find_on_path(name: &str) -> Option<PathBuf> {
+    let file_name = OsString::from(format!("{name}{}", env::consts::EXE_SUFFIX));
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = |path: &Path| path.to_string_lossy().into_owned();
+        writeln!(f, "generator_version: {}", self.generator_version)?;
+        writeln!(f, "spec_path: {}", path(&self.spec_path))?;
+        writeln!(f, "spec_dir: {}", path(&self.spec_dir))?;
+        writeln!(f, "schema: {}", self.schema)?;
+        writeln!(f, "output_dir: {}", path(&self.output_dir))?;
+        writeln!(f, "lock_path: {}", path(&self.lock_path))?;
+        writeln!(
+            f,
+            "templates_dir: {}",
+            self.templates_dir
+                .as_deref()
+                .map_or("(embedded)".to_owned(), path)
+        )?;
+        writeln!(f, "fmt: {}", self.fmt)?;
+        writeln!(f, "offline: {}", self.offline)?;
+        writeln!(f, "remote_cache_dir: {}", path(&self.remote_cache_dir))?;
+        writeln!(f, "verify_target_dir: {}", path(&self.verify_target_dir))?;
+        writeln!(f, "tools:")?;
+        for tool in &self.tools {
+            match &tool.path {
+                Some(tool_path) => writeln!(
+                    f,
+                    "  {}: {}{}{}",
+                    tool.name,
+                    path(tool_path),
+                    tool.source
+                        .map_or(String::new(), |x| format!(" (from {x})")),
+                    tool.version
+                        .as_deref()
+                        .map_or(String::new(), |x| format!(", {x}"))
+                )?,
+                None => writeln!(f, "  {}: not found", tool.name)?,
+            }
+        }
+        writeln!(f, "versions:")?;
+        for (name, version) in &self.versions {
+            writeln!(f, "  {name}: {version}")?;
+        }
+        writeln!(f, "packages:")?;
+        for package in &self.packages {
+            writeln!(
+                f,
+                "  {} {}: {} ({})",
+                package.name,
+                package.version,
+                path(&package.path),
+                package.proto_package_name
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+//! Process exit codes, so CI pipelines can branch on the kind of failure.
+//! Command line usage errors exit with 2, as reported by clap.
+
+use std::{io, process::ExitCode};
+
+use appbiotic_api_protogen_spec::SpecError;
+
+/// Any failure not covered by a more specific code, e.g. `protoc` errors.
+pub const FAILURE: u8 = 1;
+/// Generated files differ from the working copy (`check`, `--dry-run`).
+pub const DRIFT: u8 = 3;
+/// The spec could not be loaded or is invalid.
+pub const SPEC: u8 = 4;
+/// Reading or writing files or running external tools failed.
+pub const IO: u8 = 5;
+/// Protos changed in a way that breaks existing clients (`breaking`).
+pub const BREAKING: u8 = 6;
+/// The generator itself panicked, which is a bug.
+pub const INTERNAL: u8 = 70;
+
+pub fn drift() -> ExitCode {
+    ExitCode::from(DRIFT)
+}
+
+pub fn breaking() -> ExitCode {
+    ExitCode::from(BREAKING)
+}
+
+pub fn spec() -> ExitCode {
+    ExitCode::from(SPEC)
+}
+
+/// The exit code for a command that failed with `error`.
+pub fn for_error(error: &anyhow::Error) -> ExitCode {
+    if error.chain().any(|x| x.is::<SpecError>()) {
+        ExitCode::from(SPEC)
+    } else if error.chain().any(|x| x.is::<io::Error>()) {
+        ExitCode::from(IO)
+    } else {
+        ExitCode::from(FAILURE)
+    }
+}
@@ -0,0 +1,1639 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use anyhow::{bail, ensure, Context};
+use appbiotic_api_protogen_fetch::protofetch::ProtofetchManifest;
+use appbiotic_api_protogen_spec::{
+    paths::{relative_path, resolve_path},
+    DependencyLock, ProtogenSpec, Publish, RustPackage,
+};
+use handlebars::Handlebars;
+use heck::{ToShoutySnakeCase, ToSnakeCase, ToUpperCamelCase};
+use serde_json::json;
+use tracing::debug_span;
+
+use crate::{
+    descriptor::{compile_package_descriptors, package_descriptor_sha256},
+    manifest::{CargoBin, CargoLib, CargoManifest, CargoPackage, CargoPackageDep},
+    output::read_existing,
+    output_manifest::content_hash,
+    provenance::{package_provenance, TemplateSource, PROVENANCE_FILE_NAME},
+    rustfmt::format_files,
+};
+
+const TOKIO_VERSION: &str = "1.44.2";
+const CRITERION_VERSION: &str = "0.5.1";
+const TONIC_HEALTH_VERSION: &str = "0.13.1";
+const TONIC_REFLECTION_VERSION: &str = "0.13.1";
+const SCHEMARS_VERSION: &str = "1.2.3";
+const UTOIPA_VERSION: &str = "6.0.0";
+const PROPTEST_VERSION: &str = "1.12.0";
+const PROPTEST_DERIVE_VERSION: &str = "0.9.0";
+
+/// A file rendered by the generator, not yet written to disk.
+#[derive(Clone)]
+pub struct GeneratedFile {
+    pub path: PathBuf,
+    pub contents: String,
+    /// Scaffold files are only written when they do not exist yet, so they
+    /// can be customized, while managed files are overwritten on every run.
+    pub scaffold: bool,
+}
+
+#[derive(Default)]
+pub struct GenerateOptions {
+    pub protofetch_path: Option<PathBuf>,
+    /// Inherit registry dependencies from `[workspace.dependencies]` instead
+    /// of pinning versions in each package manifest.
+    pub workspace: bool,
+    /// Directory whose `<name>.hbs` templates override the embedded ones.
+    pub templates_dir: Option<PathBuf>,
+    /// Directory generated files are written below instead of the spec
+    /// directory. Paths below the spec directory are kept, as are file
+    /// contents, so the output can be compared with the working copy.
+    pub output_root: Option<PathBuf>,
+    /// Overrides `license` of the spec.
+    pub license: Option<String>,
+    /// Overrides `license_files` of the spec when not empty. Relative paths
+    /// are resolved against the working directory.
+    pub license_files: Vec<PathBuf>,
+    /// Generated build scripts read the spec of their package written next to
+    /// them instead of the spec file, e.g. when the spec was read from
+    /// standard input.
+    pub embed_package_spec: bool,
+    /// Formats generated Rust sources with `rustfmt`, also enabled by `fmt`
+    /// of the spec.
+    pub fmt: bool,
+}
+
+impl GenerateOptions {
+    /// Directory outputs of the spec in `spec_dir` are written below.
+    pub fn output_dir(&self, spec_dir: &Path) -> anyhow::Result<PathBuf> {
+        match &self.output_root {
+            Some(output_root) => Ok(resolve_path(&env::current_dir()?, output_root)?),
+            None => Ok(spec_dir.to_owned()),
+        }
+    }
+
+    /// Moves `files` rendered for the spec in `spec_dir` below the output
+    /// root, if any.
+    pub fn rebase(
+        &self,
+        spec_dir: &Path,
+        files: Vec<GeneratedFile>,
+    ) -> anyhow::Result<Vec<GeneratedFile>> {
+        if self.output_root.is_none() {
+            return Ok(files);
+        }
+        let spec_dir = spec_dir.canonicalize().with_context(|| {
+            format!(
+                "Failed to canonicalize spec directory `{}`",
+                spec_dir.to_string_lossy()
+            )
+        })?;
+        let output_dir = self.output_dir(&spec_dir)?;
+        files
+            .into_iter()
+            .map(|file| {
+                let path = file.path.strip_prefix(&spec_dir).with_context(|| {
+                    format!(
+                        "Generated file `{}` is outside of the spec directory and cannot be written below an output root",
+                        file.path.to_string_lossy()
+                    )
+                })?;
+                Ok(GeneratedFile {
+                    path: output_dir.join(path),
+                    ..file
+                })
+            })
+            .collect()
+    }
+}
+
+/// Renders the files of each of `packages`, which must not depend on each
+/// other, rendering up to `jobs` packages at a time. Returns the files in the
+/// order of `packages`.
+pub fn generate_packages(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    packages: &[&RustPackage],
+    options: &GenerateOptions,
+    jobs: NonZeroUsize,
+) -> anyhow::Result<Vec<Vec<GeneratedFile>>> {
+    let next = AtomicUsize::new(0);
+    let mut generated: Vec<(usize, anyhow::Result<Vec<GeneratedFile>>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.get().min(packages.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut generated = Vec::new();
+                    // A worker stops at its first failure, which comes before
+                    // any package left unrendered in the order of `packages`.
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(package) = packages.get(index) else {
+                            break;
+                        };
+                        let files =
+                            generate_package(protogen, protogen_path, &package.name, options);
+                        let failed = files.is_err();
+                        generated.push((index, files));
+                        if failed {
+                            break;
+                        }
+                    }
+                    generated
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| match worker.join() {
+                Ok(generated) => generated,
+                Err(panic) => std::panic::resume_unwind(panic),
+            })
+            .collect()
+    });
+    generated.sort_by_key(|(index, _)| *index);
+    generated.into_iter().map(|(_, files)| files).collect()
+}
+
+/// Renders every file of the package named `package_name`. `protogen_path`
+/// must be absolute.
+pub fn generate_package(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    package_name: &str,
+    options: &GenerateOptions,
+) -> anyhow::Result<Vec<GeneratedFile>> {
+    let _span = debug_span!("generate", package = package_name).entered();
+    let package_spec = protogen.package(package_name)?;
+
+    // The features of inherited options enable those of the dependencies, see
+    // below.
+    if let Some((dependency, option)) = protogen.missing_inherited_options(package_spec).first() {
+        bail!(
+            "Package `{package_name}` sets `{option}`, which dependency `{dependency}` must set too"
+        );
+    }
+
+    ensure!(
+        package_spec.path.is_relative(),
+        "Package spec path for `{}` was not relative",
+        package_spec.name
+    );
+
+    let spec_dir = protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")?;
+    let mut normalized = protogen.clone();
+    normalized.normalize_paths(spec_dir).with_context(|| {
+        format!(
+            "Failed to normalize paths relative to `{}`",
+            spec_dir.to_string_lossy()
+        )
+    })?;
+    let package_path = normalized
+        .rust_package(&package_spec.name)
+        .map(|x| x.path.to_owned())
+        .context("Expected normalized package spec")?;
+    let spec_dir = spec_dir.canonicalize()?;
+    let package_spec_src_path = package_path.join("src");
+
+    let rel_protogen_path = relative_path(&package_path, &spec_dir).join(
+        protogen_path
+            .file_name()
+            .context("Expected file_name from protogen_path")?,
+    );
+
+    let mut include_dirs: Vec<PathBuf> = Vec::new();
+    let protofetch = match &options.protofetch_path {
+        Some(protofetch_path) => Some(ProtofetchManifest::load(protofetch_path)?),
+        None => None,
+    };
+    if let Some(protofetch) = &protofetch {
+        protofetch.verify()?;
+        let protofetch_include_dir = resolve_path(&env::current_dir()?, &protofetch.include_dir())?;
+        include_dirs.push(relative_path(&package_path, &protofetch_include_dir));
+    }
+
+    let license_files = read_license_files(protogen, &spec_dir, options)?;
+    let license = options.license.as_ref().or(protogen.license.as_ref());
+    let mut manifest = package_manifest(protogen, package_spec)?;
+    set_license(&mut manifest, license, &license_files);
+    if options.workspace {
+        inherit_workspace_dependencies(&mut manifest);
+    }
+
+    // Generated dependencies keep their version and registry next to the path
+    // so the package can still be published.
+    let normalized_package = normalized.package(&package_spec.name)?;
+    for dependency_name in &package_spec.protogen_dependencies {
+        let dependency = normalized.package(dependency_name)?;
+        manifest.dependencies.insert(
+            dependency.name.to_owned(),
+            CargoPackageDep {
+                version: dependency.version.to_owned(),
+                optional: false,
+                default_features: None,
+                features: Vec::new(),
+                workspace: false,
+                path: Some(relative_path(&package_path, &dependency.path)),
+                registry: dependency.registry.to_owned(),
+            },
+        );
+        // Messages of dependencies embedded in the package need their
+        // schemas and strategies too. Dependencies set the same options, so
+        // they have the same features.
+        for feature in ["schemars", "utoipa", "proptest"] {
+            if let Some(features) = manifest.features.get_mut(feature) {
+                features.push(format!("{dependency_name}/{feature}"));
+            }
+        }
+    }
+    for dependency in &normalized_package.api_dependencies {
+        manifest.dependencies.insert(
+            dependency.name.to_owned(),
+            CargoPackageDep {
+                version: dependency.version.to_owned(),
+                optional: false,
+                default_features: None,
+                features: Vec::new(),
+                workspace: false,
+                path: dependency
+                    .path
+                    .as_ref()
+                    .map(|x| relative_path(&package_path, x)),
+                registry: dependency.registry.to_owned(),
+            },
+        );
+    }
+
+    let api = if package_spec.smoke_tests
+        || package_spec.examples
+        || package_spec.benches
+        || package_spec.ffi.is_some()
+    {
+        Some(package_api(protogen, protogen_path, package_spec)?)
+    } else {
+        None
+    };
+    if let Some(api) = api.as_ref().filter(|_| package_spec.examples) {
+        let examples = (!api.messages.is_empty())
+            .then_some(("serde", &["prost-serde"][..]))
+            .into_iter()
+            .chain(
+                api.services
+                    .iter()
+                    .map(|x| (x.module.as_str(), &["tonic-client", "transport"][..])),
+            );
+        for (example, required_features) in examples {
+            manifest.example.push(CargoBin {
+                name: example.to_owned(),
+                path: PathBuf::from("examples").join(format!("{example}.rs")),
+                required_features: required_features.iter().map(|x| x.to_string()).collect(),
+                harness: None,
+            });
+        }
+    }
+
+    let manifest_path = package_path.join("Cargo.toml");
+    let mut manifest_contents = manifest_toml(&manifest)?;
+    if let Some(existing) = read_existing(&manifest_path)? {
+        manifest_contents =
+            merge_manifest_toml(&existing, &manifest_contents).with_context(|| {
+                format!(
+                    "Failed to merge existing package manifest at path `{}`",
+                    manifest_path.to_string_lossy()
+                )
+            })?;
+    }
+
+    let mut files = vec![
+        GeneratedFile {
+            path: manifest_path,
+            contents: manifest_contents,
+            scaffold: false,
+        },
+        GeneratedFile {
+            path: package_spec_src_path.join("package_spec.json"),
+            contents: serde_json::to_string_pretty(&package_spec)
+                .context("Failed to serialize package spec")?,
+            scaffold: false,
+        },
+    ];
+    // Build scripts compare the descriptors their dependencies were built
+    // from with those the package was generated against.
+    if !package_spec.protogen_dependencies.is_empty() {
+        let mut lock = DependencyLock::default();
+        for dependency_name in &package_spec.protogen_dependencies {
+            lock.descriptor_sha256.insert(
+                dependency_name.to_owned(),
+                package_descriptor_sha256(protogen, protogen_path, dependency_name)?,
+            );
+        }
+        files.push(GeneratedFile {
+            path: package_path.join(DependencyLock::FILE_NAME),
+            contents: serde_json::to_string_pretty(&lock)
+                .context("Failed to serialize dependency lock")?,
+            scaffold: false,
+        });
+    }
+    for (file_name, contents) in &license_files {
+        files.push(GeneratedFile {
+            path: package_path.join(file_name),
+            contents: contents.to_owned(),
+            scaffold: false,
+        });
+    }
+
+    if let Some(templates_dir) = &options.templates_dir {
+        ensure!(
+            templates_dir.is_dir(),
+            "Templates directory `{}` does not exist",
+            templates_dir.to_string_lossy()
+        );
+    }
+    let mut handlebars = Handlebars::new();
+    let mut template_sources = BTreeMap::new();
+
+    {
+        let _span = debug_span!("templates").entered();
+        let templates = [
+            ("lib.rs", include_str!("templates/lib.rs.hbs")),
+            ("build.rs", include_str!("templates/build.rs.hbs")),
+            (
+                "prost_serde.rs",
+                include_str!("templates/prost_serde.rs.hbs"),
+            ),
+            ("bin.rs", include_str!("templates/bin.rs.hbs")),
+            ("README.md", include_str!("templates/README.md.hbs")),
+            ("smoke.rs", include_str!("templates/smoke.rs.hbs")),
+            (
+                "serde_example.rs",
+                include_str!("templates/serde_example.rs.hbs"),
+            ),
+            (
+                "client_example.rs",
+                include_str!("templates/client_example.rs.hbs"),
+            ),
+            (
+                "codec_bench.rs",
+                include_str!("templates/codec_bench.rs.hbs"),
+            ),
+            ("ffi_lib.rs", include_str!("templates/ffi_lib.rs.hbs")),
+            ("ffi.h", include_str!("templates/ffi.h.hbs")),
+        ];
+        for (name, tpl_str) in templates {
+            let override_path = options
+                .templates_dir
+                .as_ref()
+                .map(|x| x.join(format!("{name}.hbs")))
+                .filter(|x| x.is_file());
+            let source = match override_path {
+                Some(path) => {
+                    handlebars
+                        .register_template_file(name, &path)
+                        .with_context(|| {
+                            format!(
+                                "Failed to register template `{name}` from path `{}`",
+                                path.to_string_lossy()
+                            )
+                        })?;
+                    TemplateSource::file(name, &path, &spec_dir)?
+                }
+                None => {
+                    handlebars
+                        .register_template_string(name, tpl_str)
+                        .with_context(|| format!("Failed to register template `{name}`"))?;
+                    TemplateSource::embedded(name, tpl_str)
+                }
+            };
+            template_sources.insert(name, source);
+        }
+    }
+
+    // `build.rs` and `prost_serde.rs` are managed, while `lib.rs` and the
+    // README are scaffolded for crates to add their own code and docs.
+    let mut outputs = vec![
+        (
+            "build.rs",
+            json!({
+                "rel_protogen_path": (!options.embed_package_spec)
+                    .then(|| rel_protogen_path.to_string_lossy().into_owned()),
+                "include_dirs": include_dirs
+                    .iter()
+                    .map(|x| x.to_string_lossy())
+                    .collect::<Vec<_>>(),
+                "dependencies": package_spec
+                    .protogen_dependencies
+                    .iter()
+                    .chain(package_spec.api_dependencies.iter().map(|x| &x.name))
+                    .collect::<Vec<_>>(),
+            }),
+            package_path.join("build.rs"),
+            false,
+        ),
+        (
+            "lib.rs",
+            json!({}),
+            package_spec_src_path.join("lib.rs"),
+            true,
+        ),
+        (
+            "prost_serde.rs",
+            json!({}),
+            package_spec_src_path.join("prost_serde.rs"),
+            false,
+        ),
+    ];
+
+    let default_features = manifest.features.get("default");
+    outputs.push((
+        "README.md",
+        json!({
+            "name": package_spec.name,
+            "crate_ident": package_spec.name.replace('-', "_"),
+            "description": package_spec.description,
+            "proto_package_name": package_spec.proto_package_name,
+            "protogen_file_name": protogen_path
+                .file_name()
+                .context("Expected file_name from protogen_path")?
+                .to_string_lossy(),
+            "features": manifest
+                .features
+                .keys()
+                .filter(|x| x.ne(&"default"))
+                .map(|x| json!({
+                    "name": x,
+                    "default": default_features.is_some_and(|default| default.contains(x)),
+                    "description": feature_description(x),
+                }))
+                .collect::<Vec<_>>(),
+            "bins": package_spec
+                .bins
+                .iter()
+                .map(|x| json!({
+                    "name": x.name,
+                    "services": x.services,
+                    "addr_env": bin_addr_env(&x.name),
+                    "port": x.port,
+                }))
+                .collect::<Vec<_>>(),
+        }),
+        package_path.join("README.md"),
+        true,
+    ));
+
+    if let Some(api) = &api {
+        let crate_ident = package_spec.name.replace('-', "_");
+        if package_spec.smoke_tests {
+            outputs.push((
+                "smoke.rs",
+                json!({
+                    "crate_ident": crate_ident,
+                    "messages": api.messages,
+                    "services": api.services,
+                }),
+                package_path.join("tests").join("smoke.rs"),
+                false,
+            ));
+        }
+        if package_spec.benches {
+            outputs.push((
+                "codec_bench.rs",
+                json!({
+                    "crate_ident": crate_ident,
+                    "messages": api.messages,
+                }),
+                package_path.join("benches").join("codec.rs"),
+                false,
+            ));
+        }
+        if package_spec.examples {
+            let examples_path = package_path.join("examples");
+            if !api.messages.is_empty() {
+                outputs.push((
+                    "serde_example.rs",
+                    json!({
+                        "crate_ident": crate_ident,
+                        "proto_package_name": package_spec.proto_package_name,
+                        "messages": api.messages,
+                    }),
+                    examples_path.join("serde.rs"),
+                    false,
+                ));
+            }
+            for service in &api.services {
+                outputs.push((
+                    "client_example.rs",
+                    json!({
+                        "crate_ident": crate_ident,
+                        "service": service,
+                    }),
+                    examples_path.join(format!("{}.rs", service.module)),
+                    false,
+                ));
+            }
+        }
+    }
+
+    // Bins hold hand-written service implementations, so they are only
+    // scaffolded once.
+    for bin in &package_spec.bins {
+        let services = bin
+            .services
+            .iter()
+            .map(|x| {
+                let name = x
+                    .strip_prefix(&format!("{}.", package_spec.proto_package_name))
+                    .unwrap_or(x);
+                ensure!(
+                    !name.contains('.'),
+                    "Service `{x}` of bin `{}` is not in proto package `{}`",
+                    bin.name,
+                    package_spec.proto_package_name
+                );
+                Ok(json!({
+                    "name": name,
+                    "module": format!("{}_server", name.to_snake_case()),
+                }))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        outputs.push((
+            "bin.rs",
+            json!({
+                "bin_name": bin.name,
+                "crate_ident": package_spec.name.replace('-', "_"),
+                "addr_env": bin_addr_env(&bin.name),
+                "port": bin.port,
+                "services": services,
+            }),
+            package_spec_src_path
+                .join("bin")
+                .join(format!("{}.rs", bin.name)),
+            true,
+        ));
+    }
+
+    if let (Some(ffi), Some(api)) = (&package_spec.ffi, &api) {
+        let messages = ffi
+            .messages
+            .iter()
+            .map(|x| {
+                let name = x
+                    .strip_prefix(&format!("{}.", package_spec.proto_package_name))
+                    .unwrap_or(x);
+                api.messages
+                    .iter()
+                    .find(|message| message.proto_name.eq(name))
+                    .with_context(|| {
+                        format!(
+                            "FFI message `{x}` is not a top-level message of proto package `{}`",
+                            package_spec.proto_package_name
+                        )
+                    })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let ffi_path = normalized_package.ffi_path();
+        let prefix = package_spec.name.replace('-', "_");
+        let data = json!({
+            "name": package_spec.name,
+            "crate_ident": prefix,
+            "prefix": prefix,
+            "upper_prefix": prefix.to_uppercase(),
+            "messages": messages,
+        });
+        let ffi_manifest_path = ffi_path.join("Cargo.toml");
+        let mut ffi_manifest = ffi_manifest(protogen, package_spec)?;
+        set_license(&mut ffi_manifest, license, &license_files);
+        if options.workspace {
+            inherit_workspace_dependencies(&mut ffi_manifest);
+        }
+        let mut contents = manifest_toml(&ffi_manifest)?;
+        if let Some(existing) = read_existing(&ffi_manifest_path)? {
+            contents = merge_manifest_toml(&existing, &contents).with_context(|| {
+                format!(
+                    "Failed to merge existing FFI manifest at path `{}`",
+                    ffi_manifest_path.to_string_lossy()
+                )
+            })?;
+        }
+        files.push(GeneratedFile {
+            path: ffi_manifest_path,
+            contents,
+            scaffold: false,
+        });
+        for (file_name, contents) in &license_files {
+            files.push(GeneratedFile {
+                path: ffi_path.join(file_name),
+                contents: contents.to_owned(),
+                scaffold: false,
+            });
+        }
+        outputs.push((
+            "ffi_lib.rs",
+            data.clone(),
+            ffi_path.join("src").join("lib.rs"),
+            false,
+        ));
+        outputs.push((
+            "ffi.h",
+            data,
+            ffi_path.join("include").join(format!("{prefix}.h")),
+            false,
+        ));
+    }
+
+    // Every generated crate records what it was generated from.
+    let templates = outputs
+        .iter()
+        .filter_map(|(name, ..)| template_sources.remove(name))
+        .collect();
+    let provenance = package_provenance(
+        normalized_package,
+        &spec_dir,
+        protofetch.as_ref(),
+        templates,
+    )?;
+    let provenance =
+        serde_json::to_string_pretty(&provenance).context("Failed to serialize provenance")? + "\n";
+    files.push(GeneratedFile {
+        path: package_path.join(PROVENANCE_FILE_NAME),
+        contents: provenance.to_owned(),
+        scaffold: false,
+    });
+    if package_spec.ffi.is_some() {
+        files.push(GeneratedFile {
+            path: normalized_package.ffi_path().join(PROVENANCE_FILE_NAME),
+            contents: provenance,
+            scaffold: false,
+        });
+    }
+
+    let _span = debug_span!("render", templates = outputs.len()).entered();
+    for (name, data, path, scaffold) in outputs {
+        let contents = handlebars.render(name, &data).with_context(|| {
+            format!(
+                "Failed to render {name} template for path `{}`",
+                path.to_string_lossy()
+            )
+        })?;
+        files.push(GeneratedFile {
+            path,
+            contents,
+            scaffold,
+        });
+    }
+
+    if options.fmt || protogen.fmt {
+        format_files(&spec_dir, &manifest.package.edition, &mut files)?;
+    }
+    options.rebase(&spec_dir, files)
+}
+
+/// Top-level messages and services of a package, as named in Rust.
+#[derive(serde::Serialize)]
+struct PackageApi {
+    messages: Vec<MessageApi>,
+    services: Vec<ServiceApi>,
+}
+
+#[derive(serde::Serialize)]
+struct MessageApi {
+    name: String,
+    snake_name: String,
+    /// Message name as declared in the proto file.
+    proto_name: String,
+}
+
+#[derive(serde::Serialize)]
+struct ServiceApi {
+    name: String,
+    /// Module of the generated client, e.g. `foo_service_client`.
+    module: String,
+    client: String,
+    /// Environment variable the client example reads the server URL from.
+    url_env: String,
+    /// Server URL the client example connects to by default.
+    default_url: String,
+    /// A unary method taking a message of the package, called by the client
+    /// example.
+    example_method: Option<MethodApi>,
+}
+
+#[derive(serde::Serialize)]
+struct MethodApi {
+    name: String,
+    input: String,
+}
+
+/// Finds the top-level messages and services of the package by compiling its
+/// protos.
+fn package_api(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    package_spec: &RustPackage,
+) -> anyhow::Result<PackageApi> {
+    let fds = compile_package_descriptors(protogen, protogen_path, &package_spec.name)
+        .context("Failed to compile protos for smoke tests and examples")?;
+    let _span = debug_span!("descriptors").entered();
+    let type_prefix = format!(".{}.", package_spec.proto_package_name);
+    let mut api = PackageApi {
+        messages: Vec::new(),
+        services: Vec::new(),
+    };
+    for file in fds
+        .file
+        .iter()
+        .filter(|x| x.package() == package_spec.proto_package_name)
+    {
+        for message in &file.message_type {
+            api.messages.push(MessageApi {
+                name: message.name().to_upper_camel_case(),
+                snake_name: message.name().to_snake_case(),
+                proto_name: message.name().to_owned(),
+            });
+        }
+        for service in &file.service {
+            // Clients default to the port of the bin serving the service.
+            let port = package_spec
+                .bins
+                .iter()
+                .find(|bin| {
+                    bin.services.iter().any(|x| {
+                        x.eq(service.name())
+                            || x.eq(&format!(
+                                "{}.{}",
+                                package_spec.proto_package_name,
+                                service.name()
+                            ))
+                    })
+                })
+                .map_or(50051, |bin| bin.port);
+            let example_method = service
+                .method
+                .iter()
+                .filter(|x| !x.client_streaming() && !x.server_streaming())
+                .find_map(|x| {
+                    let input = x.input_type().strip_prefix(&type_prefix)?;
+                    (!input.contains('.')).then(|| MethodApi {
+                        name: x.name().to_snake_case(),
+                        input: input.to_upper_camel_case(),
+                    })
+                });
+            api.services.push(ServiceApi {
+                name: service.name().to_owned(),
+                module: format!("{}_client", service.name().to_snake_case()),
+                client: format!("{}Client", service.name().to_upper_camel_case()),
+                url_env: format!("{}_URL", service.name().to_shouty_snake_case()),
+                default_url: format!("http://[::1]:{port}"),
+                example_method,
+            });
+        }
+    }
+    Ok(api)
+}
+
+/// Hashes everything the output of `generate_package` depends on besides
+/// existing files in the package: the generator version, the spec entries of
+/// the package and its dependencies, the descriptors of its dependencies,
+/// `options`, and the contents of license files, templates, the protofetch
+/// manifest and lock, and local proto files including those of import paths.
+/// Remote proto sources are only covered by their spec entry.
+pub fn package_inputs_hash(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    package_name: &str,
+    options: &GenerateOptions,
+) -> anyhow::Result<String> {
+    let package_spec = protogen.package(package_name)?;
+    let spec_dir = protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")?;
+    let dependencies = package_spec
+        .protogen_dependencies
+        .iter()
+        .map(|x| protogen.package(x))
+        .collect::<Result<Vec<_>, _>>()?;
+    let spec = json!({
+        "generator": env!("CARGO_PKG_VERSION"),
+        "package": package_spec,
+        "dependencies": dependencies,
+        "versions": protogen.versions,
+        "license": protogen.license,
+        "license_files": protogen.license_files,
+        "fmt": protogen.fmt,
+        "options": {
+            "protofetch_path": options.protofetch_path,
+            "workspace": options.workspace,
+            "templates_dir": options.templates_dir,
+            "license": options.license,
+            "license_files": options.license_files,
+            "embed_package_spec": options.embed_package_spec,
+            "fmt": options.fmt,
+        },
+    });
+    let mut inputs = serde_json::to_vec(&spec).context("Failed to serialize package inputs")?;
+    // The dependency lock records the descriptors of the dependencies, which
+    // change with their protos.
+    for dependency in &dependencies {
+        inputs
+            .extend(package_descriptor_sha256(protogen, protogen_path, &dependency.name)?.bytes());
+    }
+
+    let mut paths = Vec::new();
+    if let Some(protofetch_path) = &options.protofetch_path {
+        paths.push(protofetch_path.to_owned());
+        // Locked commits are recorded in the provenance of the package.
+        let lock_path = ProtofetchManifest::load(protofetch_path)?.lock_path();
+        if lock_path.is_file() {
+            paths.push(lock_path);
+        }
+    }
+    if let Some(templates_dir) = &options.templates_dir {
+        list_files(templates_dir, "hbs", &mut paths)?;
+    }
+    let mut normalized = protogen.clone();
+    normalized.normalize_paths(spec_dir)?;
+    let normalized_package = normalized.package(package_name)?;
+    let proto_dirs = normalized_package
+        .protos
+        .iter()
+        .filter(|x| !x.is_remote())
+        .map(|x| &x.dir)
+        .chain(
+            normalized_package
+                .includes
+                .iter()
+                .filter(|x| !x.is_remote())
+                .map(|x| &x.dir),
+        )
+        .chain(normalized_package.import_paths.values());
+    for dir in proto_dirs {
+        list_files(dir, "proto", &mut paths)?;
+    }
+    let file_contents = read_license_files(protogen, &spec_dir.canonicalize()?, options)?
+        .into_iter()
+        .map(|(_, contents)| Ok(contents.into_bytes()))
+        .chain(paths.iter().map(|path| {
+            std::fs::read(path).with_context(|| {
+                format!("Failed to read file at path `{}`", path.to_string_lossy())
+            })
+        }));
+    for contents in file_contents {
+        let contents = contents?;
+        inputs.extend((contents.len() as u64).to_le_bytes());
+        inputs.extend(contents);
+    }
+    Ok(content_hash(&inputs))
+}
+
+/// Appends the files below `dir` with `extension` to `paths`, sorted so the
+/// order does not depend on the file system. A missing `dir` has no files.
+pub fn list_files(dir: &Path, extension: &str, paths: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => {
+            return Err(error).with_context(|| {
+                format!(
+                    "Failed to read directory at path `{}`",
+                    dir.to_string_lossy()
+                )
+            })
+        }
+    };
+    let mut entries = entries
+        .map(|x| x.map(|x| x.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            list_files(&path, extension, paths)?;
+        } else if path.extension().is_some_and(|x| x == extension) {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Reads the license files copied into generated crates, returning their
+/// file names and contents. Files given on the command line replace those of
+/// the spec in `spec_dir`.
+fn read_license_files(
+    protogen: &ProtogenSpec,
+    spec_dir: &Path,
+    options: &GenerateOptions,
+) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let (base_dir, paths) = if options.license_files.is_empty() {
+        (spec_dir.to_owned(), &protogen.license_files)
+    } else {
+        (env::current_dir()?, &options.license_files)
+    };
+    paths
+        .iter()
+        .map(|x| {
+            let path = resolve_path(&base_dir, x)?;
+            let file_name = path
+                .file_name()
+                .with_context(|| {
+                    format!(
+                        "Expected file name in license file path `{}`",
+                        x.to_string_lossy()
+                    )
+                })?
+                .into();
+            let contents = std::fs::read_to_string(&path).with_context(|| {
+                format!(
+                    "Failed to read license file at path `{}`",
+                    path.to_string_lossy()
+                )
+            })?;
+            Ok((file_name, contents))
+        })
+        .collect()
+}
+
+/// Sets the license fields of a generated manifest. `license-file` is only
+/// set without a `license` expression, as Cargo expects one or the other.
+fn set_license(
+    manifest: &mut CargoManifest,
+    license: Option<&String>,
+    license_files: &[(PathBuf, String)],
+) {
+    manifest.package.license = license.cloned();
+    manifest.package.license_file = match license {
+        Some(_) => None,
+        None => license_files
+            .first()
+            .map(|(file_name, _)| file_name.to_owned()),
+    };
+}
+
+/// Inherits the registry dependencies of `manifest` from
+/// `[workspace.dependencies]`.
+fn inherit_workspace_dependencies(manifest: &mut CargoManifest) {
+    for dep in manifest
+        .dependencies
+        .values_mut()
+        .chain(manifest.build_dependencies.values_mut())
+        .chain(manifest.dev_dependencies.values_mut())
+        .filter(|x| x.is_shareable())
+    {
+        dep.version.clear();
+        dep.workspace = true;
+    }
+}
+
+/// Builds the Cargo manifest of the FFI crate of a package, a `cdylib`
+/// depending on the package's messages.
+pub fn ffi_manifest(
+    protogen: &ProtogenSpec,
+    package_spec: &RustPackage,
+) -> anyhow::Result<CargoManifest> {
+    let package_manifest = package_manifest(protogen, package_spec)?;
+    let mut dependencies = BTreeMap::new();
+    dependencies.insert(
+        package_spec.name.to_owned(),
+        CargoPackageDep {
+            version: package_spec.version.to_owned(),
+            optional: false,
+            default_features: Some(false),
+            features: vec!["prost-serde".to_owned()],
+            workspace: false,
+            path: Some(
+                Path::new("..").join(
+                    package_spec
+                        .path
+                        .file_name()
+                        .context("Expected file_name from package path")?,
+                ),
+            ),
+            registry: package_spec.registry.to_owned(),
+        },
+    );
+    for (name, dependency) in [
+        ("prost", package_manifest.dependencies.get("prost")),
+        (
+            "serde_json",
+            package_manifest.build_dependencies.get("serde_json"),
+        ),
+    ] {
+        let dependency =
+            dependency.with_context(|| format!("Expected {name} in package template"))?;
+        dependencies.insert(
+            name.to_owned(),
+            CargoPackageDep {
+                version: dependency.version.to_owned(),
+                optional: false,
+                default_features: None,
+                features: Vec::new(),
+                workspace: false,
+                path: None,
+                registry: None,
+            },
+        );
+    }
+    Ok(CargoManifest {
+        package: CargoPackage {
+            name: package_spec.ffi_name(),
+            version: package_spec.version.to_owned(),
+            edition: package_manifest.package.edition,
+            description: Some(format!("C ABI over messages of `{}`", package_spec.name)),
+            publish: Some(Publish::Enabled(false)),
+            ..Default::default()
+        },
+        lib: Some(CargoLib {
+            crate_type: vec!["cdylib".to_owned()],
+        }),
+        dependencies,
+        ..Default::default()
+    })
+}
+
+/// Environment variable a generated bin reads its listen address from.
+fn bin_addr_env(bin_name: &str) -> String {
+    format!("{}_ADDR", bin_name.to_shouty_snake_case())
+}
+
+/// Serializes the manifest with a `##` doc comment above every feature, which
+/// `document-features` renders into the crate documentation.
+fn manifest_toml(manifest: &CargoManifest) -> anyhow::Result<String> {
+    let contents =
+        toml::to_string_pretty(manifest).context("Failed to serialize cargo manifest toml")?;
+    let mut document = contents
+        .parse::<toml_edit::DocumentMut>()
+        .context("Failed to parse serialized cargo manifest toml")?;
+    if let Some(features) = document
+        .get_mut("features")
+        .and_then(toml_edit::Item::as_table_mut)
+    {
+        let names: Vec<String> = features.iter().map(|(x, _)| x.to_owned()).collect();
+        for name in names.iter().filter(|x| x.ne(&"default")) {
+            if let Some(mut key) = features.key_mut(name) {
+                key.leaf_decor_mut()
+                    .set_prefix(format!("## {}\n", feature_description(name)));
+            }
+        }
+    }
+    let mut entries = toml_edit::Array::new();
+    for entry in manifest_entries(&document)
+        .into_iter()
+        .chain([format!("package.metadata.{GENERATED_ENTRIES_METADATA}")])
+    {
+        entries.push_formatted(toml_edit::Value::from(entry).decorated("\n    ", ""));
+    }
+    entries.set_trailing_comma(true);
+    entries.set_trailing("\n");
+    let mut generated = toml_edit::Table::new();
+    generated.insert("generated", toml_edit::value(entries));
+    document["package"]
+        .as_table_mut()
+        .context("Expected `package` of serialized cargo manifest to be a table")?
+        .entry("metadata")
+        .or_insert_with(|| {
+            let mut table = toml_edit::Table::new();
+            table.set_implicit(true);
+            toml_edit::Item::Table(table)
+        })
+        .as_table_mut()
+        .context("Expected `package.metadata` of serialized cargo manifest to be a table")?
+        .insert(
+            GENERATED_ENTRIES_METADATA,
+            toml_edit::Item::Table(generated),
+        );
+    Ok(document.to_string())
+}
+
+/// Table of `[package.metadata]` listing the entries of a package manifest
+/// written by the generator, so that merging can tell them from entries added
+/// by hand.
+pub const GENERATED_ENTRIES_METADATA: &str = "protogen";
+
+/// Entries of a manifest as merged by [merge_manifest_toml]: the keys of each
+/// table, of `[package.metadata]` and the names of targets such as `[[bin]]`,
+/// each prefixed with the name of its table.
+fn manifest_entries(document: &toml_edit::DocumentMut) -> Vec<String> {
+    let mut entries = Vec::new();
+    for (name, item) in document.iter() {
+        match item {
+            toml_edit::Item::Table(table) => {
+                for (key, item) in table.iter() {
+                    match (name, key, item) {
+                        ("package", "metadata", toml_edit::Item::Table(metadata)) => entries
+                            .extend(
+                                metadata
+                                    .iter()
+                                    .map(|(x, _)| format!("package.metadata.{x}")),
+                            ),
+                        _ => entries.push(format!("{name}.{key}")),
+                    }
+                }
+            }
+            toml_edit::Item::ArrayOfTables(targets) => entries.extend(
+                targets
+                    .iter()
+                    .filter_map(|x| x.get("name").and_then(|x| x.as_str()))
+                    .map(|x| format!("{name}.{x}")),
+            ),
+            _ => entries.push(name.to_owned()),
+        }
+    }
+    entries
+}
+
+/// The entries the generator wrote to the manifest `document`, as recorded in
+/// its metadata.
+fn generated_entries(document: &toml_edit::DocumentMut) -> Option<Vec<String>> {
+    let entries = document
+        .get("package")?
+        .get("metadata")?
+        .get(GENERATED_ENTRIES_METADATA)?
+        .get("generated")?
+        .as_array()?;
+    Some(
+        entries
+            .iter()
+            .filter_map(|x| x.as_str())
+            .map(str::to_owned)
+            .collect(),
+    )
+}
+
+/// The entries the generator wrote to the package manifest `contents`, one
+/// per line, or `None` when the manifest does not record them.
+pub fn generated_manifest_entries(contents: &str) -> Option<String> {
+    let document = contents.parse::<toml_edit::DocumentMut>().ok()?;
+    let mut generated = String::new();
+    for entry in generated_entries(&document)? {
+        let item = match entry.strip_prefix("package.metadata.") {
+            Some(key) => document
+                .get("package")
+                .and_then(|x| x.get("metadata"))
+                .and_then(|x| x.get(key))
+                .map(ToString::to_string),
+            None => match entry.split_once('.') {
+                Some((name, key)) => match document.get(name) {
+                    Some(toml_edit::Item::ArrayOfTables(targets)) => targets
+                        .iter()
+                        .find(|x| x.get("name").and_then(|x| x.as_str()) == Some(key))
+                        .map(ToString::to_string),
+                    Some(item) => item.get(key).map(ToString::to_string),
+                    None => None,
+                },
+                None => document.get(&entry).map(ToString::to_string),
+            },
+        };
+        generated.push_str(&format!("{entry} = {}\n", item.unwrap_or_default().trim()));
+    }
+    Some(generated)
+}
+
+/// Removes `entry` as listed by [manifest_entries] from `document`, along with
+/// its table if left empty.
+fn remove_manifest_entry(document: &mut toml_edit::DocumentMut, entry: &str) {
+    if let Some(key) = entry.strip_prefix("package.metadata.") {
+        if let Some(metadata) = document
+            .get_mut("package")
+            .and_then(|x| x.get_mut("metadata"))
+            .and_then(toml_edit::Item::as_table_like_mut)
+        {
+            metadata.remove(key);
+        }
+        return;
+    }
+    let Some((name, key)) = entry.split_once('.') else {
+        document.remove(entry);
+        return;
+    };
+    let is_empty = match document.get_mut(name) {
+        Some(toml_edit::Item::Table(table)) => {
+            table.remove(key);
+            table.is_empty()
+        }
+        Some(toml_edit::Item::ArrayOfTables(targets)) => {
+            targets.retain(|x| x.get("name").and_then(|x| x.as_str()) != Some(key));
+            targets.is_empty()
+        }
+        _ => false,
+    };
+    if is_empty {
+        document.remove(name);
+    }
+}
+
+/// Package fields set from the spec, which are removed from an existing
+/// manifest when the spec no longer sets them.
+const SPEC_PACKAGE_FIELDS: [&str; 4] = ["description", "readme", "links", "publish"];
+
+/// Merges the `generated` package manifest into the `existing` one, keeping
+/// dependencies, features, targets, and any other sections added by hand.
+/// Generated entries replace existing entries of the same name, and targets
+/// such as `[[bin]]` are matched by name. Entries the generator wrote before
+/// but no longer generates are removed.
+fn merge_manifest_toml(existing: &str, generated: &str) -> anyhow::Result<String> {
+    let mut document = existing
+        .parse::<toml_edit::DocumentMut>()
+        .context("Failed to parse existing cargo manifest toml")?;
+    let generated = generated
+        .parse::<toml_edit::DocumentMut>()
+        .context("Failed to parse generated cargo manifest toml")?;
+
+    let current = manifest_entries(&generated);
+    for entry in generated_entries(&document).unwrap_or_default() {
+        if !current.contains(&entry) {
+            remove_manifest_entry(&mut document, &entry);
+        }
+    }
+
+    for (name, item) in generated.iter() {
+        match item {
+            toml_edit::Item::Table(generated_table) => {
+                let table = document
+                    .entry(name)
+                    .or_insert_with(|| {
+                        let mut table = toml_edit::Table::new();
+                        table.set_implicit(true);
+                        toml_edit::Item::Table(table)
+                    })
+                    .as_table_mut()
+                    .with_context(|| format!("Expected `{name}` to be a table"))?;
+                for (key, item) in generated_table.iter() {
+                    let key = generated_table
+                        .key(key)
+                        .context("Expected key of generated manifest entry")?;
+                    // Package metadata is shared with other tools, so only
+                    // its generated entries are replaced.
+                    match (name, table.get_mut(key), item) {
+                        (
+                            "package",
+                            Some(toml_edit::Item::Table(metadata)),
+                            toml_edit::Item::Table(generated_metadata),
+                        ) if key.get() == "metadata" => {
+                            for (key, item) in generated_metadata.iter() {
+                                metadata.insert(key, item.clone());
+                            }
+                        }
+                        _ => {
+                            table.insert_formatted(key, item.clone());
+                        }
+                    }
+                }
+            }
+            toml_edit::Item::ArrayOfTables(generated_targets) => {
+                let targets = document
+                    .entry(name)
+                    .or_insert_with(|| {
+                        toml_edit::Item::ArrayOfTables(toml_edit::ArrayOfTables::new())
+                    })
+                    .as_array_of_tables_mut()
+                    .with_context(|| format!("Expected `{name}` to be an array of tables"))?;
+                for target in generated_targets.iter() {
+                    let existing = targets.iter_mut().find(|x| {
+                        x.get("name").and_then(|x| x.as_str())
+                            == target.get("name").and_then(|x| x.as_str())
+                    });
+                    match existing {
+                        Some(existing) => *existing = target.clone(),
+                        None => targets.push(target.clone()),
+                    }
+                }
+            }
+            _ => {
+                document.insert(name, item.clone());
+            }
+        }
+    }
+
+    if let Some(package) = document
+        .get_mut("package")
+        .and_then(toml_edit::Item::as_table_mut)
+    {
+        let generated_package = generated["package"].as_table();
+        for field in SPEC_PACKAGE_FIELDS {
+            if !generated_package.is_some_and(|x| x.contains_key(field)) {
+                package.remove(field);
+            }
+        }
+        // A license set by hand is kept, unless the other of the two license
+        // fields is generated, as Cargo expects only one of them.
+        for (field, other) in [("license", "license-file"), ("license-file", "license")] {
+            if generated_package.is_some_and(|x| x.contains_key(field)) {
+                package.remove(other);
+            }
+        }
+    }
+
+    Ok(document.to_string())
+}
+
+fn feature_description(feature: &str) -> &'static str {
+    match feature {
+        "prost-serde" => {
+            "generated `prost` messages and `serde` implementations in the `prost_serde` module"
+        }
+        "tonic-client" => "generated `tonic` clients of the package services",
+        "tonic-server" => "generated `tonic` servers of the package services",
+        "transport" => {
+            "`tonic` transport, for clients to `connect` to a URL and for serving over HTTP/2"
+        }
+        "schemars" => "`schemars::JsonSchema` for the generated messages and enums",
+        "utoipa" => "`utoipa::ToSchema` for the generated messages and enums",
+        "proptest" => "`proptest::arbitrary::Arbitrary` for the generated messages and enums",
+        "text-format" => "the protobuf text format of the generated messages",
+        _ => "see `Cargo.toml`",
+    }
+}
+
+/// Versions of the crates generated packages may depend on, the defaults of
+/// the generator overridden by `versions` of the spec.
+pub fn dependency_versions(protogen: &ProtogenSpec) -> anyhow::Result<BTreeMap<String, String>> {
+    let manifest: CargoManifest = toml::from_str(include_str!("package_template.toml"))
+        .context("Failed to decode package_template.toml")?;
+    let mut versions: BTreeMap<String, String> = manifest
+        .dependencies
+        .iter()
+        .chain(&manifest.build_dependencies)
+        .chain(&manifest.dev_dependencies)
+        .map(|(name, dep)| (name.to_owned(), dep.version.to_owned()))
+        .collect();
+    for (name, version) in [
+        ("tokio", TOKIO_VERSION),
+        ("criterion", CRITERION_VERSION),
+        ("tonic-health", TONIC_HEALTH_VERSION),
+        ("tonic-reflection", TONIC_REFLECTION_VERSION),
+        ("schemars", SCHEMARS_VERSION),
+        ("utoipa", UTOIPA_VERSION),
+        ("proptest", PROPTEST_VERSION),
+        ("proptest-derive", PROPTEST_DERIVE_VERSION),
+    ] {
+        versions.insert(name.to_owned(), version.to_owned());
+    }
+    versions.extend(protogen.versions.clone());
+    Ok(versions)
+}
+
+/// A dependency from the default registry at its version in `versions` of
+/// [`dependency_versions`].
+fn pinned_dep(versions: &BTreeMap<String, String>, name: &str) -> anyhow::Result<CargoPackageDep> {
+    let version = versions
+        .get(name)
+        .with_context(|| format!("Expected a version of dependency `{name}`"))?;
+    Ok(CargoPackageDep {
+        version: version.to_owned(),
+        optional: false,
+        default_features: None,
+        features: Vec::new(),
+        workspace: false,
+        path: None,
+        registry: None,
+    })
+}
+
+/// A [`pinned_dep`] enabled by a feature of the generated package.
+fn optional_pinned_dep(
+    versions: &BTreeMap<String, String>,
+    name: &str,
+) -> anyhow::Result<CargoPackageDep> {
+    Ok(CargoPackageDep {
+        optional: true,
+        ..pinned_dep(versions, name)?
+    })
+}
+
+/// Builds the Cargo manifest of a generated package from the package
+/// template, with dependency versions pinned in the manifest itself.
+pub fn package_manifest(
+    protogen: &ProtogenSpec,
+    package_spec: &RustPackage,
+) -> anyhow::Result<CargoManifest> {
+    let mut manifest: CargoManifest = toml::from_str(include_str!("package_template.toml"))
+        .context("Failed to decode package_template.toml")?;
+
+    manifest.package.name = package_spec.name.to_owned();
+    manifest.package.version = package_spec.version.to_owned();
+    manifest.package.links = Some(package_spec.name.to_owned());
+    manifest.package.description = package_spec.description.to_owned();
+    manifest.package.readme = Some(PathBuf::from("README.md"));
+    manifest.package.publish = package_spec.effective_publish();
+    let versions = dependency_versions(protogen)?;
+    for (name, dep) in manifest
+        .dependencies
+        .iter_mut()
+        .chain(manifest.build_dependencies.iter_mut())
+    {
+        if let Some(version) = protogen.versions.get(name) {
+            dep.version = version.to_owned();
+        }
+    }
+    if !package_spec.bins.is_empty() {
+        manifest.dependencies.insert(
+            "tokio".to_owned(),
+            CargoPackageDep {
+                features: vec!["macros".to_owned(), "rt-multi-thread".to_owned()],
+                ..pinned_dep(&versions, "tokio")?
+            },
+        );
+        // Bins register health and reflection services, which only servers
+        // need.
+        for name in ["tonic-health", "tonic-reflection"] {
+            manifest
+                .dependencies
+                .insert(name.to_owned(), optional_pinned_dep(&versions, name)?);
+            manifest
+                .features
+                .get_mut("tonic-server")
+                .context("Expected tonic-server feature in package template")?
+                .push(format!("dep:{name}"));
+        }
+    }
+    if package_spec.json_schemas {
+        manifest.dependencies.insert(
+            "schemars".to_owned(),
+            optional_pinned_dep(&versions, "schemars")?,
+        );
+        manifest.features.insert(
+            "schemars".to_owned(),
+            vec![
+                "prost-serde".to_owned(),
+                "appbiotic-api/schemars".to_owned(),
+                "dep:schemars".to_owned(),
+            ],
+        );
+    }
+    if package_spec.openapi_schemas {
+        manifest.dependencies.insert(
+            "utoipa".to_owned(),
+            optional_pinned_dep(&versions, "utoipa")?,
+        );
+        manifest.features.insert(
+            "utoipa".to_owned(),
+            vec![
+                "prost-serde".to_owned(),
+                "appbiotic-api/utoipa".to_owned(),
+                "dep:utoipa".to_owned(),
+            ],
+        );
+    }
+    if package_spec.arbitrary {
+        for name in ["proptest", "proptest-derive"] {
+            manifest
+                .dependencies
+                .insert(name.to_owned(), optional_pinned_dep(&versions, name)?);
+        }
+        manifest.features.insert(
+            "proptest".to_owned(),
+            vec![
+                "prost-serde".to_owned(),
+                "appbiotic-api/proptest".to_owned(),
+                "dep:proptest".to_owned(),
+                "dep:proptest-derive".to_owned(),
+            ],
+        );
+    }
+    if package_spec.text_format {
+        manifest.features.insert(
+            "text-format".to_owned(),
+            vec![
+                "prost-serde".to_owned(),
+                "appbiotic-api/text-format".to_owned(),
+            ],
+        );
+    }
+    if package_spec.smoke_tests || package_spec.examples || package_spec.benches {
+        manifest.dev_dependencies.insert(
+            "serde_json".to_owned(),
+            pinned_dep(&versions, "serde_json")?,
+        );
+        manifest.dev_dependencies.insert(
+            "tokio".to_owned(),
+            CargoPackageDep {
+                features: vec!["macros".to_owned(), "rt-multi-thread".to_owned()],
+                ..pinned_dep(&versions, "tokio")?
+            },
+        );
+    }
+    if package_spec.benches {
+        manifest
+            .dev_dependencies
+            .insert("criterion".to_owned(), pinned_dep(&versions, "criterion")?);
+        manifest.bench.push(CargoBin {
+            name: "codec".to_owned(),
+            path: PathBuf::from("benches/codec.rs"),
+            required_features: vec!["prost-serde".to_owned()],
+            harness: Some(false),
+        });
+    }
+    for bin in &package_spec.bins {
+        manifest.bin.push(CargoBin {
+            name: bin.name.to_owned(),
+            path: PathBuf::from("src/bin").join(format!("{}.rs", bin.name)),
+            required_features: vec!["tonic-server".to_owned(), "transport".to_owned()],
+            harness: None,
+        });
+    }
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn generated_manifest(dependencies: &[&str]) -> String {
+        let mut manifest = CargoManifest {
+            package: CargoPackage {
+                name: "acme-gadgets".to_owned(),
+                version: "0.1.0".to_owned(),
+                edition: "2021".to_owned(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        for name in dependencies {
+            manifest.dependencies.insert(
+                name.to_string(),
+                CargoPackageDep {
+                    version: "0.1.0".to_owned(),
+                    optional: false,
+                    default_features: None,
+                    features: Vec::new(),
+                    workspace: false,
+                    path: None,
+                    registry: None,
+                },
+            );
+        }
+        manifest_toml(&manifest).unwrap()
+    }
+
+    #[test]
+    fn it_removes_entries_no_longer_generated() {
+        let existing = generated_manifest(&["acme-things", "acme-widgets"])
+            + "\n[dependencies.bytes]\nversion = \"1\"\n\n[dev-dependencies]\nanyhow = \"1\"\n";
+        let merged = merge_manifest_toml(&existing, &generated_manifest(&["acme-things"])).unwrap();
+        let document = merged.parse::<toml_edit::DocumentMut>().unwrap();
+        let dependencies = document["dependencies"].as_table().unwrap();
+        assert!(dependencies.contains_key("acme-things"));
+        assert!(dependencies.contains_key("bytes"));
+        assert!(!dependencies.contains_key("acme-widgets"));
+        assert!(document["dev-dependencies"]
+            .as_table()
+            .unwrap()
+            .contains_key("anyhow"));
+        assert!(!generated_entries(&document)
+            .unwrap()
+            .contains(&"dependencies.acme-widgets".to_owned()));
+    }
+
+    #[test]
+    fn it_removes_tables_left_empty() {
+        let merged = merge_manifest_toml(
+            &generated_manifest(&["acme-things"]),
+            &generated_manifest(&[]),
+        )
+        .unwrap();
+        let document = merged.parse::<toml_edit::DocumentMut>().unwrap();
+        assert!(document.get("dependencies").is_none());
+    }
+
+    #[test]
+    fn it_lists_generated_entries_without_those_added_by_hand() {
+        let generated = generated_manifest(&["acme-things"]);
+        let edited = generated.clone() + "\n[dependencies.bytes]\nversion = \"1\"\n";
+        assert_eq!(
+            generated_manifest_entries(&edited),
+            generated_manifest_entries(&generated)
+        );
+        let edited = generated.replace(
+            "version = \"0.1.0\"\nfeatures",
+            "version = \"0.2.0\"\nfeatures",
+        );
+        assert_ne!(edited, generated);
+        assert_ne!(
+            generated_manifest_entries(&edited),
+            generated_manifest_entries(&generated)
+        );
+        assert_eq!(
+            generated_manifest_entries("[package]\nname = \"acme\"\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn it_keeps_entries_of_manifests_without_generated_entries() {
+        let existing = "[package]\nname = \"acme-gadgets\"\n\n[dependencies]\nbytes = \"1\"\n";
+        let merged = merge_manifest_toml(existing, &generated_manifest(&[])).unwrap();
+        let document = merged.parse::<toml_edit::DocumentMut>().unwrap();
+        assert!(document["dependencies"]
+            .as_table()
+            .unwrap()
+            .contains_key("bytes"));
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt::Write;
+
+use appbiotic_api_protogen_spec::ProtogenSpec;
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+/// Renders the package dependency graph with an edge from every package to
+/// each package it depends on. Edges to API dependencies outside of the spec
+/// are dashed.
+pub fn render_graph(protogen: &ProtogenSpec, format: GraphFormat) -> String {
+    let mut out = String::new();
+    match format {
+        GraphFormat::Dot => {
+            out.push_str("digraph protogen {\n");
+            out.push_str("    node [shape=box];\n");
+            for package in &protogen.rust {
+                let _ = writeln!(
+                    out,
+                    "    \"{}\" [label=\"{}\\n{}\\n{}\"];",
+                    package.name, package.name, package.version, package.proto_package_name
+                );
+            }
+            for package in &protogen.rust {
+                for dependency in &package.protogen_dependencies {
+                    let _ = writeln!(out, "    \"{}\" -> \"{dependency}\";", package.name);
+                }
+                for dependency in &package.api_dependencies {
+                    let _ = writeln!(
+                        out,
+                        "    \"{}\" -> \"{}\" [style=dashed];",
+                        package.name, dependency.name
+                    );
+                }
+            }
+            out.push_str("}\n");
+        }
+        GraphFormat::Mermaid => {
+            out.push_str("graph TD\n");
+            for package in &protogen.rust {
+                let _ = writeln!(
+                    out,
+                    "    {}[\"{}<br/>{}<br/>{}\"]",
+                    mermaid_id(&package.name),
+                    package.name,
+                    package.version,
+                    package.proto_package_name
+                );
+            }
+            for package in &protogen.rust {
+                for dependency in &package.protogen_dependencies {
+                    let _ = writeln!(
+                        out,
+                        "    {} --> {}",
+                        mermaid_id(&package.name),
+                        mermaid_id(dependency)
+                    );
+                }
+                for dependency in &package.api_dependencies {
+                    let _ = writeln!(
+                        out,
+                        "    {} -.-> {}",
+                        mermaid_id(&package.name),
+                        mermaid_id(&dependency.name)
+                    );
+                }
+            }
+        }
+    }
+    out
+}
+
+fn mermaid_id(name: &str) -> String {
+    name.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+}
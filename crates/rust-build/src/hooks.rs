@@ -0,0 +1,107 @@
+use std::{path::Path, process::Command};
+
+use anyhow::{ensure, Context};
+use appbiotic_api_protogen_spec::{paths::normalize_path, ProtogenSpec, RustPackage};
+use tracing::info;
+
+use crate::output_manifest::OutputManifest;
+
+/// Hooks passed on the command line, run after those of the spec. They are
+/// shell commands, run with `sh -c`.
+pub struct Hooks {
+    pub pre_generate: Vec<String>,
+    pub post_generate: Vec<String>,
+}
+
+impl Hooks {
+    /// Runs the hooks before rendering `package` of `protogen`, loaded from
+    /// the absolute `protogen_path` and written below `output_dir`.
+    pub fn pre_generate(
+        &self,
+        protogen: &ProtogenSpec,
+        protogen_path: &Path,
+        output_dir: &Path,
+        package: &RustPackage,
+    ) -> anyhow::Result<()> {
+        for command in commands(&protogen.hooks.pre_generate, &self.pre_generate) {
+            run_hook(&command, "pre_generate", protogen_path, output_dir, package)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the hooks after writing `package`, recording the generated files
+    /// they change in `output_manifest` so they are not taken for hand edits.
+    pub fn post_generate(
+        &self,
+        protogen: &ProtogenSpec,
+        protogen_path: &Path,
+        output_dir: &Path,
+        package: &RustPackage,
+        output_manifest: &mut OutputManifest,
+    ) -> anyhow::Result<()> {
+        let commands = commands(&protogen.hooks.post_generate, &self.post_generate);
+        if commands.is_empty() {
+            return Ok(());
+        }
+        for command in commands {
+            run_hook(
+                &command,
+                "post_generate",
+                protogen_path,
+                output_dir,
+                package,
+            )?;
+        }
+        output_manifest.rehash(output_dir, &package.name)
+    }
+}
+
+fn commands(spec_hooks: &[Vec<String>], shell_commands: &[String]) -> Vec<Vec<String>> {
+    spec_hooks
+        .iter()
+        .cloned()
+        .chain(
+            shell_commands
+                .iter()
+                .map(|x| vec!["sh".to_owned(), "-c".to_owned(), x.to_owned()]),
+        )
+        .collect()
+}
+
+fn run_hook(
+    command: &[String],
+    hook: &str,
+    protogen_path: &Path,
+    output_dir: &Path,
+    package: &RustPackage,
+) -> anyhow::Result<()> {
+    let (program, args) = command
+        .split_first()
+        .with_context(|| format!("Hook `{hook}` has an empty command"))?;
+    let spec_dir = protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")?;
+    let package_spec =
+        serde_json::to_string(package).context("Failed to serialize package spec")?;
+
+    info!(hook, package = %package.name, command = %command.join(" "), "Running hook");
+    let status = Command::new(program)
+        .args(args)
+        .current_dir(spec_dir)
+        .env("PROTOGEN_SPEC_PATH", protogen_path)
+        .env("PROTOGEN_PACKAGE", &package.name)
+        .env(
+            "PROTOGEN_PACKAGE_PATH",
+            normalize_path(&output_dir.join(&package.path)),
+        )
+        .env("PROTOGEN_PACKAGE_SPEC", package_spec)
+        .status()
+        .with_context(|| format!("Failed to run `{program}` of hook `{hook}`, is it installed?"))?;
+    ensure!(
+        status.success(),
+        "Hook `{hook}` command `{}` failed for package `{}` with {status}",
+        command.join(" "),
+        package.name
+    );
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use std::{fmt, path::PathBuf};
+
+use appbiotic_api_protogen_spec::{ProtogenSpec, RustPackage};
+
+/// What other tooling needs to know about a generated package.
+#[derive(serde::Serialize)]
+pub struct PackageSummary {
+    pub name: String,
+    pub version: String,
+    pub proto_package_name: String,
+    /// Package directory, relative to the spec directory.
+    pub path: PathBuf,
+    pub protogen_dependencies: Vec<String>,
+    /// Names of generated API crates from outside the spec.
+    pub api_dependencies: Vec<String>,
+}
+
+impl From<&RustPackage> for PackageSummary {
+    fn from(package: &RustPackage) -> Self {
+        Self {
+            name: package.name.to_owned(),
+            version: package.version.to_owned(),
+            proto_package_name: package.proto_package_name.to_owned(),
+            path: package.path.to_owned(),
+            protogen_dependencies: package.protogen_dependencies.to_owned(),
+            api_dependencies: package
+                .api_dependencies
+                .iter()
+                .map(|x| x.name.to_owned())
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for PackageSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} ({}) at {}",
+            self.name,
+            self.version,
+            self.proto_package_name,
+            self.path.to_string_lossy()
+        )?;
+        for dependency in &self.protogen_dependencies {
+            write!(f, "\n  -> {dependency}")?;
+        }
+        for dependency in &self.api_dependencies {
+            write!(f, "\n  -> {dependency} (api)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Summarizes the packages of the spec in spec order.
+pub fn list_packages(protogen: &ProtogenSpec) -> Vec<PackageSummary> {
+    protogen.rust.iter().map(PackageSummary::from).collect()
+}
@@ -0,0 +1,70 @@
+use std::io::{self, IsTerminal};
+
+use tracing::Level;
+use tracing_subscriber::{
+    filter::Targets, fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt,
+};
+
+#[derive(clap::Args)]
+pub struct LogArgs {
+    /// Format of the log written to standard error.
+    #[clap(long, value_enum, global = true, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Log more detail, including how long each generation phase took;
+    /// repeat for even more.
+    #[clap(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Log less; repeat to only log errors.
+    #[clap(short, long, global = true, action = clap::ArgAction::Count)]
+    quiet: u8,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum LogFormat {
+    Text,
+    /// One JSON object per event, for build orchestration to parse.
+    Json,
+}
+
+impl LogArgs {
+    /// Installs the global subscriber logging to standard error. A single
+    /// `-v` only adds detail from this crate, such as phase timings, leaving
+    /// dependencies at the default level.
+    pub fn init(&self) {
+        let level = match (self.verbose, self.quiet) {
+            (0, 0) => Level::INFO,
+            (1, _) => Level::DEBUG,
+            (_, 0) => Level::TRACE,
+            (_, 1) => Level::WARN,
+            _ => Level::ERROR,
+        };
+        let filter = Targets::new()
+            .with_target(env!("CARGO_CRATE_NAME"), level)
+            .with_default(if self.verbose == 1 {
+                Level::INFO
+            } else {
+                level
+            });
+        let builder = tracing_subscriber::fmt()
+            .with_writer(io::stderr)
+            .with_max_level(level)
+            .with_target(false)
+            .with_ansi(io::stderr().is_terminal());
+        match (self.log_format, self.verbose) {
+            (LogFormat::Text, 0) => builder.without_time().finish().with(filter).init(),
+            (LogFormat::Text, _) => builder
+                .with_span_events(FmtSpan::CLOSE)
+                .finish()
+                .with(filter)
+                .init(),
+            (LogFormat::Json, _) => builder
+                .json()
+                .with_span_events(FmtSpan::CLOSE)
+                .finish()
+                .with(filter)
+                .init(),
+        }
+    }
+}
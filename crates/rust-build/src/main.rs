@@ -1,278 +1,2054 @@
+mod breaking;
+mod bsr;
+mod descriptor;
+mod doc;
+mod environment;
+mod exit;
+mod generate;
+mod graph;
+mod hooks;
+mod list;
+mod logging;
+mod manifest;
+mod output;
+mod output_manifest;
+mod proto_lint;
+mod provenance;
+mod publish;
+mod release;
+mod rustfmt;
+mod scaffold;
+mod stats;
+mod targets;
+mod validate;
+mod vendor;
+mod verify;
+mod watch;
+mod workspace;
+
 use std::{
-    collections::BTreeMap,
-    env,
-    fs::{create_dir_all, File},
-    io::{BufReader, BufWriter, Write},
-    path::PathBuf,
+    env, fs,
+    io::{self, Write},
+    num::NonZeroUsize,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Path, PathBuf},
     process::ExitCode,
+    time::Duration,
+};
+
+use anyhow::{ensure, Context};
+use appbiotic_api_descriptor_diff::diff_descriptor_sets;
+use appbiotic_api_protogen_fetch::{protofetch::ProtofetchManifest, CACHE_DIR_ENV, OFFLINE_ENV};
+use appbiotic_api_protogen_spec::{
+    lint::Severity, migrate, paths::normalize_path, ProtogenSpec, RustPackage, SpecError,
 };
+use clap::{CommandFactory, Parser};
+use tracing::{debug, debug_span, error, info, warn};
 
-use anyhow::{anyhow, ensure, Context};
-use appbiotic_api_protogen_spec::ProtogenSpec;
-use clap::Parser;
-use handlebars::Handlebars;
-use serde_json::json;
+use crate::{
+    breaking::package_changes,
+    bsr::{pull_packages, push_packages, BsrPushOptions},
+    descriptor::{
+        compile_package_descriptors, encode_descriptor_set, export_descriptor_set,
+        load_descriptor_set, DescriptorFormat,
+    },
+    doc::render_markdown,
+    environment::resolve_environment,
+    generate::{
+        generate_package, generate_packages, package_inputs_hash, GenerateOptions, GeneratedFile,
+    },
+    graph::{render_graph, GraphFormat},
+    hooks::Hooks,
+    list::list_packages,
+    logging::LogArgs,
+    output::{diff_files, display_path, stale_files, write_files, FileStatus},
+    output_manifest::{clean_package, OutputManifest, LOCK_FILE_NAME},
+    proto_lint::lint_protos,
+    publish::{publish_packages, PublishOptions},
+    release::{
+        changelog_file, commit_and_tag, ensure_clean, ensure_new_tag, last_tag, plan_release,
+    },
+    scaffold::{add_package, add_proto, init_workspace, NewPackage, PROTOGEN_FILE_NAME},
+    stats::package_stats,
+    targets::run_targets,
+    validate::{validate, Diagnostic, Level},
+    vendor::vendor_protos,
+    verify::verify_package,
+    watch::watch_packages,
+    workspace::workspace_manifest,
+};
 
 /// Code generator for Rust APIs
 #[derive(clap::Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
+    #[clap(flatten)]
+    log: LogArgs,
+
+    /// Set to true to forbid fetching remote proto sources and protofetch
+    /// dependencies, failing unless they were fetched or vendored before.
+    /// Also applies to build scripts run by `verify`.
+    #[clap(long, global = true, default_value_t = false)]
+    offline: bool,
+
+    /// Directory remote proto sources and protofetch downloads are cached
+    /// in, shared by every spec. Defaults to `PROTOGEN_CACHE_DIR` or else
+    /// `appbiotic-protogen` in the XDG cache directory. Also applies to build
+    /// scripts run by `verify`.
+    #[clap(long, global = true, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
     #[clap(subcommand)]
     cmd: Command,
 }
 
 #[derive(clap::Subcommand)]
 enum Command {
+    /// Generate a single package
     Package(PackageCommand),
+    /// Generate every package in dependency order
+    GenerateAll(GenerateAllCommand),
+    /// Check the spec and its proto sources without generating anything
+    Validate(ValidateCommand),
+    /// Create a starter protogen workspace
+    Init(InitCommand),
+    /// Add a package and its proto skeleton to the spec
+    AddPackage(AddPackageCommand),
+    /// Print the package dependency graph
+    Graph(GraphCommand),
+    /// Verify generated packages are up to date with the spec
+    Check(CheckCommand),
+    /// Remove generated package outputs
+    Clean(CleanCommand),
+    /// List the packages in the spec
+    List(ListCommand),
+    /// Bump the version of a package and regenerate it and its dependents
+    BumpVersion(BumpVersionCommand),
+    /// Publish generated packages in dependency order
+    Publish(PublishCommand),
+    /// Regenerate packages whenever the spec or their protos change
+    Watch(WatchCommand),
+    /// Print a shell completion script
+    Completions(CompletionsCommand),
+    /// Generate a package into a temporary workspace and `cargo check` it
+    Verify(VerifyCommand),
+    /// Report breaking proto changes since a git revision
+    Breaking(BreakingCommand),
+    /// Check the protos of the spec against style rules
+    Lint(LintCommand),
+    /// Rewrite the spec to the latest version of the spec format
+    Migrate(MigrateCommand),
+    /// Add a proto file to a package and its proto directory
+    NewProto(NewProtoCommand),
+    /// Copy remote proto dependencies into the repository and use them locally
+    Vendor(VendorCommand),
+    /// Bump, regenerate and tag the packages whose protos changed since the
+    /// last release
+    Release(ReleaseCommand),
+    /// Render a Markdown API reference of the protos of each package
+    Doc(DocCommand),
+    /// Write the compiled FileDescriptorSet of a package
+    Descriptor(DescriptorCommand),
+    /// Print the resolved spec path, package paths, tools, and defaults
+    Env(EnvCommand),
+    /// Print counts of messages, enums, services, methods, and generated
+    /// lines of each package
+    Stats(StatsCommand),
+    /// Push the local protos of packages with a `bsr_module` to the Buf
+    /// Schema Registry
+    BsrPush(BsrPushCommand),
+    /// Fetch the BSR modules of packages into the cache, optionally pinning
+    /// their references to commits
+    BsrPull(BsrPullCommand),
 }
 
 #[derive(clap::Args)]
-struct PackageCommand {
-    /// Path to the protogen.json spec file
+struct SpecArgs {
+    /// Path to the protogen.json spec file, or `-` to read it from standard
+    /// input with paths relative to the working directory. Defaults to the
+    /// nearest `protogen.json` in the working directory or one of its parents.
     #[clap(long, value_name = "FILE")]
-    protogen_path: PathBuf,
+    protogen_path: Option<PathBuf>,
+}
+
+impl SpecArgs {
+    /// Loads the spec, returning it with its absolute path.
+    fn load(&self) -> anyhow::Result<(PathBuf, ProtogenSpec)> {
+        let _span = debug_span!("load_spec").entered();
+        if self.is_stdin() {
+            // Resolved as if the spec were in the working directory.
+            let protogen_path = env::current_dir()?.join(PROTOGEN_FILE_NAME);
+            let protogen = ProtogenSpec::from_reader(io::stdin().lock(), Path::new("<stdin>"))?;
+            return Ok((protogen_path, protogen));
+        }
+        let protogen_path = self.path()?;
+        let protogen = ProtogenSpec::load(&protogen_path)?;
+        Ok((protogen_path, protogen))
+    }
+
+    fn is_stdin(&self) -> bool {
+        self.protogen_path.as_deref() == Some(Path::new("-"))
+    }
+
+    /// Fails for commands that update or watch the spec file when it is read
+    /// from standard input.
+    fn ensure_file(&self) -> anyhow::Result<()> {
+        ensure!(
+            !self.is_stdin(),
+            "This command needs a spec file and cannot read it from standard input"
+        );
+        Ok(())
+    }
 
-    /// Path to the protogen.json spec file
+    /// The absolute path of the spec, found the way Cargo finds `Cargo.toml`
+    /// when not given.
+    fn path(&self) -> anyhow::Result<PathBuf> {
+        let current_dir = env::current_dir()?;
+        match &self.protogen_path {
+            Some(protogen_path) => Ok(current_dir.join(protogen_path)),
+            None => current_dir
+                .ancestors()
+                .map(|x| x.join(PROTOGEN_FILE_NAME))
+                .find(|x| x.is_file())
+                .ok_or_else(|| SpecError::NotFound { dir: current_dir }.into()),
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct PackageFilterArgs {
+    /// Only include packages whose names match one of these comma separated
+    /// names or glob patterns, e.g. `acme-*`.
+    #[clap(long, value_delimiter = ',', value_name = "PATTERNS")]
+    packages: Vec<String>,
+
+    /// Only include packages in this profile of the spec, such as `public`
+    /// or `internal`. Packages without profiles are in every profile.
+    #[clap(long, value_name = "PROFILE")]
+    profile: Option<String>,
+}
+
+impl PackageFilterArgs {
+    /// Keeps the `packages` matching the filter, failing when a pattern or
+    /// the profile matches none of them so typos do not go unnoticed.
+    fn filter<'a>(&self, packages: Vec<&'a RustPackage>) -> anyhow::Result<Vec<&'a RustPackage>> {
+        let packages = match &self.profile {
+            Some(profile) => {
+                ensure_profile(&packages, profile)?;
+                packages
+                    .into_iter()
+                    .filter(|x| x.in_profile(profile))
+                    .collect()
+            }
+            None => packages,
+        };
+        if self.packages.is_empty() {
+            return Ok(packages);
+        }
+        for pattern in &self.packages {
+            ensure!(
+                packages.iter().any(|x| glob_matches(pattern, &x.name)),
+                "No package matches `{pattern}`"
+            );
+        }
+        Ok(packages
+            .into_iter()
+            .filter(|x| self.packages.iter().any(|p| glob_matches(p, &x.name)))
+            .collect())
+    }
+}
+
+/// Checks that some package of the spec is tagged with `profile`.
+fn ensure_profile(packages: &[&RustPackage], profile: &str) -> anyhow::Result<()> {
+    ensure!(
+        packages.iter().any(|x| x.profiles.contains(profile)),
+        "No package is in profile `{profile}`"
+    );
+    Ok(())
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any run of characters
+/// and `?` any single character.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Positions to resume from after the last `*`, trying longer runs for it
+    // on each mismatch.
+    let (mut p, mut n, mut resume) = (0, 0, None);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                resume = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match resume {
+                Some((star, matched)) => {
+                    resume = Some((star, matched + 1));
+                    p = star + 1;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|x| *x == '*')
+}
+
+fn spec_dir(protogen_path: &Path) -> anyhow::Result<&Path> {
+    protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")
+}
+
+#[derive(clap::Args)]
+struct GenerateArgs {
+    /// Path to the protofetch.toml manifest whose fetched protos are added to
+    /// the package include paths.
     #[clap(long, value_name = "FILE")]
-    protofetch_path: PathBuf,
+    protofetch_path: Option<PathBuf>,
+
+    /// Set to true to also create or update the workspace Cargo.toml next to
+    /// the spec, adding generated packages to its members and their
+    /// dependencies to `[workspace.dependencies]`.
+    #[clap(long, default_value_t = false)]
+    workspace: bool,
+
+    /// Directory of handlebars templates, such as `lib.rs.hbs`, that replace
+    /// the embedded templates of the same name.
+    #[clap(long, value_name = "DIR")]
+    templates_dir: Option<PathBuf>,
+
+    /// Directory to write generated files below instead of the spec
+    /// directory, e.g. a scratch tree to compare with the working copy.
+    #[clap(long, value_name = "DIR")]
+    output_root: Option<PathBuf>,
+
+    /// SPDX license expression of the generated crates, overriding
+    /// `license` of the spec.
+    #[clap(long, value_name = "EXPR")]
+    license: Option<String>,
+
+    /// License file copied into the generated crates, overriding
+    /// `license_files` of the spec. Repeat for several files.
+    #[clap(long = "license-file", value_name = "FILE")]
+    license_files: Vec<PathBuf>,
+
+    /// Set to true to format generated Rust sources with `rustfmt`, as with
+    /// `fmt` of the spec.
+    #[clap(long, default_value_t = false)]
+    fmt: bool,
+}
+
+#[derive(clap::Args)]
+struct HookArgs {
+    /// Shell command run from the spec directory before rendering each
+    /// package, after the `pre_generate` hooks of the spec. May be repeated.
+    #[clap(long = "pre-generate-hook", value_name = "COMMAND")]
+    pre_generate: Vec<String>,
+
+    /// Shell command run from the spec directory after writing each package,
+    /// after the `post_generate` hooks of the spec. May be repeated.
+    #[clap(long = "post-generate-hook", value_name = "COMMAND")]
+    post_generate: Vec<String>,
+}
+
+impl From<HookArgs> for Hooks {
+    fn from(args: HookArgs) -> Self {
+        Self {
+            pre_generate: args.pre_generate,
+            post_generate: args.post_generate,
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct FetchArgs {
+    /// Set to true to skip running `protofetch fetch` for the
+    /// `protofetch-path` manifest before generating, e.g. when offline.
+    #[clap(long, default_value_t = false)]
+    no_fetch: bool,
+
+    /// Set to true to fail instead of updating protofetch.lock when fetching.
+    #[clap(long, default_value_t = false)]
+    locked: bool,
+}
+
+impl FetchArgs {
+    /// Fetches the external proto dependencies of the protofetch manifest, if
+    /// any, so they are available to generation.
+    fn fetch(&self, generate: &GenerateArgs) -> anyhow::Result<()> {
+        let Some(protofetch_path) = &generate.protofetch_path else {
+            return Ok(());
+        };
+        if self.no_fetch {
+            return Ok(());
+        }
+        ProtofetchManifest::load(protofetch_path)?.fetch(self.locked)
+    }
+}
+
+impl From<GenerateArgs> for GenerateOptions {
+    fn from(args: GenerateArgs) -> Self {
+        Self {
+            protofetch_path: args.protofetch_path,
+            workspace: args.workspace,
+            templates_dir: args.templates_dir,
+            output_root: args.output_root,
+            license: args.license,
+            license_files: args.license_files,
+            embed_package_spec: false,
+            fmt: args.fmt,
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct PackageCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    generate: GenerateArgs,
+
+    #[clap(flatten)]
+    hooks: HookArgs,
+
+    #[clap(flatten)]
+    fetch: FetchArgs,
 
     /// The name of the package to generate as specified in `protogen-path`
     /// spec file.
     #[clap(long)]
     package: String,
 
-    /// Set to true to skip writing to `output-path` and instead write a patch
-    /// to standard out.
+    /// Profile of the spec being generated, such as `public` or `internal`,
+    /// failing when the package is not in it.
+    #[clap(long, value_name = "PROFILE")]
+    profile: Option<String>,
+
+    /// Set to true to skip writing the package and instead write a patch of
+    /// the changes generation would make to standard out, exiting non-zero
+    /// when there are any.
     #[clap(long, default_value_t = false)]
     dry_run: bool,
+
+    /// Set to true to overwrite generated files that were modified since they
+    /// were last written, instead of failing.
+    #[clap(long, default_value_t = false)]
+    force: bool,
+}
+
+#[derive(clap::Args)]
+struct GenerateAllCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    generate: GenerateArgs,
+
+    #[clap(flatten)]
+    hooks: HookArgs,
+
+    #[clap(flatten)]
+    fetch: FetchArgs,
+
+    #[clap(flatten)]
+    filter: PackageFilterArgs,
+
+    /// Set to true to skip writing packages and instead write a patch of the
+    /// changes generation would make to standard out, exiting non-zero when
+    /// there are any.
+    #[clap(long, default_value_t = false)]
+    dry_run: bool,
+
+    /// Set to true to only generate the Rust packages, skipping the
+    /// generators of the spec's `targets`.
+    #[clap(long, default_value_t = false)]
+    skip_targets: bool,
+
+    /// Maximum number of packages to generate at a time. Defaults to the
+    /// number of CPUs.
+    #[clap(long, short = 'j', value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+
+    /// Set to true to overwrite generated files that were modified since they
+    /// were last written, instead of failing.
+    #[clap(long, default_value_t = false)]
+    force: bool,
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
-struct PackageSpec {
-    pub name: String,
-    pub version: String,
-    pub path: PathBuf,
-    pub protos: Vec<PathBuf>,
-    #[serde(default)]
-    pub api_dependencies: Vec<String>,
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    cli.log.init();
+    if cli.offline {
+        // Read by every fetcher, including those of build scripts run from
+        // here.
+        env::set_var(OFFLINE_ENV, "1");
+    }
+    if let Some(cache_dir) = &cli.cache_dir {
+        match env::current_dir() {
+            Ok(current_dir) => env::set_var(CACHE_DIR_ENV, current_dir.join(cache_dir)),
+            Err(error) => {
+                error!("Failed to resolve the cache directory: {error}");
+                return ExitCode::from(exit::IO);
+            }
+        }
+    }
+    let Ok(result) = catch_unwind(AssertUnwindSafe(|| run_cmd(cli.cmd))) else {
+        error!("The generator panicked; this is a bug, please report it");
+        return ExitCode::from(exit::INTERNAL);
+    };
+    match result {
+        Ok(exit_code) => exit_code,
+        Err(error) => {
+            error!("{error:?}");
+            if let Some(guidance) = error
+                .chain()
+                .find_map(|x| x.downcast_ref::<SpecError>())
+                .and_then(SpecError::guidance)
+            {
+                error!("hint: {guidance}");
+            }
+            exit::for_error(&error)
+        }
+    }
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
-#[serde(rename_all = "kebab-case")]
-struct CargoManifest {
-    package: CargoPackage,
-    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
-    features: BTreeMap<String, Vec<String>>,
-    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
-    dependencies: BTreeMap<String, CargoPackageDep>,
-    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
-    build_dependencies: BTreeMap<String, CargoPackageDep>,
+#[derive(clap::Args)]
+struct ValidateCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    /// Format of the diagnostics written to standard out.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
-#[serde(rename_all = "kebab-case")]
-struct CargoPackage {
-    name: String,
-    version: String,
-    edition: String,
+#[derive(clap::Args)]
+struct InitCommand {
+    /// Directory to create the workspace in.
+    #[clap(long, value_name = "DIR", default_value = ".")]
+    dir: PathBuf,
+
+    #[clap(flatten)]
+    package: NewPackageArgs,
+
+    /// Set to true to also create a protofetch.toml manifest for remote
+    /// proto dependencies.
+    #[clap(long, default_value_t = false)]
+    protofetch: bool,
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
-#[serde(rename_all = "kebab-case")]
-struct CargoPackageDep {
+#[derive(clap::Args)]
+struct NewPackageArgs {
+    /// Name of the Rust package, e.g. `foo-api`.
+    #[clap(long)]
+    name: String,
+
+    /// Proto package the Rust package generates, e.g. `foo.v1`.
+    #[clap(long)]
+    proto_package: String,
+
+    /// Initial version of the Rust package.
+    #[clap(long, default_value = "0.1.0")]
     version: String,
-    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
-    optional: bool,
-    #[serde(default)]
-    features: Vec<String>,
-    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
-    workspace: bool,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    path: Option<PathBuf>,
 }
 
-fn main() -> ExitCode {
-    let cli = Cli::parse();
-    match run_cmd(cli.cmd) {
-        Ok(_) => ExitCode::SUCCESS,
-        Err(error) => {
-            eprintln!("{error:?}");
-            ExitCode::FAILURE
+impl From<NewPackageArgs> for NewPackage {
+    fn from(args: NewPackageArgs) -> Self {
+        Self {
+            name: args.name,
+            proto_package_name: args.proto_package,
+            version: args.version,
+            protogen_dependencies: Vec::new(),
         }
     }
 }
 
-fn run_cmd(cmd: Command) -> anyhow::Result<()> {
+#[derive(clap::Args)]
+struct AddPackageCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    package: NewPackageArgs,
+
+    /// Name of an existing package in the spec this package depends on; may
+    /// be repeated.
+    #[clap(long = "protogen-dependency", value_name = "PACKAGE")]
+    protogen_dependencies: Vec<String>,
+}
+
+#[derive(clap::Args)]
+struct GraphCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    /// Graph description language to write to standard out.
+    #[clap(long, value_enum, default_value_t = GraphFormat::Dot)]
+    format: GraphFormat,
+}
+
+#[derive(clap::Args)]
+struct CheckCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    generate: GenerateArgs,
+
+    #[clap(flatten)]
+    filter: PackageFilterArgs,
+
+    /// Set to true to also write a patch of the differences to standard out.
+    #[clap(long, default_value_t = false)]
+    diff: bool,
+}
+
+#[derive(clap::Args)]
+struct CleanCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    /// Name of a package whose outputs are removed; may be repeated. Defaults
+    /// to every package with recorded outputs.
+    #[clap(long = "package", value_name = "PACKAGE")]
+    packages: Vec<String>,
+
+    /// Set to true to only remove outputs of packages no longer in the spec.
+    #[clap(long, default_value_t = false, conflicts_with = "packages")]
+    stale: bool,
+
+    /// Set to true to remove whole package directories, including scaffold
+    /// files, instead of only the generated files within them.
+    #[clap(long, default_value_t = false)]
+    remove_dirs: bool,
+
+    /// Set to true to only print what would be removed.
+    #[clap(long, default_value_t = false)]
+    dry_run: bool,
+}
+
+#[derive(clap::Args)]
+struct ListCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    /// Format of the package list written to standard out.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
+
+#[derive(clap::Args)]
+struct BumpVersionCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    generate: GenerateArgs,
+
+    #[clap(flatten)]
+    hooks: HookArgs,
+
+    /// The name of the package to bump.
+    #[clap(long)]
+    package: String,
+
+    /// Semver level to bump. `auto` infers it from the changes between
+    /// `--baseline` and the current protos.
+    #[clap(long, value_enum)]
+    level: BumpLevel,
+
+    /// Binary FileDescriptorSet of the last released protos, required with
+    /// `--level auto`.
+    #[clap(long, value_name = "FILE", required_if_eq("level", "auto"))]
+    baseline: Option<PathBuf>,
+
+    /// Set to true to overwrite generated files that were modified since they
+    /// were last written, instead of failing.
+    #[clap(long, default_value_t = false)]
+    force: bool,
+}
+
+#[derive(clap::Args)]
+struct PublishCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    /// Print the publish order instead of publishing.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Seconds to wait for a published package to show up in its registry
+    /// index before giving up on publishing its dependents.
+    #[clap(long, value_name = "SECONDS", default_value_t = 300)]
+    index_timeout: u64,
+}
+
+#[derive(clap::Args)]
+struct WatchCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    generate: GenerateArgs,
+
+    #[clap(flatten)]
+    hooks: HookArgs,
+
+    #[clap(flatten)]
+    fetch: FetchArgs,
+
+    /// Set to true to overwrite generated files that were modified since they
+    /// were last written, instead of failing.
+    #[clap(long, default_value_t = false)]
+    force: bool,
+}
+
+#[derive(clap::Args)]
+struct VerifyCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    generate: GenerateArgs,
+
+    #[clap(flatten)]
+    fetch: FetchArgs,
+
+    /// The name of the package to verify as specified in `protogen-path`
+    /// spec file. The generated packages it depends on are generated too.
+    #[clap(long)]
+    package: String,
+
+    /// Set to true to run `cargo test` instead of `cargo check`.
+    #[clap(long, default_value_t = false)]
+    test: bool,
+}
+
+#[derive(clap::Args)]
+struct BreakingCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    filter: PackageFilterArgs,
+
+    /// Git revision to compare against, e.g. `main` or a release tag.
+    #[clap(long, value_name = "REF")]
+    against: String,
+
+    /// Set to true to also report safe changes, such as added fields.
+    #[clap(long, default_value_t = false)]
+    all: bool,
+
+    /// Format of the changes written to standard out.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
+
+#[derive(clap::Args)]
+struct LintCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    filter: PackageFilterArgs,
+
+    /// Format of the findings written to standard out.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
+
+#[derive(clap::Args)]
+struct MigrateCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    /// Set to true to only write a patch of the migration to standard out,
+    /// exiting non-zero when the spec needs migrating.
+    #[clap(long, default_value_t = false)]
+    dry_run: bool,
+}
+
+#[derive(clap::Args)]
+struct NewProtoCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    /// The name of the package to add the proto file to.
+    #[clap(long)]
+    package: String,
+
+    /// Name of the proto file without extension, e.g. `widgets`. The file is
+    /// created in the directory of the package's proto package.
+    #[clap(long)]
+    name: String,
+}
+
+#[derive(clap::Args)]
+struct VendorCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    /// Directory to copy the protos into. Defaults to `third_party/proto`
+    /// next to the spec.
+    #[clap(long, value_name = "DIR")]
+    dir: Option<PathBuf>,
+
+    /// Path to a protofetch.toml manifest whose fetched protos are vendored
+    /// too and added as includes of every package.
+    #[clap(long, value_name = "FILE")]
+    protofetch_path: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct BsrPushCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    filter: PackageFilterArgs,
+
+    /// Comma separated labels to push with besides the package version.
+    #[clap(long, value_delimiter = ',', value_name = "LABELS")]
+    label: Vec<String>,
+
+    /// Create modules missing from the registry.
+    #[clap(long)]
+    create: bool,
+
+    /// Print the modules instead of pushing them.
+    #[clap(long)]
+    dry_run: bool,
+}
+
+#[derive(clap::Args)]
+struct BsrPullCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    filter: PackageFilterArgs,
+
+    /// Rewrite module references such as labels to the commits they point
+    /// at and save the spec.
+    #[clap(long)]
+    pin: bool,
+}
+
+#[derive(clap::Args)]
+struct ReleaseCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    generate: GenerateArgs,
+
+    #[clap(flatten)]
+    hooks: HookArgs,
+
+    #[clap(flatten)]
+    filter: PackageFilterArgs,
+
+    /// Name of the tag to create for the release.
+    #[clap(long)]
+    tag: String,
+
+    /// Git revision of the previous release. Defaults to the most recent tag.
+    #[clap(long, value_name = "REF")]
+    since: Option<String>,
+
+    /// Set to true to only write a patch of the changelogs to standard out,
+    /// exiting non-zero when there is something to release.
+    #[clap(long, default_value_t = false)]
+    dry_run: bool,
+
+    /// Set to true to overwrite generated files that were modified since they
+    /// were last written, instead of failing.
+    #[clap(long, default_value_t = false)]
+    force: bool,
+}
+
+#[derive(clap::Args)]
+struct DocCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    filter: PackageFilterArgs,
+
+    /// Directory to write a `<proto package>.md` file per package to. The
+    /// reference is written to standard out when not given.
+    #[clap(long, value_name = "DIR")]
+    out_dir: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct DescriptorCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    /// The name of the package to compile as specified in `protogen-path`
+    /// spec file.
+    #[clap(long)]
+    package: String,
+
+    /// Encoding of the descriptor set.
+    #[clap(long, value_enum, default_value_t = DescriptorFormat::Binpb)]
+    format: DescriptorFormat,
+
+    /// Path to write the descriptor set to, or `-` for standard out.
+    #[clap(long, value_name = "FILE")]
+    out: PathBuf,
+
+    /// Set to true to also include the files the package's protos import,
+    /// making the descriptor set self-contained.
+    #[clap(long, default_value_t = false)]
+    include_imports: bool,
+
+    /// Set to true to keep source code info, such as comments.
+    #[clap(long, default_value_t = false)]
+    include_source_info: bool,
+}
+
+#[derive(clap::Args)]
+struct EnvCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    generate: GenerateArgs,
+
+    /// Format of the environment written to standard out.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
+
+#[derive(clap::Args)]
+struct StatsCommand {
+    #[clap(flatten)]
+    spec: SpecArgs,
+
+    #[clap(flatten)]
+    filter: PackageFilterArgs,
+
+    /// Format of the statistics written to standard out.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
+
+#[derive(clap::Args)]
+struct CompletionsCommand {
+    /// Shell to complete for, e.g. by sourcing the output of
+    /// `completions bash` from `~/.bashrc`.
+    #[clap(value_enum)]
+    shell: clap_complete::Shell,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum BumpLevel {
+    Major,
+    Minor,
+    Patch,
+    Auto,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+fn run_cmd(cmd: Command) -> anyhow::Result<ExitCode> {
     match cmd {
         Command::Package(package) => build_package(package),
+        Command::GenerateAll(generate_all) => build_all_packages(generate_all),
+        Command::Validate(validate) => validate_spec(validate),
+        Command::Init(init) => create_workspace(init),
+        Command::AddPackage(add_package) => add_spec_package(add_package),
+        Command::Graph(graph) => print_graph(graph),
+        Command::Check(check) => check_packages(check),
+        Command::Clean(clean) => clean_packages(clean),
+        Command::List(list) => print_packages(list),
+        Command::BumpVersion(bump_version) => bump_package_version(bump_version),
+        Command::Publish(publish) => publish_all_packages(publish),
+        Command::Watch(watch) => watch_spec(watch),
+        Command::Completions(completions) => print_completions(completions),
+        Command::Verify(verify) => verify_generated_package(verify),
+        Command::Breaking(breaking) => report_breaking_changes(breaking),
+        Command::Lint(lint) => lint_spec_protos(lint),
+        Command::Migrate(migrate) => migrate_spec(migrate),
+        Command::NewProto(new_proto) => add_spec_proto(new_proto),
+        Command::Vendor(vendor) => vendor_remote_protos(vendor),
+        Command::Release(release) => release_packages(release),
+        Command::Doc(doc) => document_packages(doc),
+        Command::Descriptor(descriptor) => write_descriptor_set(descriptor),
+        Command::Env(env) => print_environment(env),
+        Command::Stats(stats) => print_package_stats(stats),
+        Command::BsrPush(bsr_push) => push_bsr_modules(bsr_push),
+        Command::BsrPull(bsr_pull) => pull_bsr_modules(bsr_pull),
     }
 }
 
-fn build_package(package_cmd: PackageCommand) -> anyhow::Result<()> {
-    let protogen_path = env::current_dir()
-        .as_ref()
-        .unwrap()
-        .join(package_cmd.protogen_path);
+fn build_package(package_cmd: PackageCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = package_cmd.spec.load()?;
 
-    let protogen: ProtogenSpec = serde_json::from_reader(BufReader::new(
-        File::open(&protogen_path).with_context(|| {
-            format!(
-                "Failed to open path `{}` for parsing protogen spec",
-                protogen_path.to_string_lossy()
-            )
-        })?,
-    ))
-    .with_context(|| {
-        format!(
-            "Failed to parse protogen spec at path `{}`",
-            protogen_path.to_string_lossy()
-        )
-    })?;
+    report_lint(&protogen)?;
 
-    let package_spec = protogen
-        .rust
-        .iter()
-        .find(|x| x.name.eq(&package_cmd.package))
-        .ok_or_else(|| {
-            anyhow!(
-                "Failed to find package `{}` in protogen file at path `{}`",
-                package_cmd.package,
-                protogen_path.to_string_lossy()
-            )
-        })?;
+    package_cmd.fetch.fetch(&package_cmd.generate)?;
+    let mut options: GenerateOptions = package_cmd.generate.into();
+    options.embed_package_spec = package_cmd.spec.is_stdin();
+    let package = protogen.package(&package_cmd.package)?;
+    if let Some(profile) = &package_cmd.profile {
+        ensure_profile(&protogen.rust.iter().collect::<Vec<_>>(), profile)?;
+        ensure!(
+            package.in_profile(profile),
+            "Package `{}` is not in profile `{profile}`",
+            package.name
+        );
+    }
+    let spec_dir = spec_dir(&protogen_path)?;
+    let output_dir = options.output_dir(spec_dir)?;
+    let mut output_manifest = OutputManifest::load(&output_dir)?;
+    let hooks: Hooks = package_cmd.hooks.into();
+    if !package_cmd.dry_run {
+        output_manifest.ensure_unmodified(&output_dir, &[&package.name], package_cmd.force)?;
+        hooks.pre_generate(&protogen, &protogen_path, &output_dir, package)?;
+    }
+    let files = generate_package(&protogen, &protogen_path, &package.name, &options)?;
+    let mut workspace_files = Vec::new();
+    if options.workspace {
+        workspace_files = options.rebase(
+            spec_dir,
+            vec![workspace_manifest(spec_dir, &protogen, &[package])?],
+        )?;
+    }
+
+    if package_cmd.dry_run {
+        let merges = output_manifest.merge_scaffolds(&output_dir, &package.name, &files)?;
+        let all_files: Vec<_> = files
+            .iter()
+            .chain(&workspace_files)
+            .chain(merges.iter().map(|x| &x.file))
+            .cloned()
+            .collect();
+        return match diff_files(&all_files)? {
+            Some(patch) => {
+                print!("{patch}");
+                Ok(exit::drift())
+            }
+            None => Ok(ExitCode::SUCCESS),
+        };
+    }
+
+    write_files(&workspace_files)?;
+    let inputs = package_inputs_hash(&protogen, &protogen_path, &package.name, &options)?;
+    output_manifest.write_package(&output_dir, package, inputs, &files)?;
+    output_manifest.save(&output_dir)?;
+    hooks.post_generate(
+        &protogen,
+        &protogen_path,
+        &output_dir,
+        package,
+        &mut output_manifest,
+    )?;
+    output_manifest.save(&output_dir)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn build_all_packages(generate_all_cmd: GenerateAllCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = generate_all_cmd.spec.load()?;
+
+    report_lint(&protogen)?;
+
+    generate_all_cmd.fetch.fetch(&generate_all_cmd.generate)?;
+    let mut options: GenerateOptions = generate_all_cmd.generate.into();
+    options.embed_package_spec = generate_all_cmd.spec.is_stdin();
+    let spec_dir = spec_dir(&protogen_path)?;
+    let output_dir = options.output_dir(spec_dir)?;
+    let mut output_manifest = OutputManifest::load(&output_dir)?;
+    let hooks: Hooks = generate_all_cmd.hooks.into();
+    let mut patches = String::new();
+    let packages = generate_all_cmd
+        .filter
+        .filter(protogen.dependency_order()?)?;
+    if !generate_all_cmd.dry_run {
+        let names: Vec<&str> = packages.iter().map(|x| x.name.as_str()).collect();
+        output_manifest.ensure_unmodified(&output_dir, &names, generate_all_cmd.force)?;
+    }
+    if options.workspace {
+        let workspace_file = options.rebase(
+            spec_dir,
+            vec![workspace_manifest(spec_dir, &protogen, &packages)?],
+        )?;
+        if generate_all_cmd.dry_run {
+            if let Some(patch) = diff_files(&workspace_file)? {
+                patches.push_str(&patch);
+            }
+        } else {
+            write_files(&workspace_file)?;
+        }
+    }
+    let jobs = match generate_all_cmd.jobs {
+        Some(jobs) => jobs,
+        None => std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN),
+    };
+    // Packages of a level are rendered concurrently, and written before the
+    // next level is rendered.
+    for level in protogen.dependency_levels()? {
+        let level: Vec<&RustPackage> = level
+            .into_iter()
+            .filter(|x| packages.iter().any(|package| package.name == x.name))
+            .collect();
+        if !generate_all_cmd.dry_run {
+            for package in &level {
+                hooks.pre_generate(&protogen, &protogen_path, &output_dir, package)?;
+            }
+        }
+        let generated = generate_packages(&protogen, &protogen_path, &level, &options, jobs)?;
+        for (package, files) in level.into_iter().zip(generated) {
+            if generate_all_cmd.dry_run {
+                let merges = output_manifest.merge_scaffolds(&output_dir, &package.name, &files)?;
+                let files: Vec<_> = files
+                    .into_iter()
+                    .chain(merges.into_iter().map(|x| x.file))
+                    .collect();
+                if let Some(patch) = diff_files(&files)? {
+                    patches.push_str(&patch);
+                }
+            } else {
+                let inputs =
+                    package_inputs_hash(&protogen, &protogen_path, &package.name, &options)?;
+                output_manifest.write_package(&output_dir, package, inputs, &files)?;
+                // Saved as each package is written, so files written before a
+                // failure are not taken for hand edits on the next run.
+                output_manifest.save(&output_dir)?;
+                hooks.post_generate(
+                    &protogen,
+                    &protogen_path,
+                    &output_dir,
+                    package,
+                    &mut output_manifest,
+                )?;
+                output_manifest.save(&output_dir)?;
+                info!(package = %package.name, "Generated package");
+            }
+        }
+    }
+    if !generate_all_cmd.dry_run && !generate_all_cmd.skip_targets {
+        run_targets(&protogen, &protogen_path, &packages)?;
+    }
+
+    if !patches.is_empty() {
+        print!("{patches}");
+        return Ok(exit::drift());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn validate_spec(validate_cmd: ValidateCommand) -> anyhow::Result<ExitCode> {
+    let diagnostics = match validate_cmd.spec.load() {
+        Ok((protogen_path, protogen)) => validate(&protogen, &protogen_path),
+        Err(error) => vec![Diagnostic {
+            level: Level::Error,
+            package: None,
+            message: format!("{error:#}"),
+        }],
+    };
+
+    match validate_cmd.format {
+        OutputFormat::Text => {
+            for diagnostic in &diagnostics {
+                println!("{diagnostic}");
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&diagnostics)?),
+    }
+
+    if diagnostics.iter().any(|x| x.level == Level::Error) {
+        return Ok(exit::spec());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn create_workspace(init_cmd: InitCommand) -> anyhow::Result<ExitCode> {
+    let files = init_workspace(&init_cmd.dir, &init_cmd.package.into(), init_cmd.protofetch)?;
+    write_files(&files)?;
+    for file in &files {
+        info!(path = %file.path.display(), "Created file");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn add_spec_package(add_package_cmd: AddPackageCommand) -> anyhow::Result<ExitCode> {
+    add_package_cmd.spec.ensure_file()?;
+    let (protogen_path, mut protogen) = add_package_cmd.spec.load()?;
+    let spec_dir = spec_dir(&protogen_path)?;
+
+    let package = NewPackage {
+        protogen_dependencies: add_package_cmd.protogen_dependencies,
+        ..add_package_cmd.package.into()
+    };
+    let files = add_package(&mut protogen, spec_dir, &package)?;
+    protogen.save(&protogen_path)?;
+    write_files(&files)?;
+
+    info!(
+        package = %package.name,
+        spec = %protogen_path.display(),
+        "Added package"
+    );
+    for file in &files {
+        info!(path = %file.path.display(), "Created file");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn add_spec_proto(new_proto_cmd: NewProtoCommand) -> anyhow::Result<ExitCode> {
+    new_proto_cmd.spec.ensure_file()?;
+    let (protogen_path, mut protogen) = new_proto_cmd.spec.load()?;
+    let spec_dir = spec_dir(&protogen_path)?;
+
+    let file = add_proto(
+        &mut protogen,
+        spec_dir,
+        &new_proto_cmd.package,
+        &new_proto_cmd.name,
+    )?;
+    protogen.save(&protogen_path)?;
+    let exists = file.path.exists();
+    write_files(std::slice::from_ref(&file))?;
+
+    info!(
+        package = %new_proto_cmd.package,
+        spec = %protogen_path.display(),
+        "Added proto file"
+    );
+    if exists {
+        info!(path = %display_path(&file.path), "Kept existing file");
+    } else {
+        info!(path = %display_path(&file.path), "Created file");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn vendor_remote_protos(vendor_cmd: VendorCommand) -> anyhow::Result<ExitCode> {
+    vendor_cmd.spec.ensure_file()?;
+    let (protogen_path, mut protogen) = vendor_cmd.spec.load()?;
+    let vendor_dir = match &vendor_cmd.dir {
+        Some(dir) => env::current_dir()?.join(dir),
+        None => spec_dir(&protogen_path)?.join("third_party").join("proto"),
+    };
+
+    let vendored = vendor_protos(
+        &mut protogen,
+        &protogen_path,
+        &vendor_dir,
+        vendor_cmd.protofetch_path.as_deref(),
+    )?;
+    protogen.save(&protogen_path)?;
+
+    for dir in &vendored {
+        info!(dir = %display_path(dir), "Vendored protos");
+    }
+    if vendored.is_empty() {
+        info!("Spec has no remote proto dependencies");
+    } else if vendor_cmd.protofetch_path.is_some() {
+        info!("Packages now include the vendored protofetch protos, drop `--protofetch-path` when generating");
+    }
+    Ok(ExitCode::SUCCESS)
+}
 
+fn push_bsr_modules(bsr_push_cmd: BsrPushCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = bsr_push_cmd.spec.load()?;
+    let packages = bsr_push_cmd.filter.filter(protogen.dependency_order()?)?;
     ensure!(
-        package_spec.path.is_relative(),
-        "Package spec path for `{}` was not relative",
-        package_spec.name
+        packages.iter().any(|x| x.bsr_module.is_some()),
+        "No selected package has a `bsr_module` to push to"
     );
-    let package_spec_src_path = package_spec.path.join("src");
-    create_dir_all(&package_spec_src_path).with_context(|| {
+    push_packages(
+        &protogen,
+        spec_dir(&protogen_path)?,
+        &packages,
+        &BsrPushOptions {
+            labels: bsr_push_cmd.label,
+            create: bsr_push_cmd.create,
+            dry_run: bsr_push_cmd.dry_run,
+        },
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn pull_bsr_modules(bsr_pull_cmd: BsrPullCommand) -> anyhow::Result<ExitCode> {
+    if bsr_pull_cmd.pin {
+        bsr_pull_cmd.spec.ensure_file()?;
+    }
+    let (protogen_path, mut protogen) = bsr_pull_cmd.spec.load()?;
+    let package_names: Vec<String> = bsr_pull_cmd
+        .filter
+        .filter(protogen.dependency_order()?)?
+        .into_iter()
+        .map(|x| x.name.to_owned())
+        .collect();
+    let pinned = pull_packages(
+        &mut protogen,
+        spec_dir(&protogen_path)?,
+        &package_names,
+        bsr_pull_cmd.pin,
+    )?;
+    if !pinned.is_empty() {
+        protogen.save(&protogen_path)?;
+        for bsr in &pinned {
+            info!(module = %bsr, "Pinned BSR module");
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_graph(graph_cmd: GraphCommand) -> anyhow::Result<ExitCode> {
+    let (_, protogen) = graph_cmd.spec.load()?;
+    print!("{}", render_graph(&protogen, graph_cmd.format));
+    Ok(ExitCode::SUCCESS)
+}
+
+fn check_packages(check_cmd: CheckCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = check_cmd.spec.load()?;
+
+    report_lint(&protogen)?;
+
+    let mut options: GenerateOptions = check_cmd.generate.into();
+    options.embed_package_spec = check_cmd.spec.is_stdin();
+    let packages = check_cmd.filter.filter(protogen.dependency_order()?)?;
+    let mut stale_count = 0;
+    let mut generated = Vec::new();
+    if options.workspace {
+        let spec_dir = spec_dir(&protogen_path)?;
+        generated.push((
+            None,
+            options.rebase(
+                spec_dir,
+                vec![workspace_manifest(spec_dir, &protogen, &packages)?],
+            )?,
+        ));
+    }
+    // Packages generated from unchanged inputs whose generated files are
+    // unchanged since are not generated again.
+    let output_dir = options.output_dir(spec_dir(&protogen_path)?)?;
+    let output_manifest = OutputManifest::load(&output_dir)?;
+    for package in packages {
+        let inputs = package_inputs_hash(&protogen, &protogen_path, &package.name, &options)?;
+        if output_manifest.is_fresh(&output_dir, &package.name, &inputs)? {
+            debug!(package = %package.name, "Package is up to date");
+            continue;
+        }
+        generated.push((
+            Some(&package.name),
+            generate_package(&protogen, &protogen_path, &package.name, &options)?,
+        ));
+    }
+    for (package_name, files) in generated {
+        for stale in stale_files(&files)? {
+            let status = match stale.status {
+                FileStatus::Added => "missing",
+                FileStatus::Modified => "modified",
+            };
+            warn!(
+                status,
+                path = %display_path(&stale.file.path),
+                package = package_name.map(String::as_str),
+                "Generated file is out of date"
+            );
+            stale_count += 1;
+        }
+        if check_cmd.diff {
+            if let Some(patch) = diff_files(&files)? {
+                print!("{patch}");
+            }
+        }
+    }
+
+    if stale_count > 0 {
+        error!(
+            count = stale_count,
+            "Generated files are out of date; run `generate-all` to update them"
+        );
+        return Ok(exit::drift());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn clean_packages(clean_cmd: CleanCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = clean_cmd.spec.load()?;
+    let spec_dir = spec_dir(&protogen_path)?;
+    let mut output_manifest = OutputManifest::load(spec_dir)?;
+
+    for name in &clean_cmd.packages {
+        ensure!(
+            output_manifest.packages.contains_key(name),
+            "No outputs are recorded for package `{name}`"
+        );
+    }
+    let names: Vec<String> = output_manifest
+        .packages
+        .keys()
+        .filter(|name| {
+            if clean_cmd.stale {
+                protogen.rust_package(name).is_none()
+            } else {
+                clean_cmd.packages.is_empty() || clean_cmd.packages.contains(name)
+            }
+        })
+        .cloned()
+        .collect();
+
+    for name in names {
+        let removed = clean_package(
+            spec_dir,
+            &output_manifest.packages[&name],
+            clean_cmd.remove_dirs,
+            clean_cmd.dry_run,
+        )?;
+        for path in removed {
+            let action = if clean_cmd.dry_run {
+                "Would remove"
+            } else {
+                "Removed"
+            };
+            info!(package = %name, path = %display_path(&path), "{action} file");
+        }
+        if !clean_cmd.dry_run {
+            output_manifest.packages.remove(&name);
+        }
+    }
+
+    if !clean_cmd.dry_run {
+        output_manifest.save(spec_dir)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_packages(list_cmd: ListCommand) -> anyhow::Result<ExitCode> {
+    let (_, protogen) = list_cmd.spec.load()?;
+    let packages = list_packages(&protogen);
+    match list_cmd.format {
+        OutputFormat::Text => {
+            for package in &packages {
+                println!("{package}");
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&packages)?),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn bump_package_version(bump_version_cmd: BumpVersionCommand) -> anyhow::Result<ExitCode> {
+    bump_version_cmd.spec.ensure_file()?;
+    let (protogen_path, mut protogen) = bump_version_cmd.spec.load()?;
+    let package = protogen.package(&bump_version_cmd.package)?;
+
+    let level = match bump_version_cmd.level {
+        BumpLevel::Auto => {
+            let baseline_path = bump_version_cmd
+                .baseline
+                .as_ref()
+                .context("Expected `--baseline` with `--level auto`")?;
+            let baseline = load_descriptor_set(baseline_path)?;
+            let current = compile_package_descriptors(&protogen, &protogen_path, &package.name)?;
+            let report = diff_descriptor_sets(&baseline, &current, &package.proto_package_name);
+            for change in &report.changes {
+                info!(kind = %change.kind, element = %change.path, "{}", change.description);
+            }
+            match report.max_kind() {
+                Some(kind) if kind.is_breaking() => BumpLevel::Major,
+                Some(_) => BumpLevel::Minor,
+                None => BumpLevel::Patch,
+            }
+        }
+        level => level,
+    };
+
+    let version = semver::Version::parse(&package.version).with_context(|| {
         format!(
-            "Failed to create package source path `{}`",
-            package_spec_src_path.to_string_lossy()
+            "Failed to parse version `{}` of package `{}`",
+            package.version, package.name
         )
     })?;
-
-    let mut rel_protogen_path = PathBuf::default();
-    package_spec
-        .path
-        .iter()
-        .for_each(|_| rel_protogen_path = rel_protogen_path.join(".."));
-    rel_protogen_path = rel_protogen_path.join(
-        protogen_path
-            .file_name()
-            .context("Expected file_name from protogen_path")?,
+    let bumped = bump_version(&version, level);
+    info!(
+        package = %package.name,
+        from = %version,
+        to = %bumped,
+        "Bumping package version"
     );
 
-    // let tmp_dir = tempfile::Builder::new()
-    //     .prefix("rust-build")
-    //     .tempdir()
-    //     .context("Failed to create tempdir with prefix `rust-build`")?;
+    // Dependents pin the version of the bumped package in their manifests.
+    let package_name = package.name.to_owned();
+    let names: Vec<String> = protogen
+        .dependency_order()?
+        .into_iter()
+        .filter(|x| x.name.eq(&package_name) || x.protogen_dependencies.contains(&package_name))
+        .map(|x| x.name.to_owned())
+        .collect();
+    let options: GenerateOptions = bump_version_cmd.generate.into();
+    let output_dir = options.output_dir(spec_dir(&protogen_path)?)?;
+    let mut output_manifest = OutputManifest::load(&output_dir)?;
+    output_manifest.ensure_unmodified(
+        &output_dir,
+        &names.iter().map(String::as_str).collect::<Vec<_>>(),
+        bump_version_cmd.force,
+    )?;
 
-    let mut manifest: CargoManifest = toml::from_str(include_str!("package_template.toml"))
-        .context("Failed to decode package_template.toml")?;
+    protogen
+        .rust_package_mut(&package_name)
+        .context("Expected package in protogen spec")?
+        .version = bumped.to_string();
+    save_and_regenerate(
+        &protogen,
+        &protogen_path,
+        &names,
+        &options,
+        &bump_version_cmd.hooks.into(),
+        &output_dir,
+        &mut output_manifest,
+    )?;
 
-    manifest.package.name = package_spec.name.to_owned();
-    manifest.package.version = package_spec.version.to_owned();
-
-    create_dir_all(&package_spec.path).context("Failed to create package_spec parent path")?;
+    Ok(ExitCode::SUCCESS)
+}
 
-    {
-        let manifest_path = package_spec.path.join("Cargo.toml");
-        let mut manifest_out = BufWriter::new(File::create(&manifest_path).with_context(|| {
-            format!(
-                "Failed to open path `{}` for writing package manifest",
-                manifest_path.to_string_lossy()
-            )
-        })?);
-        write!(
-            manifest_out,
-            "{}",
-            toml::to_string_pretty(&manifest).context("Failed to serialize cargo manifest toml")?
+/// Saves `protogen` to `protogen_path` and [regenerates](regenerate_packages)
+/// the packages named `names`, restoring the previous spec file if that
+/// fails, so the spec is not left with versions nothing was generated for.
+/// The spec is saved first as hooks may build the packages from it.
+fn save_and_regenerate(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    names: &[String],
+    options: &GenerateOptions,
+    hooks: &Hooks,
+    output_dir: &Path,
+    output_manifest: &mut OutputManifest,
+) -> anyhow::Result<()> {
+    let previous = fs::read(protogen_path).with_context(|| {
+        format!(
+            "Failed to read spec at path `{}`",
+            protogen_path.to_string_lossy()
         )
-        .with_context(|| {
+    })?;
+    protogen.save(protogen_path)?;
+    let result = regenerate_packages(
+        protogen,
+        protogen_path,
+        names,
+        options,
+        hooks,
+        output_dir,
+        output_manifest,
+    );
+    if result.is_err() {
+        fs::write(protogen_path, previous).with_context(|| {
             format!(
-                "Failed to write cargo manifest toml to path `{}`",
-                manifest_path.to_string_lossy()
+                "Failed to restore spec at path `{}`",
+                protogen_path.to_string_lossy()
             )
         })?;
     }
+    result
+}
 
-    {
-        let package_spec_path = package_spec_src_path.join("package_spec.json");
-        serde_json::to_writer_pretty(
-            BufWriter::new(File::create(&package_spec_path).with_context(|| {
-                format!(
-                    "Failed to open path `{}` for writing package spec",
-                    package_spec_path.to_string_lossy()
-                )
-            })?),
-            &package_spec,
-        )
-        .with_context(|| {
-            format!(
-                "Failed to write package spec to path `{}`",
-                package_spec_path.to_string_lossy()
-            )
+/// Generates and writes the packages named `names`, running the hooks around
+/// each. `output_manifest` is saved as each package is written.
+fn regenerate_packages(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    names: &[String],
+    options: &GenerateOptions,
+    hooks: &Hooks,
+    output_dir: &Path,
+    output_manifest: &mut OutputManifest,
+) -> anyhow::Result<()> {
+    for name in names {
+        let package = protogen.package(name)?;
+        hooks.pre_generate(protogen, protogen_path, output_dir, package)?;
+        let files = generate_package(protogen, protogen_path, &package.name, options)?;
+        let inputs = package_inputs_hash(protogen, protogen_path, &package.name, options)?;
+        output_manifest.write_package(output_dir, package, inputs, &files)?;
+        output_manifest.save(output_dir)?;
+        hooks.post_generate(
+            protogen,
+            protogen_path,
+            output_dir,
+            package,
+            output_manifest,
+        )?;
+        output_manifest.save(output_dir)?;
+        info!(package = %package.name, "Generated package");
+    }
+    Ok(())
+}
+
+fn publish_all_packages(publish_cmd: PublishCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = publish_cmd.spec.load()?;
+    publish_packages(
+        &protogen,
+        spec_dir(&protogen_path)?,
+        &PublishOptions {
+            dry_run: publish_cmd.dry_run,
+            index_timeout: Duration::from_secs(publish_cmd.index_timeout),
+        },
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn watch_spec(watch_cmd: WatchCommand) -> anyhow::Result<ExitCode> {
+    watch_cmd.spec.ensure_file()?;
+    let (protogen_path, _) = watch_cmd.spec.load()?;
+    watch_cmd.fetch.fetch(&watch_cmd.generate)?;
+    watch_packages(
+        &protogen_path,
+        &watch_cmd.generate.into(),
+        &watch_cmd.hooks.into(),
+        watch_cmd.force,
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn verify_generated_package(verify_cmd: VerifyCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = verify_cmd.spec.load()?;
+
+    report_lint(&protogen)?;
+
+    verify_cmd.fetch.fetch(&verify_cmd.generate)?;
+    if verify_package(
+        &protogen,
+        &protogen_path,
+        &verify_cmd.package,
+        verify_cmd.generate.into(),
+        verify_cmd.test,
+    )? {
+        info!(package = %verify_cmd.package, "Generated package verified");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        error!(package = %verify_cmd.package, "Generated package failed to build");
+        Ok(ExitCode::from(exit::FAILURE))
+    }
+}
+
+fn report_breaking_changes(breaking_cmd: BreakingCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = breaking_cmd.spec.load()?;
+    let packages = breaking_cmd.filter.filter(protogen.dependency_order()?)?;
+    let changes: Vec<_> =
+        package_changes(&protogen, &protogen_path, &packages, &breaking_cmd.against)?
+            .into_iter()
+            .filter(|x| breaking_cmd.all || x.change.kind.is_breaking())
+            .collect();
+    match breaking_cmd.format {
+        OutputFormat::Text => {
+            for change in &changes {
+                println!("{change}");
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&changes)?),
+    }
+
+    let breaking_count = changes
+        .iter()
+        .filter(|x| x.change.kind.is_breaking())
+        .count();
+    if breaking_count > 0 {
+        error!(
+            count = breaking_count,
+            against = %breaking_cmd.against,
+            "Protos have breaking changes"
+        );
+        return Ok(exit::breaking());
+    }
+    info!(against = %breaking_cmd.against, "Protos have no breaking changes");
+    Ok(ExitCode::SUCCESS)
+}
+
+fn lint_spec_protos(lint_cmd: LintCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = lint_cmd.spec.load()?;
+
+    report_lint(&protogen)?;
+
+    let mut findings = Vec::new();
+    for package in lint_cmd.filter.filter(protogen.dependency_order()?)? {
+        let fds = compile_package_descriptors(&protogen, &protogen_path, &package.name)?;
+        findings.extend(lint_protos(&protogen, package, &fds));
+    }
+    match lint_cmd.format {
+        OutputFormat::Text => {
+            for finding in &findings {
+                println!("{finding}");
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&findings)?),
+    }
+
+    let denied = findings
+        .iter()
+        .filter(|x| x.finding.severity == Severity::Deny)
+        .count();
+    if denied > 0 {
+        error!(count = denied, "Protos violate denied lint rules");
+        return Ok(ExitCode::from(exit::FAILURE));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn migrate_spec(migrate_cmd: MigrateCommand) -> anyhow::Result<ExitCode> {
+    migrate_cmd.spec.ensure_file()?;
+    let protogen_path = migrate_cmd.spec.path()?;
+    let contents = fs::read_to_string(&protogen_path).map_err(|source| SpecError::Read {
+        path: protogen_path.to_owned(),
+        source,
+    })?;
+    let mut document: serde_json::Value =
+        serde_json::from_str(&contents).map_err(|source| SpecError::Parse {
+            path: protogen_path.to_owned(),
+            source,
+        })?;
+    let applied = migrate::migrate(&mut document, &protogen_path)?;
+    if applied.is_empty() {
+        info!(
+            schema = migrate::SCHEMA_VERSION,
+            "Spec already uses the latest format"
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+    for step in &applied {
+        info!("{step}");
+    }
+
+    let migrated = migrate::to_json(&document).context("Failed to serialize migrated spec")?;
+    ProtogenSpec::from_reader(migrated.as_bytes(), &protogen_path)
+        .context("Migrated spec does not match the latest format")?;
+    let files = [GeneratedFile {
+        path: protogen_path,
+        contents: migrated,
+        scaffold: false,
+    }];
+    if let Some(patch) = diff_files(&files)? {
+        print!("{patch}");
+    }
+    if migrate_cmd.dry_run {
+        return Ok(exit::drift());
+    }
+    write_files(&files)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn release_packages(release_cmd: ReleaseCommand) -> anyhow::Result<ExitCode> {
+    release_cmd.spec.ensure_file()?;
+    let (protogen_path, mut protogen) = release_cmd.spec.load()?;
+    let spec_dir = spec_dir(&protogen_path)?.to_owned();
+    ensure_new_tag(&spec_dir, &release_cmd.tag)?;
+    let since = match release_cmd.since {
+        Some(since) => since,
+        None => last_tag(&spec_dir)?,
+    };
+
+    let packages = release_cmd.filter.filter(protogen.dependency_order()?)?;
+    let releases = plan_release(&protogen, &protogen_path, &packages, &since)?;
+    if releases.is_empty() {
+        info!(since = %since, "No proto changes to release");
+        return Ok(ExitCode::SUCCESS);
+    }
+    for release in &releases {
+        info!(
+            package = %release.package,
+            from = %release.from,
+            to = %release.to,
+            "Releasing package"
+        );
+    }
+
+    let options: GenerateOptions = release_cmd.generate.into();
+    let output_dir = options.output_dir(&spec_dir)?;
+    let package_dirs = releases
+        .iter()
+        .map(|release| {
+            let package = protogen.package(&release.package)?;
+            Ok(normalize_path(&output_dir.join(&package.path)))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let changelogs = releases
+        .iter()
+        .zip(&package_dirs)
+        .map(|(release, dir)| changelog_file(dir, release, &releases))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if release_cmd.dry_run {
+        if let Some(patch) = diff_files(&changelogs)? {
+            print!("{patch}");
+        }
+        return Ok(exit::drift());
+    }
+    ensure_clean(&spec_dir)?;
+    let mut output_manifest = OutputManifest::load(&output_dir)?;
+    output_manifest.ensure_unmodified(
+        &output_dir,
+        &releases
+            .iter()
+            .map(|x| x.package.as_str())
+            .collect::<Vec<_>>(),
+        release_cmd.force,
+    )?;
+
+    for release in &releases {
+        protogen
+            .rust_package_mut(&release.package)
+            .context("Expected package in protogen spec")?
+            .version = release.to.to_string();
+    }
+    save_and_regenerate(
+        &protogen,
+        &protogen_path,
+        &releases
+            .iter()
+            .map(|x| x.package.to_owned())
+            .collect::<Vec<_>>(),
+        &options,
+        &release_cmd.hooks.into(),
+        &output_dir,
+        &mut output_manifest,
+    )?;
+    write_files(&changelogs)?;
+
+    let mut paths = vec![protogen_path.to_owned(), output_dir.join(LOCK_FILE_NAME)];
+    paths.extend(package_dirs);
+    commit_and_tag(&spec_dir, &paths, &release_cmd.tag)?;
+    info!(tag = %release_cmd.tag, "Tagged release");
+    Ok(ExitCode::SUCCESS)
+}
+
+fn document_packages(doc_cmd: DocCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = doc_cmd.spec.load()?;
+    let out_dir = match &doc_cmd.out_dir {
+        Some(out_dir) => Some(env::current_dir()?.join(out_dir)),
+        None => None,
+    };
+
+    let mut files = Vec::new();
+    for package in doc_cmd.filter.filter(protogen.dependency_order()?)? {
+        let fds = compile_package_descriptors(&protogen, &protogen_path, &package.name)?;
+        let contents = render_markdown(package, &fds);
+        match &out_dir {
+            Some(out_dir) => files.push(GeneratedFile {
+                path: out_dir.join(format!("{}.md", package.proto_package_name)),
+                contents,
+                scaffold: false,
+            }),
+            None => println!("{contents}"),
+        }
+    }
+    write_files(&files)?;
+    for file in &files {
+        info!(path = %display_path(&file.path), "Wrote API reference");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn write_descriptor_set(descriptor_cmd: DescriptorCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = descriptor_cmd.spec.load()?;
+    let package = protogen.package(&descriptor_cmd.package)?;
+    let fds = export_descriptor_set(
+        compile_package_descriptors(&protogen, &protogen_path, &package.name)?,
+        package,
+        descriptor_cmd.include_imports,
+        descriptor_cmd.include_source_info,
+    );
+    let bytes = encode_descriptor_set(&fds, descriptor_cmd.format)?;
+
+    if descriptor_cmd.out.as_os_str() == "-" {
+        io::stdout()
+            .lock()
+            .write_all(&bytes)
+            .context("Failed to write descriptor set to standard out")?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let out = &descriptor_cmd.out;
+    if let Some(parent) = out.parent().filter(|x| !x.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).with_context(|| {
+            format!("Failed to create directory `{}`", parent.to_string_lossy())
         })?;
     }
+    fs::write(out, bytes).with_context(|| {
+        format!(
+            "Failed to write descriptor set to path `{}`",
+            out.to_string_lossy()
+        )
+    })?;
+    info!(
+        package = %package.name,
+        files = fds.file.len(),
+        path = %out.display(),
+        "Wrote descriptor set"
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_environment(env_cmd: EnvCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = env_cmd.spec.load()?;
+    let options: GenerateOptions = env_cmd.generate.into();
+    let environment = resolve_environment(&protogen, &protogen_path, &options)?;
+    match env_cmd.format {
+        OutputFormat::Text => print!("{environment}"),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&environment)?),
+    }
+    Ok(ExitCode::SUCCESS)
+}
 
-    let mut handlebars = Handlebars::new();
+fn print_package_stats(stats_cmd: StatsCommand) -> anyhow::Result<ExitCode> {
+    let (protogen_path, protogen) = stats_cmd.spec.load()?;
+    let spec_dir = spec_dir(&protogen_path)?;
+    let output_manifest = OutputManifest::load(spec_dir)?;
+    let mut stats = Vec::new();
+    for package in stats_cmd.filter.filter(protogen.dependency_order()?)? {
+        let fds = compile_package_descriptors(&protogen, &protogen_path, &package.name)?;
+        stats.push(package_stats(package, &fds, spec_dir, &output_manifest)?);
+    }
+    match stats_cmd.format {
+        OutputFormat::Text => {
+            for package in &stats {
+                println!("{package}");
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&stats)?),
+    }
+    Ok(ExitCode::SUCCESS)
+}
 
-    {
-        let templates = [
-            ("lib.rs", include_str!("templates/lib.rs.hbs")),
-            ("build.rs", include_str!("templates/build.rs.hbs")),
-            (
-                "prost_serde.rs",
-                include_str!("templates/prost_serde.rs.hbs"),
-            ),
-        ];
-        for (name, tpl_str) in templates {
-            handlebars
-                .register_template_string(name, tpl_str)
-                .with_context(|| format!("Failed to register template `{name}`"))?;
+fn print_completions(completions_cmd: CompletionsCommand) -> anyhow::Result<ExitCode> {
+    let mut command = Cli::command();
+    let bin_name = command.get_name().to_owned();
+    // Rendered up front since clap_complete panics on write errors, such as
+    // a closed pipe.
+    let mut script = Vec::new();
+    clap_complete::generate(completions_cmd.shell, &mut command, bin_name, &mut script);
+    let mut stdout = io::stdout();
+    match stdout.write_all(&script).and_then(|_| stdout.flush()) {
+        Err(error) if error.kind() != io::ErrorKind::BrokenPipe => {
+            Err(error).context("Failed to write completions")
         }
+        _ => Ok(ExitCode::SUCCESS),
+    }
+}
+
+/// Bumps `version` by `level`, treating the leftmost non-zero component as
+/// the major version as cargo does for `0.x` versions. A pre-release of the
+/// version the bump would reach is released instead, so `1.2.0-rc.1` bumps to
+/// `1.2.0` rather than `1.2.1`. Build metadata is dropped.
+fn bump_version(version: &semver::Version, level: BumpLevel) -> semver::Version {
+    let component = match (level, version.major, version.minor) {
+        (BumpLevel::Major, 0, 0) => 2,
+        (BumpLevel::Major, 0, _) => 1,
+        (BumpLevel::Major, _, _) => 0,
+        (BumpLevel::Minor, 0, _) => 2,
+        (BumpLevel::Minor, _, _) => 1,
+        (BumpLevel::Patch | BumpLevel::Auto, _, _) => 2,
+    };
+    let mut parts = [version.major, version.minor, version.patch];
+    if version.pre.is_empty() || parts[component + 1..].iter().any(|x| *x != 0) {
+        parts[component] += 1;
+        parts[component + 1..].fill(0);
     }
+    semver::Version::new(parts[0], parts[1], parts[2])
+}
 
-    {
-        let outputs = [
-            (
-                "build.rs",
-                json!({
-                    "rel_protogen_path": rel_protogen_path.to_string_lossy().as_ref()
-                }),
-                package_spec.path.join("build.rs"),
+/// Prints spec lint findings, failing if any rule is set to `deny`.
+fn report_lint(protogen: &ProtogenSpec) -> anyhow::Result<()> {
+    let findings = protogen.lint();
+    for finding in &findings {
+        match finding.severity {
+            Severity::Deny => error!(
+                rule = %finding.rule,
+                package = %finding.package,
+                "{}",
+                finding.message
             ),
-            ("lib.rs", json!({}), package_spec_src_path.join("lib.rs")),
-            (
-                "prost_serde.rs",
-                json!({}),
-                package_spec_src_path.join("prost_serde.rs"),
+            _ => warn!(
+                rule = %finding.rule,
+                package = %finding.package,
+                "{}",
+                finding.message
             ),
-        ];
-
-        for (name, data, path) in outputs {
-            handlebars
-                .render_to_write(
-                    name,
-                    &data,
-                    BufWriter::new(File::create(&path).with_context(|| {
-                        format!(
-                            "Failed to open path `{}` for writing {name} file",
-                            path.to_string_lossy()
-                        )
-                    })?),
-                )
-                .with_context(|| {
-                    format!(
-                        "Failed to render {name} template to path `{}`",
-                        path.to_string_lossy()
-                    )
-                })?;
         }
     }
-
+    let denied = findings
+        .iter()
+        .filter(|x| x.severity == Severity::Deny)
+        .count();
+    if denied > 0 {
+        return Err(SpecError::LintDenied { count: denied }.into());
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_matches_globs() {
+        assert!(glob_matches("*", "acme-things"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("acme-*", "acme-things"));
+        assert!(glob_matches("*-things", "acme-things"));
+        assert!(glob_matches("acme-?hings", "acme-things"));
+        assert!(glob_matches("a*-*s", "acme-things"));
+        assert!(glob_matches("*me*in*", "acme-things"));
+        assert!(glob_matches("acme-things", "acme-things"));
+    }
+
+    #[test]
+    fn it_rejects_non_matching_globs() {
+        assert!(!glob_matches("acme-*", "other-things"));
+        assert!(!glob_matches("*-gadgets", "acme-things"));
+        assert!(!glob_matches("a*-*x", "acme-things"));
+        assert!(!glob_matches("acme-?", "acme-things"));
+        assert!(!glob_matches("acme", "acme-things"));
+        assert!(!glob_matches("", "acme-things"));
+    }
+
+    fn bumped(version: &str, level: BumpLevel) -> String {
+        bump_version(&semver::Version::parse(version).unwrap(), level).to_string()
+    }
+
+    #[test]
+    fn it_bumps_versions() {
+        assert_eq!(bumped("0.0.3", BumpLevel::Major), "0.0.4");
+        assert_eq!(bumped("0.0.3", BumpLevel::Minor), "0.0.4");
+        assert_eq!(bumped("0.0.3", BumpLevel::Patch), "0.0.4");
+        assert_eq!(bumped("0.0.3", BumpLevel::Auto), "0.0.4");
+        assert_eq!(bumped("0.2.3", BumpLevel::Major), "0.3.0");
+        assert_eq!(bumped("0.2.3", BumpLevel::Minor), "0.2.4");
+        assert_eq!(bumped("0.2.3", BumpLevel::Patch), "0.2.4");
+        assert_eq!(bumped("0.2.3", BumpLevel::Auto), "0.2.4");
+        assert_eq!(bumped("1.2.3", BumpLevel::Major), "2.0.0");
+        assert_eq!(bumped("1.2.3", BumpLevel::Minor), "1.3.0");
+        assert_eq!(bumped("1.2.3", BumpLevel::Patch), "1.2.4");
+        assert_eq!(bumped("1.2.3", BumpLevel::Auto), "1.2.4");
+    }
+
+    #[test]
+    fn it_releases_pre_release_versions() {
+        assert_eq!(bumped("1.2.0-rc.1", BumpLevel::Patch), "1.2.0");
+        assert_eq!(bumped("1.2.0-rc.1", BumpLevel::Minor), "1.2.0");
+        assert_eq!(bumped("1.2.0-rc.1", BumpLevel::Major), "2.0.0");
+        assert_eq!(bumped("2.0.0-alpha", BumpLevel::Major), "2.0.0");
+        assert_eq!(bumped("1.2.3-rc.1", BumpLevel::Minor), "1.3.0");
+        assert_eq!(bumped("0.3.0-rc.1", BumpLevel::Major), "0.3.0");
+        assert_eq!(bumped("0.0.4-rc.1", BumpLevel::Major), "0.0.4");
+    }
+
+    #[test]
+    fn it_drops_build_metadata() {
+        assert_eq!(bumped("1.2.3+build.5", BumpLevel::Patch), "1.2.4");
+        assert_eq!(bumped("1.2.0-rc.1+build.5", BumpLevel::Patch), "1.2.0");
+    }
+}
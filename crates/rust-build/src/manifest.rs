@@ -0,0 +1,95 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use appbiotic_api_protogen_spec::Publish;
+
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CargoManifest {
+    pub package: CargoPackage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lib: Option<CargoLib>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub features: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, CargoPackageDep>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub build_dependencies: BTreeMap<String, CargoPackageDep>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dev_dependencies: BTreeMap<String, CargoPackageDep>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bin: Vec<CargoBin>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub example: Vec<CargoBin>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bench: Vec<CargoBin>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CargoBin {
+    pub name: String,
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_features: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub harness: Option<bool>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CargoLib {
+    pub crate_type: Vec<String>,
+}
+
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CargoPackage {
+    pub name: String,
+    pub version: String,
+    pub edition: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readme: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license_file: Option<PathBuf>,
+    /// Native library name, which generated packages set to their own name
+    /// so dependent build scripts receive their proto package spec.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publish: Option<Publish>,
+    /// Tool configuration such as `docs.rs`, kept as in the package template.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<toml::Table>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CargoPackageDep {
+    /// Empty for dependencies inherited from the workspace.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub version: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub optional: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_features: Option<bool>,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub workspace: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+}
+
+impl CargoPackageDep {
+    /// Whether the dependency comes from the default registry with default
+    /// features, so it can be inherited from `[workspace.dependencies]`.
+    pub fn is_shareable(&self) -> bool {
+        self.path.is_none() && self.registry.is_none() && self.default_features.is_none()
+    }
+}
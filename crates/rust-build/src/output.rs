@@ -0,0 +1,121 @@
+use std::{
+    env,
+    fs::{create_dir_all, read_to_string, write},
+    io::ErrorKind,
+    path::Path,
+};
+
+use anyhow::Context;
+use similar::TextDiff;
+use tracing::debug_span;
+
+use crate::generate::GeneratedFile;
+
+/// Writes generated files, creating parent directories as needed and leaving
+/// existing scaffold files untouched. Files whose contents are unchanged are
+/// not rewritten so their modification times, and cargo's fingerprints of
+/// them, stay the same.
+pub fn write_files(files: &[GeneratedFile]) -> anyhow::Result<()> {
+    let _span = debug_span!("write_files", files = files.len()).entered();
+    for StaleFile { file, .. } in stale_files(files)? {
+        if let Some(parent) = file.path.parent() {
+            create_dir_all(parent).with_context(|| {
+                format!(
+                    "Failed to create parent path `{}`",
+                    parent.to_string_lossy()
+                )
+            })?;
+        }
+        write(&file.path, &file.contents).with_context(|| {
+            format!(
+                "Failed to write generated file to path `{}`",
+                file.path.to_string_lossy()
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// How writing a generated file would change the tree.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Added,
+    Modified,
+}
+
+/// A generated file that differs from what is on disk.
+pub struct StaleFile<'a> {
+    pub file: &'a GeneratedFile,
+    pub status: FileStatus,
+    /// Contents currently on disk, empty for added files.
+    pub existing: String,
+}
+
+/// Finds the files writing `files` would add or modify, ignoring scaffold
+/// files that already exist.
+pub fn stale_files(files: &[GeneratedFile]) -> anyhow::Result<Vec<StaleFile<'_>>> {
+    let mut stale = Vec::new();
+    for file in files {
+        let existing = read_existing(&file.path)?;
+        if file.scaffold && existing.is_some() {
+            continue;
+        }
+        match existing {
+            Some(existing) if existing.eq(&file.contents) => {}
+            Some(existing) => stale.push(StaleFile {
+                file,
+                status: FileStatus::Modified,
+                existing,
+            }),
+            None => stale.push(StaleFile {
+                file,
+                status: FileStatus::Added,
+                existing: String::new(),
+            }),
+        }
+    }
+    Ok(stale)
+}
+
+/// Renders a unified diff of the changes writing `files` would make, or
+/// `None` when everything is up to date.
+pub fn diff_files(files: &[GeneratedFile]) -> anyhow::Result<Option<String>> {
+    let mut patch = String::new();
+    for stale in stale_files(files)? {
+        let display_path = display_path(&stale.file.path);
+        let old_header = match stale.status {
+            FileStatus::Modified => format!("a/{display_path}"),
+            FileStatus::Added => "/dev/null".to_owned(),
+        };
+        patch.push_str(
+            &TextDiff::from_lines(&stale.existing, &stale.file.contents)
+                .unified_diff()
+                .header(&old_header, &format!("b/{display_path}"))
+                .to_string(),
+        );
+    }
+    Ok((!patch.is_empty()).then_some(patch))
+}
+
+pub fn read_existing(path: &Path) -> anyhow::Result<Option<String>> {
+    match read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error).with_context(|| {
+            format!(
+                "Failed to read existing file at path `{}`",
+                path.to_string_lossy()
+            )
+        }),
+    }
+}
+
+/// Shows `path` relative to the working directory when it is below it.
+pub fn display_path(path: &Path) -> String {
+    env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
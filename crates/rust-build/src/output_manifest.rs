@@ -0,0 +1,437 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{read_dir, read_to_string, remove_dir, remove_dir_all, remove_file, write},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{ensure, Context};
+use appbiotic_api_protogen_spec::{paths::relative_path, RustPackage};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::{
+    generate::{generated_manifest_entries, GeneratedFile},
+    output::{display_path, write_files},
+};
+
+pub const LOCK_FILE_NAME: &str = "protogen.lock";
+/// Output manifest written before the lock file, read when there is no lock
+/// file yet and removed once the lock file is saved.
+const LEGACY_FILE_NAME: &str = ".protogen-outputs.json";
+
+/// The `protogen.lock` next to the spec, recording for each package hashes of
+/// its generation inputs and of the files generated from them. `check` skips
+/// packages whose inputs and outputs are unchanged, and `clean` removes the
+/// generated files even after a package is renamed or removed from the spec.
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+pub struct OutputManifest {
+    #[serde(default)]
+    pub packages: BTreeMap<String, PackageOutputs>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct PackageOutputs {
+    /// Package directory, relative to the spec directory.
+    pub path: PathBuf,
+    /// Hash of the generation inputs, empty when not known.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub inputs: String,
+    /// SHA-256 of each generated file, keyed by its path relative to the spec
+    /// directory, of only the generated entries for package manifests.
+    /// Scaffold files are not included as they hold hand-written code.
+    pub files: BTreeMap<PathBuf, String>,
+    /// Contents each scaffold file was last rendered with, keyed like
+    /// `files`. The base of three-way merges of template changes into edited
+    /// scaffold files.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub scaffolds: BTreeMap<PathBuf, String>,
+}
+
+/// A scaffold file with template changes merged into its edited contents.
+pub struct ScaffoldMerge {
+    /// The merged file, managed so it is written over the edited one.
+    pub file: GeneratedFile,
+    /// Whether the merged contents hold conflict markers.
+    pub conflicted: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct LegacyOutputManifest {
+    #[serde(default)]
+    packages: BTreeMap<String, LegacyPackageOutputs>,
+}
+
+#[derive(serde::Deserialize)]
+struct LegacyPackageOutputs {
+    path: PathBuf,
+    files: BTreeSet<PathBuf>,
+}
+
+impl OutputManifest {
+    /// Loads the lock file in `spec_dir`, which is empty when it does not
+    /// exist yet.
+    pub fn load(spec_dir: &Path) -> anyhow::Result<Self> {
+        let path = spec_dir.join(LOCK_FILE_NAME);
+        if let Some(contents) = read_optional(&path)? {
+            return serde_json::from_str(&contents).with_context(|| {
+                format!(
+                    "Failed to parse lock file at path `{}`",
+                    path.to_string_lossy()
+                )
+            });
+        }
+
+        let legacy_path = spec_dir.join(LEGACY_FILE_NAME);
+        let Some(contents) = read_optional(&legacy_path)? else {
+            return Ok(Self::default());
+        };
+        let legacy: LegacyOutputManifest = serde_json::from_str(&contents).with_context(|| {
+            format!(
+                "Failed to parse output manifest at path `{}`",
+                legacy_path.to_string_lossy()
+            )
+        })?;
+        Ok(Self {
+            packages: legacy
+                .packages
+                .into_iter()
+                .map(|(name, outputs)| {
+                    let files = outputs
+                        .files
+                        .into_iter()
+                        .map(|x| (x, String::new()))
+                        .collect();
+                    let outputs = PackageOutputs {
+                        path: outputs.path,
+                        inputs: String::new(),
+                        files,
+                        scaffolds: BTreeMap::new(),
+                    };
+                    (name, outputs)
+                })
+                .collect(),
+        })
+    }
+
+    pub fn save(&self, spec_dir: &Path) -> anyhow::Result<()> {
+        let path = spec_dir.join(LOCK_FILE_NAME);
+        let mut contents =
+            serde_json::to_string_pretty(self).context("Failed to serialize lock file")?;
+        contents.push('\n');
+        write(&path, contents).with_context(|| {
+            format!(
+                "Failed to write lock file to path `{}`",
+                path.to_string_lossy()
+            )
+        })?;
+
+        let legacy_path = spec_dir.join(LEGACY_FILE_NAME);
+        match remove_file(&legacy_path) {
+            Err(error) if error.kind() != ErrorKind::NotFound => Err(error).with_context(|| {
+                format!(
+                    "Failed to remove output manifest at path `{}`",
+                    legacy_path.to_string_lossy()
+                )
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `package` was generated from the same `inputs` and its
+    /// generated files in `spec_dir` are unchanged since, so generating it
+    /// again would not change anything.
+    pub fn is_fresh(&self, spec_dir: &Path, package: &str, inputs: &str) -> anyhow::Result<bool> {
+        let Some(outputs) = self.packages.get(package) else {
+            return Ok(false);
+        };
+        if outputs.inputs.is_empty() || outputs.inputs.ne(inputs) {
+            return Ok(false);
+        }
+        for (file, hash) in &outputs.files {
+            match read_optional(&spec_dir.join(file))? {
+                Some(contents) if matches_hash(file, &contents, hash) => {}
+                _ => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Generated files of `packages` in `spec_dir` whose contents differ from
+    /// what was last written, e.g. after being edited by hand. Removed files
+    /// are not included, as writing them again loses nothing.
+    pub fn modified_files(
+        &self,
+        spec_dir: &Path,
+        packages: &[&str],
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        let mut modified = Vec::new();
+        for outputs in packages.iter().filter_map(|x| self.packages.get(*x)) {
+            for (file, hash) in &outputs.files {
+                let path = spec_dir.join(file);
+                match read_optional(&path)? {
+                    Some(contents) if !matches_hash(file, &contents, hash) => modified.push(path),
+                    _ => {}
+                }
+            }
+        }
+        Ok(modified)
+    }
+
+    /// Checks that no generated files of `packages` in `spec_dir` were
+    /// modified since they were last written, unless `force` allows
+    /// overwriting them.
+    pub fn ensure_unmodified(
+        &self,
+        spec_dir: &Path,
+        packages: &[&str],
+        force: bool,
+    ) -> anyhow::Result<()> {
+        if force {
+            return Ok(());
+        }
+        let modified = self.modified_files(spec_dir, packages)?;
+        ensure!(
+            modified.is_empty(),
+            "Generated files were modified since they were last written, edit the protos or \
+             the spec instead, or pass `--force` to overwrite them:\n{}",
+            modified
+                .iter()
+                .map(|x| format!("  {}", display_path(x)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+        Ok(())
+    }
+
+    /// Replaces the recorded outputs of `package` with the generated `files`
+    /// and the hash of the `inputs` they were generated from.
+    pub fn record(
+        &mut self,
+        spec_dir: &Path,
+        package: &RustPackage,
+        inputs: String,
+        files: &[GeneratedFile],
+    ) -> anyhow::Result<()> {
+        let spec_dir = canonical_spec_dir(spec_dir)?;
+        self.packages.insert(
+            package.name.to_owned(),
+            PackageOutputs {
+                path: package.path.to_owned(),
+                inputs,
+                files: files
+                    .iter()
+                    .filter(|x| !x.scaffold)
+                    .map(|x| {
+                        (
+                            relative_path(&spec_dir, &x.path),
+                            file_hash(&x.path, &x.contents),
+                        )
+                    })
+                    .collect(),
+                scaffolds: files
+                    .iter()
+                    .filter(|x| x.scaffold)
+                    .map(|x| (relative_path(&spec_dir, &x.path), x.contents.to_owned()))
+                    .collect(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Updates the recorded hashes of the generated files of `package` in
+    /// `spec_dir` to their current contents, e.g. after a hook changed them.
+    pub fn rehash(&mut self, spec_dir: &Path, package: &str) -> anyhow::Result<()> {
+        let Some(outputs) = self.packages.get_mut(package) else {
+            return Ok(());
+        };
+        for (file, hash) in &mut outputs.files {
+            if let Some(contents) = read_optional(&spec_dir.join(file))? {
+                *hash = file_hash(file, &contents);
+            }
+        }
+        Ok(())
+    }
+
+    /// Three-way merges the template changes to the scaffold files among
+    /// `files` of `package` into the existing files that were edited, using
+    /// the contents they were last rendered with as the base. Scaffold files
+    /// rendered before bases were recorded are left alone.
+    pub fn merge_scaffolds(
+        &self,
+        spec_dir: &Path,
+        package: &str,
+        files: &[GeneratedFile],
+    ) -> anyhow::Result<Vec<ScaffoldMerge>> {
+        let Some(outputs) = self.packages.get(package) else {
+            return Ok(Vec::new());
+        };
+        let spec_dir = canonical_spec_dir(spec_dir)?;
+        let mut merges = Vec::new();
+        for file in files.iter().filter(|x| x.scaffold) {
+            let Some(base) = outputs.scaffolds.get(&relative_path(&spec_dir, &file.path)) else {
+                continue;
+            };
+            let Some(existing) = read_optional(&file.path)? else {
+                continue;
+            };
+            if base.eq(&file.contents) || existing.eq(&file.contents) {
+                continue;
+            }
+            let (contents, conflicted) = match diffy::merge(base, &existing, &file.contents) {
+                Ok(merged) => (merged, false),
+                Err(conflicted) => (conflicted, true),
+            };
+            if contents.ne(&existing) {
+                merges.push(ScaffoldMerge {
+                    file: GeneratedFile {
+                        path: file.path.to_owned(),
+                        contents,
+                        scaffold: false,
+                    },
+                    conflicted,
+                });
+            }
+        }
+        Ok(merges)
+    }
+
+    /// Writes the generated `files` of `package`, merging template changes
+    /// into edited scaffold files, and records them along with the hash of
+    /// their `inputs`.
+    pub fn write_package(
+        &mut self,
+        spec_dir: &Path,
+        package: &RustPackage,
+        inputs: String,
+        files: &[GeneratedFile],
+    ) -> anyhow::Result<()> {
+        let merges = self.merge_scaffolds(spec_dir, &package.name, files)?;
+        write_files(files)?;
+        for merge in &merges {
+            write_files(std::slice::from_ref(&merge.file))?;
+            let path = display_path(&merge.file.path);
+            if merge.conflicted {
+                warn!(
+                    path = %path,
+                    "Template changes conflict with edits, resolve the conflict markers"
+                );
+            } else {
+                info!(path = %path, "Merged template changes into scaffold file");
+            }
+        }
+        self.record(spec_dir, package, inputs, files)
+    }
+}
+
+fn canonical_spec_dir(spec_dir: &Path) -> anyhow::Result<PathBuf> {
+    spec_dir.canonicalize().with_context(|| {
+        format!(
+            "Failed to canonicalize spec directory `{}`",
+            spec_dir.to_string_lossy()
+        )
+    })
+}
+
+/// Removes the recorded outputs of a package, or its whole directory when
+/// `remove_dir` is set, returning the removed paths. Directories left empty
+/// within the package directory are removed as well. Nothing is removed when
+/// `dry_run` is set.
+pub fn clean_package(
+    spec_dir: &Path,
+    outputs: &PackageOutputs,
+    remove_dir: bool,
+    dry_run: bool,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let package_dir = spec_dir.join(&outputs.path);
+    if remove_dir {
+        if !package_dir.exists() {
+            return Ok(Vec::new());
+        }
+        if !dry_run {
+            remove_dir_all(&package_dir).with_context(|| {
+                format!(
+                    "Failed to remove package directory `{}`",
+                    package_dir.to_string_lossy()
+                )
+            })?;
+        }
+        return Ok(vec![package_dir]);
+    }
+
+    let mut removed = Vec::new();
+    for file in outputs.files.keys() {
+        let path = spec_dir.join(file);
+        if !path.is_file() {
+            continue;
+        }
+        if !dry_run {
+            remove_file(&path)
+                .with_context(|| format!("Failed to remove file `{}`", path.to_string_lossy()))?;
+            remove_empty_parents(&path, &package_dir)?;
+        }
+        removed.push(path);
+    }
+    Ok(removed)
+}
+
+/// Hash of the managed contents of the generated file at `path`. Package
+/// manifests keep entries added by hand when generated again, so only the
+/// entries written by the generator are hashed.
+fn file_hash(path: &Path, contents: &str) -> String {
+    let generated = path
+        .file_name()
+        .is_some_and(|x| x == "Cargo.toml")
+        .then(|| generated_manifest_entries(contents))
+        .flatten();
+    match generated {
+        Some(generated) => content_hash(generated.as_bytes()),
+        None => content_hash(contents.as_bytes()),
+    }
+}
+
+/// Whether `contents` of the generated file at `path` match the recorded
+/// `hash`, which lock files written before only generated entries of package
+/// manifests were hashed recorded for the whole file.
+fn matches_hash(path: &Path, contents: &str, hash: &str) -> bool {
+    file_hash(path, contents).eq(hash) || content_hash(contents.as_bytes()).eq(hash)
+}
+
+/// Hex encoded SHA-256 of `bytes`.
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect()
+}
+
+pub fn read_optional(path: &Path) -> anyhow::Result<Option<String>> {
+    match read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error)
+            .with_context(|| format!("Failed to read file at path `{}`", path.to_string_lossy())),
+    }
+}
+
+/// Removes the empty ancestors of `path` up to and including `root`.
+fn remove_empty_parents(path: &Path, root: &Path) -> anyhow::Result<()> {
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if !current.starts_with(root) {
+            break;
+        }
+        let is_empty = read_dir(current)
+            .with_context(|| format!("Failed to read directory `{}`", current.to_string_lossy()))?
+            .next()
+            .is_none();
+        if !is_empty {
+            break;
+        }
+        remove_dir(current).with_context(|| {
+            format!("Failed to remove directory `{}`", current.to_string_lossy())
+        })?;
+        dir = current.parent();
+    }
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use std::fmt;
+
+use appbiotic_api_proto_lint::{Finding, RuleSet};
+use appbiotic_api_protogen_spec::{lint::ProtoLintRule, ProtogenSpec, RustPackage};
+use prost_types::FileDescriptorSet;
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ProtoLintFinding {
+    pub package: String,
+    #[serde(flatten)]
+    pub finding: Finding,
+}
+
+impl fmt::Display for ProtoLintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.finding.fmt(f)
+    }
+}
+
+/// Runs every proto lint rule not set to `allow` over the files of `fds`
+/// declaring the proto package of `package`, using the severities configured
+/// in the spec's `lint` section. `fds` must include source code info.
+pub fn lint_protos(
+    protogen: &ProtogenSpec,
+    package: &RustPackage,
+    fds: &FileDescriptorSet,
+) -> Vec<ProtoLintFinding> {
+    let mut rules = RuleSet::default();
+    for rule in ProtoLintRule::ALL {
+        rules.set_severity(rule.to_string(), protogen.lint.proto_severity(rule));
+    }
+    rules
+        .lint(fds, &package.proto_package_name)
+        .into_iter()
+        .map(|finding| ProtoLintFinding {
+            package: package.name.to_owned(),
+            finding,
+        })
+        .collect()
+}
@@ -0,0 +1,186 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+use appbiotic_api_protogen_fetch::protofetch::ProtofetchManifest;
+use appbiotic_api_protogen_spec::{paths::relative_path, RustPackage};
+
+use crate::{generate::list_files, output_manifest::content_hash};
+
+pub const PROVENANCE_FILE_NAME: &str = "provenance.json";
+
+/// Traces a generated crate back to the inputs it was generated from, for
+/// supply-chain audits. Only holds what generation reads, so it changes
+/// exactly when the generated code may.
+#[derive(serde::Serialize)]
+pub struct Provenance {
+    pub package: String,
+    pub version: String,
+    pub proto_package: String,
+    pub generator: Generator,
+    /// Templates the crate was rendered from.
+    pub templates: Vec<TemplateSource>,
+    /// Local proto files of the package sources and includes, with paths
+    /// relative to the spec directory.
+    pub protos: Vec<FileDigest>,
+    /// Remote proto sources and includes at their pinned revisions.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<ProtoDependency>,
+}
+
+#[derive(serde::Serialize)]
+pub struct Generator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct TemplateSource {
+    pub name: String,
+    /// Override template relative to the spec directory, or `None` for the
+    /// template embedded in the generator.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub sha256: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct FileDigest {
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProtoDependency {
+    Git {
+        url: String,
+        rev: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        subdir: Option<String>,
+    },
+    Bsr {
+        module: String,
+    },
+    /// A dependency of the protofetch manifest at its locked commit.
+    Protofetch {
+        name: String,
+        url: String,
+        commit: String,
+    },
+}
+
+impl TemplateSource {
+    /// A template embedded in the generator.
+    pub fn embedded(name: &str, contents: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            path: None,
+            sha256: content_hash(contents.as_bytes()),
+        }
+    }
+
+    /// A template read from `path`, which replaces the embedded one.
+    pub fn file(name: &str, path: &Path, spec_dir: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read(path).with_context(|| {
+            format!(
+                "Failed to read template at path `{}`",
+                path.to_string_lossy()
+            )
+        })?;
+        Ok(Self {
+            name: name.to_owned(),
+            path: Some(relative_path(spec_dir, path).to_string_lossy().into_owned()),
+            sha256: content_hash(&contents),
+        })
+    }
+}
+
+/// Collects the provenance of `package`, whose paths must be normalized,
+/// rendered from `templates`. `spec_dir` must be canonical.
+pub fn package_provenance(
+    package: &RustPackage,
+    spec_dir: &Path,
+    protofetch: Option<&ProtofetchManifest>,
+    templates: Vec<TemplateSource>,
+) -> anyhow::Result<Provenance> {
+    let mut paths = Vec::new();
+    let local_dirs = package
+        .protos
+        .iter()
+        .filter(|x| !x.is_remote())
+        .map(|x| &x.dir)
+        .chain(
+            package
+                .includes
+                .iter()
+                .filter(|x| !x.is_remote())
+                .map(|x| &x.dir),
+        );
+    for dir in local_dirs {
+        list_files(dir, "proto", &mut paths)?;
+    }
+    let mut protos = BTreeMap::new();
+    for path in paths {
+        let contents = std::fs::read(&path)
+            .with_context(|| format!("Failed to read file at path `{}`", path.to_string_lossy()))?;
+        protos.insert(
+            relative_path(spec_dir, &path)
+                .to_string_lossy()
+                .into_owned(),
+            content_hash(&contents),
+        );
+    }
+
+    let remote = package
+        .protos
+        .iter()
+        .map(|x| (&x.git, &x.bsr))
+        .chain(package.includes.iter().map(|x| (&x.git, &x.bsr)));
+    let mut dependencies = Vec::new();
+    for (git, bsr) in remote {
+        if let Some(git) = git {
+            dependencies.push(ProtoDependency::Git {
+                url: git.url.to_owned(),
+                rev: git.rev.to_owned(),
+                subdir: git
+                    .subdir
+                    .as_ref()
+                    .map(|x| x.to_string_lossy().into_owned()),
+            });
+        }
+        if let Some(bsr) = bsr {
+            dependencies.push(ProtoDependency::Bsr {
+                module: bsr.to_string(),
+            });
+        }
+    }
+    if let Some(protofetch) = protofetch {
+        for locked in protofetch.load_lock()?.dependencies {
+            dependencies.push(ProtoDependency::Protofetch {
+                url: protofetch
+                    .dependencies
+                    .get(&locked.name)
+                    .map(|x| x.url.to_owned())
+                    .unwrap_or_default(),
+                name: locked.name,
+                commit: locked.commit_hash,
+            });
+        }
+    }
+
+    Ok(Provenance {
+        package: package.name.to_owned(),
+        version: package.version.to_owned(),
+        proto_package: package.proto_package_name.to_owned(),
+        generator: Generator {
+            name: env!("CARGO_PKG_NAME").to_owned(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+        },
+        templates,
+        protos: protos
+            .into_iter()
+            .map(|(path, sha256)| FileDigest { path, sha256 })
+            .collect(),
+        dependencies,
+    })
+}
@@ -0,0 +1,131 @@
+use std::{
+    env,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, ensure, Context};
+use appbiotic_api_protogen_spec::{ProtogenSpec, Publish, RustPackage};
+use tracing::info;
+
+const INDEX_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct PublishOptions {
+    pub dry_run: bool,
+    /// How long to wait for a published level to show up in the registry
+    /// index before publishing the packages that depend on it.
+    pub index_timeout: Duration,
+}
+
+/// Runs `cargo publish` for every publishable package of the spec, one
+/// dependency level at a time, waiting until all packages of a level are
+/// available in their registry index before publishing the next level. With
+/// `dry_run` the publish order is only printed.
+pub fn publish_packages(
+    protogen: &ProtogenSpec,
+    spec_dir: &Path,
+    options: &PublishOptions,
+) -> anyhow::Result<()> {
+    let levels: Vec<Vec<&RustPackage>> = protogen
+        .dependency_levels()?
+        .into_iter()
+        .map(|level| level.into_iter().filter(|x| is_publishable(x)).collect())
+        .filter(|level: &Vec<_>| !level.is_empty())
+        .collect();
+
+    let level_count = levels.len();
+    for (index, level) in levels.into_iter().enumerate() {
+        for package in &level {
+            if options.dry_run {
+                println!(
+                    "{}: {} {}{}",
+                    index + 1,
+                    package.name,
+                    package.version,
+                    package
+                        .registry
+                        .as_ref()
+                        .map(|x| format!(" ({x})"))
+                        .unwrap_or_default()
+                );
+            } else {
+                cargo_publish(spec_dir, package)?;
+            }
+        }
+        if !options.dry_run && index + 1 < level_count {
+            for package in &level {
+                wait_for_index(package, options.index_timeout)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_publishable(package: &RustPackage) -> bool {
+    match package.effective_publish() {
+        Some(Publish::Enabled(enabled)) => enabled,
+        Some(Publish::Registries(registries)) => !registries.is_empty(),
+        None => true,
+    }
+}
+
+fn cargo_publish(spec_dir: &Path, package: &RustPackage) -> anyhow::Result<()> {
+    let manifest_path = spec_dir.join(&package.path).join("Cargo.toml");
+    let mut command = Command::new("cargo");
+    command
+        .arg("publish")
+        .arg("--manifest-path")
+        .arg(&manifest_path);
+    if let Some(registry) = &package.registry {
+        command.args(["--registry", registry]);
+    }
+    info!(package = %package.name, version = %package.version, "Publishing package");
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run `cargo publish` for `{}`", package.name))?;
+    ensure!(
+        status.success(),
+        "`cargo publish` failed for package `{}` at path `{}`",
+        package.name,
+        manifest_path.to_string_lossy()
+    );
+    Ok(())
+}
+
+/// Polls the registry index with `cargo info` until `package` is available.
+/// Runs outside of any workspace so cargo does not pick up the local package.
+fn wait_for_index(package: &RustPackage, timeout: Duration) -> anyhow::Result<()> {
+    let spec = format!("{}@{}", package.name, package.version);
+    let started = Instant::now();
+    loop {
+        let mut command = Command::new("cargo");
+        command
+            .args(["info", &spec])
+            .current_dir(env::temp_dir())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if let Some(registry) = &package.registry {
+            command.args(["--registry", registry]);
+        }
+        let status = command
+            .status()
+            .with_context(|| format!("Failed to run `cargo info {spec}`"))?;
+        if status.success() {
+            return Ok(());
+        }
+        if started.elapsed() >= timeout {
+            bail!(
+                "Package `{spec}` was not available in the registry index after {} seconds",
+                timeout.as_secs()
+            );
+        }
+        info!(
+            package = %package.name,
+            version = %package.version,
+            "Waiting for package to be available in the registry index"
+        );
+        thread::sleep(INDEX_POLL_INTERVAL);
+    }
+}
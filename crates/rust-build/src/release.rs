@@ -0,0 +1,195 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{ensure, Context};
+use appbiotic_api_descriptor_diff::{max_change_kind, Change};
+use appbiotic_api_protogen_spec::{ProtogenSpec, RustPackage};
+
+use crate::{
+    breaking::{git, package_changes},
+    bump_version,
+    generate::GeneratedFile,
+    output_manifest::read_optional,
+    BumpLevel,
+};
+
+pub const CHANGELOG_FILE_NAME: &str = "CHANGELOG.md";
+
+/// A version bump of a generated package.
+pub struct PackageRelease {
+    pub package: String,
+    pub from: semver::Version,
+    pub to: semver::Version,
+    /// Schema changes to the protos of the package since the last release.
+    pub changes: Vec<Change>,
+    /// Generated packages this package depends on that are released too.
+    pub dependencies: Vec<String>,
+}
+
+/// Plans the release of `packages` given their schema changes since git
+/// revision `since`. Breaking changes bump the major version and safe
+/// ones the minor version. Packages depending on a released package get a
+/// patch release, as their manifests pin its version. `protogen_path` must be
+/// absolute. Returns the releases in dependency order.
+pub fn plan_release(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    packages: &[&RustPackage],
+    since: &str,
+) -> anyhow::Result<Vec<PackageRelease>> {
+    let mut changes: BTreeMap<String, Vec<Change>> = BTreeMap::new();
+    for change in package_changes(protogen, protogen_path, packages, since)? {
+        changes
+            .entry(change.package)
+            .or_default()
+            .push(change.change);
+    }
+
+    let mut releases: Vec<PackageRelease> = Vec::new();
+    for package in protogen.dependency_order()? {
+        let changes = changes.remove(&package.name).unwrap_or_default();
+        let dependencies: Vec<String> = package
+            .protogen_dependencies
+            .iter()
+            .filter(|x| releases.iter().any(|release| release.package.eq(*x)))
+            .cloned()
+            .collect();
+        let level = match max_change_kind(&changes) {
+            Some(kind) if kind.is_breaking() => BumpLevel::Major,
+            Some(_) => BumpLevel::Minor,
+            None if !dependencies.is_empty() => BumpLevel::Patch,
+            None => continue,
+        };
+        let from = semver::Version::parse(&package.version).with_context(|| {
+            format!(
+                "Failed to parse version `{}` of package `{}`",
+                package.version, package.name
+            )
+        })?;
+        releases.push(PackageRelease {
+            package: package.name.to_owned(),
+            to: bump_version(&from, level),
+            from,
+            changes,
+            dependencies,
+        });
+    }
+    Ok(releases)
+}
+
+impl PackageRelease {
+    /// The changelog section of the release.
+    fn changelog_section(&self, releases: &[PackageRelease]) -> String {
+        let mut section = format!("## {}\n", self.to);
+        for (breaking, heading) in [(true, "Breaking changes"), (false, "Changes")] {
+            let mut changes = self
+                .changes
+                .iter()
+                .filter(|x| x.kind.is_breaking() == breaking)
+                .peekable();
+            if changes.peek().is_none() {
+                continue;
+            }
+            let _ = writeln!(section, "\n### {heading}\n");
+            for change in changes {
+                let _ = writeln!(section, "- `{}`: {}", change.path, change.description);
+            }
+        }
+        if !self.dependencies.is_empty() {
+            section.push_str("\n### Dependencies\n\n");
+            for release in releases
+                .iter()
+                .filter(|x| self.dependencies.contains(&x.package))
+            {
+                let _ = writeln!(section, "- Updated `{}` to {}", release.package, release.to);
+            }
+        }
+        section
+    }
+}
+
+/// Adds the section of `release` to the top of the changelog in
+/// `package_dir`, creating the changelog if needed. `releases` are all
+/// releases made along with it.
+pub fn changelog_file(
+    package_dir: &Path,
+    release: &PackageRelease,
+    releases: &[PackageRelease],
+) -> anyhow::Result<GeneratedFile> {
+    let path = package_dir.join(CHANGELOG_FILE_NAME);
+    let existing = read_optional(&path)?.unwrap_or_else(|| "# Changelog\n".to_owned());
+    let section = release.changelog_section(releases);
+    let contents = if existing.starts_with("## ") {
+        format!("{section}\n{existing}")
+    } else if let Some(index) = existing.find("\n## ") {
+        let (head, tail) = existing.split_at(index + 1);
+        format!("{head}{section}\n{tail}")
+    } else {
+        format!("{}\n\n{section}", existing.trim_end())
+    };
+    Ok(GeneratedFile {
+        path,
+        contents,
+        scaffold: false,
+    })
+}
+
+/// The most recent tag reachable from `HEAD` of the repository in `dir`.
+pub fn last_tag(dir: &Path) -> anyhow::Result<String> {
+    let tag = git(dir, &["describe", "--tags", "--abbrev=0"])
+        .context("Failed to find the last release tag, pass `--since` for the first release")?;
+    Ok(tag.trim().to_owned())
+}
+
+/// Checks that the repository in `dir` has no tag named `tag` yet.
+pub fn ensure_new_tag(dir: &Path, tag: &str) -> anyhow::Result<()> {
+    ensure!(
+        git(
+            dir,
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("refs/tags/{tag}")
+            ]
+        )
+        .is_err(),
+        "Tag `{tag}` already exists"
+    );
+    Ok(())
+}
+
+/// Checks that the repository in `dir` has no uncommitted changes, which the
+/// release commit would otherwise leave out.
+pub fn ensure_clean(dir: &Path) -> anyhow::Result<()> {
+    let status = git(dir, &["status", "--porcelain"])?;
+    ensure!(
+        status.trim().is_empty(),
+        "Working tree has uncommitted changes, commit or stash them before releasing"
+    );
+    Ok(())
+}
+
+/// Commits `paths` in the repository in `dir` and tags the commit `tag`.
+pub fn commit_and_tag(dir: &Path, paths: &[PathBuf], tag: &str) -> anyhow::Result<()> {
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|x| x.to_string_lossy().into_owned())
+        .collect();
+    let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+    let message = format!("Release {tag}");
+    git(dir, &[&["add", "--all", "--"], paths.as_slice()].concat())?;
+    git(
+        dir,
+        &[
+            &["commit", "--quiet", "-m", &message, "--"],
+            paths.as_slice(),
+        ]
+        .concat(),
+    )?;
+    git(dir, &["tag", "--annotate", "-m", &message, tag])?;
+    Ok(())
+}
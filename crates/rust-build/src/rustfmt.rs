@@ -0,0 +1,55 @@
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::{ensure, Context};
+use tracing::debug_span;
+
+use crate::generate::GeneratedFile;
+
+/// Formats the Rust sources among `files` with `rustfmt` for `edition`. Runs
+/// in `dir` so `rustfmt` picks up the `rustfmt.toml` of the repository.
+pub fn format_files(dir: &Path, edition: &str, files: &mut [GeneratedFile]) -> anyhow::Result<()> {
+    let _span = debug_span!("rustfmt").entered();
+    for file in files
+        .iter_mut()
+        .filter(|x| x.path.extension().is_some_and(|x| x == "rs"))
+    {
+        file.contents = format_source(dir, edition, &file.contents).with_context(|| {
+            format!(
+                "Failed to format generated file `{}`",
+                file.path.to_string_lossy()
+            )
+        })?;
+    }
+    Ok(())
+}
+
+fn format_source(dir: &Path, edition: &str, source: &str) -> anyhow::Result<String> {
+    let mut child = Command::new("rustfmt")
+        .args(["--edition", edition, "--emit", "stdout"])
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run `rustfmt`; install it with `rustup component add rustfmt`")?;
+    // rustfmt reads all of its input before writing any output.
+    child
+        .stdin
+        .take()
+        .context("Expected stdin of `rustfmt`")?
+        .write_all(source.as_bytes())
+        .context("Failed to write to `rustfmt`")?;
+    let output = child
+        .wait_with_output()
+        .context("Failed to wait for `rustfmt`")?;
+    ensure!(
+        output.status.success(),
+        "`rustfmt` failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    String::from_utf8(output.stdout).context("Expected UTF-8 output from `rustfmt`")
+}
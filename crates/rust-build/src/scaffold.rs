@@ -0,0 +1,209 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context};
+use appbiotic_api_protogen_spec::{
+    migrate::SCHEMA_VERSION, paths::normalize_path, ProtoSrc, ProtogenSpec, RustPackage,
+};
+use handlebars::Handlebars;
+use heck::ToUpperCamelCase;
+use serde_json::json;
+
+use crate::generate::GeneratedFile;
+
+pub const PROTOGEN_FILE_NAME: &str = "protogen.json";
+const PROTOFETCH_FILE_NAME: &str = "protofetch.toml";
+const PROTOS_DIR: &str = "protos";
+const CRATES_DIR: &str = "crates";
+
+pub struct NewPackage {
+    pub name: String,
+    pub proto_package_name: String,
+    pub version: String,
+    pub protogen_dependencies: Vec<String>,
+}
+
+/// Renders a starter workspace in `dir`: a spec with a single package, the
+/// package's proto directory skeleton, and optionally a protofetch manifest.
+pub fn init_workspace(
+    dir: &Path,
+    package: &NewPackage,
+    protofetch: bool,
+) -> anyhow::Result<Vec<GeneratedFile>> {
+    let protogen_path = dir.join(PROTOGEN_FILE_NAME);
+    ensure!(
+        !protogen_path.exists(),
+        "Protogen spec already exists at path `{}`",
+        protogen_path.to_string_lossy()
+    );
+
+    let mut protogen = ProtogenSpec {
+        schema: SCHEMA_VERSION,
+        ..Default::default()
+    };
+    let mut files = add_package(&mut protogen, dir, package)?;
+    files.insert(
+        0,
+        GeneratedFile {
+            path: protogen_path,
+            contents: protogen
+                .to_json()
+                .context("Failed to serialize protogen spec")?,
+            scaffold: true,
+        },
+    );
+
+    if protofetch {
+        files.push(GeneratedFile {
+            path: dir.join(PROTOFETCH_FILE_NAME),
+            contents: render(
+                "protofetch.toml",
+                include_str!("templates/protofetch.toml.hbs"),
+                &json!({ "name": package.name }),
+            )?,
+            scaffold: true,
+        });
+    }
+
+    Ok(files)
+}
+
+/// Appends `package` to the spec whose directory is `spec_dir`, returning
+/// the proto skeleton files for it. Protos live under `protos/` at the spec
+/// directory and the crate under `crates/<name>`.
+pub fn add_package(
+    protogen: &mut ProtogenSpec,
+    spec_dir: &Path,
+    package: &NewPackage,
+) -> anyhow::Result<Vec<GeneratedFile>> {
+    ensure!(
+        protogen.rust_package(&package.name).is_none(),
+        "Package `{}` already exists in the protogen spec",
+        package.name
+    );
+    for dependency in &package.protogen_dependencies {
+        protogen.package(dependency)?;
+    }
+
+    let proto_file = proto_file_path(&package.proto_package_name)?;
+    let package_path = PathBuf::from(CRATES_DIR).join(&package.name);
+    protogen.rust.push(RustPackage {
+        name: package.name.to_owned(),
+        version: package.version.to_owned(),
+        path: package_path,
+        proto_package_name: package.proto_package_name.to_owned(),
+        protos: vec![ProtoSrc {
+            dir: PathBuf::from("../..").join(PROTOS_DIR),
+            files: vec![proto_file.to_owned()],
+            ..Default::default()
+        }],
+        protogen_dependencies: package.protogen_dependencies.to_owned(),
+        ..Default::default()
+    });
+
+    Ok(vec![GeneratedFile {
+        path: spec_dir.join(PROTOS_DIR).join(proto_file),
+        contents: render(
+            "package.proto",
+            include_str!("templates/package.proto.hbs"),
+            &json!({ "proto_package": package.proto_package_name, "message": "Example" }),
+        )?,
+        scaffold: true,
+    }])
+}
+
+/// Adds `<name>.proto` to the directory of the proto package of the package
+/// named `package_name`, e.g. `foo/v1/widgets.proto`, registering it in the
+/// first local proto source of the package. Returns the proto skeleton, which
+/// is only written when the file does not exist yet.
+pub fn add_proto(
+    protogen: &mut ProtogenSpec,
+    spec_dir: &Path,
+    package_name: &str,
+    name: &str,
+) -> anyhow::Result<GeneratedFile> {
+    ensure!(
+        is_proto_identifier(name),
+        "Invalid proto file name `{name}`, expected lower_snake_case without `.proto`"
+    );
+    protogen.package(package_name)?;
+    let package = protogen
+        .rust_package_mut(package_name)
+        .context("Expected package in protogen spec")?;
+    let proto_file = proto_package_dir(&package.proto_package_name)?.join(format!("{name}.proto"));
+    let package_path = package.path.to_owned();
+    let proto_package_name = package.proto_package_name.to_owned();
+    let src = package
+        .protos
+        .iter_mut()
+        .find(|x| !x.is_remote())
+        .with_context(|| {
+            format!("Package `{package_name}` has no local proto source to add the file to")
+        })?;
+    ensure!(
+        !src.files.contains(&proto_file),
+        "Proto file `{}` is already part of package `{package_name}`",
+        proto_file.to_string_lossy()
+    );
+    let sibling = src
+        .files
+        .iter()
+        .find(|x| x.parent() == proto_file.parent())
+        .map(|x| x.to_string_lossy().into_owned());
+    src.files.push(proto_file.to_owned());
+
+    Ok(GeneratedFile {
+        path: normalize_path(&spec_dir.join(package_path).join(&src.dir).join(proto_file)),
+        contents: render(
+            "package.proto",
+            include_str!("templates/package.proto.hbs"),
+            &json!({
+                "proto_package": proto_package_name,
+                "message": name.to_upper_camel_case(),
+                "sibling": sibling,
+            }),
+        )?,
+        scaffold: true,
+    })
+}
+
+/// The starter proto file for a proto package, e.g. `foo/v1/foo.proto` for
+/// `foo.v1`, naming the file after the last non-version segment.
+fn proto_file_path(proto_package_name: &str) -> anyhow::Result<PathBuf> {
+    let segments: Vec<&str> = proto_package_name.split('.').collect();
+    let file_stem = segments
+        .iter()
+        .rev()
+        .find(|x| !is_version_segment(x))
+        .unwrap_or(&segments[0]);
+    Ok(proto_package_dir(proto_package_name)?.join(format!("{file_stem}.proto")))
+}
+
+/// The directory of the files of a proto package, e.g. `foo/v1` for `foo.v1`.
+fn proto_package_dir(proto_package_name: &str) -> anyhow::Result<PathBuf> {
+    let segments: Vec<&str> = proto_package_name.split('.').collect();
+    ensure!(
+        segments.iter().all(|x| is_proto_identifier(x)),
+        "Invalid proto package name `{proto_package_name}`"
+    );
+    Ok(segments.iter().collect())
+}
+
+fn is_proto_identifier(value: &str) -> bool {
+    value.starts_with(|c: char| c.is_ascii_lowercase())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn is_version_segment(segment: &str) -> bool {
+    segment
+        .strip_prefix('v')
+        .and_then(|x| x.chars().next())
+        .is_some_and(|c| c.is_ascii_digit())
+}
+
+fn render(name: &str, template: &str, data: &serde_json::Value) -> anyhow::Result<String> {
+    Handlebars::new()
+        .render_template(template, data)
+        .with_context(|| format!("Failed to render {name} template"))
+}
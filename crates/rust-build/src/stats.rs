@@ -0,0 +1,101 @@
+use std::{fmt, path::Path};
+
+use appbiotic_api_protogen_spec::RustPackage;
+use prost_types::{DescriptorProto, FileDescriptorSet};
+
+use crate::output_manifest::{read_optional, OutputManifest};
+
+/// The API surface of a package and the size of the code generated for it.
+#[derive(serde::Serialize)]
+pub struct PackageStats {
+    pub name: String,
+    pub version: String,
+    pub proto_package_name: String,
+    /// Messages of the proto package, including nested ones.
+    pub messages: usize,
+    /// Enums of the proto package, including nested ones.
+    pub enums: usize,
+    pub services: usize,
+    pub methods: usize,
+    /// Generated files recorded in the lock file that exist, excluding
+    /// scaffold files.
+    pub generated_files: usize,
+    pub generated_lines: usize,
+}
+
+impl fmt::Display for PackageStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} ({}): {} messages, {} enums, {} services, {} methods, {} generated lines in {} files",
+            self.name,
+            self.version,
+            self.proto_package_name,
+            self.messages,
+            self.enums,
+            self.services,
+            self.methods,
+            self.generated_lines,
+            self.generated_files
+        )
+    }
+}
+
+/// Counts the elements of the proto package of `package` in its compiled
+/// `fds` and the lines of its generated files below `spec_dir` recorded in
+/// `output_manifest`.
+pub fn package_stats(
+    package: &RustPackage,
+    fds: &FileDescriptorSet,
+    spec_dir: &Path,
+    output_manifest: &OutputManifest,
+) -> anyhow::Result<PackageStats> {
+    let mut stats = PackageStats {
+        name: package.name.to_owned(),
+        version: package.version.to_owned(),
+        proto_package_name: package.proto_package_name.to_owned(),
+        messages: 0,
+        enums: 0,
+        services: 0,
+        methods: 0,
+        generated_files: 0,
+        generated_lines: 0,
+    };
+    for file in fds
+        .file
+        .iter()
+        .filter(|x| x.package() == package.proto_package_name)
+    {
+        stats.enums += file.enum_type.len();
+        for message in &file.message_type {
+            stats.count_message(message);
+        }
+        stats.services += file.service.len();
+        stats.methods += file.service.iter().map(|x| x.method.len()).sum::<usize>();
+    }
+    if let Some(outputs) = output_manifest.packages.get(&package.name) {
+        for file in outputs.files.keys() {
+            if let Some(contents) = read_optional(&spec_dir.join(file))? {
+                stats.generated_files += 1;
+                stats.generated_lines += contents.lines().count();
+            }
+        }
+    }
+    Ok(stats)
+}
+
+impl PackageStats {
+    fn count_message(&mut self, message: &DescriptorProto) {
+        let map_entry = message
+            .options
+            .as_ref()
+            .is_some_and(|x| x.map_entry.unwrap_or_default());
+        if !map_entry {
+            self.messages += 1;
+        }
+        self.enums += message.enum_type.len();
+        for nested in &message.nested_type {
+            self.count_message(nested);
+        }
+    }
+}
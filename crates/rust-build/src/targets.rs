@@ -0,0 +1,80 @@
+use std::{fs::create_dir_all, path::Path, process::Command};
+
+use anyhow::{ensure, Context};
+use appbiotic_api_prost_serde_build::protoc_inputs;
+use appbiotic_api_protogen_spec::{paths::normalize_path, ProtogenSpec, RustPackage};
+use tracing::info;
+
+use crate::descriptor::remote_fetcher;
+
+/// Runs the generators of the spec's `targets` for each of `packages` they
+/// apply to, passing the resolved protoc inputs of the package.
+/// `protogen_path` must be absolute.
+pub fn run_targets(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    packages: &[&RustPackage],
+) -> anyhow::Result<()> {
+    if protogen.targets.is_empty() {
+        return Ok(());
+    }
+    let spec_dir = protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")?
+        .canonicalize()?;
+    let mut normalized = protogen.clone();
+    normalized.normalize_paths(&spec_dir)?;
+    let fetcher = remote_fetcher(&spec_dir);
+
+    for target in &protogen.targets {
+        let (program, args) = target
+            .command
+            .split_first()
+            .with_context(|| format!("Target `{}` has an empty command", target.name))?;
+        let out_dir = match &target.out_dir {
+            Some(out_dir) => {
+                let out_dir = normalize_path(&spec_dir.join(out_dir));
+                create_dir_all(&out_dir).with_context(|| {
+                    format!("Failed to create directory `{}`", out_dir.to_string_lossy())
+                })?;
+                out_dir.to_string_lossy().into_owned()
+            }
+            None => String::new(),
+        };
+        for package in packages.iter().filter(|x| target.includes_package(&x.name)) {
+            let normalized_package = normalized.package(&package.name)?;
+            let inputs = protoc_inputs(normalized_package, &normalized_package.path, &fetcher)?;
+            let mut command = Command::new(program);
+            command
+                .current_dir(&spec_dir)
+                .args(args.iter().map(|x| {
+                    x.replace("{package}", &package.name)
+                        .replace("{proto_package}", &package.proto_package_name)
+                        .replace("{out_dir}", &out_dir)
+                }))
+                .args(&inputs.protoc_args)
+                .args(
+                    inputs
+                        .include_dirs
+                        .iter()
+                        .map(|x| format!("--proto_path={}", x.to_string_lossy())),
+                )
+                .args(&inputs.files);
+
+            info!(target = %target.name, package = %package.name, "Running generator");
+            let status = command.status().with_context(|| {
+                format!(
+                    "Failed to run `{program}` of target `{}`, is it installed?",
+                    target.name
+                )
+            })?;
+            ensure!(
+                status.success(),
+                "Generator of target `{}` failed for package `{}` with {status}",
+                target.name,
+                package.name
+            );
+        }
+    }
+    Ok(())
+}
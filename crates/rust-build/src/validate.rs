@@ -0,0 +1,173 @@
+use std::{fmt, path::Path};
+
+use appbiotic_api_protogen_spec::{lint::Severity, ProtogenSpec};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        })
+    }
+}
+
+/// A single problem found while validating a spec.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Diagnostic {
+    pub level: Level,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(package: Option<&str>, message: String) -> Self {
+        Self {
+            level: Level::Error,
+            package: package.map(str::to_owned),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.package {
+            Some(package) => write!(f, "{}: package `{package}`: {}", self.level, self.message),
+            None => write!(f, "{}: {}", self.level, self.message),
+        }
+    }
+}
+
+/// Checks the spec loaded from the absolute `protogen_path` without
+/// generating anything: lint findings, the package dependency graph, the
+/// generation targets and hooks, and the existence of local proto sources,
+/// includes, and import paths.
+pub fn validate(protogen: &ProtogenSpec, protogen_path: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = protogen
+        .lint()
+        .into_iter()
+        .map(|finding| Diagnostic {
+            level: match finding.severity {
+                Severity::Deny => Level::Error,
+                _ => Level::Warning,
+            },
+            message: format!("{} ({})", finding.message, finding.rule),
+            package: Some(finding.package),
+        })
+        .collect();
+
+    if let Err(error) = protogen.dependency_levels() {
+        diagnostics.push(Diagnostic::error(None, error.to_string()));
+    }
+
+    for target in &protogen.targets {
+        if target.command.is_empty() {
+            diagnostics.push(Diagnostic::error(
+                None,
+                format!("Target `{}` has an empty command", target.name),
+            ));
+        }
+        for name in &target.packages {
+            if protogen.rust_package(name).is_none() {
+                diagnostics.push(Diagnostic::error(
+                    None,
+                    format!("Target `{}` lists unknown package `{name}`", target.name),
+                ));
+            }
+        }
+    }
+
+    for (hook, commands) in [
+        ("pre_generate", &protogen.hooks.pre_generate),
+        ("post_generate", &protogen.hooks.post_generate),
+    ] {
+        if commands.iter().any(Vec::is_empty) {
+            diagnostics.push(Diagnostic::error(
+                None,
+                format!("Hook `{hook}` has an empty command"),
+            ));
+        }
+    }
+
+    for package in &protogen.rust {
+        for (dependency, option) in protogen.missing_inherited_options(package) {
+            diagnostics.push(Diagnostic::error(
+                Some(&package.name),
+                format!("Sets `{option}`, which dependency `{dependency}` must set too"),
+            ));
+        }
+    }
+
+    let Some(spec_dir) = protogen_path.parent() else {
+        diagnostics.push(Diagnostic::error(
+            None,
+            "Expected parent directory of protogen_path".to_owned(),
+        ));
+        return diagnostics;
+    };
+    let mut normalized = protogen.clone();
+    if let Err(error) = normalized.normalize_paths(spec_dir) {
+        diagnostics.push(Diagnostic::error(None, error.to_string()));
+        return diagnostics;
+    }
+
+    for package in &normalized.rust {
+        let name = Some(package.name.as_str());
+        for src in package.protos.iter().filter(|x| !x.is_remote()) {
+            if !src.dir.is_dir() {
+                diagnostics.push(Diagnostic::error(
+                    name,
+                    format!(
+                        "Proto directory `{}` does not exist",
+                        src.dir.to_string_lossy()
+                    ),
+                ));
+                continue;
+            }
+            for file in &src.files {
+                if !src.dir.join(file).is_file() {
+                    diagnostics.push(Diagnostic::error(
+                        name,
+                        format!(
+                            "Proto file `{}` does not exist in `{}`",
+                            file.to_string_lossy(),
+                            src.dir.to_string_lossy()
+                        ),
+                    ));
+                }
+            }
+        }
+        for include in package.includes.iter().filter(|x| !x.is_remote()) {
+            if !include.dir.is_dir() {
+                diagnostics.push(Diagnostic::error(
+                    name,
+                    format!(
+                        "Include directory `{}` does not exist",
+                        include.dir.to_string_lossy()
+                    ),
+                ));
+            }
+        }
+        for (prefix, dir) in &package.import_paths {
+            if !dir.is_dir() {
+                diagnostics.push(Diagnostic::error(
+                    name,
+                    format!(
+                        "Import path `{prefix}` directory `{}` does not exist",
+                        dir.to_string_lossy()
+                    ),
+                ));
+            }
+        }
+    }
+
+    diagnostics
+}
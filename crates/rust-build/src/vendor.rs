@@ -0,0 +1,141 @@
+use std::{
+    collections::BTreeSet,
+    env,
+    fs::{copy, create_dir_all, remove_dir_all},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use appbiotic_api_protogen_fetch::{protofetch::ProtofetchManifest, Fetcher};
+use appbiotic_api_protogen_spec::{
+    paths::{normalize_path, relative_path, resolve_path},
+    ProtoInclude, ProtogenSpec,
+};
+use tracing::debug_span;
+
+use crate::{descriptor::remote_fetcher, generate::list_files};
+
+/// Directory below `vendor_dir` the protos of the protofetch manifest are
+/// copied to.
+const PROTOFETCH_DIR: &str = "protofetch";
+
+/// Copies the protos of the remote proto sources and includes of the spec
+/// below `vendor_dir`, laid out like the fetch cache, and rewrites them into
+/// local directories. Protos fetched by the protofetch manifest at
+/// `protofetch_path` are vendored too and added to the includes of every
+/// package. `protogen_path` must be absolute. Returns the vendored
+/// directories.
+pub fn vendor_protos(
+    protogen: &mut ProtogenSpec,
+    protogen_path: &Path,
+    vendor_dir: &Path,
+    protofetch_path: Option<&Path>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let spec_dir = protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")?
+        .canonicalize()?;
+    let vendor_dir = &normalize_path(vendor_dir);
+    let fetcher = remote_fetcher(&spec_dir);
+    let mut vendor = Vendor {
+        fetcher: &fetcher,
+        vendor_dir,
+        vendored: BTreeSet::new(),
+    };
+
+    let protofetch_dir = match protofetch_path {
+        Some(protofetch_path) => {
+            let protofetch = ProtofetchManifest::load(protofetch_path)?;
+            protofetch.verify()?;
+            let include_dir = resolve_path(&env::current_dir()?, &protofetch.include_dir())?;
+            let vendored_dir = vendor_dir.join(PROTOFETCH_DIR);
+            vendor.copy(&include_dir, &vendored_dir)?;
+            Some(vendored_dir)
+        }
+        None => None,
+    };
+
+    for package in &mut protogen.rust {
+        let package_path = normalize_path(&spec_dir.join(&package.path));
+        for src in package.protos.iter_mut().filter(|x| x.is_remote()) {
+            let include_dir = fetcher.resolve_dir(src, &package_path)?;
+            src.dir = relative_path(&package_path, &vendor.vendor(&include_dir)?);
+            src.git = None;
+            src.bsr = None;
+        }
+        for include in package.includes.iter_mut().filter(|x| x.is_remote()) {
+            let include_dir = fetcher.resolve_include_dir(include, &package_path)?;
+            include.dir = relative_path(&package_path, &vendor.vendor(&include_dir)?);
+            include.git = None;
+            include.bsr = None;
+        }
+        if let Some(protofetch_dir) = &protofetch_dir {
+            let dir = relative_path(&package_path, protofetch_dir);
+            if !package.includes.iter().any(|x| x.dir == dir) {
+                package.includes.push(ProtoInclude {
+                    dir,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    Ok(vendor.vendored.into_iter().collect())
+}
+
+struct Vendor<'a> {
+    fetcher: &'a Fetcher,
+    vendor_dir: &'a Path,
+    vendored: BTreeSet<PathBuf>,
+}
+
+impl Vendor<'_> {
+    /// Copies the fetched `include_dir` below the vendor directory once,
+    /// returning the vendored directory.
+    fn vendor(&mut self, include_dir: &Path) -> anyhow::Result<PathBuf> {
+        let cache_path = include_dir
+            .strip_prefix(self.fetcher.cache_dir())
+            .with_context(|| {
+                format!(
+                    "Expected fetched protos below `{}`",
+                    self.fetcher.cache_dir().to_string_lossy()
+                )
+            })?;
+        let vendored_dir = self.vendor_dir.join(cache_path);
+        if !self.vendored.contains(&vendored_dir) {
+            self.copy(include_dir, &vendored_dir)?;
+        }
+        Ok(vendored_dir)
+    }
+
+    /// Replaces `to` with the proto files below `from`.
+    fn copy(&mut self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        let _span = debug_span!("vendor", dir = %to.to_string_lossy()).entered();
+        if to.exists() {
+            remove_dir_all(to).with_context(|| {
+                format!(
+                    "Failed to remove vendored protos at path `{}`",
+                    to.to_string_lossy()
+                )
+            })?;
+        }
+        let mut files = Vec::new();
+        list_files(from, "proto", &mut files)?;
+        for file in files {
+            let target = to.join(file.strip_prefix(from)?);
+            if let Some(parent) = target.parent() {
+                create_dir_all(parent).with_context(|| {
+                    format!("Failed to create directory `{}`", parent.to_string_lossy())
+                })?;
+            }
+            copy(&file, &target).with_context(|| {
+                format!(
+                    "Failed to copy `{}` to `{}`",
+                    file.to_string_lossy(),
+                    target.to_string_lossy()
+                )
+            })?;
+        }
+        self.vendored.insert(to.to_owned());
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    collections::BTreeSet,
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+use appbiotic_api_protogen_spec::{
+    paths::{normalize_path, relative_path, resolve_path},
+    ProtogenSpec, RustPackage,
+};
+use toml_edit::{DocumentMut, TableLike};
+use tracing::info;
+
+use crate::{
+    generate::{generate_package, GenerateOptions},
+    output::write_files,
+    workspace::workspace_manifest,
+};
+
+/// Generates the package named `package_name`, along with the generated
+/// packages it depends on, into a temporary workspace and runs `cargo check`
+/// there, or `cargo test` when `test` is set. `protogen_path` must be
+/// absolute. Returns whether cargo succeeded.
+///
+/// The workspace holds a copy of the spec whose local proto directories point
+/// back at the originals, and builds into `target/protogen/verify` next to the
+/// spec so repeated runs reuse compiled dependencies.
+pub fn verify_package(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    package_name: &str,
+    options: GenerateOptions,
+    test: bool,
+) -> anyhow::Result<bool> {
+    let spec_dir = protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")?
+        .canonicalize()?;
+    let names = dependency_closure(protogen, package_name)?;
+
+    let mut spec = protogen.clone();
+    spec.rust.retain(|x| names.contains(&x.name));
+    spec.normalize_paths(&spec_dir)?;
+    for package in &mut spec.rust {
+        package.path = protogen.package(&package.name)?.path.to_owned();
+    }
+    for path in &mut spec.license_files {
+        *path = resolve_path(&spec_dir, path)?;
+    }
+
+    let workspace_dir = tempfile::Builder::new()
+        .prefix("protogen-verify-")
+        .tempdir()
+        .context("Failed to create temporary workspace")?;
+    let verify_dir = workspace_dir.path().canonicalize()?;
+    let verify_path = verify_dir.join(
+        protogen_path
+            .file_name()
+            .context("Expected file_name from protogen_path")?,
+    );
+    spec.save(&verify_path)?;
+
+    let options = GenerateOptions {
+        output_root: None,
+        embed_package_spec: false,
+        ..options
+    };
+    let packages: Vec<&RustPackage> = spec.dependency_order()?;
+    let mut files = vec![workspace_manifest(&verify_dir, &spec, &packages)?];
+    for package in &packages {
+        files.extend(generate_package(
+            &spec,
+            &verify_path,
+            &package.name,
+            &options,
+        )?);
+    }
+    for file in files.iter_mut().filter(|x| x.path.ends_with("Cargo.toml")) {
+        let dir = file.path.parent().context("Expected manifest directory")?;
+        let original_dir = spec_dir.join(relative_path(&verify_dir, dir));
+        file.contents = anchor_path_dependencies(&file.contents, &verify_dir, dir, &original_dir)
+            .with_context(|| {
+            format!(
+                "Failed to update path dependencies of `{}`",
+                file.path.to_string_lossy()
+            )
+        })?;
+    }
+    write_files(&files)?;
+
+    // Tests, examples and benches are checked too, while `cargo test` also
+    // runs doc tests.
+    let subcommand = if test { "test" } else { "check" };
+    let mut command = Command::new("cargo");
+    command
+        .args([subcommand, "--workspace"])
+        .current_dir(workspace_dir.path());
+    if !test {
+        command.arg("--all-targets");
+    }
+    if env::var_os("CARGO_TARGET_DIR").is_none() {
+        command
+            .arg("--target-dir")
+            .arg(verify_target_dir(&spec_dir));
+    }
+    info!(package = package_name, "Running `cargo {subcommand}`");
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run `cargo {subcommand}`"))?;
+    Ok(status.success())
+}
+
+/// Directory verification of the spec in `spec_dir` builds into, unless
+/// `CARGO_TARGET_DIR` is set.
+pub fn verify_target_dir(spec_dir: &Path) -> PathBuf {
+    spec_dir.join("target").join("protogen").join("verify")
+}
+
+/// Replaces relative path dependencies of the manifest in `dir` that lead out
+/// of `verify_dir` with absolute paths resolved against `original_dir`, where
+/// the manifest is generated outside of verification.
+fn anchor_path_dependencies(
+    contents: &str,
+    verify_dir: &Path,
+    dir: &Path,
+    original_dir: &Path,
+) -> anyhow::Result<String> {
+    let mut document = contents.parse::<DocumentMut>()?;
+    anchor_table(document.as_table_mut(), verify_dir, dir, original_dir);
+    Ok(document.to_string())
+}
+
+fn anchor_table(table: &mut dyn TableLike, verify_dir: &Path, dir: &Path, original_dir: &Path) {
+    for (key, item) in table.iter_mut() {
+        let Some(child) = item.as_table_like_mut() else {
+            continue;
+        };
+        if !key.get().ends_with("dependencies") {
+            anchor_table(child, verify_dir, dir, original_dir);
+            continue;
+        }
+        for (_, dependency) in child.iter_mut() {
+            let Some(path) = dependency
+                .as_table_like_mut()
+                .and_then(|x| x.get_mut("path"))
+            else {
+                continue;
+            };
+            let Some(relative) = path.as_str().map(PathBuf::from) else {
+                continue;
+            };
+            if !normalize_path(&dir.join(&relative)).starts_with(verify_dir) {
+                let anchored = normalize_path(&original_dir.join(&relative));
+                *path = toml_edit::value(anchored.to_string_lossy().as_ref());
+            }
+        }
+    }
+}
+
+/// Names of the package named `package_name` and of the generated packages
+/// it depends on, directly or not.
+fn dependency_closure(
+    protogen: &ProtogenSpec,
+    package_name: &str,
+) -> anyhow::Result<BTreeSet<String>> {
+    let mut names = BTreeSet::new();
+    let mut pending = vec![package_name.to_owned()];
+    while let Some(name) = pending.pop() {
+        let package = protogen.package(&name)?;
+        if names.insert(name) {
+            pending.extend(package.protogen_dependencies.iter().cloned());
+        }
+    }
+    Ok(names)
+}
@@ -0,0 +1,230 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+use anyhow::{bail, Context};
+use appbiotic_api_protogen_spec::{paths::normalize_path, ProtogenSpec};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tracing::{error, info, warn};
+
+use crate::{
+    descriptor::compile_package_descriptors,
+    generate::{generate_package, package_inputs_hash, GenerateOptions},
+    hooks::Hooks,
+    output_manifest::OutputManifest,
+};
+
+/// Quiet period after a change before regenerating, so editors saving
+/// several files at once trigger a single run.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches the spec at `protogen_path`, which must be absolute, and the local
+/// proto directories of its packages. A change to the spec regenerates every
+/// package, a change to a proto directory regenerates the packages reading
+/// from it, and their protos are compiled to report schema errors. Errors are
+/// printed and watching continues, including when generated files were
+/// modified since they were last written, unless `force` allows overwriting
+/// them. Runs until the process is interrupted.
+pub fn watch_packages(
+    protogen_path: &Path,
+    options: &GenerateOptions,
+    hooks: &Hooks,
+    force: bool,
+) -> anyhow::Result<()> {
+    let protogen_path = &normalize_path(protogen_path);
+    let (sender, receiver) = channel();
+    let mut watcher = RecommendedWatcher::new(sender, notify::Config::default())
+        .context("Failed to create file watcher")?;
+
+    let mut watched = WatchedDirs::default();
+    let mut protogen = reload(protogen_path, &mut watcher, &mut watched)?;
+    let names: Vec<String> = protogen.rust.iter().map(|x| x.name.to_owned()).collect();
+    regenerate(&protogen, protogen_path, &names, options, hooks, force);
+
+    loop {
+        let changed = next_changes(&receiver)?;
+        if changed.iter().any(|x| x.eq(protogen_path)) {
+            info!("Spec changed");
+            match reload(protogen_path, &mut watcher, &mut watched) {
+                Ok(reloaded) => protogen = reloaded,
+                Err(error) => {
+                    error!("{error:?}");
+                    continue;
+                }
+            }
+            let names: Vec<String> = protogen.rust.iter().map(|x| x.name.to_owned()).collect();
+            regenerate(&protogen, protogen_path, &names, options, hooks, force);
+            continue;
+        }
+
+        let affected: Vec<String> = watched
+            .package_dirs
+            .iter()
+            .filter(|(_, dir)| changed.iter().any(|x| x.starts_with(dir)))
+            .map(|(name, _)| name.to_owned())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        if !affected.is_empty() {
+            regenerate(&protogen, protogen_path, &affected, options, hooks, force);
+        }
+    }
+}
+
+#[derive(Default)]
+struct WatchedDirs {
+    /// Directories currently registered with the watcher.
+    dirs: BTreeSet<PathBuf>,
+    /// Local proto directories read by each package.
+    package_dirs: Vec<(String, PathBuf)>,
+}
+
+/// Loads the spec and updates the watcher to its current proto directories.
+fn reload(
+    protogen_path: &Path,
+    watcher: &mut RecommendedWatcher,
+    watched: &mut WatchedDirs,
+) -> anyhow::Result<ProtogenSpec> {
+    let protogen = ProtogenSpec::load(protogen_path)?;
+    let spec_dir = protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")?;
+    let mut normalized = protogen.clone();
+    normalized.normalize_paths(spec_dir)?;
+
+    let mut package_dirs = Vec::new();
+    for package in &normalized.rust {
+        let dirs = package
+            .protos
+            .iter()
+            .filter(|x| !x.is_remote())
+            .map(|x| &x.dir)
+            .chain(
+                package
+                    .includes
+                    .iter()
+                    .filter(|x| !x.is_remote())
+                    .map(|x| &x.dir),
+            )
+            .chain(package.import_paths.values());
+        for dir in dirs {
+            package_dirs.push((package.name.to_owned(), dir.to_owned()));
+        }
+    }
+
+    let mut dirs: BTreeSet<PathBuf> = package_dirs
+        .iter()
+        .map(|(_, dir)| dir.to_owned())
+        .filter(|x| x.is_dir())
+        .collect();
+    dirs.insert(spec_dir.to_owned());
+    for dir in watched.dirs.difference(&dirs) {
+        let _ = watcher.unwatch(dir);
+    }
+    for dir in dirs.difference(&watched.dirs) {
+        // The spec directory is only watched for the spec itself, which keeps
+        // generated crates next to it from retriggering generation.
+        let mode = if dir.eq(spec_dir) {
+            RecursiveMode::NonRecursive
+        } else {
+            RecursiveMode::Recursive
+        };
+        watcher
+            .watch(dir, mode)
+            .with_context(|| format!("Failed to watch directory `{}`", dir.to_string_lossy()))?;
+        info!(dir = %dir.display(), "Watching directory");
+    }
+
+    *watched = WatchedDirs { dirs, package_dirs };
+    Ok(protogen)
+}
+
+/// Blocks for the next batch of changed paths.
+fn next_changes(receiver: &Receiver<notify::Result<Event>>) -> anyhow::Result<BTreeSet<PathBuf>> {
+    let mut changed = BTreeSet::new();
+    let mut event = receiver
+        .recv()
+        .context("File watcher stopped unexpectedly")?;
+    loop {
+        match event {
+            Ok(event)
+                if matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                ) =>
+            {
+                changed.extend(event.paths)
+            }
+            Ok(_) => {}
+            Err(error) => warn!("File watcher error: {error}"),
+        }
+        event = match receiver.recv_timeout(DEBOUNCE) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) if !changed.is_empty() => return Ok(changed),
+            Err(RecvTimeoutError::Timeout) => receiver
+                .recv()
+                .context("File watcher stopped unexpectedly")?,
+            Err(RecvTimeoutError::Disconnected) => {
+                bail!("File watcher stopped unexpectedly")
+            }
+        };
+    }
+}
+
+/// Generates the packages named `names` and compiles their protos, printing
+/// the outcome of each.
+fn regenerate(
+    protogen: &ProtogenSpec,
+    protogen_path: &Path,
+    names: &[String],
+    options: &GenerateOptions,
+    hooks: &Hooks,
+    force: bool,
+) {
+    let result = protogen_path
+        .parent()
+        .context("Expected parent directory of protogen_path")
+        .and_then(|spec_dir| options.output_dir(spec_dir));
+    let output_dir = match result {
+        Ok(output_dir) => output_dir,
+        Err(error) => {
+            error!("{error:?}");
+            return;
+        }
+    };
+    let mut output_manifest = match OutputManifest::load(&output_dir) {
+        Ok(output_manifest) => output_manifest,
+        Err(error) => {
+            error!("{error:?}");
+            return;
+        }
+    };
+    for name in names {
+        let result = (|| {
+            let package = protogen.package(name)?;
+            output_manifest.ensure_unmodified(&output_dir, &[name], force)?;
+            hooks.pre_generate(protogen, protogen_path, &output_dir, package)?;
+            let files = generate_package(protogen, protogen_path, name, options)?;
+            let inputs = package_inputs_hash(protogen, protogen_path, name, options)?;
+            output_manifest.write_package(&output_dir, package, inputs, &files)?;
+            hooks.post_generate(
+                protogen,
+                protogen_path,
+                &output_dir,
+                package,
+                &mut output_manifest,
+            )?;
+            compile_package_descriptors(protogen, protogen_path, name)
+        })();
+        match result {
+            Ok(_) => info!(package = %name, "Generated package"),
+            Err(error) => error!(package = %name, "Failed to generate package: {error:?}"),
+        }
+    }
+    if let Err(error) = output_manifest.save(&output_dir) {
+        error!("{error:?}");
+    }
+}
@@ -0,0 +1,127 @@
+use std::{collections::BTreeMap, fs::read_to_string, io::ErrorKind, path::Path};
+
+use anyhow::Context;
+use appbiotic_api_protogen_spec::{ProtogenSpec, RustPackage};
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Table};
+
+use crate::generate::{ffi_manifest, package_manifest, GeneratedFile};
+
+const WORKSPACE_MANIFEST_FILE_NAME: &str = "Cargo.toml";
+
+/// Renders the workspace `Cargo.toml` in `spec_dir`, creating it or updating
+/// the existing one in place. Each of `packages`, and its FFI crate, is added
+/// to the workspace members unless an existing member glob covers it, and the
+/// registry
+/// dependencies of generated packages are added to `[workspace.dependencies]`
+/// unless already declared there. Everything else in the manifest is kept as
+/// is.
+pub fn workspace_manifest(
+    spec_dir: &Path,
+    protogen: &ProtogenSpec,
+    packages: &[&RustPackage],
+) -> anyhow::Result<GeneratedFile> {
+    let path = spec_dir.join(WORKSPACE_MANIFEST_FILE_NAME);
+    let mut document = match read_to_string(&path) {
+        Ok(contents) => contents.parse::<DocumentMut>().with_context(|| {
+            format!(
+                "Failed to parse workspace manifest at path `{}`",
+                path.to_string_lossy()
+            )
+        })?,
+        Err(error) if error.kind() == ErrorKind::NotFound => DocumentMut::new(),
+        Err(error) => {
+            return Err(error).with_context(|| {
+                format!(
+                    "Failed to read workspace manifest at path `{}`",
+                    path.to_string_lossy()
+                )
+            })
+        }
+    };
+
+    let workspace = document
+        .entry("workspace")
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_mut()
+        .context("Expected `workspace` in workspace manifest to be a table")?;
+    if !workspace.contains_key("resolver") {
+        workspace["resolver"] = toml_edit::value("2");
+    }
+
+    let members = workspace
+        .entry("members")
+        .or_insert_with(|| toml_edit::value(Array::new()))
+        .as_array_mut()
+        .context("Expected `workspace.members` in workspace manifest to be an array")?;
+    let paths = packages.iter().flat_map(|x| {
+        std::iter::once(x.path.to_owned()).chain(x.ffi.as_ref().map(|_| x.ffi_path()))
+    });
+    for path in paths {
+        let member = path.to_string_lossy().replace('\\', "/");
+        let covered = members
+            .iter()
+            .filter_map(|x| x.as_str())
+            .any(|x| member_matches(x, &member));
+        if !covered {
+            members.push(member);
+        }
+    }
+
+    let dependencies = workspace
+        .entry("dependencies")
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_mut()
+        .context("Expected `workspace.dependencies` in workspace manifest to be a table")?;
+    for (name, version) in shared_dependencies(protogen, packages)? {
+        if dependencies.contains_key(&name) {
+            continue;
+        }
+        let mut dependency = InlineTable::new();
+        dependency.insert("version", version.into());
+        dependencies[&name] = toml_edit::value(dependency);
+    }
+
+    Ok(GeneratedFile {
+        path,
+        contents: document.to_string(),
+        scaffold: false,
+    })
+}
+
+/// Registry dependencies of the generated packages and their versions.
+fn shared_dependencies(
+    protogen: &ProtogenSpec,
+    packages: &[&RustPackage],
+) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut shared = BTreeMap::new();
+    for package in packages {
+        let mut manifests = vec![package_manifest(protogen, package)?];
+        if package.ffi.is_some() {
+            manifests.push(ffi_manifest(protogen, package)?);
+        }
+        for manifest in manifests {
+            for (name, dep) in manifest
+                .dependencies
+                .into_iter()
+                .chain(manifest.build_dependencies)
+                .chain(manifest.dev_dependencies)
+                .filter(|(_, x)| x.is_shareable())
+            {
+                shared.entry(name).or_insert(dep.version);
+            }
+        }
+    }
+    Ok(shared)
+}
+
+/// Whether workspace member `pattern`, either a path or a path ending with a
+/// `*` component, includes the package at `member`.
+fn member_matches(pattern: &str, member: &str) -> bool {
+    let pattern = pattern.trim_end_matches('/');
+    match pattern.strip_suffix("/*") {
+        Some(parent) => member
+            .rsplit_once('/')
+            .is_some_and(|(member_parent, _)| member_parent.eq(parent)),
+        None => pattern.eq(member),
+    }
+}
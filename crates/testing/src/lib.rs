@@ -0,0 +1,222 @@
+//! In-process gRPC servers for tests of generated services, served over
+//! in-memory duplex streams instead of ports, with every call recorded:
+//!
+//! ```ignore
+//! let server = TestServer::start(WidgetServiceServer::new(MyWidgets)).await?;
+//! let mut client = server.client(WidgetServiceClient::new);
+//! client.get_widget(GetWidgetRequest::default()).await?;
+//! let calls = server.recorder().calls_to("/acme.widgets.v1.WidgetService/GetWidget");
+//! ```
+
+mod record;
+
+use std::{convert::Infallible, io};
+
+use http::{Request, Response};
+use hyper_util::rt::TokioIo;
+use tokio::{
+    io::DuplexStream,
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{
+    body::Body,
+    server::NamedService,
+    service::{Routes, RoutesBuilder},
+    transport::{Channel, Endpoint, Server},
+};
+use tower::{service_fn, Service};
+
+use crate::record::RecordLayer;
+pub use crate::record::{RecordedCall, Recorder};
+
+/// Capacity of the in-memory stream of each connection, in bytes.
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A tonic server running on the current tokio runtime, reachable only
+/// through its [`channel`](Self::channel). Stops when dropped.
+pub struct TestServer {
+    channel: Channel,
+    recorder: Recorder,
+    shutdown: Option<oneshot::Sender<()>>,
+    handle: Option<JoinHandle<Result<(), tonic::transport::Error>>>,
+}
+
+impl TestServer {
+    /// Serves the single `service`, e.g. `WidgetServiceServer::new(MyWidgets)`.
+    pub async fn start<S>(service: S) -> Result<Self, tonic::transport::Error>
+    where
+        S: Service<Request<Body>, Response = Response<Body>, Error = Infallible>
+            + NamedService
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        let mut builder = Self::builder();
+        builder.add_service(service);
+        builder.start().await
+    }
+
+    pub fn builder() -> TestServerBuilder {
+        TestServerBuilder {
+            routes: Routes::builder(),
+        }
+    }
+
+    /// A channel to the server, which connects again after the server closes
+    /// a connection.
+    pub fn channel(&self) -> Channel {
+        self.channel.clone()
+    }
+
+    /// A generated client of the server, e.g.
+    /// `server.client(WidgetServiceClient::new)`.
+    pub fn client<C>(&self, new: impl FnOnce(Channel) -> C) -> C {
+        new(self.channel())
+    }
+
+    pub fn recorder(&self) -> &Recorder {
+        &self.recorder
+    }
+
+    /// Stops the server, waiting for the calls in progress to complete.
+    pub async fn shutdown(mut self) -> Result<(), tonic::transport::Error> {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        match self.handle.take() {
+            Some(handle) => handle.await.expect("Expected test server not to panic"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// A [`TestServer`] of several services.
+pub struct TestServerBuilder {
+    routes: RoutesBuilder,
+}
+
+impl TestServerBuilder {
+    pub fn add_service<S>(&mut self, service: S) -> &mut Self
+    where
+        S: Service<Request<Body>, Response = Response<Body>, Error = Infallible>
+            + NamedService
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        self.routes.add_service(service);
+        self
+    }
+
+    /// Starts serving on the current tokio runtime and connects a channel.
+    pub async fn start(self) -> Result<TestServer, tonic::transport::Error> {
+        let recorder = Recorder::default();
+        let (connections, incoming) = mpsc::channel::<io::Result<DuplexStream>>(1);
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+        let server = Server::builder()
+            .layer(RecordLayer(recorder.clone()))
+            .serve_with_incoming_shutdown(
+                self.routes.routes(),
+                ReceiverStream::new(incoming),
+                async {
+                    let _ = shutdown_signal.await;
+                },
+            );
+        let handle = tokio::spawn(server);
+
+        // The URI is never resolved, each connection is a new duplex stream.
+        let channel = Endpoint::from_static("http://test.server")
+            .connect_with_connector(service_fn(move |_| {
+                let connections = connections.clone();
+                async move {
+                    let (client, server) = tokio::io::duplex(STREAM_BUFFER_SIZE);
+                    connections.send(Ok(server)).await.map_err(|_| {
+                        io::Error::new(io::ErrorKind::BrokenPipe, "Test server stopped")
+                    })?;
+                    Ok::<_, io::Error>(TokioIo::new(client))
+                }
+            }))
+            .await?;
+
+        Ok(TestServer {
+            channel,
+            recorder,
+            shutdown: Some(shutdown),
+            handle: Some(handle),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tonic::Code;
+    use tonic_health::{
+        pb::{health_client::HealthClient, HealthCheckRequest, HealthCheckResponse},
+        server::health_reporter,
+    };
+
+    use super::*;
+
+    const CHECK_PATH: &str = "/grpc.health.v1.Health/Check";
+
+    fn check_request(service: &str) -> HealthCheckRequest {
+        HealthCheckRequest {
+            service: service.to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_records_calls() {
+        let (_, health) = health_reporter();
+        let server = TestServer::start(health).await.unwrap();
+        let mut client = server.client(HealthClient::new);
+        client.check(check_request("")).await.unwrap();
+
+        let calls = server.recorder().calls_to(CHECK_PATH);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].code, Some(Code::Ok));
+        assert_eq!(
+            calls[0].requests::<HealthCheckRequest>().unwrap(),
+            vec![check_request("")]
+        );
+        assert_eq!(
+            calls[0].responses::<HealthCheckResponse>().unwrap().len(),
+            1
+        );
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_records_failed_calls() {
+        let (_, health) = health_reporter();
+        let server = TestServer::start(health).await.unwrap();
+        let status = server
+            .client(HealthClient::new)
+            .check(check_request("acme.Unknown"))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+
+        let calls = server.recorder().calls();
+        assert_eq!(calls[0].code, Some(Code::NotFound));
+        assert_eq!(calls[0].message, status.message());
+        assert!(calls[0]
+            .responses::<HealthCheckResponse>()
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use http::{HeaderMap, Request, Response};
+use http_body::{Frame, SizeHint};
+use prost::{DecodeError, Message};
+use tonic::{body::Body, Code};
+use tower::{Layer, Service};
+
+/// Length of the prefix of gRPC messages: a compression flag and the length.
+const MESSAGE_PREFIX_LEN: usize = 5;
+
+/// The calls a [`TestServer`](crate::TestServer) served, in the order they
+/// started.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    calls: Arc<Mutex<Vec<Arc<Mutex<RecordedCall>>>>>,
+}
+
+impl Recorder {
+    /// Snapshots of the calls, with the messages sent so far.
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        lock(&self.calls).iter().map(|x| lock(x).clone()).collect()
+    }
+
+    /// The calls of the method at `path`, e.g. `/acme.widgets.v1.WidgetService/GetWidget`.
+    pub fn calls_to(&self, path: &str) -> Vec<RecordedCall> {
+        self.calls()
+            .into_iter()
+            .filter(|x| x.path == path)
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        lock(&self.calls).clear();
+    }
+
+    fn start(&self, path: &str) -> Arc<Mutex<RecordedCall>> {
+        let call = Arc::new(Mutex::new(RecordedCall {
+            path: path.to_owned(),
+            request: Vec::new(),
+            response: Vec::new(),
+            code: None,
+            message: String::new(),
+        }));
+        lock(&self.calls).push(call.clone());
+        call
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // A panicking test must not hide the calls from other tests.
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A call as seen by the server: the encoded messages of both directions and
+/// the status it ended with.
+#[derive(Clone, Debug)]
+pub struct RecordedCall {
+    /// Path of the method, e.g. `/acme.widgets.v1.WidgetService/GetWidget`.
+    pub path: String,
+    request: Vec<u8>,
+    response: Vec<u8>,
+    /// Status code of the call, `None` while it is in progress.
+    pub code: Option<Code>,
+    pub message: String,
+}
+
+impl RecordedCall {
+    pub fn requests<T: Message + Default>(&self) -> Result<Vec<T>, DecodeError> {
+        decode_messages(&self.request)
+    }
+
+    pub fn responses<T: Message + Default>(&self) -> Result<Vec<T>, DecodeError> {
+        decode_messages(&self.response)
+    }
+
+    fn set_status(&mut self, headers: &HeaderMap) {
+        if let Some(status) = tonic::Status::from_header_map(headers) {
+            self.code = Some(status.code());
+            self.message = status.message().to_owned();
+        }
+    }
+}
+
+/// Decodes the length-prefixed messages of a gRPC stream, which the test
+/// server never compresses.
+fn decode_messages<T: Message + Default>(mut bytes: &[u8]) -> Result<Vec<T>, DecodeError> {
+    let mut messages = Vec::new();
+    while bytes.len() >= MESSAGE_PREFIX_LEN {
+        let len = u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]) as usize;
+        let Some(message) = bytes.get(MESSAGE_PREFIX_LEN..MESSAGE_PREFIX_LEN + len) else {
+            break;
+        };
+        messages.push(T::decode(message)?);
+        bytes = &bytes[MESSAGE_PREFIX_LEN + len..];
+    }
+    Ok(messages)
+}
+
+#[derive(Clone)]
+pub(crate) struct RecordLayer(pub Recorder);
+
+impl<S> Layer<S> for RecordLayer {
+    type Service = RecordService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RecordService {
+            inner,
+            recorder: self.0.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RecordService<S> {
+    inner: S,
+    recorder: Recorder,
+}
+
+impl<S> Service<Request<Body>> for RecordService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let call = self.recorder.start(request.uri().path());
+        let request = request.map(|body| {
+            Body::new(RecordingBody {
+                inner: body,
+                call: call.clone(),
+                direction: Direction::Request,
+            })
+        });
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            // Trailers-only responses carry the status in the headers.
+            lock(&call).set_status(response.headers());
+            Ok(response.map(|body| {
+                Body::new(RecordingBody {
+                    inner: body,
+                    call,
+                    direction: Direction::Response,
+                })
+            }))
+        })
+    }
+}
+
+enum Direction {
+    Request,
+    Response,
+}
+
+/// Passes the frames of a body through, recording them on the call.
+struct RecordingBody {
+    inner: Body,
+    call: Arc<Mutex<RecordedCall>>,
+    direction: Direction,
+}
+
+impl http_body::Body for RecordingBody {
+    type Data = Bytes;
+    type Error = tonic::Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            let mut call = lock(&self.call);
+            if let Some(data) = frame.data_ref() {
+                match self.direction {
+                    Direction::Request => call.request.extend_from_slice(data),
+                    Direction::Response => call.response.extend_from_slice(data),
+                }
+            }
+            if let Some(trailers) = frame.trailers_ref() {
+                call.set_status(trailers);
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,69 @@
+use axum::{
+    body::to_bytes,
+    extract::Request,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    status::{error_body, http_status},
+    Error, Route, Transcoder,
+};
+
+/// Largest request body read, like the default limit of axum extractors.
+pub const BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// A gRPC status as an HTTP error response with a `google.rpc.Status` body.
+pub struct ErrorResponse(pub tonic::Status);
+
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        (http_status(self.0.code()), Json(error_body(&self.0))).into_response()
+    }
+}
+
+impl From<tonic::Status> for ErrorResponse {
+    fn from(status: tonic::Status) -> Self {
+        Self(status)
+    }
+}
+
+impl From<Error> for ErrorResponse {
+    fn from(error: Error) -> Self {
+        Self(error.into())
+    }
+}
+
+/// Transcodes `request` to the `input_type` message of the method of
+/// `route`, failing with `NOT_FOUND` when the route does not match it.
+pub async fn bind_request<T: DeserializeOwned>(
+    transcoder: &Transcoder,
+    input_type: &str,
+    route: &Route,
+    request: Request,
+) -> Result<T, ErrorResponse> {
+    let (parts, body) = request.into_parts();
+    let variables = route
+        .matches(&parts.method, parts.uri.path())
+        .ok_or_else(|| tonic::Status::not_found(format!("No route for `{}`", parts.uri.path())))?;
+    let body = to_bytes(body, BODY_LIMIT)
+        .await
+        .map_err(|x| tonic::Status::invalid_argument(format!("Failed to read body: {x}")))?;
+    Ok(transcoder.bind(input_type, route, variables, parts.uri.query(), &body)?)
+}
+
+/// The HTTP response of the `result` of calling the method of `route`.
+pub fn into_response<T: Serialize>(
+    route: &Route,
+    result: Result<tonic::Response<T>, tonic::Status>,
+) -> Response {
+    let response = match result {
+        Ok(response) => response,
+        Err(status) => return ErrorResponse(status).into_response(),
+    };
+    match route.response_json(response.get_ref()) {
+        Ok(json) => Json(json).into_response(),
+        Err(error) => ErrorResponse::from(error).into_response(),
+    }
+}
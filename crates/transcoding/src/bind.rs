@@ -0,0 +1,403 @@
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE},
+    Engine,
+};
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use percent_encoding::percent_decode_str;
+use prost_reflect::{FieldDescriptor, Kind, MessageDescriptor, OneofDescriptor};
+use serde_json::{Map, Number, Value};
+
+use crate::Error;
+
+/// The JSON of a request message in the `serde` representation of the
+/// generated types: fields keyed by their Rust names, enums as numbers, bytes
+/// as arrays, and oneofs nested by variant.
+pub(crate) struct RequestJson {
+    message: MessageDescriptor,
+    json: Map<String, Value>,
+    /// Field paths set from the path, which the query may not set.
+    path_variables: Vec<String>,
+    /// Field paths set from the query, which may only be set once unless
+    /// repeated.
+    query_paths: Vec<String>,
+}
+
+impl RequestJson {
+    pub fn new(message: MessageDescriptor) -> Self {
+        Self {
+            message,
+            json: Map::new(),
+            path_variables: Vec::new(),
+            query_paths: Vec::new(),
+        }
+    }
+
+    /// Uses the request body as the whole message.
+    pub fn set_body(&mut self, body: Value) -> Result<(), Error> {
+        match body {
+            Value::Object(json) => {
+                self.json = json;
+                Ok(())
+            }
+            _ => Err(Error::InvalidBody("expected a JSON object".to_owned())),
+        }
+    }
+
+    /// Uses the request body as the top-level field `name`.
+    pub fn set_body_field(&mut self, name: &str, body: Value) -> Result<(), Error> {
+        let message = self.message.clone();
+        let field = find_field(&message, name)?;
+        *field_entry(&mut self.json, &field) = body;
+        Ok(())
+    }
+
+    /// Sets the field at `field_path` to the path variable `value`.
+    pub fn set_path_variable(&mut self, field_path: &str, value: &str) -> Result<(), Error> {
+        let (field, target) = self.field_target(field_path)?;
+        if field.is_list() || field.is_map() {
+            return Err(unsupported(
+                field_path,
+                "path variables bind singular fields",
+            ));
+        }
+        *target = coerce(field_path, &field, value)?;
+        self.path_variables.push(field_path.to_owned());
+        Ok(())
+    }
+
+    /// Sets the fields of the `&`-separated `query` parameters.
+    pub fn set_query(&mut self, query: &str) -> Result<(), Error> {
+        for param in query.split('&').filter(|x| !x.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let key = decode_query_component(key)?;
+            let value = decode_query_component(value)?;
+            self.set_query_param(&key, &value)?;
+        }
+        Ok(())
+    }
+
+    fn set_query_param(&mut self, field_path: &str, value: &str) -> Result<(), Error> {
+        if self.path_variables.iter().any(|x| x == field_path) {
+            return Err(Error::InvalidValue {
+                field_path: field_path.to_owned(),
+                value: value.to_owned(),
+                reason: "field is bound by the path".to_owned(),
+            });
+        }
+        let once = !self.query_paths.iter().any(|x| x == field_path);
+        let (field, target) = self.field_target(field_path)?;
+        if field.is_map() {
+            return Err(unsupported(
+                field_path,
+                "map fields cannot be query parameters",
+            ));
+        }
+        if field.is_list() {
+            let value = coerce(field_path, &field, value)?;
+            match target {
+                Value::Array(values) => values.push(value),
+                _ => *target = Value::Array(vec![value]),
+            }
+        } else if is_field_mask(&field) {
+            // Masks are given as comma separated paths, in any number of
+            // parameters.
+            let paths = mask_paths(value);
+            match target.get_mut("paths").and_then(Value::as_array_mut) {
+                Some(values) => values.extend(paths),
+                None => *target = serde_json::json!({ "paths": paths }),
+            }
+        } else if once {
+            *target = coerce(field_path, &field, value)?;
+        } else {
+            return Err(Error::InvalidValue {
+                field_path: field_path.to_owned(),
+                value: value.to_owned(),
+                reason: "singular field set more than once".to_owned(),
+            });
+        }
+        self.query_paths.push(field_path.to_owned());
+        Ok(())
+    }
+
+    /// The field at the `.`-separated `field_path` and its JSON value,
+    /// creating the enclosing messages.
+    fn field_target(&mut self, field_path: &str) -> Result<(FieldDescriptor, &mut Value), Error> {
+        let names: Vec<&str> = field_path.split('.').collect();
+        let mut message = self.message.clone();
+        let mut object = &mut self.json;
+        for (index, name) in names.iter().enumerate() {
+            let field = find_field(&message, name).map_err(|_| Error::UnknownField {
+                message: self.message.full_name().to_owned(),
+                field_path: field_path.to_owned(),
+            })?;
+            let entry = field_entry(object, &field);
+            if index + 1 == names.len() {
+                return Ok((field, entry));
+            }
+            message = match field.kind() {
+                Kind::Message(nested)
+                    if !field.is_list() && !field.is_map() && !is_well_known(&nested) =>
+                {
+                    nested
+                }
+                _ => {
+                    return Err(unsupported(
+                        field_path,
+                        &format!("`{name}` is not a singular message field"),
+                    ))
+                }
+            };
+            if !entry.is_object() {
+                *entry = Value::Object(Map::new());
+            }
+            object = entry.as_object_mut().expect("Expected object");
+        }
+        unreachable!("Expected non-empty field path")
+    }
+
+    /// The message with the fields not set by the request at their defaults,
+    /// as the `serde` derives of the generated types require every field.
+    pub fn into_json(mut self) -> Value {
+        fill_defaults(&mut self.json, &self.message);
+        Value::Object(self.json)
+    }
+}
+
+fn unsupported(field_path: &str, reason: &str) -> Error {
+    Error::UnsupportedField {
+        field_path: field_path.to_owned(),
+        reason: reason.to_owned(),
+    }
+}
+
+/// Looks up `name` as a proto field name or as its JSON name.
+fn find_field(message: &MessageDescriptor, name: &str) -> Result<FieldDescriptor, Error> {
+    message
+        .get_field_by_name(name)
+        .or_else(|| message.get_field_by_json_name(name))
+        .ok_or_else(|| Error::UnknownField {
+            message: message.full_name().to_owned(),
+            field_path: name.to_owned(),
+        })
+}
+
+/// The JSON value of `field` in `object`, within the variant object of its
+/// oneof, if any.
+fn field_entry<'a>(object: &'a mut Map<String, Value>, field: &FieldDescriptor) -> &'a mut Value {
+    match real_oneof(field) {
+        Some(oneof) => {
+            let variant = field.name().to_upper_camel_case();
+            let entry = object
+                .entry(oneof.name().to_snake_case())
+                .or_insert(Value::Null);
+            // A oneof holds one variant, so setting another replaces it.
+            if entry.get(&variant).is_none() {
+                *entry = Value::Object(Map::new());
+            }
+            entry
+                .as_object_mut()
+                .expect("Expected object")
+                .entry(variant)
+                .or_insert(Value::Null)
+        }
+        None => object.entry(field_key(field)).or_insert(Value::Null),
+    }
+}
+
+/// The oneof of `field`, unless it is the synthetic oneof of a proto3
+/// `optional` field, which is generated as an `Option`.
+fn real_oneof(field: &FieldDescriptor) -> Option<OneofDescriptor> {
+    field
+        .containing_oneof()
+        .filter(|_| !is_proto3_optional(field))
+}
+
+fn is_proto3_optional(field: &FieldDescriptor) -> bool {
+    field.field_descriptor_proto().proto3_optional()
+}
+
+fn field_key(field: &FieldDescriptor) -> String {
+    field.name().to_snake_case()
+}
+
+fn decode_query_component(component: &str) -> Result<String, Error> {
+    let component = component.replace('+', " ");
+    percent_decode_str(&component)
+        .decode_utf8()
+        .map(|x| x.into_owned())
+        .map_err(|_| Error::InvalidValue {
+            field_path: String::new(),
+            value: component.to_owned(),
+            reason: "query parameter is not UTF-8".to_owned(),
+        })
+}
+
+fn is_well_known(message: &MessageDescriptor) -> bool {
+    message.package_name() == "google.protobuf"
+}
+
+fn is_field_mask(field: &FieldDescriptor) -> bool {
+    matches!(field.kind(), Kind::Message(x) if x.full_name() == "google.protobuf.FieldMask")
+}
+
+/// Paths of a field mask given in JSON form, e.g. `displayName,book.id`, with
+/// names converted to the proto names the generated types use.
+fn mask_paths(value: &str) -> Vec<Value> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|path| {
+            let path: Vec<String> = path.split('.').map(|x| x.to_snake_case()).collect();
+            Value::String(path.join("."))
+        })
+        .collect()
+}
+
+/// Converts the text `value` of a path variable or query parameter to the
+/// JSON of `field`.
+fn coerce(field_path: &str, field: &FieldDescriptor, value: &str) -> Result<Value, Error> {
+    let invalid = |reason: &str| Error::InvalidValue {
+        field_path: field_path.to_owned(),
+        value: value.to_owned(),
+        reason: reason.to_owned(),
+    };
+    coerce_kind(&field.kind(), value).map_err(|reason| invalid(&reason))
+}
+
+fn coerce_kind(kind: &Kind, value: &str) -> Result<Value, String> {
+    fn parse<T: std::str::FromStr + Into<Number>>(
+        value: &str,
+        type_name: &str,
+    ) -> Result<Value, String> {
+        value
+            .parse::<T>()
+            .map(|x| Value::Number(x.into()))
+            .map_err(|_| format!("expected {type_name}"))
+    }
+    match kind {
+        Kind::Double | Kind::Float => value
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| "expected a finite number".to_owned()),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => parse::<i32>(value, "a 32-bit integer"),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => parse::<i64>(value, "a 64-bit integer"),
+        Kind::Uint32 | Kind::Fixed32 => parse::<u32>(value, "an unsigned 32-bit integer"),
+        Kind::Uint64 | Kind::Fixed64 => parse::<u64>(value, "an unsigned 64-bit integer"),
+        Kind::Bool => match value {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Err("expected `true` or `false`".to_owned()),
+        },
+        Kind::String => Ok(Value::String(value.to_owned())),
+        Kind::Bytes => STANDARD
+            .decode(value)
+            .or_else(|_| URL_SAFE.decode(value))
+            .or_else(|_| {
+                let unpadded = value.trim_end_matches('=');
+                let padding = "=".repeat((4 - unpadded.len() % 4) % 4);
+                URL_SAFE.decode(format!("{unpadded}{padding}"))
+            })
+            .map(|x| Value::Array(x.into_iter().map(Value::from).collect()))
+            .map_err(|_| "expected base64".to_owned()),
+        Kind::Enum(enum_) => enum_
+            .get_value_by_name(value)
+            .map(|x| x.number())
+            .or_else(|| value.parse().ok())
+            .map(Value::from)
+            .ok_or_else(|| format!("expected a value of `{}`", enum_.full_name())),
+        Kind::Message(message) => match message.full_name() {
+            "google.protobuf.Timestamp" | "google.protobuf.Duration" => {
+                Ok(Value::String(value.to_owned()))
+            }
+            "google.protobuf.FieldMask" => Ok(serde_json::json!({ "paths": mask_paths(value) })),
+            // Wrappers are generated as optional scalars.
+            name if is_well_known(message) && name.ends_with("Value") => {
+                match message.get_field_by_name("value") {
+                    Some(field) => coerce_kind(&field.kind(), value),
+                    None => Err(format!("`{name}` cannot be a query parameter")),
+                }
+            }
+            name => Err(format!("message `{name}` cannot be a query parameter")),
+        },
+    }
+}
+
+/// Adds the fields missing from `object` of `message` at their defaults,
+/// recursing into the messages that are set.
+fn fill_defaults(object: &mut Map<String, Value>, message: &MessageDescriptor) {
+    for oneof in message
+        .oneofs()
+        .filter(|x| !x.fields().any(|x| is_proto3_optional(&x)))
+    {
+        let entry = object
+            .entry(oneof.name().to_snake_case())
+            .or_insert(Value::Null);
+        if let Some(variants) = entry.as_object_mut() {
+            for field in oneof.fields() {
+                if let Some(value) = variants.get_mut(&field.name().to_upper_camel_case()) {
+                    fill_value_defaults(value, &field);
+                }
+            }
+        }
+    }
+    for field in message.fields() {
+        if real_oneof(&field).is_some() {
+            continue;
+        }
+        match object.get_mut(&field_key(&field)) {
+            Some(value) => fill_value_defaults(value, &field),
+            None => {
+                object.insert(field_key(&field), default_value(&field));
+            }
+        }
+    }
+}
+
+fn fill_value_defaults(value: &mut Value, field: &FieldDescriptor) {
+    let Kind::Message(message) = field.kind() else {
+        return;
+    };
+    if is_well_known(&message) {
+        return;
+    }
+    if field.is_map() {
+        let value_field = message.map_entry_value_field();
+        if let Some(entries) = value.as_object_mut() {
+            for value in entries.values_mut() {
+                fill_value_defaults(value, &value_field);
+            }
+        }
+    } else if field.is_list() {
+        if let Some(values) = value.as_array_mut() {
+            for value in values.iter_mut().filter_map(Value::as_object_mut) {
+                fill_defaults(value, &message);
+            }
+        }
+    } else if let Some(object) = value.as_object_mut() {
+        fill_defaults(object, &message);
+    }
+}
+
+fn default_value(field: &FieldDescriptor) -> Value {
+    if field.is_map() {
+        return Value::Object(Map::new());
+    }
+    if field.is_list() {
+        return Value::Array(Vec::new());
+    }
+    // Proto3 optional fields are generated as `Option`.
+    if field.supports_presence() {
+        return Value::Null;
+    }
+    match field.kind() {
+        Kind::Double | Kind::Float => Value::from(0.0),
+        Kind::Bool => Value::Bool(false),
+        Kind::String => Value::String(String::new()),
+        Kind::Bytes => Value::Array(Vec::new()),
+        Kind::Message(_) => Value::Null,
+        _ => Value::from(0),
+    }
+}
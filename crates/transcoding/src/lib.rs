@@ -0,0 +1,730 @@
+//! Runtime of HTTP/JSON gateways for `google.api.http` annotated methods:
+//! matching path templates, binding path variables, query parameters and
+//! bodies to request messages, and mapping gRPC statuses to HTTP errors with
+//! `google.rpc.Status` bodies. Requests and responses use the `serde`
+//! representation of the generated types.
+//!
+//! ```ignore
+//! let transcoder = appbiotic_api_transcoding::transcoder!(acme_widgets);
+//! let route = Route::new(Method::GET, "/v1/{name=widgets/*}")?;
+//! let request: GetWidgetRequest = transcoder.bind(
+//!     "acme.widgets.v1.GetWidgetRequest",
+//!     &route,
+//!     route.matches(&method, path).unwrap(),
+//!     query,
+//!     &body,
+//! )?;
+//! ```
+
+mod bind;
+mod path_template;
+mod rule;
+pub mod status;
+
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "utoipa")]
+pub mod openapi;
+
+use std::fmt;
+
+use http::Method;
+use prost_reflect::{DescriptorError, DescriptorPool, MethodDescriptor};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::bind::RequestJson;
+pub use crate::path_template::{PathTemplate, TemplateError};
+
+#[derive(Debug)]
+pub enum Error {
+    Descriptor(DescriptorError),
+    /// The input type of a method is not registered.
+    UnknownMessage(String),
+    UnknownField {
+        message: String,
+        field_path: String,
+    },
+    /// The field cannot be bound from a path variable or query parameter.
+    UnsupportedField {
+        field_path: String,
+        reason: String,
+    },
+    InvalidValue {
+        field_path: String,
+        value: String,
+        reason: String,
+    },
+    InvalidBody(String),
+    /// The `google.api.http` rule of a method has no valid route.
+    InvalidRule {
+        method: String,
+        reason: String,
+    },
+    /// The bound JSON does not deserialize to the request type.
+    Request(serde_json::Error),
+    Response(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Descriptor(error) => write!(f, "Invalid descriptor set: {error}"),
+            Self::UnknownMessage(name) => write!(f, "Message `{name}` is not registered"),
+            Self::UnknownField {
+                message,
+                field_path,
+            } => write!(f, "`{field_path}` is not a field of `{message}`"),
+            Self::UnsupportedField { field_path, reason } => {
+                write!(f, "Field `{field_path}` cannot be bound: {reason}")
+            }
+            Self::InvalidValue {
+                field_path,
+                value,
+                reason,
+            } => write!(f, "Invalid value `{value}` of `{field_path}`: {reason}"),
+            Self::InvalidBody(reason) => write!(f, "Invalid request body: {reason}"),
+            Self::InvalidRule { method, reason } => {
+                write!(f, "Invalid `google.api.http` rule of `{method}`: {reason}")
+            }
+            Self::Request(error) => write!(f, "Invalid request: {error}"),
+            Self::Response(error) => write!(f, "Failed to serialize response: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Descriptor(error) => Some(error),
+            Self::Request(error) | Self::Response(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<DescriptorError> for Error {
+    fn from(error: DescriptorError) -> Self {
+        Self::Descriptor(error)
+    }
+}
+
+impl From<Error> for tonic::Status {
+    /// Errors of the request are `INVALID_ARGUMENT`, others `INTERNAL`.
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Descriptor(_)
+            | Error::UnknownMessage(_)
+            | Error::InvalidRule { .. }
+            | Error::Response(_) => tonic::Status::internal(error.to_string()),
+            _ => tonic::Status::invalid_argument(error.to_string()),
+        }
+    }
+}
+
+/// The HTTP binding of a method, as given by its `google.api.http` rule.
+#[derive(Clone, Debug)]
+pub struct Route {
+    pub method: Method,
+    pub template: PathTemplate,
+    /// The request field the body is bound to, or `*` for the whole request.
+    pub body: Option<String>,
+    /// The response field the response body is taken from, if not the whole
+    /// response.
+    pub response_body: Option<String>,
+}
+
+impl Route {
+    pub fn new(method: Method, template: &str) -> Result<Self, TemplateError> {
+        Ok(Self {
+            method,
+            template: template.parse()?,
+            body: None,
+            response_body: None,
+        })
+    }
+
+    pub fn with_body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    pub fn with_response_body(mut self, response_body: impl Into<String>) -> Self {
+        self.response_body = Some(response_body.into());
+        self
+    }
+
+    /// The path variables of a request with `method` and `path` this route
+    /// matches.
+    pub fn matches(&self, method: &Method, path: &str) -> Option<Vec<(String, String)>> {
+        if method != self.method {
+            return None;
+        }
+        self.template.matches(path)
+    }
+
+    /// The JSON of the response body of `response`.
+    pub fn response_json<T: Serialize>(&self, response: &T) -> Result<serde_json::Value, Error> {
+        let json = serde_json::to_value(response).map_err(Error::Response)?;
+        Ok(match &self.response_body {
+            Some(field) => json.get(field).cloned().unwrap_or(serde_json::Value::Null),
+            None => json,
+        })
+    }
+}
+
+/// A method with the route of one of the bindings of its `google.api.http`
+/// rule.
+#[derive(Clone, Debug)]
+pub struct MethodRoute {
+    pub method: MethodDescriptor,
+    pub route: Route,
+}
+
+/// Binds HTTP requests to the request messages of the registered descriptor
+/// sets.
+#[derive(Clone, Debug, Default)]
+pub struct Transcoder {
+    pool: DescriptorPool,
+}
+
+impl Transcoder {
+    /// Adds the files of an encoded `FileDescriptorSet`, such as the
+    /// `FILE_DESCRIPTOR_SET` of a generated package.
+    pub fn register(&mut self, file_descriptor_set: &[u8]) -> Result<(), Error> {
+        self.pool.decode_file_descriptor_set(file_descriptor_set)?;
+        Ok(())
+    }
+
+    pub fn pool(&self) -> &DescriptorPool {
+        &self.pool
+    }
+
+    /// The routes of the `google.api.http` rules of the registered methods,
+    /// which requires their descriptor sets to include
+    /// `google/api/annotations.proto`.
+    pub fn routes(&self) -> Result<Vec<MethodRoute>, Error> {
+        rule::method_routes(&self.pool)
+    }
+
+    /// Binds a request matched by `route` to its `input_type` message: the
+    /// body as given by the route, then the `variables` of the path, then,
+    /// unless the body is the whole request, the `query` parameters for the
+    /// remaining fields. Fields the request does not set keep their defaults.
+    pub fn bind<T: DeserializeOwned>(
+        &self,
+        input_type: &str,
+        route: &Route,
+        variables: Vec<(String, String)>,
+        query: Option<&str>,
+        body: &[u8],
+    ) -> Result<T, Error> {
+        let message = self
+            .pool
+            .get_message_by_name(input_type)
+            .ok_or_else(|| Error::UnknownMessage(input_type.to_owned()))?;
+        let mut json = RequestJson::new(message);
+
+        if let Some(field) = &route.body {
+            let body = if body.is_empty() {
+                serde_json::Value::Object(Default::default())
+            } else {
+                serde_json::from_slice(body).map_err(|x| Error::InvalidBody(x.to_string()))?
+            };
+            if field == "*" {
+                json.set_body(body)?;
+            } else {
+                json.set_body_field(field, body)?;
+            }
+        }
+        for (field_path, value) in &variables {
+            json.set_path_variable(field_path, value)?;
+        }
+        if route.body.as_deref() != Some("*") {
+            if let Some(query) = query {
+                json.set_query(query)?;
+            }
+        }
+
+        serde_json::from_value(json.into_json()).map_err(Error::Request)
+    }
+}
+
+/// Builds a [`Transcoder`] of the descriptors of the listed generated crates,
+/// which must enable their `prost-serde` feature.
+#[macro_export]
+macro_rules! transcoder {
+    ($($crate_name:ident),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut transcoder = $crate::Transcoder::default();
+        $(
+            transcoder
+                .register($crate_name::prost_serde::FILE_DESCRIPTOR_SET)
+                .expect(concat!(
+                    "Failed to register descriptor set of `",
+                    stringify!($crate_name),
+                    "`"
+                ));
+        )*
+        transcoder
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use prost::Message;
+    use prost_types::{
+        field_descriptor_proto::{Label, Type},
+        DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto,
+        FileDescriptorProto, FileDescriptorSet, OneofDescriptorProto,
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn field(
+        name: &str,
+        number: i32,
+        r#type: Type,
+        type_name: Option<&str>,
+    ) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_owned()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(r#type as i32),
+            type_name: type_name.map(str::to_owned),
+            ..Default::default()
+        }
+    }
+
+    fn test_file() -> FileDescriptorProto {
+        let mut states = field("states", 3, Type::Enum, Some(".test.v1.State"));
+        states.label = Some(Label::Repeated as i32);
+        let mut author = field("author", 5, Type::String, None);
+        author.oneof_index = Some(0);
+        let mut year = field("year", 6, Type::Int32, None);
+        year.oneof_index = Some(0);
+        FileDescriptorProto {
+            name: Some("test/v1/test.proto".to_owned()),
+            package: Some("test.v1".to_owned()),
+            syntax: Some("proto3".to_owned()),
+            message_type: vec![
+                DescriptorProto {
+                    name: Some("ListBooksRequest".to_owned()),
+                    field: vec![
+                        field("name", 1, Type::String, None),
+                        field("page_size", 2, Type::Int64, None),
+                        states,
+                        field("book", 4, Type::Message, Some(".test.v1.Book")),
+                        author,
+                        year,
+                    ],
+                    oneof_decl: vec![OneofDescriptorProto {
+                        name: Some("filter".to_owned()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("Book".to_owned()),
+                    field: vec![
+                        field("id", 1, Type::String, None),
+                        field("data", 2, Type::Bytes, None),
+                    ],
+                    ..Default::default()
+                },
+            ],
+            enum_type: vec![EnumDescriptorProto {
+                name: Some("State".to_owned()),
+                value: ["STATE_UNSPECIFIED", "STATE_DRAFT", "STATE_PUBLISHED"]
+                    .iter()
+                    .enumerate()
+                    .map(|(number, name)| EnumValueDescriptorProto {
+                        name: Some(name.to_string()),
+                        number: Some(number as i32),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn transcoder() -> Transcoder {
+        let mut transcoder = Transcoder::default();
+        transcoder
+            .register(
+                &FileDescriptorSet {
+                    file: vec![test_file()],
+                }
+                .encode_to_vec(),
+            )
+            .unwrap();
+        transcoder
+    }
+
+    /// The parts of `google/protobuf/descriptor.proto` declaring a service
+    /// with `google.api.http` options, which `prost-types` drops.
+    mod annotated {
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct FileDescriptorSet {
+            #[prost(message, repeated, tag = "1")]
+            pub file: Vec<FileDescriptorProto>,
+        }
+
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct FileDescriptorProto {
+            #[prost(string, tag = "1")]
+            pub name: String,
+            #[prost(string, tag = "2")]
+            pub package: String,
+            #[prost(string, repeated, tag = "3")]
+            pub dependency: Vec<String>,
+            #[prost(message, repeated, tag = "6")]
+            pub service: Vec<ServiceDescriptorProto>,
+            #[prost(string, tag = "12")]
+            pub syntax: String,
+        }
+
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct ServiceDescriptorProto {
+            #[prost(string, tag = "1")]
+            pub name: String,
+            #[prost(message, repeated, tag = "2")]
+            pub method: Vec<MethodDescriptorProto>,
+        }
+
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct MethodDescriptorProto {
+            #[prost(string, tag = "1")]
+            pub name: String,
+            #[prost(string, tag = "2")]
+            pub input_type: String,
+            #[prost(string, tag = "3")]
+            pub output_type: String,
+            #[prost(message, optional, tag = "4")]
+            pub options: Option<MethodOptions>,
+        }
+
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct MethodOptions {
+            #[prost(message, optional, tag = "72295728")]
+            pub http: Option<HttpRule>,
+        }
+
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct HttpRule {
+            #[prost(string, optional, tag = "2")]
+            pub get: Option<String>,
+            #[prost(string, optional, tag = "3")]
+            pub put: Option<String>,
+            #[prost(string, optional, tag = "4")]
+            pub post: Option<String>,
+            #[prost(string, tag = "7")]
+            pub body: String,
+            #[prost(message, optional, tag = "8")]
+            pub custom: Option<CustomHttpPattern>,
+            #[prost(message, repeated, tag = "11")]
+            pub additional_bindings: Vec<HttpRule>,
+            #[prost(string, tag = "12")]
+            pub response_body: String,
+        }
+
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct CustomHttpPattern {
+            #[prost(string, tag = "1")]
+            pub kind: String,
+            #[prost(string, tag = "2")]
+            pub path: String,
+        }
+    }
+
+    /// `google/api/http.proto` and `google/api/annotations.proto`, with the
+    /// fields of `HttpRule` the tests use.
+    fn http_files() -> Vec<FileDescriptorProto> {
+        let mut additional_bindings = field(
+            "additional_bindings",
+            11,
+            Type::Message,
+            Some(".google.api.HttpRule"),
+        );
+        additional_bindings.label = Some(Label::Repeated as i32);
+        let mut patterns: Vec<FieldDescriptorProto> = [("get", 2), ("put", 3), ("post", 4)]
+            .into_iter()
+            .map(|(name, number)| field(name, number, Type::String, None))
+            .collect();
+        let mut custom = field(
+            "custom",
+            8,
+            Type::Message,
+            Some(".google.api.CustomHttpPattern"),
+        );
+        custom.oneof_index = Some(0);
+        for pattern in &mut patterns {
+            pattern.oneof_index = Some(0);
+        }
+        patterns.extend([
+            field("body", 7, Type::String, None),
+            custom,
+            additional_bindings,
+            field("response_body", 12, Type::String, None),
+        ]);
+        let mut http = field(
+            "http",
+            72295728,
+            Type::Message,
+            Some(".google.api.HttpRule"),
+        );
+        http.extendee = Some(".google.protobuf.MethodOptions".to_owned());
+        vec![
+            DescriptorPool::global()
+                .get_file_by_name("google/protobuf/descriptor.proto")
+                .unwrap()
+                .file_descriptor_proto()
+                .clone(),
+            FileDescriptorProto {
+                name: Some("google/api/http.proto".to_owned()),
+                package: Some("google.api".to_owned()),
+                syntax: Some("proto3".to_owned()),
+                message_type: vec![
+                    DescriptorProto {
+                        name: Some("HttpRule".to_owned()),
+                        field: patterns,
+                        oneof_decl: vec![OneofDescriptorProto {
+                            name: Some("pattern".to_owned()),
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    },
+                    DescriptorProto {
+                        name: Some("CustomHttpPattern".to_owned()),
+                        field: vec![
+                            field("kind", 1, Type::String, None),
+                            field("path", 2, Type::String, None),
+                        ],
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            FileDescriptorProto {
+                name: Some("google/api/annotations.proto".to_owned()),
+                package: Some("google.api".to_owned()),
+                dependency: vec![
+                    "google/api/http.proto".to_owned(),
+                    "google/protobuf/descriptor.proto".to_owned(),
+                ],
+                syntax: Some("proto3".to_owned()),
+                extension: vec![http],
+                ..Default::default()
+            },
+        ]
+    }
+
+    /// A transcoder of `test.v1.ShelfService`, whose methods have
+    /// `google.api.http` rules.
+    pub(crate) fn annotated_transcoder() -> Transcoder {
+        use annotated::{
+            CustomHttpPattern, HttpRule, MethodDescriptorProto, MethodOptions,
+            ServiceDescriptorProto,
+        };
+
+        let method = |name: &str, http: HttpRule| MethodDescriptorProto {
+            name: name.to_owned(),
+            input_type: ".test.v1.ListBooksRequest".to_owned(),
+            output_type: ".test.v1.Book".to_owned(),
+            options: Some(MethodOptions { http: Some(http) }),
+        };
+        let service_file = annotated::FileDescriptorProto {
+            name: "test/v1/service.proto".to_owned(),
+            package: "test.v1".to_owned(),
+            dependency: vec![
+                "google/api/annotations.proto".to_owned(),
+                "test/v1/test.proto".to_owned(),
+            ],
+            service: vec![ServiceDescriptorProto {
+                name: "ShelfService".to_owned(),
+                method: vec![
+                    method(
+                        "ListBooks",
+                        HttpRule {
+                            get: Some("/v1/{name=shelves/*}/books".to_owned()),
+                            additional_bindings: vec![HttpRule {
+                                post: Some("/v1/{name=shelves/*}/books:search".to_owned()),
+                                body: "*".to_owned(),
+                                ..Default::default()
+                            }],
+                            ..Default::default()
+                        },
+                    ),
+                    method(
+                        "UpdateBook",
+                        HttpRule {
+                            put: Some("/v1/{book.id}".to_owned()),
+                            body: "book".to_owned(),
+                            response_body: "data".to_owned(),
+                            ..Default::default()
+                        },
+                    ),
+                    method(
+                        "ListAllBooks",
+                        HttpRule {
+                            custom: Some(CustomHttpPattern {
+                                kind: "LIST".to_owned(),
+                                path: "/v1/books".to_owned(),
+                            }),
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            }],
+            syntax: "proto3".to_owned(),
+        };
+
+        let mut files = http_files();
+        files.push(test_file());
+        let mut transcoder = Transcoder::default();
+        transcoder
+            .register(&FileDescriptorSet { file: files }.encode_to_vec())
+            .unwrap();
+        transcoder
+            .register(
+                &annotated::FileDescriptorSet {
+                    file: vec![service_file],
+                }
+                .encode_to_vec(),
+            )
+            .unwrap();
+        transcoder
+    }
+
+    fn bind(route: &Route, path: &str, query: Option<&str>, body: &str) -> Result<Value, Error> {
+        let variables = route.matches(&route.method, path).unwrap();
+        transcoder().bind(
+            "test.v1.ListBooksRequest",
+            route,
+            variables,
+            query,
+            body.as_bytes(),
+        )
+    }
+
+    #[test]
+    fn it_binds_path_variables_and_query_parameters() {
+        let route = Route::new(Method::GET, "/v1/{name=shelves/*}/books").unwrap();
+        let request = bind(
+            &route,
+            "/v1/shelves/1/books",
+            Some("pageSize=20&states=STATE_DRAFT&states=2&book.data=aGk%3D&year=1999"),
+            "",
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            json!({
+                "name": "shelves/1",
+                "page_size": 20,
+                "states": [1, 2],
+                "book": { "id": "", "data": [104, 105] },
+                "filter": { "Year": 1999 },
+            })
+        );
+    }
+
+    #[test]
+    fn it_binds_bodies() {
+        let route = Route::new(Method::POST, "/v1/{name=shelves/*}/books")
+            .unwrap()
+            .with_body("*");
+        let request = bind(
+            &route,
+            "/v1/shelves/1/books",
+            Some("page_size=5"),
+            r#"{ "name": "ignored", "book": { "id": "b" } }"#,
+        )
+        .unwrap();
+        assert_eq!(request["name"], "shelves/1");
+        assert_eq!(request["page_size"], 0);
+        assert_eq!(request["book"], json!({ "id": "b", "data": [] }));
+
+        let route = Route::new(Method::PUT, "/v1/{name=shelves/*}")
+            .unwrap()
+            .with_body("book");
+        let request = bind(&route, "/v1/shelves/1", None, r#"{ "id": "b" }"#).unwrap();
+        assert_eq!(request["book"]["id"], "b");
+    }
+
+    #[test]
+    fn it_rejects_invalid_parameters() {
+        let route = Route::new(Method::GET, "/v1/{name=shelves/*}/books").unwrap();
+        for query in [
+            "author=a&author=b",
+            "page_size=x",
+            "states=STATE_GONE",
+            "shelf=1",
+        ] {
+            assert!(
+                bind(&route, "/v1/shelves/1/books", Some(query), "").is_err(),
+                "{query}"
+            );
+        }
+        let status = tonic::Status::from(
+            bind(&route, "/v1/shelves/1/books", Some("name=1"), "").unwrap_err(),
+        );
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn it_reads_routes_of_http_rules() {
+        let routes: Vec<_> = annotated_transcoder()
+            .routes()
+            .unwrap()
+            .into_iter()
+            .map(|x| {
+                (
+                    x.method.name().to_owned(),
+                    x.route.method.to_string(),
+                    x.route.template.to_string(),
+                    x.route.body,
+                    x.route.response_body,
+                )
+            })
+            .collect();
+        let route = |method: &str,
+                     http_method: &str,
+                     template: &str,
+                     body: Option<&str>,
+                     response_body: Option<&str>| {
+            (
+                method.to_owned(),
+                http_method.to_owned(),
+                template.to_owned(),
+                body.map(str::to_owned),
+                response_body.map(str::to_owned),
+            )
+        };
+        assert_eq!(
+            routes,
+            vec![
+                route("ListBooks", "GET", "/v1/{name=shelves/*}/books", None, None),
+                route(
+                    "ListBooks",
+                    "POST",
+                    "/v1/{name=shelves/*}/books:search",
+                    Some("*"),
+                    None
+                ),
+                route(
+                    "UpdateBook",
+                    "PUT",
+                    "/v1/{book.id}",
+                    Some("book"),
+                    Some("data")
+                ),
+                route("ListAllBooks", "LIST", "/v1/books", None, None),
+            ]
+        );
+        assert!(transcoder().routes().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,418 @@
+//! OpenAPI path items of the transcoded methods, for documenting gateways
+//! with `utoipa`. Bodies refer to the schemas of messages by their full
+//! names, as derived by generated packages setting `openapi_schemas`:
+//!
+//! ```ignore
+//! #[derive(OpenApi)]
+//! #[openapi(
+//!     components(schemas(Widget, GetWidgetRequest)),
+//!     modifiers(&appbiotic_api::openapi::ProtoSchemaNames)
+//! )]
+//! struct ApiDoc;
+//!
+//! let mut openapi = ApiDoc::openapi();
+//! openapi.paths.merge(appbiotic_api_transcoding::openapi::paths(&transcoder)?);
+//! ```
+
+use std::collections::HashSet;
+
+use prost_reflect::{FieldDescriptor, Kind, MessageDescriptor, MethodDescriptor};
+use utoipa::openapi::{
+    path::{HttpMethod, Operation, OperationBuilder, Parameter, ParameterBuilder, ParameterIn},
+    request_body::RequestBodyBuilder,
+    schema::{AdditionalProperties, ArrayBuilder, ObjectBuilder, Type},
+    ContentBuilder, KnownFormat, Paths, Ref, RefOr, Required, ResponseBuilder, Schema,
+    SchemaFormat,
+};
+
+use crate::{Error, MethodRoute, Route, Transcoder};
+
+const JSON: &str = "application/json";
+
+/// The path items of the routes of the registered methods, keyed by their
+/// OpenAPI paths. Variables spanning several segments, such as `name` of
+/// `/v1/{name=shelves/*}`, are single path parameters whose `/` clients must
+/// not escape. Routes of custom methods OpenAPI has no operation for are left
+/// out.
+pub fn paths(transcoder: &Transcoder) -> Result<Paths, Error> {
+    let mut paths = Paths::new();
+    for MethodRoute { method, route } in transcoder.routes()? {
+        let Some(http_method) = http_method(&route.method) else {
+            continue;
+        };
+        paths.add_path_operation(
+            route.template.openapi_path(),
+            vec![http_method],
+            operation(&method, &route)?,
+        );
+    }
+    Ok(paths)
+}
+
+/// The operation of `method` bound to `route`, identified by the full name
+/// of the method and tagged with the full name of its service.
+pub fn operation(method: &MethodDescriptor, route: &Route) -> Result<Operation, Error> {
+    let input = method.input();
+    let mut operation = OperationBuilder::new()
+        .operation_id(Some(method.full_name()))
+        .tag(method.parent_service().full_name());
+
+    let mut bound = Vec::new();
+    for field_path in route.template.field_paths() {
+        let field = resolve(&input, field_path)?;
+        operation = operation.parameter(parameter(field_path, ParameterIn::Path, &field));
+        bound.push(field_path.to_owned());
+    }
+    match route.body.as_deref() {
+        Some("*") => {
+            operation = operation.request_body(Some(
+                RequestBodyBuilder::new()
+                    .content(
+                        JSON,
+                        ContentBuilder::new()
+                            .schema(Some(message_ref(&input)))
+                            .build(),
+                    )
+                    .required(Some(Required::True))
+                    .build(),
+            ));
+        }
+        body => {
+            if let Some(body) = body {
+                let field = resolve(&input, body)?;
+                operation = operation.request_body(Some(
+                    RequestBodyBuilder::new()
+                        .content(
+                            JSON,
+                            ContentBuilder::new()
+                                .schema(Some(body_schema(&field)))
+                                .build(),
+                        )
+                        .build(),
+                ));
+                bound.push(body.to_owned());
+            }
+            let mut visited = HashSet::from([input.full_name().to_owned()]);
+            for (field_path, field) in query_fields(&input, "", &bound, &mut visited) {
+                operation = operation.parameter(parameter(&field_path, ParameterIn::Query, &field));
+            }
+        }
+    }
+
+    let response = match &route.response_body {
+        Some(response_body) => body_schema(&resolve(&method.output(), response_body)?),
+        None => message_ref(&method.output()),
+    };
+    Ok(operation
+        .response(
+            "200",
+            ResponseBuilder::new()
+                .description("OK")
+                .content(JSON, ContentBuilder::new().schema(Some(response)).build()),
+        )
+        .response(
+            "default",
+            ResponseBuilder::new()
+                .description("The `google.rpc.Status` of the error")
+                .content(
+                    JSON,
+                    ContentBuilder::new().schema(Some(status_schema())).build(),
+                ),
+        )
+        .build())
+}
+
+fn http_method(method: &http::Method) -> Option<HttpMethod> {
+    Some(match *method {
+        http::Method::GET => HttpMethod::Get,
+        http::Method::PUT => HttpMethod::Put,
+        http::Method::POST => HttpMethod::Post,
+        http::Method::DELETE => HttpMethod::Delete,
+        http::Method::PATCH => HttpMethod::Patch,
+        http::Method::HEAD => HttpMethod::Head,
+        http::Method::OPTIONS => HttpMethod::Options,
+        http::Method::TRACE => HttpMethod::Trace,
+        _ => return None,
+    })
+}
+
+/// The field of `message` at the dotted `field_path`.
+fn resolve(message: &MessageDescriptor, field_path: &str) -> Result<FieldDescriptor, Error> {
+    let unknown = || Error::UnknownField {
+        message: message.full_name().to_owned(),
+        field_path: field_path.to_owned(),
+    };
+    let mut parent = message.clone();
+    let mut names = field_path.split('.').peekable();
+    while let Some(name) = names.next() {
+        let field = parent.get_field_by_name(name).ok_or_else(unknown)?;
+        if names.peek().is_none() {
+            return Ok(field);
+        }
+        match field.kind() {
+            Kind::Message(message) if !field.is_list() && !field.is_map() => parent = message,
+            _ => return Err(unknown()),
+        }
+    }
+    Err(unknown())
+}
+
+/// The fields of `message` that query parameters set, by field path: those
+/// not `bound` to the path or body, including the fields of singular
+/// messages other than the well-known types, which are not visited twice.
+fn query_fields(
+    message: &MessageDescriptor,
+    prefix: &str,
+    bound: &[String],
+    visited: &mut HashSet<String>,
+) -> Vec<(String, FieldDescriptor)> {
+    let mut fields = Vec::new();
+    for field in message.fields() {
+        let field_path = format!("{prefix}{}", field.name());
+        if bound.contains(&field_path) || field.is_map() {
+            continue;
+        }
+        match field.kind() {
+            Kind::Message(nested) if query_schema(&field.kind()).is_none() => {
+                if field.is_list()
+                    || nested.package_name() == "google.protobuf"
+                    || !visited.insert(nested.full_name().to_owned())
+                {
+                    continue;
+                }
+                fields.extend(query_fields(
+                    &nested,
+                    &format!("{field_path}."),
+                    bound,
+                    visited,
+                ));
+                visited.remove(nested.full_name());
+            }
+            _ => fields.push((field_path, field)),
+        }
+    }
+    fields
+}
+
+fn parameter(field_path: &str, parameter_in: ParameterIn, field: &FieldDescriptor) -> Parameter {
+    let required = match parameter_in {
+        ParameterIn::Path => Required::True,
+        _ => Required::False,
+    };
+    let schema = query_schema(&field.kind())
+        .unwrap_or_else(|| ObjectBuilder::new().schema_type(Type::String).into());
+    let schema = if field.is_list() {
+        ArrayBuilder::new().items(schema).into()
+    } else {
+        schema
+    };
+    ParameterBuilder::new()
+        .name(field_path)
+        .parameter_in(parameter_in)
+        .required(required)
+        .schema(Some(schema))
+        .build()
+}
+
+/// The schema of the text of a path variable or query parameter of `kind`,
+/// as the transcoder parses it, if it can be one.
+fn query_schema(kind: &Kind) -> Option<RefOr<Schema>> {
+    let schema = match kind {
+        Kind::Message(message) => {
+            return match message.full_name() {
+                "google.protobuf.Timestamp" => Some(formatted(Type::String, KnownFormat::DateTime)),
+                "google.protobuf.Duration" | "google.protobuf.FieldMask" => {
+                    Some(ObjectBuilder::new().schema_type(Type::String).into())
+                }
+                name if name.starts_with("google.protobuf.") && name.ends_with("Value") => message
+                    .get_field_by_name("value")
+                    .and_then(|x| query_schema(&x.kind())),
+                _ => None,
+            };
+        }
+        Kind::Bytes => formatted(Type::String, KnownFormat::Byte),
+        Kind::Enum(enum_) => ObjectBuilder::new()
+            .schema_type(Type::String)
+            .enum_values(Some(enum_.values().map(|x| x.name().to_owned())))
+            .into(),
+        kind => scalar_schema(kind),
+    };
+    Some(schema)
+}
+
+/// The schema of the `serde` representation of a body `field`.
+fn body_schema(field: &FieldDescriptor) -> RefOr<Schema> {
+    let schema = |kind: &Kind| match kind {
+        Kind::Message(message) => message_ref(message),
+        Kind::Bytes => ArrayBuilder::new()
+            .items(formatted(Type::Integer, KnownFormat::Int32))
+            .into(),
+        // Enums are their numbers.
+        Kind::Enum(_) => formatted(Type::Integer, KnownFormat::Int32),
+        kind => scalar_schema(kind),
+    };
+    if field.is_map() {
+        let Kind::Message(entry) = field.kind() else {
+            unreachable!("Expected map entry message");
+        };
+        ObjectBuilder::new()
+            .additional_properties(Some(AdditionalProperties::RefOr(schema(
+                &entry.map_entry_value_field().kind(),
+            ))))
+            .into()
+    } else if field.is_list() {
+        ArrayBuilder::new().items(schema(&field.kind())).into()
+    } else {
+        schema(&field.kind())
+    }
+}
+
+fn scalar_schema(kind: &Kind) -> RefOr<Schema> {
+    match kind {
+        Kind::Double => formatted(Type::Number, KnownFormat::Double),
+        Kind::Float => formatted(Type::Number, KnownFormat::Float),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 | Kind::Uint32 | Kind::Fixed32 => {
+            formatted(Type::Integer, KnownFormat::Int32)
+        }
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 | Kind::Uint64 | Kind::Fixed64 => {
+            formatted(Type::Integer, KnownFormat::Int64)
+        }
+        Kind::Bool => ObjectBuilder::new().schema_type(Type::Boolean).into(),
+        _ => ObjectBuilder::new().schema_type(Type::String).into(),
+    }
+}
+
+fn formatted(schema_type: Type, format: KnownFormat) -> RefOr<Schema> {
+    ObjectBuilder::new()
+        .schema_type(schema_type)
+        .format(Some(SchemaFormat::KnownFormat(format)))
+        .into()
+}
+
+/// A reference to the schema of `message`, named by its full name. Wrappers
+/// are generated as optional scalars.
+fn message_ref(message: &MessageDescriptor) -> RefOr<Schema> {
+    let name = message.full_name();
+    if name.starts_with("google.protobuf.") && name.ends_with("Value") {
+        if let Some(value) = message.get_field_by_name("value") {
+            return body_schema(&value);
+        }
+    }
+    RefOr::Ref(Ref::from_schema_name(name))
+}
+
+/// The `google.rpc.Status` body of error responses.
+fn status_schema() -> RefOr<Schema> {
+    ObjectBuilder::new()
+        .property("code", formatted(Type::Integer, KnownFormat::Int32))
+        .required("code")
+        .property("message", ObjectBuilder::new().schema_type(Type::String))
+        .required("message")
+        .property(
+            "details",
+            ArrayBuilder::new().items(
+                ObjectBuilder::new()
+                    .additional_properties(Some(AdditionalProperties::<Schema>::FreeForm(true))),
+            ),
+        )
+        .into()
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::test::annotated_transcoder;
+
+    fn operation_json(paths: &Paths, path: &str, http_method: HttpMethod) -> Value {
+        serde_json::to_value(paths.get_path_operation(path, http_method).unwrap()).unwrap()
+    }
+
+    fn parameter_names(operation: &Value) -> Vec<(String, String)> {
+        operation["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| {
+                (
+                    x["name"].as_str().unwrap().to_owned(),
+                    x["in"].as_str().unwrap().to_owned(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_documents_path_items_of_routes() {
+        let paths = paths(&annotated_transcoder()).unwrap();
+        assert_eq!(
+            paths.paths.keys().collect::<Vec<_>>(),
+            [
+                "/v1/{book.id}",
+                "/v1/{name}/books",
+                "/v1/{name}/books:search"
+            ]
+        );
+
+        let list = operation_json(&paths, "/v1/{name}/books", HttpMethod::Get);
+        assert_eq!(list["operationId"], "test.v1.ShelfService.ListBooks");
+        assert_eq!(list["tags"], json!(["test.v1.ShelfService"]));
+        let parameter = |name: &str, r#in: &str| (name.to_owned(), r#in.to_owned());
+        assert_eq!(
+            parameter_names(&list),
+            [
+                parameter("name", "path"),
+                parameter("page_size", "query"),
+                parameter("states", "query"),
+                parameter("book.id", "query"),
+                parameter("book.data", "query"),
+                parameter("author", "query"),
+                parameter("year", "query"),
+            ]
+        );
+        assert_eq!(
+            list["parameters"][2]["schema"],
+            json!({
+                "type": "array",
+                "items": {
+                    "type": "string",
+                    "enum": ["STATE_UNSPECIFIED", "STATE_DRAFT", "STATE_PUBLISHED"],
+                },
+            })
+        );
+        assert_eq!(
+            list["responses"]["200"]["content"]["application/json"]["schema"],
+            json!({ "$ref": "#/components/schemas/test.v1.Book" })
+        );
+        assert!(list.get("requestBody").is_none());
+
+        let search = operation_json(&paths, "/v1/{name}/books:search", HttpMethod::Post);
+        assert_eq!(parameter_names(&search), [parameter("name", "path")]);
+        assert_eq!(
+            search["requestBody"]["content"]["application/json"]["schema"],
+            json!({ "$ref": "#/components/schemas/test.v1.ListBooksRequest" })
+        );
+
+        let update = operation_json(&paths, "/v1/{book.id}", HttpMethod::Put);
+        assert_eq!(
+            parameter_names(&update),
+            [
+                parameter("book.id", "path"),
+                parameter("name", "query"),
+                parameter("page_size", "query"),
+                parameter("states", "query"),
+                parameter("author", "query"),
+                parameter("year", "query"),
+            ]
+        );
+        assert_eq!(
+            update["requestBody"]["content"]["application/json"]["schema"],
+            json!({ "$ref": "#/components/schemas/test.v1.Book" })
+        );
+        assert_eq!(
+            update["responses"]["200"]["content"]["application/json"]["schema"],
+            json!({ "type": "array", "items": { "type": "integer", "format": "int32" } })
+        );
+    }
+}
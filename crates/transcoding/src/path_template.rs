@@ -0,0 +1,341 @@
+use std::{fmt, str::FromStr};
+
+use percent_encoding::percent_decode_str;
+
+/// A `google.api.http` path template such as
+/// `/v1/{name=shelves/*/books/*}:publish`, matching request paths and binding
+/// their variables to request field paths.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathTemplate {
+    template: String,
+    segments: Vec<Segment>,
+    variables: Vec<Variable>,
+    verb: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    /// `*`, a single segment.
+    Wildcard,
+    /// `**`, any number of segments, only last.
+    DoubleWildcard,
+}
+
+/// A variable capturing the segments `start..end` of the template.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Variable {
+    field_path: String,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug)]
+pub struct TemplateError {
+    pub template: String,
+    pub reason: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid path template `{}`: {}",
+            self.template, self.reason
+        )
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl PathTemplate {
+    /// Field paths of the variables, e.g. `name` or `book.id`.
+    pub fn field_paths(&self) -> impl Iterator<Item = &str> {
+        self.variables.iter().map(|x| x.field_path.as_str())
+    }
+
+    /// The template as an OpenAPI path, with variables named by their field
+    /// paths, e.g. `/v1/{name}:publish` of `/v1/{name=shelves/*/books/*}:publish`.
+    pub fn openapi_path(&self) -> String {
+        let mut path = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = start + rest[start..].find('}').expect("Expected closed variable");
+            let variable = &rest[start + 1..end];
+            let field_path = variable.split_once('=').map_or(variable, |x| x.0);
+            path.push_str(&rest[..start]);
+            path.push('{');
+            path.push_str(field_path);
+            path.push('}');
+            rest = &rest[end + 1..];
+        }
+        path.push_str(rest);
+        path
+    }
+
+    /// Matches `path`, without query, returning the percent-decoded values of
+    /// the variables by field path. Values of variables spanning several
+    /// segments keep their `/` separators, and escaped `%2F` stays escaped.
+    pub fn matches(&self, path: &str) -> Option<Vec<(String, String)>> {
+        let path = path.strip_prefix('/')?;
+        let path = match &self.verb {
+            Some(verb) => path.strip_suffix(verb.as_str())?.strip_suffix(':')?,
+            None => path,
+        };
+        let parts: Vec<&str> = path.split('/').collect();
+
+        // The part each template segment starts at, with `**` taking what is
+        // left, plus the end.
+        let mut offsets = Vec::with_capacity(self.segments.len() + 1);
+        let mut index = 0;
+        for segment in &self.segments {
+            offsets.push(index);
+            match segment {
+                Segment::Literal(literal) => {
+                    if parts.get(index)? != literal {
+                        return None;
+                    }
+                    index += 1;
+                }
+                Segment::Wildcard => {
+                    if parts.get(index)?.is_empty() {
+                        return None;
+                    }
+                    index += 1;
+                }
+                Segment::DoubleWildcard => index = parts.len(),
+            }
+        }
+        if index != parts.len() {
+            return None;
+        }
+        offsets.push(index);
+
+        self.variables
+            .iter()
+            .map(|variable| {
+                let parts = &parts[offsets[variable.start]..offsets[variable.end]];
+                let value = if variable.end - variable.start == 1
+                    && self.segments[variable.start] == Segment::Wildcard
+                {
+                    percent_decode_str(parts[0])
+                        .decode_utf8()
+                        .ok()?
+                        .into_owned()
+                } else {
+                    parts
+                        .iter()
+                        .map(|x| decode_keeping_slashes(x))
+                        .collect::<Option<Vec<_>>>()?
+                        .join("/")
+                };
+                Some((variable.field_path.to_owned(), value))
+            })
+            .collect()
+    }
+}
+
+fn decode_keeping_slashes(part: &str) -> Option<String> {
+    let decoded: Option<Vec<String>> = part
+        .split("%2F")
+        .flat_map(|x| x.split("%2f"))
+        .map(|x| {
+            percent_decode_str(x)
+                .decode_utf8()
+                .ok()
+                .map(|x| x.into_owned())
+        })
+        .collect();
+    Some(decoded?.join("%2F"))
+}
+
+impl FromStr for PathTemplate {
+    type Err = TemplateError;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let error = |reason: &str| TemplateError {
+            template: template.to_owned(),
+            reason: reason.to_owned(),
+        };
+        let rest = template
+            .strip_prefix('/')
+            .ok_or_else(|| error("must start with `/`"))?;
+
+        // A verb follows the last `:` outside of variables.
+        let mut depth = 0;
+        let mut verb_start = None;
+        for (index, c) in rest.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                ':' if depth == 0 => verb_start = Some(index),
+                _ => {}
+            }
+        }
+        let (rest, verb) = match verb_start {
+            Some(index) => (&rest[..index], Some(rest[index + 1..].to_owned())),
+            None => (rest, None),
+        };
+        if verb.as_deref().is_some_and(str::is_empty) {
+            return Err(error("has an empty verb"));
+        }
+
+        let mut segments = Vec::new();
+        let mut variables = Vec::new();
+        let mut chars = rest;
+        while !chars.is_empty() {
+            if let Some(variable) = chars.strip_prefix('{') {
+                let end = variable
+                    .find('}')
+                    .ok_or_else(|| error("has an unclosed variable"))?;
+                let (field_path, sub_template) = match variable[..end].split_once('=') {
+                    Some((field_path, sub_template)) => (field_path, sub_template),
+                    None => (&variable[..end], "*"),
+                };
+                if field_path.is_empty()
+                    || !field_path.split('.').all(|x| {
+                        !x.is_empty() && x.chars().all(|c| c.is_alphanumeric() || c == '_')
+                    })
+                {
+                    return Err(error("has a variable with an invalid field path"));
+                }
+                let start = segments.len();
+                for part in sub_template.split('/') {
+                    segments
+                        .push(parse_segment(part).ok_or_else(|| error("has an empty segment"))?);
+                }
+                variables.push(Variable {
+                    field_path: field_path.to_owned(),
+                    start,
+                    end: segments.len(),
+                });
+                chars = &variable[end + 1..];
+            } else {
+                let end = chars.find('/').unwrap_or(chars.len());
+                let part = &chars[..end];
+                if part.contains(['{', '}']) {
+                    return Err(error("has a variable that is not a whole segment"));
+                }
+                segments.push(parse_segment(part).ok_or_else(|| error("has an empty segment"))?);
+                chars = &chars[end..];
+            }
+            match chars.strip_prefix('/') {
+                Some(rest) if !rest.is_empty() => chars = rest,
+                Some(_) => return Err(error("has an empty segment")),
+                None if chars.is_empty() => {}
+                None => return Err(error("has a variable that is not a whole segment")),
+            }
+        }
+        if segments.is_empty() {
+            return Err(error("has no segments"));
+        }
+        if segments[..segments.len() - 1].contains(&Segment::DoubleWildcard) {
+            return Err(error("has `**` before the last segment"));
+        }
+
+        Ok(Self {
+            template: template.to_owned(),
+            segments,
+            variables,
+            verb,
+        })
+    }
+}
+
+fn parse_segment(part: &str) -> Option<Segment> {
+    match part {
+        "" => None,
+        "*" => Some(Segment::Wildcard),
+        "**" => Some(Segment::DoubleWildcard),
+        literal => Some(Segment::Literal(literal.to_owned())),
+    }
+}
+
+impl fmt::Display for PathTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn bindings(template: &str, path: &str) -> Option<Vec<(String, String)>> {
+        template.parse::<PathTemplate>().unwrap().matches(path)
+    }
+
+    fn binding(field_path: &str, value: &str) -> (String, String) {
+        (field_path.to_owned(), value.to_owned())
+    }
+
+    #[test]
+    fn it_binds_single_segment_variables() {
+        assert_eq!(
+            bindings(
+                "/v1/shelves/{shelf}/books/{book.id}",
+                "/v1/shelves/a%20b/books/7"
+            ),
+            Some(vec![binding("shelf", "a b"), binding("book.id", "7")])
+        );
+        assert_eq!(bindings("/v1/shelves/{shelf}", "/v1/shelves/a/b"), None);
+        assert_eq!(bindings("/v1/shelves/{shelf}", "/v1/shelves/"), None);
+    }
+
+    #[test]
+    fn it_binds_multi_segment_variables() {
+        assert_eq!(
+            bindings("/v1/{name=shelves/*/books/*}", "/v1/shelves/1/books/a%2Fb"),
+            Some(vec![binding("name", "shelves/1/books/a%2Fb")])
+        );
+        assert_eq!(
+            bindings("/v1/{name=files/**}", "/v1/files/a/b/c"),
+            Some(vec![binding("name", "files/a/b/c")])
+        );
+        assert_eq!(bindings("/v1/{name=shelves/*}", "/v1/books/1"), None);
+    }
+
+    #[test]
+    fn it_matches_verbs() {
+        assert_eq!(
+            bindings("/v1/{name=books/*}:publish", "/v1/books/1:publish"),
+            Some(vec![binding("name", "books/1")])
+        );
+        assert_eq!(bindings("/v1/{name=books/*}:publish", "/v1/books/1"), None);
+        assert_eq!(
+            bindings("/v1/books:batchGet", "/v1/books:batchGet"),
+            Some(Vec::new())
+        );
+    }
+
+    #[test]
+    fn it_renders_openapi_paths() {
+        for (template, path) in [
+            ("/v1/books", "/v1/books"),
+            (
+                "/v1/shelves/{shelf}/books/{book.id}",
+                "/v1/shelves/{shelf}/books/{book.id}",
+            ),
+            ("/v1/{name=shelves/*/books/*}:publish", "/v1/{name}:publish"),
+            ("/v1/{name=files/**}", "/v1/{name}"),
+        ] {
+            let template: PathTemplate = template.parse().unwrap();
+            assert_eq!(template.openapi_path(), path);
+        }
+    }
+
+    #[test]
+    fn it_rejects_invalid_templates() {
+        for template in [
+            "v1/books",
+            "/v1//books",
+            "/v1/**/books",
+            "/v1/{name",
+            "/v1/x{name}",
+            "/v1/{}",
+            "/v1/books:",
+        ] {
+            assert!(template.parse::<PathTemplate>().is_err(), "{template}");
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use http::Method;
+use prost_reflect::{DescriptorPool, DynamicMessage, MethodDescriptor};
+
+use crate::{Error, MethodRoute, Route};
+
+/// The `google.api.http` extension of `google.protobuf.MethodOptions`.
+const HTTP_EXTENSION: &str = "google.api.http";
+
+/// The routes of the `google.api.http` rules of the methods in `pool`, with
+/// additional bindings after the rule they belong to. Pools without
+/// `google/api/annotations.proto` have none.
+pub(crate) fn method_routes(pool: &DescriptorPool) -> Result<Vec<MethodRoute>, Error> {
+    let Some(extension) = pool.get_extension_by_name(HTTP_EXTENSION) else {
+        return Ok(Vec::new());
+    };
+    let mut routes = Vec::new();
+    for service in pool.services() {
+        for method in service.methods() {
+            let options = method.options();
+            if !options.has_extension(&extension) {
+                continue;
+            }
+            let value = options.get_extension(&extension);
+            let Some(rule) = value.as_message() else {
+                continue;
+            };
+            routes.push(rule_route(&method, rule)?);
+            if let Some(bindings) = rule
+                .get_field_by_name("additional_bindings")
+                .as_deref()
+                .and_then(|x| x.as_list())
+            {
+                for binding in bindings.iter().filter_map(|x| x.as_message()) {
+                    routes.push(rule_route(&method, binding)?);
+                }
+            }
+        }
+    }
+    Ok(routes)
+}
+
+fn rule_route(method: &MethodDescriptor, rule: &DynamicMessage) -> Result<MethodRoute, Error> {
+    let invalid = |reason: String| Error::InvalidRule {
+        method: method.full_name().to_owned(),
+        reason,
+    };
+    let string_field = |name: &str| {
+        rule.get_field_by_name(name)
+            .and_then(|x| x.as_str().map(str::to_owned))
+            .filter(|x| !x.is_empty())
+    };
+
+    let mut pattern = None;
+    for (name, http_method) in [
+        ("get", Method::GET),
+        ("put", Method::PUT),
+        ("post", Method::POST),
+        ("delete", Method::DELETE),
+        ("patch", Method::PATCH),
+    ] {
+        if rule.has_field_by_name(name) {
+            pattern = string_field(name).map(|x| (http_method, x));
+        }
+    }
+    if rule.has_field_by_name("custom") {
+        let custom = rule.get_field_by_name("custom");
+        let custom = custom.as_deref().and_then(|x| x.as_message());
+        let field = |name: &str| {
+            custom
+                .and_then(|x| x.get_field_by_name(name))
+                .and_then(|x| x.as_str().map(str::to_owned))
+                .unwrap_or_default()
+        };
+        let http_method = Method::from_bytes(field("kind").as_bytes())
+            .map_err(|_| invalid(format!("invalid custom method `{}`", field("kind"))))?;
+        pattern = Some((http_method, field("path")));
+    }
+    let (http_method, template) =
+        pattern.ok_or_else(|| invalid("rule has no pattern".to_owned()))?;
+
+    let mut route =
+        Route::new(http_method, &template).map_err(|error| invalid(error.to_string()))?;
+    route.body = string_field("body");
+    route.response_body = string_field("response_body");
+    Ok(MethodRoute {
+        method: method.clone(),
+        route,
+    })
+}
@@ -0,0 +1,33 @@
+use appbiotic_api::status::Status;
+use http::StatusCode;
+use tonic::Code;
+
+/// The HTTP status of a gRPC status code, as mapped by `google.rpc.Code`.
+pub fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        // Client Closed Request, which `http` has no constant for.
+        Code::Cancelled => StatusCode::from_u16(499).expect("Expected valid status code"),
+        Code::Unknown | Code::Internal | Code::DataLoss => StatusCode::INTERNAL_SERVER_ERROR,
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// The JSON body of an error response: the `google.rpc.Status` of `status`,
+/// with the details whose types are linked in unpacked.
+pub fn error_body(status: &tonic::Status) -> serde_json::Value {
+    let status =
+        Status::try_from(status).unwrap_or_else(|_| Status::new(status.code(), status.message()));
+    serde_json::to_value(&status)
+        .unwrap_or_else(|_| serde_json::json!({ "code": status.code, "message": status.message }))
+}